@@ -0,0 +1,221 @@
+// Long-lived V8 isolates for the signing scripts platforms ship. Each engine owns a few
+// worker threads, each holding a JsRuntime with the engine's scripts already evaluated, and
+// takes requests over a channel. A request that runs past its timeout is terminated, and a
+// runtime whose script threw or was terminated is thrown away and rebuilt for the next
+// request, so one bad page can't leave state behind. Users that need different globals get
+// separate engines. Only built with the js-signing feature.
+
+use deno_core::v8::IsolateHandle;
+use deno_core::{FastString, JsRuntime, RuntimeOptions};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+#[cfg(not(test))]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+#[cfg(test)]
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+
+struct Job {
+    scripts: Vec<String>,
+    reply: oneshot::Sender<Result<String, String>>,
+    // Filled in by the worker while the job runs, so a timed-out caller can stop it
+    running: Arc<Mutex<Option<IsolateHandle>>>,
+}
+
+pub struct JsEngine {
+    name: &'static str,
+    jobs: mpsc::Sender<Job>,
+}
+
+impl JsEngine {
+    // `preload` is evaluated once per runtime, in order, before any request
+    pub fn spawn(
+        name: &'static str,
+        preload: Vec<(&'static str, &'static str)>,
+        workers: usize,
+    ) -> Self {
+        let (jobs, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for i in 0..workers.max(1) {
+            let receiver = receiver.clone();
+            let preload = preload.clone();
+            let spawned = std::thread::Builder::new()
+                .name(format!("js-{}-{}", name, i))
+                .spawn(move || worker(name, &preload, &receiver));
+            if let Err(e) = spawned {
+                eprintln!("[Rust/js_engine] Failed to start a {} worker: {}", name, e);
+            }
+        }
+        Self { name, jobs }
+    }
+
+    // Runs the scripts in order and returns the last one's value, which has to be a string
+    pub async fn eval(&self, scripts: Vec<String>) -> Result<String, String> {
+        let (reply, reply_rx) = oneshot::channel();
+        let running = Arc::new(Mutex::new(None));
+        self.jobs
+            .send(Job {
+                scripts,
+                reply,
+                running: running.clone(),
+            })
+            .map_err(|_| format!("The {} JS engine has no workers", self.name))?;
+        match tokio::time::timeout(REQUEST_TIMEOUT, reply_rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(format!("The {} JS engine worker exited", self.name)),
+            Err(_) => {
+                // A job still in the queue is skipped once its caller is gone
+                if let Some(isolate) = running.lock().unwrap().take() {
+                    isolate.terminate_execution();
+                }
+                Err(format!(
+                    "The {} JS engine timed out after {:?}",
+                    self.name, REQUEST_TIMEOUT
+                ))
+            }
+        }
+    }
+}
+
+fn start_runtime(preload: &[(&'static str, &'static str)]) -> Result<JsRuntime, String> {
+    let mut runtime = JsRuntime::new(RuntimeOptions::default());
+    for (script_name, source) in preload {
+        runtime
+            .execute_script(script_name, FastString::from_static(source))
+            .map_err(|e| format!("Preloading {} failed: {}", script_name, e))?;
+    }
+    Ok(runtime)
+}
+
+fn run_job(runtime: &mut JsRuntime, scripts: &[String]) -> Result<String, String> {
+    let mut last = None;
+    for script in scripts {
+        last = Some(
+            runtime
+                .execute_script("[request]", FastString::from(script.clone()))
+                .map_err(|e| e.to_string())?,
+        );
+    }
+    let last = last.ok_or("No script to run")?;
+    let scope = &mut runtime.handle_scope();
+    let value = deno_core::v8::Local::new(scope, last);
+    if !value.is_string() {
+        return Err("The script did not return a string".to_string());
+    }
+    Ok(value.to_rust_string_lossy(scope))
+}
+
+fn worker(
+    name: &'static str,
+    preload: &[(&'static str, &'static str)],
+    receiver: &Mutex<mpsc::Receiver<Job>>,
+) {
+    let mut runtime: Option<JsRuntime> = None;
+    loop {
+        // Ends when the engine is dropped
+        let Ok(job) = receiver.lock().unwrap().recv() else {
+            return;
+        };
+        if job.reply.is_closed() {
+            continue; // Timed out while queued
+        }
+        if runtime.is_none() {
+            match start_runtime(preload) {
+                Ok(started) => runtime = Some(started),
+                Err(e) => {
+                    eprintln!("[Rust/js_engine] {}: {}", name, e);
+                    let _ = job.reply.send(Err(e));
+                    continue;
+                }
+            }
+        }
+        let Some(current) = runtime.as_mut() else {
+            continue;
+        };
+        *job.running.lock().unwrap() = Some(current.v8_isolate().thread_safe_handle());
+        let result = run_job(current, &job.scripts);
+        // If the handle is gone the caller timed out and may have terminated the isolate
+        // after the script finished, which would break the next request
+        let terminated = job.running.lock().unwrap().take().is_none();
+        if let Err(e) = &result {
+            eprintln!(
+                "[Rust/js_engine] {} request failed, restarting its runtime: {}",
+                name, e
+            );
+            runtime = None;
+        } else if terminated {
+            runtime = None;
+        }
+        let _ = job.reply.send(result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Counts requests per runtime, so a rebuilt runtime starts again from 1
+    fn engine() -> JsEngine {
+        JsEngine::spawn("test", vec![("counter.js", "globalThis.calls = 0;")], 1)
+    }
+
+    fn count() -> Vec<String> {
+        vec!["String(++globalThis.calls)".to_string()]
+    }
+
+    #[tokio::test]
+    async fn terminates_scripts_that_run_past_the_timeout() {
+        let engine = engine();
+        assert_eq!(engine.eval(count()).await.unwrap(), "1");
+        let error = engine
+            .eval(vec!["while (true) {}".to_string()])
+            .await
+            .unwrap_err();
+        assert!(error.contains("timed out"), "{}", error);
+        assert_eq!(engine.eval(count()).await.unwrap(), "1");
+        assert_eq!(engine.eval(count()).await.unwrap(), "2");
+    }
+
+    #[tokio::test]
+    async fn rebuilds_the_runtime_after_a_throw() {
+        let engine = engine();
+        assert_eq!(engine.eval(count()).await.unwrap(), "1");
+        let error = engine
+            .eval(vec![
+                "globalThis.calls = 100;".to_string(),
+                "throw new Error('bad page')".to_string(),
+            ])
+            .await
+            .unwrap_err();
+        assert!(error.contains("bad page"), "{}", error);
+        assert_eq!(engine.eval(count()).await.unwrap(), "1");
+    }
+
+    #[tokio::test]
+    async fn skips_queued_jobs_whose_caller_has_gone() {
+        let engine = Arc::new(engine());
+        // Keeps the only worker busy while the next job waits in the queue
+        let busy = tokio::spawn({
+            let engine = engine.clone();
+            async move {
+                let script = "const end = Date.now() + 200; while (Date.now() < end) {} 'done'";
+                engine.eval(vec![script.to_string()]).await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let gone = tokio::spawn({
+            let engine = engine.clone();
+            async move {
+                engine
+                    .eval(vec!["globalThis.calls = 100; 'ran'".to_string()])
+                    .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        gone.abort();
+        assert_eq!(busy.await.unwrap().unwrap(), "done");
+        assert_eq!(engine.eval(count()).await.unwrap(), "1");
+    }
+}
@@ -7,22 +7,33 @@ use std::sync::{Arc, Mutex};
 use tokio::sync::oneshot;
 
 // mod douyu; // Removed old direct module
+#[cfg(feature = "js-signing")]
+mod js_engine;
+mod media;
 mod platforms;
 mod proxy; // Added platforms module
            // use platforms::douyu; // No longer need this specific use if functions are directly available via platforms::douyu::* from main
+mod recording;
 
 // Assuming API commands are correctly re-exported or defined in these modules
-use platforms::common::DouyinDanmakuState;
+use platforms::bilibili::get_bilibili_stream_url;
+use platforms::common::platform_commands::{
+    platform_fetch_categories, platform_fetch_room_info, platform_fetch_rooms, platform_list,
+    platform_resolve_stream, platform_search, platform_start_danmaku, platform_stop_danmaku,
+};
+use platforms::common::types_rust::SupportedPlatformRust;
+use platforms::common::{DouyinDanmakuHandles, PlatformDanmakuHandles};
 use platforms::douyin::danmu::signature::generate_douyin_ms_token;
 use platforms::douyin::fetch_douyin_partition_rooms;
 use platforms::douyin::fetch_douyin_room_info;
 use platforms::douyin::fetch_douyin_streamer_info;
 use platforms::douyin::get_douyin_live_stream_url;
-use platforms::douyin::start_douyin_danmu_listener;
+use platforms::douyin::{start_douyin_danmu_listener, stop_douyin_danmu_listener};
 use platforms::douyu::fetch_categories;
 use platforms::douyu::fetch_douyu_room_info;
 use platforms::douyu::fetch_three_cate;
 use platforms::douyu::{fetch_live_list, fetch_live_list_for_cate3};
+use platforms::huya::get_huya_stream_url;
 // get_stream_url and search_anchor will be directly available via platforms::douyu now
 
 #[derive(Default, Clone)]
@@ -37,14 +48,17 @@ pub struct DouyuDanmakuHandles(Arc<Mutex<HashMap<String, oneshot::Sender<()>>>>)
 // DanmakuState remains for the danmaku listener
 // struct DanmakuState(Mutex<Option<mpsc::Sender<()>>>); // Old Douyu state, to be replaced by DouyuDanmakuHandles
 
-// DouyinDanmakuState is already defined in and re-exported by platforms::common::types
-// No need to redefine it here if it's correctly imported.
+// DouyinDanmakuHandles is defined in and re-exported by platforms::common::types
 
 // This is the command that should be used for getting stream URL if it interacts with StreamUrlStore
 #[tauri::command]
-async fn get_stream_url_cmd(room_id: String) -> Result<String, String> {
+async fn get_stream_url_cmd(
+    room_id: String,
+    rate: Option<i32>,
+    cdn: Option<String>,
+) -> Result<String, String> {
     // Call the actual function to fetch the stream URL from the new location
-    platforms::douyu::get_stream_url(&room_id)
+    platforms::douyu::get_stream_url_with_quality(&room_id, rate, cdn.as_deref())
         .await
         .map_err(|e| {
             eprintln!(
@@ -71,7 +85,7 @@ async fn set_stream_url_cmd(
 #[tauri::command]
 async fn start_danmaku_listener(
     room_id: String,
-    window: tauri::Window,
+    app_handle: tauri::AppHandle,
     danmaku_handles: tauri::State<'_, DouyuDanmakuHandles>,
 ) -> Result<(), String> {
     // If a listener for this room_id already exists, stop it first.
@@ -86,12 +100,11 @@ async fn start_danmaku_listener(
         .unwrap()
         .insert(room_id.clone(), stop_tx);
 
-    let window_clone = window.clone();
     let room_id_clone = room_id.clone();
     tokio::spawn(async move {
         let mut client = platforms::douyu::danmu_start::DanmakuClient::new(
             &room_id_clone,
-            window_clone,
+            app_handle,
             stop_rx, // Pass the receiver part of the oneshot channel
         );
         if let Err(e) = client.start().await {
@@ -124,6 +137,54 @@ async fn stop_danmaku_listener(
     }
 }
 
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ActiveDanmakuListener {
+    platform: SupportedPlatformRust,
+    room_id: String,
+}
+
+// Lists every running danmaku listener, whichever command started it
+#[tauri::command]
+fn list_active_danmaku_listeners(
+    douyu_handles: tauri::State<'_, DouyuDanmakuHandles>,
+    douyin_handles: tauri::State<'_, DouyinDanmakuHandles>,
+    platform_handles: tauri::State<'_, PlatformDanmakuHandles>,
+) -> Vec<ActiveDanmakuListener> {
+    // A closed sender means the listener task has already exited
+    let douyu = douyu_handles
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, sender)| !sender.is_closed())
+        .map(|(room_id, _)| (SupportedPlatformRust::Douyu, room_id.clone()))
+        .collect::<Vec<_>>();
+    let douyin = douyin_handles
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, sender)| !sender.is_closed())
+        .map(|(room_id, _)| (SupportedPlatformRust::Douyin, room_id.clone()))
+        .collect::<Vec<_>>();
+    let platform = platform_handles
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, sender)| !sender.is_closed())
+        .map(|((platform, room_id), _)| (*platform, room_id.clone()))
+        .collect::<Vec<_>>();
+
+    douyu
+        .into_iter()
+        .chain(douyin)
+        .chain(platform)
+        .map(|(platform, room_id)| ActiveDanmakuListener { platform, room_id })
+        .collect()
+}
+
 // search_anchor seems fine, assuming douyu::search_anchor is correct
 #[tauri::command]
 async fn search_anchor(keyword: String) -> Result<String, String> {
@@ -145,18 +206,39 @@ fn main() {
         .manage(client) // Manage the reqwest client
         // .manage(DanmakuState(Mutex::new(None))) // Old Douyu state, remove this
         .manage(DouyuDanmakuHandles::default()) // Manage new DouyuDanmakuHandles
-        .manage(DouyinDanmakuState::default()) // Manage DouyinDanmakuState
+        .manage(DouyinDanmakuHandles::default()) // Douyin danmaku handles, keyed by room
         .manage(StreamUrlStore::default())
+        .manage(platforms::build_platform_registry())
+        .manage(PlatformDanmakuHandles::default())
         .manage(proxy::ProxyServerHandle::default())
+        .manage(proxy::StreamStatsStore::default())
+        .manage(proxy::ProxyStreamRegistry::default())
+        .manage(proxy::ProxySettingsStore::default())
+        .manage(recording::DanmakuRecorderHandles::default())
+        .manage(recording::StreamRecorderHandles::default())
+        .manage(recording::AutoRecordHandles::default())
+        .setup(|app| {
+            proxy::settings::load_proxy_settings(app.handle());
+            recording::auto_record::spawn_auto_recorder(app.handle().clone());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             get_stream_url_cmd,
+            platforms::douyu::stream_url::get_stream_qualities,
             set_stream_url_cmd,
             search_anchor,
             start_danmaku_listener,      // Douyu danmaku start
             stop_danmaku_listener,       // Douyu danmaku stop
-            start_douyin_danmu_listener, // Added Douyin danmaku listener command
+            start_douyin_danmu_listener, // Douyin danmaku start
+            stop_douyin_danmu_listener,  // Douyin danmaku stop
+            list_active_danmaku_listeners,
             proxy::start_proxy,
             proxy::stop_proxy,
+            proxy::get_stream_stats,
+            proxy::streams::add_proxy_stream,
+            proxy::streams::remove_proxy_stream,
+            proxy::settings::get_proxy_settings,
+            proxy::settings::set_proxy_settings,
             fetch_categories,
             fetch_live_list,
             fetch_live_list_for_cate3,
@@ -166,7 +248,23 @@ fn main() {
             fetch_douyin_partition_rooms,
             get_douyin_live_stream_url,
             fetch_douyin_room_info,
-            fetch_douyin_streamer_info
+            fetch_douyin_streamer_info,
+            get_bilibili_stream_url,
+            get_huya_stream_url,
+            platform_list,
+            platform_fetch_categories,
+            platform_fetch_rooms,
+            platform_fetch_room_info,
+            platform_resolve_stream,
+            platform_search,
+            platform_start_danmaku,
+            platform_stop_danmaku,
+            recording::danmaku_recorder::set_danmaku_recording,
+            recording::danmaku_export::export_danmaku_recording,
+            recording::stream_recorder::start_stream_recording,
+            recording::stream_recorder::stop_stream_recording,
+            recording::auto_record::get_auto_record_rules,
+            recording::auto_record::set_auto_record_rules
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
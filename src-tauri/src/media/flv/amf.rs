@@ -0,0 +1,202 @@
+// AMF0, as used by FLV script tags: a name string (e.g. "onMetaData") followed by a value,
+// usually an ECMA array of the stream's properties. Decoded into JSON for convenience.
+
+use serde_json::{Map, Value};
+
+const MARKER_NUMBER: u8 = 0x00;
+const MARKER_BOOLEAN: u8 = 0x01;
+const MARKER_STRING: u8 = 0x02;
+const MARKER_OBJECT: u8 = 0x03;
+const MARKER_NULL: u8 = 0x05;
+const MARKER_UNDEFINED: u8 = 0x06;
+const MARKER_REFERENCE: u8 = 0x07;
+const MARKER_ECMA_ARRAY: u8 = 0x08;
+const MARKER_OBJECT_END: u8 = 0x09;
+const MARKER_STRICT_ARRAY: u8 = 0x0A;
+const MARKER_DATE: u8 = 0x0B;
+const MARKER_LONG_STRING: u8 = 0x0C;
+// Metadata is flat in practice; this only guards against hostile nesting
+const MAX_DEPTH: usize = 16;
+
+struct AmfReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> AmfReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or("AMF data ends early")?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn f64(&mut self) -> Result<f64, String> {
+        let b = self.take(8)?;
+        Ok(f64::from_be_bytes(b.try_into().unwrap()))
+    }
+
+    fn string(&mut self, len: usize) -> Result<String, String> {
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    // Key / value pairs up to an empty key followed by the object end marker
+    fn properties(&mut self, depth: usize) -> Result<Map<String, Value>, String> {
+        let mut map = Map::new();
+        loop {
+            // Some encoders drop the end marker at the very end of the tag
+            if self.pos >= self.data.len() {
+                return Ok(map);
+            }
+            let key_len = self.u16()? as usize;
+            if key_len == 0 {
+                if self.data.get(self.pos) == Some(&MARKER_OBJECT_END) {
+                    self.pos += 1;
+                }
+                return Ok(map);
+            }
+            let key = self.string(key_len)?;
+            let value = self.value(depth + 1)?;
+            map.insert(key, value);
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, String> {
+        if depth > MAX_DEPTH {
+            return Err("AMF data nested too deeply".to_string());
+        }
+        match self.u8()? {
+            MARKER_NUMBER => Ok(serde_json::Number::from_f64(self.f64()?)
+                .map(Value::Number)
+                .unwrap_or(Value::Null)),
+            MARKER_BOOLEAN => Ok(Value::Bool(self.u8()? != 0)),
+            MARKER_STRING => {
+                let len = self.u16()? as usize;
+                Ok(Value::String(self.string(len)?))
+            }
+            MARKER_LONG_STRING => {
+                let len = self.u32()? as usize;
+                Ok(Value::String(self.string(len)?))
+            }
+            MARKER_OBJECT => Ok(Value::Object(self.properties(depth)?)),
+            MARKER_ECMA_ARRAY => {
+                self.u32()?; // Approximate count; the end marker is authoritative
+                Ok(Value::Object(self.properties(depth)?))
+            }
+            MARKER_STRICT_ARRAY => {
+                let count = self.u32()?;
+                let mut items = Vec::new();
+                for _ in 0..count {
+                    items.push(self.value(depth + 1)?);
+                }
+                Ok(Value::Array(items))
+            }
+            MARKER_DATE => {
+                let millis = self.f64()?;
+                self.u16()?; // Time zone, unused
+                Ok(serde_json::Number::from_f64(millis)
+                    .map(Value::Number)
+                    .unwrap_or(Value::Null))
+            }
+            MARKER_NULL | MARKER_UNDEFINED => Ok(Value::Null),
+            MARKER_REFERENCE => {
+                self.u16()?;
+                Ok(Value::Null)
+            }
+            marker => Err(format!("Unsupported AMF0 marker {:#04x}", marker)),
+        }
+    }
+}
+
+// Decodes a script tag body into its name and value
+pub fn parse_script_tag(body: &[u8]) -> Result<(String, Value), String> {
+    let mut reader = AmfReader { data: body, pos: 0 };
+    let name = match reader.value(0)? {
+        Value::String(name) => name,
+        _ => return Err("Script tag does not start with a name".to_string()),
+    };
+    let value = if reader.pos < body.len() {
+        reader.value(0)?
+    } else {
+        Value::Null
+    };
+    Ok((name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Vec<u8> {
+        let mut out = (s.len() as u16).to_be_bytes().to_vec();
+        out.extend_from_slice(s.as_bytes());
+        out
+    }
+
+    #[test]
+    fn parses_metadata() {
+        let mut body = vec![MARKER_STRING];
+        body.extend(string("onMetaData"));
+        body.extend([MARKER_ECMA_ARRAY, 0, 0, 0, 3]);
+        body.extend(string("width"));
+        body.push(MARKER_NUMBER);
+        body.extend(1920f64.to_be_bytes());
+        body.extend(string("encoder"));
+        body.push(MARKER_STRING);
+        body.extend(string("obs-output module"));
+        body.extend(string("keyframes"));
+        body.push(MARKER_OBJECT);
+        body.extend(string("times"));
+        body.extend([MARKER_STRICT_ARRAY, 0, 0, 0, 2, MARKER_NUMBER]);
+        body.extend(0f64.to_be_bytes());
+        body.push(MARKER_NUMBER);
+        body.extend(2.5f64.to_be_bytes());
+        body.extend([0, 0, MARKER_OBJECT_END]);
+        // No end marker for the ECMA array, as some encoders write it
+
+        let (name, value) = parse_script_tag(&body).unwrap();
+        assert_eq!(name, "onMetaData");
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "width": 1920.0,
+                "encoder": "obs-output module",
+                "keyframes": { "times": [0.0, 2.5] },
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_deep_nesting_and_truncation() {
+        let mut body = vec![MARKER_STRING];
+        body.extend(string("onMetaData"));
+        for _ in 0..=MAX_DEPTH {
+            body.extend([MARKER_STRICT_ARRAY, 0, 0, 0, 1]);
+        }
+        body.push(MARKER_NULL);
+        assert!(parse_script_tag(&body).is_err());
+
+        let mut body = vec![MARKER_STRING];
+        body.extend(string("onMetaData"));
+        body.extend([MARKER_STRING, 0, 10, b'a']);
+        assert!(parse_script_tag(&body).is_err());
+    }
+}
@@ -0,0 +1,550 @@
+// Decoder configuration parsing: just enough of the AVC/HEVC sequence headers and the AAC
+// AudioSpecificConfig to report resolution, frame rate and audio format.
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VideoConfig {
+    pub codec: String, // e.g. "avc1.640028", "hvc1.1.6.L120.B0"
+    pub width: u32,
+    pub height: u32,
+    pub fps: Option<f64>, // Only when the SPS / config record carries timing info
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AudioConfig {
+    pub codec: String, // e.g. "mp4a.40.2"
+    pub sample_rate: u32,
+    pub channels: u8,
+}
+
+// MSB-first bit reader over an RBSP (emulation prevention bytes already removed)
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize, // In bits
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bit(&mut self) -> Result<u32, String> {
+        let byte = self.data.get(self.pos / 8).ok_or("SPS ends early")?;
+        let bit = (byte >> (7 - self.pos % 8)) & 1;
+        self.pos += 1;
+        Ok(bit as u32)
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0u32;
+        for _ in 0..count {
+            value = (value << 1) | self.bit()?;
+        }
+        Ok(value)
+    }
+
+    fn skip(&mut self, count: usize) -> Result<(), String> {
+        if self.pos + count > self.data.len() * 8 {
+            return Err("SPS ends early".to_string());
+        }
+        self.pos += count;
+        Ok(())
+    }
+
+    fn flag(&mut self) -> Result<bool, String> {
+        Ok(self.bit()? == 1)
+    }
+
+    // Exp-Golomb ue(v)
+    fn ue(&mut self) -> Result<u32, String> {
+        let mut zeros = 0;
+        while self.bit()? == 0 {
+            zeros += 1;
+            if zeros > 31 {
+                return Err("Invalid Exp-Golomb code".to_string());
+            }
+        }
+        Ok((1u32 << zeros) - 1 + self.bits(zeros)?)
+    }
+
+    fn se(&mut self) -> Result<i32, String> {
+        let value = self.ue()?;
+        Ok(if value % 2 == 1 {
+            value.div_ceil(2) as i32
+        } else {
+            -((value / 2) as i32)
+        })
+    }
+}
+
+// Drops the 0x03 in every 00 00 03 sequence
+fn to_rbsp(nal: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(nal.len());
+    let mut zeros = 0;
+    for &byte in nal {
+        if zeros >= 2 && byte == 3 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        out.push(byte);
+    }
+    out
+}
+
+fn skip_scaling_list(reader: &mut BitReader, size: usize) -> Result<(), String> {
+    let mut last = 8i32;
+    let mut next = 8i32;
+    for _ in 0..size {
+        if next != 0 {
+            next = (last + reader.se()? + 256) % 256;
+        }
+        if next != 0 {
+            last = next;
+        }
+    }
+    Ok(())
+}
+
+// 8K is 7680x4320; anything past this is a corrupt or hostile SPS
+const MAX_DIMENSION: u64 = 16384;
+
+// Exp-Golomb values go up to u32::MAX, so the sums and products are done in u64
+fn cropped_size(coded: u64, crop: u64) -> Result<u32, String> {
+    if coded > MAX_DIMENSION || crop >= coded {
+        return Err(format!(
+            "SPS reports an impossible frame size ({} - {})",
+            coded, crop
+        ));
+    }
+    Ok((coded - crop) as u32)
+}
+
+fn frame_rate(time_scale: u32, num_units_in_tick: u32, divisor: f64) -> Option<f64> {
+    if time_scale == 0 || num_units_in_tick == 0 {
+        return None;
+    }
+    let fps = time_scale as f64 / (num_units_in_tick as f64 * divisor);
+    // Round to 3 decimals so 29.97 and friends read sensibly
+    Some((fps * 1000.0).round() / 1000.0)
+}
+
+// H.264 sequence parameter set, NAL header included
+fn parse_avc_sps(nal: &[u8]) -> Result<(u32, u32, Option<f64>), String> {
+    let rbsp = to_rbsp(nal.get(1..).ok_or("Empty SPS")?);
+    let mut r = BitReader::new(&rbsp);
+    let profile_idc = r.bits(8)?;
+    r.skip(16)?; // Constraint flags and level
+    r.ue()?; // seq_parameter_set_id
+
+    let mut chroma_format_idc = 1;
+    let mut separate_colour_plane = false;
+    if matches!(
+        profile_idc,
+        100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135
+    ) {
+        chroma_format_idc = r.ue()?;
+        if chroma_format_idc == 3 {
+            separate_colour_plane = r.flag()?;
+        }
+        r.ue()?; // bit_depth_luma_minus8
+        r.ue()?; // bit_depth_chroma_minus8
+        r.skip(1)?; // qpprime_y_zero_transform_bypass_flag
+        if r.flag()? {
+            let lists = if chroma_format_idc == 3 { 12 } else { 8 };
+            for i in 0..lists {
+                if r.flag()? {
+                    skip_scaling_list(&mut r, if i < 6 { 16 } else { 64 })?;
+                }
+            }
+        }
+    }
+
+    r.ue()?; // log2_max_frame_num_minus4
+    match r.ue()? {
+        0 => {
+            r.ue()?; // log2_max_pic_order_cnt_lsb_minus4
+        }
+        1 => {
+            r.skip(1)?;
+            r.se()?;
+            r.se()?;
+            for _ in 0..r.ue()? {
+                r.se()?;
+            }
+        }
+        _ => {}
+    }
+    r.ue()?; // max_num_ref_frames
+    r.skip(1)?; // gaps_in_frame_num_value_allowed_flag
+    let width_mbs = r.ue()? as u64 + 1;
+    let height_map_units = r.ue()? as u64 + 1;
+    let frame_mbs_only = r.flag()?;
+    if !frame_mbs_only {
+        r.skip(1)?; // mb_adaptive_frame_field_flag
+    }
+    r.skip(1)?; // direct_8x8_inference_flag
+
+    let frame_height_factor = if frame_mbs_only { 1 } else { 2 };
+    let (mut crop_width, mut crop_height) = (0, 0);
+    if r.flag()? {
+        let (left, right, top, bottom) = (r.ue()?, r.ue()?, r.ue()?, r.ue()?);
+        let (crop_x, crop_y) = match (chroma_format_idc, separate_colour_plane) {
+            (0, _) | (_, true) => (1, frame_height_factor),
+            (1, _) => (2, 2 * frame_height_factor),
+            (2, _) => (2, frame_height_factor),
+            _ => (1, frame_height_factor),
+        };
+        crop_width = (left as u64 + right as u64) * crop_x;
+        crop_height = (top as u64 + bottom as u64) * crop_y;
+    }
+    let width = cropped_size(width_mbs * 16, crop_width)?;
+    let height = cropped_size(height_map_units * 16 * frame_height_factor, crop_height)?;
+
+    // Timing lives in the VUI; anything unreadable there just means no fps
+    let fps = parse_avc_vui_timing(&mut r).ok().flatten();
+    Ok((width, height, fps))
+}
+
+fn parse_avc_vui_timing(r: &mut BitReader) -> Result<Option<f64>, String> {
+    if !r.flag()? {
+        return Ok(None);
+    }
+    if r.flag()? {
+        // aspect_ratio_info
+        if r.bits(8)? == 255 {
+            r.skip(32)?; // Extended SAR
+        }
+    }
+    if r.flag()? {
+        r.skip(1)?; // overscan_appropriate_flag
+    }
+    if r.flag()? {
+        r.skip(4)?; // video_format, video_full_range_flag
+        if r.flag()? {
+            r.skip(24)?; // Colour description
+        }
+    }
+    if r.flag()? {
+        r.ue()?;
+        r.ue()?; // Chroma sample locations
+    }
+    if !r.flag()? {
+        return Ok(None);
+    }
+    let num_units_in_tick = r.bits(32)?;
+    let time_scale = r.bits(32)?;
+    // Each frame is two fields' worth of ticks
+    Ok(frame_rate(time_scale, num_units_in_tick, 2.0))
+}
+
+// AVCDecoderConfigurationRecord from an AVC sequence header tag
+pub fn parse_avc_config(record: &[u8]) -> Result<VideoConfig, String> {
+    if record.len() < 8 || record[0] != 1 {
+        return Err("Invalid AVC decoder configuration".to_string());
+    }
+    let codec = format!("avc1.{:02x}{:02x}{:02x}", record[1], record[2], record[3]);
+    let sps_count = record[5] & 0x1F;
+    if sps_count == 0 {
+        return Err("AVC decoder configuration has no SPS".to_string());
+    }
+    let sps_len = u16::from_be_bytes([record[6], record[7]]) as usize;
+    let sps = record.get(8..8 + sps_len).ok_or("AVC SPS truncated")?;
+    let (width, height, fps) = parse_avc_sps(sps)?;
+    Ok(VideoConfig {
+        codec,
+        width,
+        height,
+        fps,
+    })
+}
+
+fn skip_hevc_profile_tier_level(
+    r: &mut BitReader,
+    max_sub_layers_minus1: u32,
+) -> Result<(), String> {
+    r.skip(88)?; // General profile space, tier, idc, compatibility and constraint flags
+    r.skip(8)?; // general_level_idc
+    let mut sub_layer_flags = Vec::new();
+    for _ in 0..max_sub_layers_minus1 {
+        sub_layer_flags.push((r.flag()?, r.flag()?));
+    }
+    if max_sub_layers_minus1 > 0 {
+        for _ in max_sub_layers_minus1..8 {
+            r.skip(2)?; // reserved_zero_2bits
+        }
+    }
+    for (profile_present, level_present) in sub_layer_flags {
+        if profile_present {
+            r.skip(88)?;
+        }
+        if level_present {
+            r.skip(8)?;
+        }
+    }
+    Ok(())
+}
+
+// H.265 sequence parameter set, 2-byte NAL header included
+fn parse_hevc_sps(nal: &[u8]) -> Result<(u32, u32), String> {
+    let rbsp = to_rbsp(nal.get(2..).ok_or("Empty SPS")?);
+    let mut r = BitReader::new(&rbsp);
+    r.skip(4)?; // sps_video_parameter_set_id
+    let max_sub_layers_minus1 = r.bits(3)?;
+    r.skip(1)?; // sps_temporal_id_nesting_flag
+    skip_hevc_profile_tier_level(&mut r, max_sub_layers_minus1)?;
+    r.ue()?; // sps_seq_parameter_set_id
+    let chroma_format_idc = r.ue()?;
+    if chroma_format_idc == 3 {
+        r.skip(1)?; // separate_colour_plane_flag
+    }
+    let width = r.ue()? as u64;
+    let height = r.ue()? as u64;
+    let (mut crop_width, mut crop_height) = (0, 0);
+    if r.flag()? {
+        let (left, right, top, bottom) = (r.ue()?, r.ue()?, r.ue()?, r.ue()?);
+        let (sub_width, sub_height) = match chroma_format_idc {
+            1 => (2, 2),
+            2 => (2, 1),
+            _ => (1, 1),
+        };
+        crop_width = (left as u64 + right as u64) * sub_width;
+        crop_height = (top as u64 + bottom as u64) * sub_height;
+    }
+    Ok((
+        cropped_size(width, crop_width)?,
+        cropped_size(height, crop_height)?,
+    ))
+}
+
+const HEVC_NAL_SPS: u8 = 33;
+
+// RFC 6381 / ISO 14496-15 E.3 string, e.g. "hvc1.1.6.L120.B0"; MSE wants the full form
+fn hevc_codec_string(record: &[u8]) -> String {
+    let profile_space = ["", "A", "B", "C"][(record[1] >> 6) as usize];
+    let tier = if record[1] & 0x20 != 0 { 'H' } else { 'L' };
+    let profile_idc = record[1] & 0x1F;
+    // Compatibility flags are written bit-reversed
+    let compatibility = u32::from_be_bytes([record[2], record[3], record[4], record[5]]);
+    let mut codec = format!(
+        "hvc1.{}{}.{:X}.{}{}",
+        profile_space,
+        profile_idc,
+        compatibility.reverse_bits(),
+        tier,
+        record[12]
+    );
+    // Constraint bytes, trailing zero bytes omitted
+    let constraints = &record[6..12];
+    let used = constraints
+        .iter()
+        .rposition(|b| *b != 0)
+        .map_or(0, |last| last + 1);
+    for byte in &constraints[..used] {
+        codec.push_str(&format!(".{:X}", byte));
+    }
+    codec
+}
+
+// HEVCDecoderConfigurationRecord from an HEVC sequence header tag
+pub fn parse_hevc_config(record: &[u8]) -> Result<VideoConfig, String> {
+    if record.len() < 23 || record[0] != 1 {
+        return Err("Invalid HEVC decoder configuration".to_string());
+    }
+    // avgFrameRate is in frames per 256 seconds, 0 when unspecified
+    let avg_frame_rate = u16::from_be_bytes([record[19], record[20]]);
+    let mut fps = (avg_frame_rate != 0).then(|| avg_frame_rate as f64 / 256.0);
+
+    let mut pos = 23;
+    let array_count = record[22];
+    for _ in 0..array_count {
+        let nal_type = record.get(pos).ok_or("HEVC configuration truncated")? & 0x3F;
+        let nal_count = u16::from_be_bytes(
+            record
+                .get(pos + 1..pos + 3)
+                .ok_or("HEVC configuration truncated")?
+                .try_into()
+                .unwrap(),
+        );
+        pos += 3;
+        for _ in 0..nal_count {
+            let len = u16::from_be_bytes(
+                record
+                    .get(pos..pos + 2)
+                    .ok_or("HEVC configuration truncated")?
+                    .try_into()
+                    .unwrap(),
+            ) as usize;
+            let nal = record
+                .get(pos + 2..pos + 2 + len)
+                .ok_or("HEVC NAL unit truncated")?;
+            pos += 2 + len;
+            if nal_type == HEVC_NAL_SPS {
+                let (width, height) = parse_hevc_sps(nal)?;
+                if let Some(value) = fps {
+                    fps = Some((value * 1000.0).round() / 1000.0);
+                }
+                return Ok(VideoConfig {
+                    codec: hevc_codec_string(record),
+                    width,
+                    height,
+                    fps,
+                });
+            }
+        }
+    }
+    Err("HEVC decoder configuration has no SPS".to_string())
+}
+
+const AAC_SAMPLE_RATES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+// AudioSpecificConfig from an AAC sequence header tag
+pub fn parse_aac_config(config: &[u8]) -> Result<AudioConfig, String> {
+    let mut r = BitReader::new(config);
+    let mut object_type = r.bits(5)?;
+    if object_type == 31 {
+        object_type = 32 + r.bits(6)?;
+    }
+    let frequency_index = r.bits(4)?;
+    let sample_rate = if frequency_index == 15 {
+        r.bits(24)?
+    } else {
+        *AAC_SAMPLE_RATES
+            .get(frequency_index as usize)
+            .ok_or("Invalid AAC sampling frequency index")?
+    };
+    let channels = r.bits(4)? as u8;
+    Ok(AudioConfig {
+        codec: format!("mp4a.40.{}", object_type),
+        sample_rate,
+        // Channel configuration 7 is 7.1; 0 means it's defined in-band, leave it as is
+        channels: if channels == 7 { 8 } else { channels },
+    })
+}
+
+// Non-AAC audio only has the coarse rate / channel bits of the tag header
+pub fn legacy_audio_config(flags: u8) -> AudioConfig {
+    let codec = match flags >> 4 {
+        2 => "mp3",
+        11 => "speex",
+        14 => "mp3",
+        0 | 3 => "pcm",
+        _ => "unknown",
+    };
+    let sample_rate = match (flags >> 2) & 0x03 {
+        0 => 5512,
+        1 => 11025,
+        2 => 22050,
+        _ => 44100,
+    };
+    AudioConfig {
+        codec: codec.to_string(),
+        sample_rate,
+        channels: if flags & 0x01 == 1 { 2 } else { 1 },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // High@4.0, 120x68 macroblocks cropped to 1920x1080, 30 fps timing in the VUI
+    const AVC_1080P: [u8; 38] = [
+        0x01, 0x64, 0x00, 0x28, 0xff, 0xe1, 0x00, 0x17, 0x67, 0x64, 0x00, 0x28, 0xac, 0xd9, 0x40,
+        0x78, 0x02, 0x27, 0xe5, 0x84, 0x00, 0x00, 0x03, 0x00, 0x04, 0x00, 0x00, 0x03, 0x00, 0xf2,
+        0x10, 0x01, 0x00, 0x04, 0x68, 0xeb, 0xe3, 0xcb,
+    ];
+
+    // Main, 1920x1088 cropped to 1920x1080
+    const HEVC_1080P: [u8; 53] = [
+        0x01, 0x01, 0x60, 0x00, 0x00, 0x00, 0x90, 0x00, 0x00, 0x00, 0x00, 0x00, 0x78, 0xf0, 0x00,
+        0xfc, 0xfd, 0xf8, 0xf8, 0x00, 0x00, 0x0f, 0x01, 0xa1, 0x00, 0x01, 0x00, 0x19, 0x42, 0x01,
+        0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00, 0x00, 0x03, 0x00,
+        0x78, 0xa0, 0x03, 0xc0, 0x80, 0x11, 0x07, 0xcb,
+    ];
+
+    #[test]
+    fn parses_avc_config() {
+        let config = parse_avc_config(&AVC_1080P).unwrap();
+        assert_eq!(
+            config,
+            VideoConfig {
+                codec: "avc1.640028".to_string(),
+                width: 1920,
+                height: 1080,
+                fps: Some(30.0),
+            }
+        );
+    }
+
+    #[test]
+    fn parses_hevc_config() {
+        let config = parse_hevc_config(&HEVC_1080P).unwrap();
+        assert_eq!(config.codec, "hvc1.1.6.L120.90");
+        assert_eq!((config.width, config.height), (1920, 1080));
+        assert_eq!(config.fps, None);
+    }
+
+    #[test]
+    fn rejects_impossible_avc_sizes() {
+        // pic_width_in_mbs_minus1 = 2^32 - 2
+        let too_wide = [
+            0x01, 0x64, 0x00, 0x28, 0xff, 0xe1, 0x00, 0x1e, 0x67, 0x64, 0x00, 0x28, 0xac, 0xd9,
+            0x40, 0x00, 0x00, 0x03, 0x00, 0x3f, 0xff, 0xff, 0xff, 0xc0, 0x89, 0xf9, 0x61, 0x00,
+            0x00, 0x03, 0x00, 0x01, 0x00, 0x00, 0x03, 0x00, 0x3c, 0x84, 0x01, 0x00, 0x04, 0x68,
+            0xeb, 0xe3, 0xcb,
+        ];
+        assert!(parse_avc_config(&too_wide).is_err());
+
+        // frame_crop_left_offset = frame_crop_right_offset = 2^32 - 2
+        let over_cropped = [
+            0x01, 0x64, 0x00, 0x28, 0xff, 0xe1, 0x00, 0x28, 0x67, 0x64, 0x00, 0x28, 0xac, 0xd9,
+            0x40, 0x78, 0x02, 0x27, 0x00, 0x00, 0x03, 0x00, 0x01, 0xff, 0xff, 0xff, 0xfe, 0x00,
+            0x00, 0x03, 0x00, 0x03, 0xff, 0xff, 0xff, 0xff, 0x84, 0x00, 0x00, 0x03, 0x00, 0x04,
+            0x00, 0x00, 0x03, 0x00, 0xf2, 0x10, 0x01, 0x00, 0x04, 0x68, 0xeb, 0xe3, 0xcb,
+        ];
+        assert!(parse_avc_config(&over_cropped).is_err());
+    }
+
+    #[test]
+    fn rejects_impossible_hevc_sizes() {
+        // conf_win_left_offset = conf_win_right_offset = 2^32 - 2
+        let over_cropped = [
+            0x01, 0x01, 0x60, 0x00, 0x00, 0x00, 0x90, 0x00, 0x00, 0x00, 0x00, 0x00, 0x78, 0xf0,
+            0x00, 0xfc, 0xfd, 0xf8, 0xf8, 0x00, 0x00, 0x0f, 0x01, 0xa1, 0x00, 0x01, 0x00, 0x2a,
+            0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00,
+            0x00, 0x03, 0x00, 0x78, 0xa0, 0x03, 0xc0, 0x80, 0x11, 0x06, 0x00, 0x00, 0x03, 0x00,
+            0x03, 0xff, 0xff, 0xff, 0xfc, 0x00, 0x00, 0x03, 0x00, 0x07, 0xff, 0xff, 0xff, 0xff,
+        ];
+        assert!(parse_hevc_config(&over_cropped).is_err());
+    }
+
+    #[test]
+    fn parses_aac_config() {
+        // AAC LC, 44.1 kHz, stereo
+        assert_eq!(
+            parse_aac_config(&[0x12, 0x10]).unwrap(),
+            AudioConfig {
+                codec: "mp4a.40.2".to_string(),
+                sample_rate: 44100,
+                channels: 2,
+            }
+        );
+        // HE-AAC with an explicit 48 kHz rate, 7.1
+        let config = parse_aac_config(&[0x2f, 0x80, 0x5d, 0xc0, 0x38]).unwrap();
+        assert_eq!(config.codec, "mp4a.40.5");
+        assert_eq!(config.sample_rate, 48000);
+        assert_eq!(config.channels, 8);
+        assert!(parse_aac_config(&[0x12]).is_err());
+    }
+
+    #[test]
+    fn reads_legacy_audio_flags() {
+        // MP3, 44 kHz, 16-bit, stereo
+        let config = legacy_audio_config(0x2f);
+        assert_eq!(config.codec, "mp3");
+        assert_eq!(config.sample_rate, 44100);
+        assert_eq!(config.channels, 2);
+    }
+}
@@ -0,0 +1,8 @@
+mod amf;
+mod codec;
+mod reader;
+mod stats;
+
+pub use codec::{parse_aac_config, parse_avc_config, parse_hevc_config};
+pub use reader::{FlvHeader, FlvItem, FlvReader, FlvTag, TAG_AUDIO, TAG_SCRIPT, TAG_VIDEO};
+pub use stats::{FlvStatsCollector, FlvStreamStats};
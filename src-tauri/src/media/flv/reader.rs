@@ -0,0 +1,347 @@
+// Splits an FLV byte stream into its header and tags.
+//
+// File: 9-byte header ("FLV", version, flags, u32 BE header size), u32 PreviousTagSize0,
+// then tags. Tag: u8 type, u24 BE body size, u24 BE timestamp + u8 timestamp extension
+// (upper bits), u24 stream id, body, then u32 BE size of the whole tag (11 + body).
+//
+// Video bodies start with frame type (upper 4 bits) and codec id (lower 4 bits). With the
+// Enhanced RTMP extension (top bit set) the lower bits are a packet type instead and a
+// FourCC ("hvc1", "av01", ...) follows.
+
+const TAG_HEADER_LEN: usize = 11;
+const PREV_TAG_SIZE_LEN: usize = 4;
+// A live stream's header is always 9 bytes; anything much bigger is garbage
+const MAX_HEADER_LEN: usize = 64;
+
+pub const TAG_AUDIO: u8 = 8;
+pub const TAG_VIDEO: u8 = 9;
+pub const TAG_SCRIPT: u8 = 18;
+
+pub const CODEC_AVC: u8 = 7;
+pub const CODEC_HEVC: u8 = 12; // Not in the spec, but what Chinese CDNs use for H.265 in FLV
+pub const SOUND_FORMAT_AAC: u8 = 10;
+
+const FRAME_TYPE_KEY: u8 = 1;
+const EX_HEADER_FLAG: u8 = 0x80;
+const EX_PACKET_SEQUENCE_START: u8 = 0;
+const EX_PACKET_CODED_FRAMES: u8 = 1;
+const EX_PACKET_CODED_FRAMES_X: u8 = 3; // Coded frames with a zero composition time
+const AVC_PACKET_NALU: u8 = 1;
+
+#[derive(Clone)]
+pub struct FlvHeader {
+    pub has_video: bool,
+    pub bytes: Vec<u8>, // Including PreviousTagSize0, ready to start a new file with
+}
+
+pub struct FlvTag {
+    pub tag_type: u8,
+    pub timestamp: u32, // Milliseconds
+    pub offset: u64,    // Byte position of the tag in the stream
+    pub body: Vec<u8>,
+}
+
+impl FlvTag {
+    fn is_enhanced_video(&self) -> bool {
+        self.tag_type == TAG_VIDEO && self.body.first().map_or(false, |b| b & EX_HEADER_FLAG != 0)
+    }
+
+    pub fn is_video_keyframe(&self) -> bool {
+        self.tag_type == TAG_VIDEO
+            && self
+                .body
+                .first()
+                .map_or(false, |b| (b >> 4) & 0x07 == FRAME_TYPE_KEY)
+    }
+
+    // AVC/HEVC decoder configuration or AAC AudioSpecificConfig; every file needs these
+    // before the first frame that uses them
+    pub fn is_sequence_header(&self) -> bool {
+        if self.is_enhanced_video() {
+            return self.body[0] & 0x0F == EX_PACKET_SEQUENCE_START;
+        }
+        match (self.tag_type, self.body.first(), self.body.get(1)) {
+            (TAG_VIDEO, Some(b), Some(0)) => matches!(b & 0x0F, CODEC_AVC | CODEC_HEVC),
+            (TAG_AUDIO, Some(b), Some(0)) => b >> 4 == SOUND_FORMAT_AAC,
+            _ => false,
+        }
+    }
+
+    // A picture, as opposed to a sequence header, end of sequence or metadata packet
+    pub fn is_video_frame(&self) -> bool {
+        if self.is_enhanced_video() {
+            return matches!(
+                self.body[0] & 0x0F,
+                EX_PACKET_CODED_FRAMES | EX_PACKET_CODED_FRAMES_X
+            );
+        }
+        self.tag_type == TAG_VIDEO && self.body.get(1) == Some(&AVC_PACKET_NALU)
+    }
+
+    // Signed pts - dts offset of a video frame in milliseconds
+    pub fn composition_time(&self) -> i32 {
+        let bytes = if self.is_enhanced_video() {
+            if self.body[0] & 0x0F != EX_PACKET_CODED_FRAMES {
+                return 0;
+            }
+            self.body.get(5..8)
+        } else if self.tag_type == TAG_VIDEO {
+            self.body.get(2..5)
+        } else {
+            None
+        };
+        // SI24: shift through the top byte to sign-extend
+        bytes.map_or(0, |b| i32::from_be_bytes([b[0], b[1], b[2], 0]) >> 8)
+    }
+
+    // Legacy codec id, or the FourCC of an Enhanced RTMP tag
+    pub fn video_codec(&self) -> Option<VideoCodecId> {
+        let first = *self.body.first()?;
+        if self.tag_type != TAG_VIDEO {
+            return None;
+        }
+        if self.is_enhanced_video() {
+            let fourcc: [u8; 4] = self.body.get(1..5)?.try_into().ok()?;
+            return Some(VideoCodecId::FourCc(fourcc));
+        }
+        Some(VideoCodecId::Legacy(first & 0x0F))
+    }
+
+    // The codec payload: what follows the AVC/HEVC packet type and composition time, the
+    // Enhanced RTMP FourCC (and composition time for coded frames), or the AAC packet type
+    pub fn payload(&self) -> &[u8] {
+        let skip = match self.tag_type {
+            TAG_VIDEO if self.is_enhanced_video() => {
+                if self.body[0] & 0x0F == EX_PACKET_CODED_FRAMES {
+                    8
+                } else {
+                    5
+                }
+            }
+            TAG_VIDEO => 5,
+            TAG_AUDIO
+                if self
+                    .body
+                    .first()
+                    .map_or(false, |b| b >> 4 == SOUND_FORMAT_AAC) =>
+            {
+                2
+            }
+            TAG_AUDIO => 1,
+            _ => 0,
+        };
+        self.body.get(skip..).unwrap_or(&[])
+    }
+
+    // The tag as it appears in a file, including its trailing PreviousTagSize
+    pub fn encode(&self, timestamp: u32) -> Vec<u8> {
+        let body_len = self.body.len() as u32;
+        let mut out = Vec::with_capacity(TAG_HEADER_LEN + self.body.len() + PREV_TAG_SIZE_LEN);
+        out.push(self.tag_type);
+        out.extend_from_slice(&body_len.to_be_bytes()[1..]);
+        out.extend_from_slice(&timestamp.to_be_bytes()[1..]);
+        out.push((timestamp >> 24) as u8);
+        out.extend_from_slice(&[0, 0, 0]); // Stream id, always 0
+        out.extend_from_slice(&self.body);
+        out.extend_from_slice(&(TAG_HEADER_LEN as u32 + body_len).to_be_bytes());
+        out
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoCodecId {
+    Legacy(u8),
+    FourCc([u8; 4]),
+}
+
+impl VideoCodecId {
+    pub fn is_avc(&self) -> bool {
+        *self == VideoCodecId::Legacy(CODEC_AVC) || *self == VideoCodecId::FourCc(*b"avc1")
+    }
+
+    pub fn is_hevc(&self) -> bool {
+        *self == VideoCodecId::Legacy(CODEC_HEVC) || *self == VideoCodecId::FourCc(*b"hvc1")
+    }
+}
+
+pub enum FlvItem {
+    Header(FlvHeader),
+    Tag(FlvTag),
+}
+
+// Feed it chunks as they arrive; partial items are buffered until the rest comes in
+#[derive(Default)]
+pub struct FlvReader {
+    buffer: Vec<u8>,
+    consumed: u64, // Stream bytes drained from the front of `buffer`
+    header_read: bool,
+}
+
+impl FlvReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<FlvItem>, String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut items = Vec::new();
+        let mut offset = 0;
+        if !self.header_read {
+            if self.buffer.len() < 9 {
+                return Ok(items);
+            }
+            if &self.buffer[..3] != b"FLV" {
+                return Err("Upstream did not return an FLV stream".to_string());
+            }
+            let header_len = u32::from_be_bytes([
+                self.buffer[5],
+                self.buffer[6],
+                self.buffer[7],
+                self.buffer[8],
+            ]) as usize;
+            if !(9..=MAX_HEADER_LEN).contains(&header_len) {
+                return Err(format!("Invalid FLV header size {}", header_len));
+            }
+            let total = header_len + PREV_TAG_SIZE_LEN;
+            if self.buffer.len() < total {
+                return Ok(items);
+            }
+            let flags = self.buffer[4];
+            items.push(FlvItem::Header(FlvHeader {
+                has_video: flags & 0x01 != 0,
+                bytes: self.buffer[..total].to_vec(),
+            }));
+            self.header_read = true;
+            offset = total;
+        }
+
+        while self.buffer.len() - offset >= TAG_HEADER_LEN {
+            let mut header = [0u8; TAG_HEADER_LEN];
+            header.copy_from_slice(&self.buffer[offset..offset + TAG_HEADER_LEN]);
+            let tag_type = header[0] & 0x1F; // Upper bits are the reserved / filter flags
+            if !matches!(tag_type, TAG_AUDIO | TAG_VIDEO | TAG_SCRIPT) {
+                // Nothing after a bad tag header can be trusted
+                self.buffer.clear();
+                return Err(format!("Invalid FLV tag type {}", header[0]));
+            }
+            let body_len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+            let total = TAG_HEADER_LEN + body_len + PREV_TAG_SIZE_LEN;
+            if self.buffer.len() - offset < total {
+                break; // Rest of the tag is in a later chunk
+            }
+            let timestamp = u32::from_be_bytes([header[7], header[4], header[5], header[6]]);
+            let body_start = offset + TAG_HEADER_LEN;
+            items.push(FlvItem::Tag(FlvTag {
+                tag_type,
+                timestamp,
+                offset: self.consumed + offset as u64,
+                body: self.buffer[body_start..body_start + body_len].to_vec(),
+            }));
+            offset += total;
+        }
+
+        self.buffer.drain(..offset);
+        self.consumed += offset as u64;
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // onMetaData, AVC and AAC sequence headers, then 2s of 30 fps video with a keyframe
+    // every second and AAC frames in between
+    const SAMPLE: &[u8] = include_bytes!("fixtures/sample.flv");
+
+    fn tags(items: Vec<FlvItem>) -> Vec<FlvTag> {
+        items
+            .into_iter()
+            .filter_map(|item| match item {
+                FlvItem::Tag(tag) => Some(tag),
+                FlvItem::Header(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_header_and_tags() {
+        let items = FlvReader::new().push(SAMPLE).unwrap();
+        let Some(FlvItem::Header(header)) = items.first() else {
+            panic!("expected the header first");
+        };
+        assert!(header.has_video);
+        assert_eq!(header.bytes.len(), 13);
+
+        let tags = tags(items);
+        assert_eq!(tags.len(), 95);
+        assert_eq!(tags[0].tag_type, TAG_SCRIPT);
+        assert!(tags[1].is_sequence_header());
+        assert!(tags[1].video_codec().unwrap().is_avc());
+        assert!(tags[2].is_sequence_header());
+        assert_eq!(tags[2].payload(), &[0x12, 0x10]);
+
+        let frame = &tags[3];
+        assert!(frame.is_video_frame() && frame.is_video_keyframe());
+        assert_eq!(frame.composition_time(), 66);
+        assert_eq!(frame.payload(), &[0, 0, 0, 3, 0x65, 0x88, 0x84]);
+
+        let last = tags.last().unwrap();
+        assert_eq!(last.timestamp, 1980);
+        assert_eq!(
+            last.offset as usize + 11 + last.body.len() + 4,
+            SAMPLE.len()
+        );
+    }
+
+    #[test]
+    fn buffers_partial_chunks() {
+        let whole = tags(FlvReader::new().push(SAMPLE).unwrap());
+        let mut reader = FlvReader::new();
+        let mut split = Vec::new();
+        for chunk in SAMPLE.chunks(7) {
+            split.extend(tags(reader.push(chunk).unwrap()));
+        }
+        assert_eq!(split.len(), whole.len());
+        for (a, b) in split.iter().zip(&whole) {
+            assert_eq!(
+                (a.offset, a.timestamp, &a.body),
+                (b.offset, b.timestamp, &b.body)
+            );
+        }
+    }
+
+    #[test]
+    fn encodes_tags_as_read() {
+        for tag in tags(FlvReader::new().push(SAMPLE).unwrap()) {
+            let start = tag.offset as usize;
+            let encoded = tag.encode(tag.timestamp);
+            assert_eq!(encoded, &SAMPLE[start..start + encoded.len()]);
+        }
+    }
+
+    #[test]
+    fn reads_extended_timestamps_and_negative_composition_times() {
+        let tag = FlvTag {
+            tag_type: TAG_VIDEO,
+            timestamp: 0x0123_4567,
+            offset: 0,
+            body: vec![0x27, 1, 0xff, 0xff, 0xdf, 0, 0, 0, 0],
+        };
+        assert_eq!(tag.composition_time(), -33);
+        let mut stream = SAMPLE[..13].to_vec();
+        stream.extend(tag.encode(tag.timestamp));
+        let read = tags(FlvReader::new().push(&stream).unwrap());
+        assert_eq!(read[0].timestamp, 0x0123_4567);
+    }
+
+    #[test]
+    fn rejects_bad_streams() {
+        assert!(FlvReader::new().push(b"<html>not a stream</html>").is_err());
+
+        let mut reader = FlvReader::new();
+        reader.push(&SAMPLE[..13]).unwrap();
+        assert!(reader.push(&[0x07; 16]).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+// Running statistics for an FLV stream, fed the same chunks the player gets.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::amf::parse_script_tag;
+use super::codec::{legacy_audio_config, parse_aac_config, parse_avc_config, parse_hevc_config};
+use super::reader::{
+    FlvItem, FlvReader, FlvTag, SOUND_FORMAT_AAC, TAG_AUDIO, TAG_SCRIPT, TAG_VIDEO,
+};
+
+// Enough to seek around the last few minutes of a typical 2-4s GOP stream
+const MAX_KEYFRAMES: usize = 100;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyframePosition {
+    pub timestamp_ms: u32,
+    pub offset: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlvStreamStats {
+    pub video_codec: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fps: Option<f64>,
+    pub audio_codec: Option<String>,
+    pub audio_sample_rate: Option<u32>,
+    pub audio_channels: Option<u8>,
+    pub bitrate_kbps: Option<u32>, // Measured over the whole session, not taken from metadata
+    pub duration_ms: u32,
+    pub bytes: u64,
+    pub video_frames: u64,
+    pub keyframes: Vec<KeyframePosition>, // Most recent MAX_KEYFRAMES
+    pub metadata: Option<Value>,          // Raw onMetaData
+    pub encoder: Option<String>,
+    pub error: Option<String>, // Parsing stopped here; the stream itself may still play
+}
+
+#[derive(Default)]
+pub struct FlvStatsCollector {
+    reader: FlvReader,
+    stats: FlvStreamStats,
+    first_timestamp: Option<u32>,
+    last_timestamp: u32,
+}
+
+impl FlvStatsCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.stats.bytes += chunk.len() as u64;
+        if self.stats.error.is_some() {
+            return;
+        }
+        match self.reader.push(chunk) {
+            Ok(items) => {
+                for item in items {
+                    if let FlvItem::Tag(tag) = item {
+                        self.on_tag(&tag);
+                    }
+                }
+            }
+            Err(e) => self.stats.error = Some(e),
+        }
+    }
+
+    fn on_tag(&mut self, tag: &FlvTag) {
+        if tag.tag_type != TAG_SCRIPT {
+            self.first_timestamp.get_or_insert(tag.timestamp);
+            self.last_timestamp = self.last_timestamp.max(tag.timestamp);
+        }
+        match tag.tag_type {
+            TAG_VIDEO if tag.is_sequence_header() => self.on_video_config(tag),
+            TAG_VIDEO => {
+                self.stats.video_frames += 1;
+                if tag.is_video_keyframe() {
+                    if self.stats.keyframes.len() == MAX_KEYFRAMES {
+                        self.stats.keyframes.remove(0);
+                    }
+                    self.stats.keyframes.push(KeyframePosition {
+                        timestamp_ms: tag.timestamp,
+                        offset: tag.offset,
+                    });
+                }
+            }
+            TAG_AUDIO if tag.is_sequence_header() => match parse_aac_config(tag.payload()) {
+                Ok(config) => {
+                    self.stats.audio_codec = Some(config.codec);
+                    self.stats.audio_sample_rate = Some(config.sample_rate);
+                    self.stats.audio_channels = Some(config.channels);
+                }
+                Err(e) => eprintln!("[FLV stats] Bad AAC config: {}", e),
+            },
+            TAG_AUDIO if self.stats.audio_codec.is_none() => {
+                // AAC is described by its sequence header; anything else by the flag bits
+                if let Some(&flags) = tag.body.first() {
+                    if flags >> 4 != SOUND_FORMAT_AAC {
+                        let config = legacy_audio_config(flags);
+                        self.stats.audio_codec = Some(config.codec);
+                        self.stats.audio_sample_rate = Some(config.sample_rate);
+                        self.stats.audio_channels = Some(config.channels);
+                    }
+                }
+            }
+            TAG_SCRIPT => match parse_script_tag(&tag.body) {
+                Ok((name, value)) if name == "onMetaData" => {
+                    self.stats.encoder = value
+                        .get("encoder")
+                        .and_then(Value::as_str)
+                        .map(str::to_string);
+                    self.stats.metadata = Some(value);
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("[FLV stats] Bad script tag: {}", e),
+            },
+            _ => {}
+        }
+    }
+
+    fn on_video_config(&mut self, tag: &FlvTag) {
+        let Some(codec) = tag.video_codec() else {
+            return;
+        };
+        let parsed = if codec.is_avc() {
+            parse_avc_config(tag.payload())
+        } else if codec.is_hevc() {
+            parse_hevc_config(tag.payload())
+        } else {
+            Err(format!("Unsupported video codec {:?}", codec))
+        };
+        match parsed {
+            Ok(config) => {
+                self.stats.video_codec = Some(config.codec);
+                self.stats.width = Some(config.width);
+                self.stats.height = Some(config.height);
+                self.stats.fps = config.fps;
+            }
+            Err(e) => eprintln!("[FLV stats] Bad video config: {}", e),
+        }
+    }
+
+    pub fn snapshot(&self) -> FlvStreamStats {
+        let mut stats = self.stats.clone();
+        let duration_ms = self
+            .first_timestamp
+            .map_or(0, |first| self.last_timestamp.saturating_sub(first));
+        stats.duration_ms = duration_ms;
+        if duration_ms > 0 {
+            stats.bitrate_kbps = Some((stats.bytes * 8 / duration_ms as u64) as u32);
+        }
+        // Not every SPS carries timing info; fall back to metadata, then to what we've seen
+        if stats.fps.is_none() {
+            stats.fps = stats
+                .metadata
+                .as_ref()
+                .and_then(|m| m.get("framerate"))
+                .and_then(Value::as_f64)
+                .filter(|fps| *fps > 0.0)
+                .or_else(|| {
+                    (duration_ms >= 1000 && stats.video_frames > 0).then(|| {
+                        let fps = stats.video_frames as f64 * 1000.0 / duration_ms as f64;
+                        (fps * 100.0).round() / 100.0
+                    })
+                });
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_stream_stats() {
+        let sample = include_bytes!("fixtures/sample.flv");
+        let mut collector = FlvStatsCollector::new();
+        for chunk in sample.chunks(100) {
+            collector.push(chunk);
+        }
+        let stats = collector.snapshot();
+        assert_eq!(stats.error, None);
+        assert_eq!(stats.video_codec.as_deref(), Some("avc1.640028"));
+        assert_eq!((stats.width, stats.height), (Some(1920), Some(1080)));
+        assert_eq!(stats.fps, Some(30.0));
+        assert_eq!(stats.audio_codec.as_deref(), Some("mp4a.40.2"));
+        assert_eq!(stats.audio_sample_rate, Some(44100));
+        assert_eq!(stats.audio_channels, Some(2));
+        assert_eq!(
+            stats.encoder.as_deref(),
+            Some("obs-output module (libobs version 30.0.2)")
+        );
+        assert_eq!(stats.duration_ms, 1980);
+        assert_eq!(stats.bytes, sample.len() as u64);
+        assert_eq!(
+            stats.bitrate_kbps,
+            Some((sample.len() as u64 * 8 / 1980) as u32)
+        );
+        assert_eq!(stats.video_frames, 61);
+        let keyframes: Vec<u32> = stats.keyframes.iter().map(|k| k.timestamp_ms).collect();
+        assert_eq!(keyframes, vec![0, 990, 1980]);
+    }
+
+    #[test]
+    fn stops_at_a_broken_stream() {
+        let sample = include_bytes!("fixtures/sample.flv");
+        let mut collector = FlvStatsCollector::new();
+        collector.push(&sample[..13]);
+        collector.push(&[0x07; 16]);
+        collector.push(&sample[13..]);
+        let stats = collector.snapshot();
+        assert!(stats.error.is_some());
+        assert_eq!(stats.video_frames, 0);
+        assert_eq!(stats.bytes, sample.len() as u64 + 16);
+    }
+}
@@ -0,0 +1,345 @@
+// ISO BMFF box writers for fragmented MP4: an init segment (ftyp + moov with empty sample
+// tables), moof + mdat fragments, and the mfra index that makes finished files seekable.
+
+// Identity transform for mvhd / tkhd
+const MATRIX: [u32; 9] = [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000];
+const LANGUAGE_UND: u16 = 0x55C4; // Packed ISO 639-2 "und"
+
+pub const SAMPLE_FLAGS_SYNC: u32 = 0x0200_0000; // Depends on no other sample
+pub const SAMPLE_FLAGS_NON_SYNC: u32 = 0x0101_0000; // Depends on others, not a sync sample
+
+fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(8 + payload.len());
+    out.extend_from_slice(&(8 + payload.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(payload);
+    out
+}
+
+fn full_box(kind: &[u8; 4], version: u8, flags: u32, payload: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(4 + payload.len());
+    body.push(version);
+    body.extend_from_slice(&flags.to_be_bytes()[1..]);
+    body.extend_from_slice(payload);
+    mp4_box(kind, &body)
+}
+
+fn u16s(out: &mut Vec<u8>, values: &[u16]) {
+    for value in values {
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+fn u32s(out: &mut Vec<u8>, values: &[u32]) {
+    for value in values {
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+pub struct TrackInfo {
+    pub id: u32,
+    pub timescale: u32,
+    pub is_video: bool,
+    pub width: u32,
+    pub height: u32,
+    pub sample_entry: Vec<u8>,
+}
+
+// avc1 / hvc1 entry wrapping the FLV sequence header's decoder configuration record
+pub fn visual_sample_entry(
+    kind: &[u8; 4],
+    config_kind: &[u8; 4],
+    record: &[u8],
+    width: u32,
+    height: u32,
+) -> Vec<u8> {
+    let mut body = vec![0u8; 6]; // Reserved
+    u16s(&mut body, &[1]); // data_reference_index
+    body.extend_from_slice(&[0u8; 16]); // pre_defined / reserved
+    u16s(&mut body, &[width as u16, height as u16]);
+    u32s(&mut body, &[0x0048_0000, 0x0048_0000, 0]); // 72 dpi, reserved
+    u16s(&mut body, &[1]); // frame_count
+    body.extend_from_slice(&[0u8; 32]); // compressorname
+    u16s(&mut body, &[0x0018, 0xFFFF]); // depth, pre_defined
+    body.extend_from_slice(&mp4_box(config_kind, record));
+    mp4_box(kind, &body)
+}
+
+// Descriptor with a single-byte length; everything written here is well under 128 bytes
+fn descriptor(tag: u8, payload: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag, payload.len() as u8];
+    out.extend_from_slice(payload);
+    out
+}
+
+// mp4a entry carrying the AudioSpecificConfig in an esds
+pub fn mp4a_sample_entry(audio_specific_config: &[u8], channels: u8, sample_rate: u32) -> Vec<u8> {
+    let mut decoder_config = vec![0x40, 0x15]; // MPEG-4 audio, audio stream
+    decoder_config.extend_from_slice(&[0, 0, 0]); // bufferSizeDB
+    u32s(&mut decoder_config, &[0, 0]); // max / avg bitrate, unknown
+    decoder_config.extend_from_slice(&descriptor(0x05, audio_specific_config));
+
+    let mut es = Vec::new();
+    u16s(&mut es, &[0]); // ES_ID
+    es.push(0); // No stream dependence, URL or OCR
+    es.extend_from_slice(&descriptor(0x04, &decoder_config));
+    es.extend_from_slice(&descriptor(0x06, &[0x02])); // SLConfig, predefined MP4
+    let esds = full_box(b"esds", 0, 0, &descriptor(0x03, &es));
+
+    let mut body = vec![0u8; 6];
+    u16s(&mut body, &[1]); // data_reference_index
+    body.extend_from_slice(&[0u8; 8]);
+    u16s(&mut body, &[channels as u16, 16, 0, 0]); // channelcount, samplesize
+                                                   // 16.16 fixed point; rates above 65535 Hz don't fit, the esds carries the real one
+    u32s(
+        &mut body,
+        &[if sample_rate > 0xFFFF {
+            0
+        } else {
+            sample_rate << 16
+        }],
+    );
+    body.extend_from_slice(&esds);
+    mp4_box(b"mp4a", &body)
+}
+
+fn trak(track: &TrackInfo) -> Vec<u8> {
+    let mut tkhd = Vec::new();
+    u32s(&mut tkhd, &[0, 0, track.id, 0, 0, 0, 0]); // Times, id, reserved, duration, reserved
+    u16s(
+        &mut tkhd,
+        &[0, 0, if track.is_video { 0 } else { 0x0100 }, 0],
+    );
+    u32s(&mut tkhd, &MATRIX);
+    u32s(&mut tkhd, &[track.width << 16, track.height << 16]);
+    let tkhd = full_box(b"tkhd", 0, 0x000003, &tkhd); // Enabled, in movie
+
+    let mut mdhd = Vec::new();
+    u32s(&mut mdhd, &[0, 0, track.timescale, 0]);
+    u16s(&mut mdhd, &[LANGUAGE_UND, 0]);
+    let mdhd = full_box(b"mdhd", 0, 0, &mdhd);
+
+    let (handler, name, media_header) = if track.is_video {
+        (
+            b"vide",
+            &b"VideoHandler\0"[..],
+            full_box(b"vmhd", 0, 1, &[0u8; 8]),
+        )
+    } else {
+        (
+            b"soun",
+            &b"SoundHandler\0"[..],
+            full_box(b"smhd", 0, 0, &[0u8; 4]),
+        )
+    };
+    let mut hdlr = vec![0u8; 4];
+    hdlr.extend_from_slice(handler);
+    hdlr.extend_from_slice(&[0u8; 12]);
+    hdlr.extend_from_slice(name);
+    let hdlr = full_box(b"hdlr", 0, 0, &hdlr);
+
+    let url = full_box(b"url ", 0, 1, &[]); // Media is in this file
+    let mut dref = 1u32.to_be_bytes().to_vec();
+    dref.extend_from_slice(&url);
+    let dinf = mp4_box(b"dinf", &full_box(b"dref", 0, 0, &dref));
+
+    // Samples live in the fragments, so every table here is empty
+    let mut stsd = 1u32.to_be_bytes().to_vec();
+    stsd.extend_from_slice(&track.sample_entry);
+    let stbl = [
+        full_box(b"stsd", 0, 0, &stsd),
+        full_box(b"stts", 0, 0, &[0u8; 4]),
+        full_box(b"stsc", 0, 0, &[0u8; 4]),
+        full_box(b"stsz", 0, 0, &[0u8; 8]),
+        full_box(b"stco", 0, 0, &[0u8; 4]),
+    ]
+    .concat();
+
+    let minf = mp4_box(
+        b"minf",
+        &[media_header, dinf, mp4_box(b"stbl", &stbl)].concat(),
+    );
+    let mdia = mp4_box(b"mdia", &[mdhd, hdlr, minf].concat());
+    mp4_box(b"trak", &[tkhd, mdia].concat())
+}
+
+// ftyp + moov. Also returns where the mvhd and mehd durations sit, so a finished file can
+// have them filled in.
+pub fn init_segment(tracks: &[&TrackInfo]) -> (Vec<u8>, [usize; 2]) {
+    let mut ftyp = Vec::new();
+    ftyp.extend_from_slice(b"isom");
+    u32s(&mut ftyp, &[0x200]);
+    ftyp.extend_from_slice(b"isomiso6mp41");
+    let ftyp = mp4_box(b"ftyp", &ftyp);
+
+    let next_track_id = tracks.iter().map(|t| t.id).max().unwrap_or(0) + 1;
+    let mut mvhd = Vec::new();
+    u32s(&mut mvhd, &[0, 0, super::MOVIE_TIMESCALE, 0, 0x0001_0000]);
+    u16s(&mut mvhd, &[0x0100, 0]); // Volume, reserved
+    u32s(&mut mvhd, &[0, 0]);
+    u32s(&mut mvhd, &MATRIX);
+    u32s(&mut mvhd, &[0; 6]);
+    u32s(&mut mvhd, &[next_track_id]);
+    let mvhd = full_box(b"mvhd", 0, 0, &mvhd);
+    // Header (8), version/flags (4), creation and modification times (8), timescale (4)
+    let mvhd_duration = ftyp.len() + 8 + 24;
+
+    let traks: Vec<u8> = tracks.iter().flat_map(|track| trak(track)).collect();
+
+    let mehd = full_box(b"mehd", 0, 0, &[0u8; 4]);
+    let mut mvex = mehd.clone();
+    for track in tracks {
+        let mut trex = Vec::new();
+        u32s(&mut trex, &[track.id, 1, 0, 0, 0]);
+        mvex.extend_from_slice(&full_box(b"trex", 0, 0, &trex));
+    }
+    let mehd_duration = ftyp.len() + 8 + mvhd.len() + traks.len() + 8 + 12;
+    let moov = mp4_box(b"moov", &[mvhd, traks, mp4_box(b"mvex", &mvex)].concat());
+
+    ([ftyp, moov].concat(), [mvhd_duration, mehd_duration])
+}
+
+pub struct FragmentSample<'a> {
+    pub duration: u32,
+    pub flags: u32,
+    pub composition_offset: i32,
+    pub data: &'a [u8],
+}
+
+// One track's samples as a moof + mdat pair
+pub fn fragment(
+    sequence: u32,
+    track_id: u32,
+    base_decode_time: u64,
+    samples: &[FragmentSample],
+) -> Vec<u8> {
+    let build_moof = |data_offset: u32| {
+        let mut trun = Vec::new();
+        u32s(&mut trun, &[samples.len() as u32, data_offset]);
+        for sample in samples {
+            u32s(
+                &mut trun,
+                &[sample.duration, sample.data.len() as u32, sample.flags],
+            );
+            trun.extend_from_slice(&sample.composition_offset.to_be_bytes());
+        }
+        // Version 1 for signed composition offsets; data offset and per-sample duration,
+        // size, flags and composition offset present
+        let trun = full_box(b"trun", 1, 0x000F01, &trun);
+        // default-base-is-moof, so data offsets are relative to this moof
+        let tfhd = full_box(b"tfhd", 0, 0x020000, &track_id.to_be_bytes());
+        let tfdt = full_box(b"tfdt", 1, 0, &base_decode_time.to_be_bytes());
+        let traf = mp4_box(b"traf", &[tfhd, tfdt, trun].concat());
+        let mfhd = full_box(b"mfhd", 0, 0, &sequence.to_be_bytes());
+        mp4_box(b"moof", &[mfhd, traf].concat())
+    };
+    // The offset doesn't change the moof's size, so measure with a placeholder first
+    let moof_len = build_moof(0).len();
+    let moof = build_moof(moof_len as u32 + 8);
+    let data: Vec<u8> = samples
+        .iter()
+        .flat_map(|s| s.data.iter().copied())
+        .collect();
+    [moof, mp4_box(b"mdat", &data)].concat()
+}
+
+// Random access index: for each track, (decode time, moof offset) of fragments that start
+// with a sync sample
+pub fn mfra(tracks: &[(u32, &[(u64, u64)])]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (track_id, entries) in tracks {
+        let mut tfra = Vec::new();
+        // Traf, trun and sample numbers are one byte each
+        u32s(&mut tfra, &[*track_id, 0, entries.len() as u32]);
+        for (time, offset) in entries.iter() {
+            tfra.extend_from_slice(&time.to_be_bytes());
+            tfra.extend_from_slice(&offset.to_be_bytes());
+            tfra.extend_from_slice(&[1, 1, 1]);
+        }
+        body.extend_from_slice(&full_box(b"tfra", 1, 0, &tfra));
+    }
+    // mfro holds the size of the whole mfra so readers can find it from the end of the file
+    let mfra_len = 8 + body.len() as u32 + 16;
+    body.extend_from_slice(&full_box(b"mfro", 0, 0, &mfra_len.to_be_bytes()));
+    mp4_box(b"mfra", &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn init_segment_durations_point_into_mvhd_and_mehd() {
+        let track = TrackInfo {
+            id: 1,
+            timescale: 1000,
+            is_video: true,
+            width: 1920,
+            height: 1080,
+            sample_entry: mp4_box(b"avc1", &[]),
+        };
+        let (init, [mvhd, mehd]) = init_segment(&[&track]);
+        assert_eq!(&init[4..8], b"ftyp");
+        let ftyp_len = u32_at(&init, 0) as usize;
+        assert_eq!(&init[ftyp_len + 4..ftyp_len + 8], b"moov");
+        assert_eq!(ftyp_len + u32_at(&init, ftyp_len) as usize, init.len());
+        // Size, type, version/flags, creation and modification times, timescale, duration
+        assert_eq!(&init[mvhd - 20..mvhd - 16], b"mvhd");
+        assert_eq!(u32_at(&init, mvhd - 4), super::super::MOVIE_TIMESCALE);
+        // Size, type, version/flags, duration
+        assert_eq!(&init[mehd - 8..mehd - 4], b"mehd");
+        assert_eq!(u32_at(&init, mehd), 0);
+    }
+
+    #[test]
+    fn fragment_points_trun_at_its_samples() {
+        let samples = [
+            FragmentSample {
+                duration: 33,
+                flags: SAMPLE_FLAGS_SYNC,
+                composition_offset: 66,
+                data: &[1, 2, 3],
+            },
+            FragmentSample {
+                duration: 34,
+                flags: SAMPLE_FLAGS_NON_SYNC,
+                composition_offset: -33,
+                data: &[4, 5],
+            },
+        ];
+        let out = fragment(7, 2, 90_000, &samples);
+        // moof: mfhd (16) and traf: tfhd (16), tfdt (20), trun (20 + 16 per sample)
+        let moof_len = 8 + 16 + 8 + 16 + 20 + 20 + 16 * 2;
+        assert_eq!(u32_at(&out, 0), moof_len as u32);
+        assert_eq!(u32_at(&out, 20), 7); // mfhd sequence
+        assert_eq!(u32_at(&out, 44), 2); // tfhd track id
+        assert_eq!(u64::from_be_bytes(out[60..68].try_into().unwrap()), 90_000);
+        assert_eq!(&out[72..76], b"trun");
+        assert_eq!(u32_at(&out, 80), 2);
+        assert_eq!(u32_at(&out, 84), moof_len as u32 + 8); // First byte of the mdat payload
+        assert_eq!(
+            [
+                u32_at(&out, 88),
+                u32_at(&out, 92),
+                u32_at(&out, 104),
+                u32_at(&out, 108)
+            ],
+            [33, 3, 34, 2]
+        );
+        assert_eq!(u32_at(&out, 116) as i32, -33);
+        assert_eq!(&out[moof_len + 4..moof_len + 8], b"mdat");
+        assert_eq!(&out[moof_len + 8..], [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn mfro_holds_the_mfra_size() {
+        let entries = [(0, 0), (990, 12_345)];
+        let out = mfra(&[(1, &entries), (2, &[])]);
+        assert_eq!(u32_at(&out, 0), out.len() as u32);
+        assert_eq!(u32_at(&out, out.len() - 4), out.len() as u32);
+    }
+}
@@ -0,0 +1,7 @@
+mod boxes;
+mod remuxer;
+
+pub use remuxer::Fmp4Remuxer;
+
+// mvhd / mehd durations are in milliseconds
+const MOVIE_TIMESCALE: u32 = 1000;
@@ -0,0 +1,575 @@
+// Turns FLV tags into fragmented MP4 without re-encoding. AVC/HEVC frames in FLV are
+// already length-prefixed NAL units and AAC frames are raw, so samples are copied as they
+// are; only the containers change.
+//
+// Output starts at the first video keyframe (the first audio frame for audio-only
+// streams). Each track is fragmented on its own: video at every keyframe, and both
+// tracks at least every MAX_FRAGMENT_MS so live playback isn't a whole GOP behind.
+
+use super::boxes::{
+    fragment, init_segment, mfra, mp4a_sample_entry, visual_sample_entry, FragmentSample,
+    TrackInfo, SAMPLE_FLAGS_NON_SYNC, SAMPLE_FLAGS_SYNC,
+};
+use super::MOVIE_TIMESCALE;
+use crate::media::flv::{
+    parse_aac_config, parse_avc_config, parse_hevc_config, FlvItem, FlvTag, TAG_AUDIO, TAG_VIDEO,
+};
+
+const VIDEO_TRACK_ID: u32 = 1;
+const AUDIO_TRACK_ID: u32 = 2;
+const MAX_FRAGMENT_MS: u32 = 500;
+// Used for a track's final sample when it has no successor to measure against
+const DEFAULT_VIDEO_FRAME_MS: u32 = 40;
+const AAC_FRAME_SAMPLES: u32 = 1024;
+
+struct Sample {
+    timestamp: u32, // FLV milliseconds
+    composition_offset: i32,
+    sync: bool,
+    data: Vec<u8>,
+}
+
+struct Track {
+    info: TrackInfo,
+    config: Vec<u8>, // The sequence header payload this track was set up from
+    pending: Vec<Sample>,
+    last_duration: u32,
+    end_time: u64,                  // Decode time after the last written sample
+    random_access: Vec<(u64, u64)>, // (decode time, moof offset) of sync fragments
+}
+
+impl Track {
+    // Timestamps relative to the first output sample, in the track timescale
+    fn decode_time(&self, timestamp: u32, base: u32) -> u64 {
+        timestamp.saturating_sub(base) as u64 * self.info.timescale as u64 / 1000
+    }
+}
+
+// Byte offsets (from the start of the output) to overwrite once the stream is over
+pub struct Fmp4Trailer {
+    pub bytes: Vec<u8>,
+    pub patches: Vec<(u64, Vec<u8>)>,
+}
+
+#[derive(Default)]
+pub struct Fmp4Remuxer {
+    expect_video: bool,
+    video_config: Option<Vec<u8>>,
+    audio_config: Option<Vec<u8>>,
+    video: Option<Track>,
+    audio: Option<Track>,
+    initialized: bool,
+    base_timestamp: u32,
+    sequence: u32,
+    bytes_out: u64,
+    duration_offsets: Vec<u64>,
+}
+
+impl Fmp4Remuxer {
+    pub fn new() -> Self {
+        Self {
+            expect_video: true,
+            ..Self::default()
+        }
+    }
+
+    // Returns the bytes to append to the output, often none
+    pub fn push(&mut self, item: &FlvItem) -> Result<Vec<u8>, String> {
+        match item {
+            FlvItem::Header(header) => {
+                self.expect_video = header.has_video;
+                Ok(Vec::new())
+            }
+            FlvItem::Tag(tag) => self.push_tag(tag),
+        }
+    }
+
+    pub fn push_tag(&mut self, tag: &FlvTag) -> Result<Vec<u8>, String> {
+        if tag.is_sequence_header() {
+            let config = tag.payload().to_vec();
+            match tag.tag_type {
+                TAG_VIDEO => self.video_config = Some(config),
+                _ => self.audio_config = Some(config),
+            }
+            return Ok(Vec::new());
+        }
+
+        let mut out = Vec::new();
+        let is_video = match tag.tag_type {
+            TAG_VIDEO if tag.is_video_frame() => true,
+            // Only AAC has a sample entry here; other audio is dropped
+            TAG_AUDIO if self.audio_config.is_some() => false,
+            _ => return Ok(out),
+        };
+        let sync = !is_video || tag.is_video_keyframe();
+
+        // New codec parameters take effect at the next keyframe with a fresh init segment.
+        // Players that use MSE handle that; recordings start a new file instead.
+        let video_changed = self
+            .video
+            .as_ref()
+            .map_or(false, |t| self.video_config.as_ref() != Some(&t.config));
+        let audio_changed = self
+            .audio
+            .as_ref()
+            .map_or(false, |t| self.audio_config.as_ref() != Some(&t.config));
+        let starts_output = if is_video {
+            sync && self.video_config.is_some()
+        } else {
+            !self.expect_video
+        };
+        if (!self.initialized && starts_output)
+            || ((video_changed || audio_changed) && is_video && sync)
+        {
+            out.extend(self.flush_all());
+            out.extend(self.start(tag.timestamp)?);
+        }
+        if !self.initialized || tag.timestamp < self.base_timestamp {
+            return Ok(out);
+        }
+
+        let Some(track) = (if is_video {
+            self.video.as_mut()
+        } else {
+            self.audio.as_mut()
+        }) else {
+            return Ok(out); // Audio that showed up after the init segment was written
+        };
+        let due = track.pending.first().map_or(false, |first| {
+            (is_video && sync) || tag.timestamp.saturating_sub(first.timestamp) >= MAX_FRAGMENT_MS
+        });
+        if due {
+            let fragment = self.flush(is_video, Some(tag.timestamp));
+            out.extend(fragment);
+        }
+
+        let track = if is_video {
+            self.video.as_mut()
+        } else {
+            self.audio.as_mut()
+        };
+        if let Some(track) = track {
+            track.pending.push(Sample {
+                timestamp: tag.timestamp,
+                composition_offset: if is_video { tag.composition_time() } else { 0 },
+                sync,
+                data: tag.payload().to_vec(),
+            });
+        }
+        Ok(out)
+    }
+
+    // Builds the tracks from the configs seen so far and writes an init segment
+    fn start(&mut self, base_timestamp: u32) -> Result<Vec<u8>, String> {
+        self.video = self
+            .video_config
+            .as_ref()
+            .map(|c| video_track(c))
+            .transpose()?;
+        self.audio = self
+            .audio_config
+            .as_ref()
+            .map(|c| audio_track(c))
+            .transpose()?;
+        if self.video.is_none() && self.audio.is_none() {
+            return Err("No supported tracks to remux".to_string());
+        }
+        let tracks: Vec<&TrackInfo> = self
+            .video
+            .iter()
+            .chain(&self.audio)
+            .map(|t| &t.info)
+            .collect();
+        let (init, duration_offsets) = init_segment(&tracks);
+        self.duration_offsets = duration_offsets
+            .iter()
+            .map(|offset| self.bytes_out + *offset as u64)
+            .collect();
+        self.bytes_out += init.len() as u64;
+        // A re-init keeps the timeline going rather than starting over at 0
+        if !self.initialized {
+            self.base_timestamp = base_timestamp;
+        }
+        self.initialized = true;
+        Ok(init)
+    }
+
+    // Writes a track's pending samples as one fragment. `next_timestamp` is the sample
+    // that follows them, which gives the last one its duration.
+    fn flush(&mut self, video: bool, next_timestamp: Option<u32>) -> Vec<u8> {
+        let base = self.base_timestamp;
+        let sequence = self.sequence + 1;
+        let moof_offset = self.bytes_out;
+        let track_count = self.video.is_some() as usize + self.audio.is_some() as usize;
+        let Some(track) = (if video {
+            self.video.as_mut()
+        } else {
+            self.audio.as_mut()
+        }) else {
+            return Vec::new();
+        };
+        if track.pending.is_empty() {
+            return Vec::new();
+        }
+
+        let samples = std::mem::take(&mut track.pending);
+        let times: Vec<u64> = samples
+            .iter()
+            .map(|s| track.decode_time(s.timestamp, base))
+            .collect();
+        let next_time = next_timestamp.map(|ts| track.decode_time(ts, base));
+        let mut entries = Vec::with_capacity(samples.len());
+        for (i, sample) in samples.iter().enumerate() {
+            let duration = match times.get(i + 1).copied().or(next_time) {
+                Some(next) if next > times[i] => (next - times[i]) as u32,
+                _ => track.last_duration, // Last sample, or a repeated timestamp
+            };
+            track.last_duration = duration;
+            entries.push(FragmentSample {
+                duration,
+                flags: if sample.sync {
+                    SAMPLE_FLAGS_SYNC
+                } else {
+                    SAMPLE_FLAGS_NON_SYNC
+                },
+                composition_offset: (sample.composition_offset as i64 * track.info.timescale as i64
+                    / 1000) as i32,
+                data: &sample.data,
+            });
+        }
+        let total: u64 = entries.iter().map(|e| e.duration as u64).sum();
+        let base_decode_time = times[0].max(track.end_time);
+
+        // Video fragments that open on a keyframe are seek points; audio-only streams can
+        // seek to any fragment
+        if samples[0].sync && (video || track_count == 1) {
+            track.random_access.push((base_decode_time, moof_offset));
+        }
+        track.end_time = base_decode_time + total;
+
+        let bytes = fragment(sequence, track.info.id, base_decode_time, &entries);
+        self.sequence = sequence;
+        self.bytes_out += bytes.len() as u64;
+        bytes
+    }
+
+    fn flush_all(&mut self) -> Vec<u8> {
+        let mut out = self.flush(true, None);
+        out.extend(self.flush(false, None));
+        out
+    }
+
+    // Writes out what's still buffered plus an mfra index, and says how to fill in the
+    // duration so the finished file is seekable
+    pub fn finish(&mut self) -> Fmp4Trailer {
+        if !self.initialized {
+            return Fmp4Trailer {
+                bytes: Vec::new(),
+                patches: Vec::new(),
+            };
+        }
+        let mut bytes = self.flush_all();
+        let index: Vec<(u32, &[(u64, u64)])> = self
+            .video
+            .iter()
+            .chain(&self.audio)
+            .map(|t| (t.info.id, t.random_access.as_slice()))
+            .collect();
+        bytes.extend(mfra(&index));
+
+        let duration_ms = self
+            .video
+            .iter()
+            .chain(&self.audio)
+            .map(|t| t.end_time * MOVIE_TIMESCALE as u64 / t.info.timescale as u64)
+            .max()
+            .unwrap_or(0);
+        let duration = (duration_ms.min(u32::MAX as u64) as u32)
+            .to_be_bytes()
+            .to_vec();
+        let patches = self
+            .duration_offsets
+            .iter()
+            .map(|offset| (*offset, duration.clone()))
+            .collect();
+        Fmp4Trailer { bytes, patches }
+    }
+}
+
+fn video_track(record: &[u8]) -> Result<Track, String> {
+    // The decoder configuration record says which codec it is
+    let (config, kind, config_kind) = match parse_avc_config(record) {
+        Ok(config) => (config, b"avc1", b"avcC"),
+        Err(_) => (parse_hevc_config(record)?, b"hvc1", b"hvcC"),
+    };
+    let frame_ms = config
+        .fps
+        .filter(|fps| *fps > 0.0)
+        .map_or(DEFAULT_VIDEO_FRAME_MS, |fps| (1000.0 / fps).round() as u32);
+    Ok(Track {
+        info: TrackInfo {
+            id: VIDEO_TRACK_ID,
+            timescale: 1000, // Same as FLV, so timestamps carry over exactly
+            is_video: true,
+            width: config.width,
+            height: config.height,
+            sample_entry: visual_sample_entry(
+                kind,
+                config_kind,
+                record,
+                config.width,
+                config.height,
+            ),
+        },
+        config: record.to_vec(),
+        pending: Vec::new(),
+        last_duration: frame_ms,
+        end_time: 0,
+        random_access: Vec::new(),
+    })
+}
+
+fn audio_track(audio_specific_config: &[u8]) -> Result<Track, String> {
+    let config = parse_aac_config(audio_specific_config)?;
+    if config.sample_rate == 0 {
+        return Err("AAC config has no sample rate".to_string());
+    }
+    Ok(Track {
+        info: TrackInfo {
+            id: AUDIO_TRACK_ID,
+            timescale: config.sample_rate,
+            is_video: false,
+            width: 0,
+            height: 0,
+            sample_entry: mp4a_sample_entry(
+                audio_specific_config,
+                config.channels,
+                config.sample_rate,
+            ),
+        },
+        config: audio_specific_config.to_vec(),
+        pending: Vec::new(),
+        last_duration: AAC_FRAME_SAMPLES,
+        end_time: 0,
+        random_access: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::flv::FlvReader;
+
+    // 1080p30 AVC with a keyframe every 30 frames, 44.1 kHz AAC every other frame
+    const SAMPLE: &[u8] = include_bytes!("../flv/fixtures/sample.flv");
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    // (offset, type, payload) of each box in `data`, which has to be made of whole boxes
+    fn boxes(data: &[u8]) -> Vec<(usize, [u8; 4], &[u8])> {
+        let mut out = Vec::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let size = u32_at(data, offset) as usize;
+            assert!(
+                size >= 8 && offset + size <= data.len(),
+                "box overruns its parent"
+            );
+            let kind = data[offset + 4..offset + 8].try_into().unwrap();
+            out.push((offset, kind, &data[offset + 8..offset + size]));
+            offset += size;
+        }
+        out
+    }
+
+    fn child<'a>(data: &'a [u8], kind: &[u8; 4]) -> &'a [u8] {
+        let found: Vec<&[u8]> = boxes(data)
+            .into_iter()
+            .filter(|(_, k, _)| k == kind)
+            .map(|(_, _, payload)| payload)
+            .collect();
+        assert_eq!(found.len(), 1);
+        found[0]
+    }
+
+    fn remux(items: &[FlvItem]) -> Vec<u8> {
+        let mut remuxer = Fmp4Remuxer::new();
+        let mut out = Vec::new();
+        for item in items {
+            out.extend(remuxer.push(item).unwrap());
+        }
+        let trailer = remuxer.finish();
+        out.extend(trailer.bytes);
+        for (offset, bytes) in trailer.patches {
+            let offset = offset as usize;
+            out[offset..offset + bytes.len()].copy_from_slice(&bytes);
+        }
+        out
+    }
+
+    struct Fragment {
+        offset: usize,
+        track_id: u32,
+        base_decode_time: u64,
+        durations: Vec<u32>,
+        sync: Vec<bool>,
+        composition_offsets: Vec<i32>,
+    }
+
+    // Checks each moof against the mdat after it and returns what its trun says
+    fn fragments(file: &[u8]) -> Vec<Fragment> {
+        let top = boxes(file);
+        let mut out = Vec::new();
+        for (i, (offset, kind, moof)) in top.iter().enumerate() {
+            if kind != b"moof" {
+                continue;
+            }
+            let traf = child(moof, b"traf");
+            let tfdt = child(traf, b"tfdt");
+            let trun = child(traf, b"trun");
+            let count = u32_at(trun, 4) as usize;
+            assert_eq!(trun.len(), 12 + 16 * count);
+            let entries: Vec<&[u8]> = trun[12..].chunks(16).collect();
+            let sizes: u32 = entries.iter().map(|e| u32_at(e, 4)).sum();
+
+            let (mdat_offset, mdat_kind, mdat) = top[i + 1];
+            assert_eq!(&mdat_kind, b"mdat");
+            assert_eq!(mdat.len() as u32, sizes);
+            // Data offsets count from the moof to the mdat payload
+            assert_eq!(offset + u32_at(trun, 8) as usize, mdat_offset + 8);
+
+            out.push(Fragment {
+                offset: *offset,
+                track_id: u32_at(child(traf, b"tfhd"), 4),
+                base_decode_time: u64::from_be_bytes(tfdt[4..12].try_into().unwrap()),
+                durations: entries.iter().map(|e| u32_at(e, 0)).collect(),
+                sync: entries
+                    .iter()
+                    .map(|e| u32_at(e, 8) == SAMPLE_FLAGS_SYNC)
+                    .collect(),
+                composition_offsets: entries.iter().map(|e| u32_at(e, 12) as i32).collect(),
+            });
+        }
+        out
+    }
+
+    #[test]
+    fn remuxes_the_sample_recording() {
+        let file = remux(&FlvReader::new().push(SAMPLE).unwrap());
+        let top = boxes(&file);
+        let kinds: Vec<&[u8; 4]> = top.iter().map(|(_, kind, _)| kind).collect();
+        assert_eq!(kinds[..2], [b"ftyp", b"moov"]);
+        assert_eq!(kinds.last(), Some(&b"mfra"));
+
+        let fragments = fragments(&file);
+        assert_eq!(top.len(), 3 + 2 * fragments.len());
+        let video: Vec<&Fragment> = fragments.iter().filter(|f| f.track_id == 1).collect();
+        let audio: Vec<&Fragment> = fragments.iter().filter(|f| f.track_id == 2).collect();
+        assert_eq!(video.len() + audio.len(), fragments.len());
+
+        // Split at each keyframe and otherwise every MAX_FRAGMENT_MS
+        let counts: Vec<usize> = video.iter().map(|f| f.durations.len()).collect();
+        assert_eq!(counts, [16, 14, 16, 14, 1]);
+        let counts: Vec<usize> = audio.iter().map(|f| f.durations.len()).collect();
+        assert_eq!(counts, [8, 8, 8, 7]);
+        assert!(video.iter().all(|f| f.durations.iter().all(|d| *d == 33)));
+        assert!(video
+            .iter()
+            .all(|f| f.composition_offsets.iter().all(|c| *c == 66)));
+        let keyframes: Vec<u64> = video
+            .iter()
+            .filter(|f| f.sync[0])
+            .map(|f| f.base_decode_time)
+            .collect();
+        assert_eq!(keyframes, [0, 990, 1980]);
+        assert_eq!(
+            video.iter().flat_map(|f| &f.sync).filter(|s| **s).count(),
+            3
+        );
+        // 66 ms of 44.1 kHz, give or take the rounding of each timestamp
+        assert!(audio
+            .iter()
+            .flat_map(|f| &f.durations)
+            .all(|d| (2910..=2911).contains(d)));
+
+        // Each fragment carries on where the track's previous one ended
+        for track in [&video, &audio] {
+            let mut end = 0;
+            for fragment in track {
+                assert_eq!(fragment.base_decode_time, end);
+                end += fragment.durations.iter().map(|d| *d as u64).sum::<u64>();
+            }
+        }
+
+        // The audio track runs longest: its last frame at 1980 ms plus 66 ms
+        let moov = child(&file, b"moov");
+        assert_eq!(u32_at(child(moov, b"mvhd"), 16), 2046);
+        assert_eq!(u32_at(child(child(moov, b"mvex"), b"mehd"), 4), 2046);
+
+        // Video fragments that open on a keyframe are indexed; audio ones aren't, as the
+        // stream has video
+        let (_, _, mfra) = top.last().unwrap();
+        let tfra: Vec<&[u8]> = boxes(mfra)
+            .into_iter()
+            .filter(|(_, kind, _)| kind == b"tfra")
+            .map(|(_, _, payload)| payload)
+            .collect();
+        assert_eq!(u32_at(tfra[0], 4), 1);
+        assert_eq!(u32_at(tfra[0], 12), 3);
+        for (i, entry) in tfra[0][16..].chunks(19).enumerate() {
+            let time = u64::from_be_bytes(entry[..8].try_into().unwrap());
+            let offset = u64::from_be_bytes(entry[8..16].try_into().unwrap()) as usize;
+            assert_eq!(time, keyframes[i]);
+            let fragment = fragments.iter().find(|f| f.offset == offset).unwrap();
+            assert_eq!((fragment.track_id, fragment.base_decode_time), (1, time));
+        }
+        assert_eq!(u32_at(tfra[1], 4), 2);
+        assert_eq!(u32_at(tfra[1], 12), 0);
+    }
+
+    #[test]
+    fn drops_samples_from_before_the_start() {
+        let items = FlvReader::new().push(SAMPLE).unwrap();
+        let tag = |tag_type: u8, timestamp: u32, body: &[u8]| {
+            FlvItem::Tag(FlvTag {
+                tag_type,
+                timestamp,
+                offset: 0,
+                body: body.to_vec(),
+            })
+        };
+        let sample_tag = |pick: fn(&FlvTag) -> bool| {
+            items
+                .iter()
+                .find_map(|item| match item {
+                    FlvItem::Tag(tag) if pick(tag) => Some(tag.body.clone()),
+                    _ => None,
+                })
+                .unwrap()
+        };
+        let avc_config = sample_tag(|t| t.tag_type == TAG_VIDEO && t.is_sequence_header());
+        let aac_config = sample_tag(|t| t.tag_type == TAG_AUDIO && t.is_sequence_header());
+        let keyframe = sample_tag(|t| t.is_video_keyframe() && t.is_video_frame());
+        let aac_frame = sample_tag(|t| t.tag_type == TAG_AUDIO && !t.is_sequence_header());
+
+        // Audio that predates the keyframe output starts at, before and after it arrives
+        let file = remux(&[
+            tag(TAG_VIDEO, 900, &avc_config),
+            tag(TAG_AUDIO, 900, &aac_config),
+            tag(TAG_AUDIO, 950, &aac_frame),
+            tag(TAG_VIDEO, 1000, &keyframe),
+            tag(TAG_AUDIO, 980, &aac_frame),
+            tag(TAG_AUDIO, 1010, &aac_frame),
+        ]);
+        let fragments = fragments(&file);
+        assert_eq!(fragments.len(), 2);
+        assert_eq!(fragments[0].track_id, 1);
+        assert_eq!(fragments[0].base_decode_time, 0);
+        assert_eq!(fragments[0].durations, [33]); // One frame at the SPS's 30 fps
+        assert_eq!(fragments[1].track_id, 2);
+        assert_eq!(fragments[1].base_decode_time, 441); // 10 ms at 44.1 kHz
+        assert_eq!(fragments[1].durations, [AAC_FRAME_SAMPLES]);
+    }
+}
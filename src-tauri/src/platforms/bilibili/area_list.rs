@@ -0,0 +1,142 @@
+use super::models::{BilibiliAreaRoomListData, BilibiliParentArea};
+use super::utils::{
+    bilibili_http_client, format_viewer_count, get_bilibili_data, BILIBILI_LIVE_API,
+};
+use crate::platforms::common::types_rust::{
+    CommonCategoryGroupRust, CommonLiveRoomRust, CommonPlatformCategoryRust, CommonRoomListRust,
+    SupportedPlatformRust,
+};
+
+// Each parent area becomes a group; sub areas carry the parent id so fetch_area_rooms
+// can send both ids back to getRoomList.
+pub fn areas_to_category_groups(parents: Vec<BilibiliParentArea>) -> Vec<CommonCategoryGroupRust> {
+    parents
+        .into_iter()
+        .map(|parent| CommonCategoryGroupRust {
+            group_name: parent.name,
+            platform: SupportedPlatformRust::Bilibili,
+            categories: parent
+                .list
+                .into_iter()
+                .map(|area| CommonPlatformCategoryRust {
+                    id: area.id,
+                    name: area.name,
+                    platform: SupportedPlatformRust::Bilibili,
+                    icon_url: area.pic.filter(|s| !s.is_empty()),
+                    parent_id: Some(area.parent_id),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+pub async fn fetch_bilibili_areas() -> Result<Vec<CommonCategoryGroupRust>, String> {
+    let http_client = bilibili_http_client()?;
+    let url = format!("{}/room/v1/Area/getList", BILIBILI_LIVE_API);
+    let parents: Vec<BilibiliParentArea> = get_bilibili_data(&http_client, &url).await?;
+    Ok(areas_to_category_groups(parents))
+}
+
+pub fn area_rooms_to_room_list(
+    data: BilibiliAreaRoomListData,
+    page: u32,
+    page_size: u32,
+) -> CommonRoomListRust {
+    let fetched_so_far = (page as i64 + 1) * page_size as i64;
+    // `count` is sometimes 0 on the last pages, so fall back to "got a full page"
+    let has_more = if data.count > 0 {
+        fetched_so_far < data.count && !data.list.is_empty()
+    } else {
+        data.list.len() as u32 >= page_size
+    };
+
+    let rooms = data
+        .list
+        .into_iter()
+        .map(|room| CommonLiveRoomRust {
+            room_id: room.roomid.to_string(),
+            title: room.title,
+            anchor_name: room.uname,
+            avatar_url: room.face.filter(|s| !s.is_empty()),
+            cover_url: room
+                .user_cover
+                .filter(|s| !s.is_empty())
+                .or(room.system_cover.filter(|s| !s.is_empty())),
+            viewer_count: Some(format_viewer_count(room.online)),
+            is_live: true, // The area list only contains live rooms
+            platform: SupportedPlatformRust::Bilibili,
+        })
+        .collect();
+
+    CommonRoomListRust { rooms, has_more }
+}
+
+// `area_id` 0 lists every room under the parent area
+pub async fn fetch_area_rooms(
+    parent_area_id: &str,
+    area_id: &str,
+    page: u32,
+    page_size: u32,
+) -> Result<CommonRoomListRust, String> {
+    let http_client = bilibili_http_client()?;
+    let url = format!(
+        "{}/room/v3/area/getRoomList?platform=web&parent_area_id={}&cate_id=0&area_id={}&sort_type=online&page={}&page_size={}",
+        BILIBILI_LIVE_API,
+        parent_area_id,
+        area_id,
+        page + 1, // Bilibili pages are 1-indexed
+        page_size
+    );
+    let data: BilibiliAreaRoomListData = get_bilibili_data(&http_client, &url).await?;
+    Ok(area_rooms_to_room_list(data, page, page_size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platforms::bilibili::utils::parse_bilibili_response;
+
+    fn room_list() -> BilibiliAreaRoomListData {
+        parse_bilibili_response(include_str!("fixtures/area_room_list.json")).unwrap()
+    }
+
+    #[test]
+    fn converts_area_rooms() {
+        let list = area_rooms_to_room_list(room_list(), 0, 2);
+        assert!(list.has_more);
+        assert_eq!(list.rooms.len(), 2);
+
+        let first = &list.rooms[0];
+        assert_eq!(first.room_id, "21452505");
+        assert_eq!(first.title, "周末一起打游戏");
+        assert_eq!(first.anchor_name, "测试主播");
+        assert_eq!(first.viewer_count.as_deref(), Some("12.3万"));
+        assert!(first
+            .cover_url
+            .as_deref()
+            .unwrap()
+            .contains("new_room_cover"));
+        assert!(first.is_live);
+
+        // No user cover or face: the key frame stands in and the avatar is left out
+        let second = &list.rooms[1];
+        assert_eq!(second.viewer_count.as_deref(), Some("987"));
+        assert!(second.cover_url.as_deref().unwrap().contains("keyframe"));
+        assert_eq!(second.avatar_url, None);
+    }
+
+    #[test]
+    fn stops_at_the_reported_count() {
+        // count is 5, so the third page of two ends the list
+        assert!(area_rooms_to_room_list(room_list(), 1, 2).has_more);
+        assert!(!area_rooms_to_room_list(room_list(), 2, 2).has_more);
+    }
+
+    #[test]
+    fn guesses_more_pages_without_a_count() {
+        let mut data = room_list();
+        data.count = 0;
+        assert!(area_rooms_to_room_list(data.clone(), 0, 2).has_more);
+        assert!(!area_rooms_to_room_list(data, 0, 3).has_more);
+    }
+}
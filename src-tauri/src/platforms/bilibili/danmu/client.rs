@@ -0,0 +1,325 @@
+use futures_util::{SinkExt, StreamExt};
+use reqwest::header::COOKIE;
+use std::future::Future;
+use tauri::AppHandle;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+use super::message_parser::parse_message;
+use super::packet::{
+    decode_packets, encode_packet, heartbeat_packet, OP_AUTH, OP_AUTH_REPLY, OP_HEARTBEAT_REPLY,
+    OP_MESSAGE,
+};
+use crate::platforms::bilibili::models::BilibiliDanmuInfoData;
+use crate::platforms::bilibili::room_info::resolve_real_room_id;
+use crate::platforms::bilibili::utils::{
+    bilibili_http_client, fetch_buvid3, get_bilibili_data, BILIBILI_LIVE_API,
+};
+use crate::platforms::common::http_client::DEFAULT_USER_AGENT;
+use crate::platforms::common::types_rust::SupportedPlatformRust;
+use crate::platforms::common::{supervise_danmaku, DanmakuEvent, DanmakuSession};
+
+// Used when getDanmuInfo is unavailable (it is often risk-controlled for anonymous users)
+const DEFAULT_DANMU_URL: &str = "wss://broadcastlv.chat.bilibili.com:443/sub";
+const HEARTBEAT_INTERVAL_SECS: u64 = 30;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+async fn fetch_danmu_info(real_room_id: i64, buvid: &str) -> Result<BilibiliDanmuInfoData, String> {
+    let mut http_client = bilibili_http_client()?;
+    if !buvid.is_empty() {
+        http_client.insert_header(COOKIE, &format!("buvid3={}", buvid))?;
+    }
+    let url = format!(
+        "{}/xlive/web-room/v1/index/getDanmuInfo?id={}&type=0",
+        BILIBILI_LIVE_API, real_room_id
+    );
+    get_bilibili_data(&http_client, &url).await
+}
+
+async fn connect_first_available(urls: &[String]) -> Result<WsStream, String> {
+    let mut last_error = String::from("no danmaku servers to try");
+    for url in urls {
+        let mut request = match url.as_str().into_client_request() {
+            Ok(request) => request,
+            Err(e) => {
+                last_error = format!("invalid danmaku url {}: {}", url, e);
+                continue;
+            }
+        };
+        let headers = request.headers_mut();
+        headers.insert("User-Agent", DEFAULT_USER_AGENT.parse().unwrap());
+        headers.insert("Origin", "https://live.bilibili.com".parse().unwrap());
+
+        match connect_async(request).await {
+            Ok((ws_stream, _)) => return Ok(ws_stream),
+            Err(e) => {
+                eprintln!("[Bilibili Danmaku] Failed to connect to {}: {}", url, e);
+                last_error = format!("failed to connect to {}: {}", url, e);
+            }
+        }
+    }
+    Err(last_error)
+}
+
+// Runs one Bilibili danmaku connection until the session is stopped or the connection ends;
+// reconnecting is up to supervise_danmaku. Events go out on `danmaku-bilibili-{room_id}`
+// using the room id the caller passed in, not the resolved one.
+pub async fn run_bilibili_session(session: DanmakuSession) -> Result<(), String> {
+    let room_id = session.room_id().to_string();
+    let real_room_id = resolve_real_room_id(&room_id).await?;
+
+    let buvid = fetch_buvid3().await.unwrap_or_else(|e| {
+        eprintln!("[Bilibili Danmaku] Failed to fetch buvid3: {}", e);
+        String::new()
+    });
+
+    let (token, mut urls) = match fetch_danmu_info(real_room_id, &buvid).await {
+        Ok(info) => {
+            let urls: Vec<String> = info
+                .host_list
+                .iter()
+                .filter(|h| h.wss_port != 0)
+                .map(|h| format!("wss://{}:{}/sub", h.host, h.wss_port))
+                .collect();
+            (info.token, urls)
+        }
+        Err(e) => {
+            eprintln!(
+                "[Bilibili Danmaku] getDanmuInfo failed for room {}, using default server: {}",
+                real_room_id, e
+            );
+            (String::new(), Vec::new())
+        }
+    };
+    urls.push(DEFAULT_DANMU_URL.to_string());
+
+    let ws_stream = connect_first_available(&urls).await?;
+    let auth_body = serde_json::json!({
+        "uid": 0,
+        "roomid": real_room_id,
+        "protover": 3,
+        "buvid": buvid,
+        "platform": "web",
+        "type": 2,
+        "key": token
+    });
+    run_connection(
+        ws_stream,
+        &room_id,
+        &auth_body,
+        session.stopped(),
+        || session.mark_connected(),
+        |event| session.emit(event),
+    )
+    .await
+}
+
+// Authenticates on an open connection, then reads until `stopped` completes or the
+// connection ends
+async fn run_connection(
+    ws_stream: WsStream,
+    room_id: &str,
+    auth_body: &serde_json::Value,
+    stopped: impl Future<Output = ()>,
+    on_connected: impl Fn(),
+    mut on_event: impl FnMut(DanmakuEvent),
+) -> Result<(), String> {
+    let (mut write, mut read) = ws_stream.split();
+    write
+        .send(Message::Binary(encode_packet(
+            OP_AUTH,
+            auth_body.to_string().as_bytes(),
+        )))
+        .await
+        .map_err(|e| format!("Failed to send auth packet: {}", e))?;
+
+    // Outgoing messages go through a channel so the heartbeat task doesn't need the sink
+    let (tx, mut rx) = mpsc::channel::<Message>(8);
+    let heartbeat_task = tokio::spawn(async move {
+        loop {
+            if tx.send(Message::Binary(heartbeat_packet())).await.is_err() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_secs(HEARTBEAT_INTERVAL_SECS)).await;
+        }
+    });
+    let send_task = tokio::spawn(async move {
+        while let Some(msg_to_send) = rx.recv().await {
+            if write.send(msg_to_send).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    tokio::pin!(stopped);
+
+    let result = loop {
+        tokio::select! {
+            _ = &mut stopped => {
+                println!("[Bilibili Danmaku {}] Stop signal received, terminating listener.", room_id);
+                break Ok(());
+            }
+            msg_option = read.next() => {
+                match msg_option {
+                    Some(Ok(Message::Binary(data))) => {
+                        let (packets, error) = decode_packets(&data);
+                        if let Some(e) = error {
+                            eprintln!("[Bilibili Danmaku {}] Failed to decode frame: {}", room_id, e);
+                        }
+                        for packet in packets {
+                            match packet.op {
+                                OP_AUTH_REPLY => {
+                                    let reply: serde_json::Value =
+                                        serde_json::from_slice(&packet.body).unwrap_or_default();
+                                    if reply.get("code").and_then(|c| c.as_i64()) == Some(0) {
+                                        on_connected();
+                                    } else {
+                                        eprintln!("[Bilibili Danmaku {}] Auth rejected: {}", room_id, reply);
+                                    }
+                                }
+                                OP_MESSAGE => {
+                                    if let Some(event) = parse_message(&packet.body) {
+                                        on_event(event);
+                                    }
+                                }
+                                // Popularity counter; nothing shows it yet
+                                OP_HEARTBEAT_REPLY => {}
+                                _ => {}
+                            }
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | None => {
+                        break Err("Bilibili danmaku websocket closed".to_string());
+                    }
+                    Some(Err(e)) => {
+                        break Err(format!("Bilibili danmaku websocket error: {}", e));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    };
+
+    heartbeat_task.abort();
+    send_task.abort();
+    result
+}
+
+// Keeps a Bilibili danmaku connection alive, reconnecting as needed, until `stop_signal` completes
+pub async fn run_bilibili_danmaku(
+    app_handle: AppHandle,
+    room_id: String,
+    stop_signal: impl Future<Output = ()> + Send,
+) -> Result<(), String> {
+    supervise_danmaku(
+        app_handle,
+        SupportedPlatformRust::Bilibili,
+        room_id,
+        stop_signal,
+        run_bilibili_session,
+    )
+    .await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::packet::{compressed_frame, PROTOVER_BROTLI};
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tokio::net::TcpListener;
+
+    // Stand-in for a danmaku server: checks the auth packet, accepts it, sends one
+    // batched danmaku frame and hangs up
+    async fn serve_one_session(listener: TcpListener) -> serde_json::Value {
+        let (tcp, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+
+        let Some(Ok(Message::Binary(auth))) = ws.next().await else {
+            panic!("expected an auth packet first");
+        };
+        let (packets, error) = decode_packets(&auth);
+        assert_eq!(error, None);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].op, OP_AUTH);
+        let auth_body: serde_json::Value = serde_json::from_slice(&packets[0].body).unwrap();
+
+        ws.send(Message::Binary(encode_packet(
+            OP_AUTH_REPLY,
+            br#"{"code":0}"#,
+        )))
+        .await
+        .unwrap();
+        let mut danmu = encode_packet(OP_MESSAGE, include_bytes!("fixtures/danmu_msg.json"));
+        danmu[6..8].copy_from_slice(&0u16.to_be_bytes());
+        ws.send(Message::Binary(compressed_frame(PROTOVER_BROTLI, &[danmu])))
+            .await
+            .unwrap();
+        ws.close(None).await.unwrap();
+        auth_body
+    }
+
+    #[tokio::test]
+    async fn authenticates_and_emits_danmaku() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/sub", listener.local_addr().unwrap());
+        let server = tokio::spawn(serve_one_session(listener));
+
+        // Port 1 refuses the connection, so the client has to move on to the stand-in
+        let ws_stream = connect_first_available(&["ws://127.0.0.1:1/sub".to_string(), url])
+            .await
+            .unwrap();
+        let connected = AtomicBool::new(false);
+        let mut events = Vec::new();
+        let auth_body =
+            serde_json::json!({ "uid": 0, "roomid": 21452505, "protover": 3, "key": "token" });
+        let result = run_connection(
+            ws_stream,
+            "6",
+            &auth_body,
+            std::future::pending(),
+            || connected.store(true, Ordering::SeqCst),
+            |event| events.push(event),
+        )
+        .await;
+
+        assert_eq!(server.await.unwrap(), auth_body);
+        assert!(connected.load(Ordering::SeqCst));
+        assert!(matches!(
+            events.as_slice(),
+            [DanmakuEvent::Chat { content, .. }] if content == "主播晚上好"
+        ));
+        // A closed connection is an error so supervise_danmaku reconnects
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn returns_when_stopped() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/sub", listener.local_addr().unwrap());
+        // Accepts and then stays silent
+        let server = tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            while let Some(Ok(_)) = ws.next().await {}
+        });
+
+        let ws_stream = connect_first_available(&[url]).await.unwrap();
+        let result = run_connection(
+            ws_stream,
+            "6",
+            &serde_json::json!({}),
+            tokio::time::sleep(Duration::from_millis(200)),
+            || {},
+            |_| {},
+        )
+        .await;
+        assert_eq!(result, Ok(()));
+        server.abort();
+    }
+}
@@ -0,0 +1,279 @@
+use serde_json::Value;
+
+use crate::platforms::common::{DanmakuEvent, DanmakuUser};
+
+// "DANMU_MSG:4:0:2:2:2:0" and "DANMU_MSG" are the same command
+fn base_cmd(message: &Value) -> Option<&str> {
+    message
+        .get("cmd")
+        .and_then(|c| c.as_str())
+        .map(|c| c.split(':').next().unwrap_or(c))
+}
+
+fn value_to_string(value: Option<&Value>) -> Option<String> {
+    match value {
+        Some(Value::String(s)) if !s.is_empty() => Some(s.clone()),
+        Some(Value::Number(n)) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn value_to_i64(value: Option<&Value>) -> Option<i64> {
+    match value {
+        Some(Value::Number(n)) => n.as_i64(),
+        Some(Value::String(s)) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+// Shared by the object-shaped commands: data{uid, uname, fans_medal / medal_info{medal_name, medal_level}}
+fn data_user(data: &Value, medal_key: &str) -> DanmakuUser {
+    let medal = data.get(medal_key);
+    DanmakuUser {
+        uid: value_to_string(data.get("uid")),
+        nickname: data
+            .get("uname")
+            .and_then(|n| n.as_str())
+            .unwrap_or("unknown")
+            .to_string(),
+        level: None,
+        badge_name: medal.and_then(|m| value_to_string(m.get("medal_name"))),
+        badge_level: medal
+            .and_then(|m| value_to_i64(m.get("medal_level")))
+            .filter(|l| *l > 0),
+    }
+}
+
+// DANMU_MSG keeps everything in positional arrays:
+// info[0][3] colour, info[1] text, info[2] = [uid, uname, ...],
+// info[3] = [medal level, medal name, ...] (empty without a medal), info[4][0] user level
+fn parse_danmu_msg(message: &Value) -> Option<DanmakuEvent> {
+    let info = message.get("info")?.as_array()?;
+    let content = info.get(1)?.as_str()?;
+    let user = info.get(2)?.as_array()?;
+    let nickname = user.get(1).and_then(|n| n.as_str()).unwrap_or("unknown");
+
+    let medal = info.get(3).and_then(|m| m.as_array());
+    let badge_name = medal.and_then(|m| value_to_string(m.get(1)));
+    let badge_level = medal
+        .and_then(|m| value_to_i64(m.first()))
+        .filter(|_| badge_name.is_some());
+    let level = info
+        .get(4)
+        .and_then(|l| l.as_array())
+        .and_then(|l| value_to_i64(l.first()));
+
+    // White is the default colour; leave it to the frontend's per-user colour
+    let color = info
+        .first()
+        .and_then(|meta| meta.get(3))
+        .and_then(|c| c.as_u64())
+        .filter(|c| *c != 0xFFFFFF)
+        .map(|c| format!("#{:06x}", c));
+
+    Some(DanmakuEvent::Chat {
+        user: DanmakuUser {
+            uid: value_to_string(user.first()),
+            nickname: nickname.to_string(),
+            level,
+            badge_name,
+            badge_level,
+        },
+        content: content.to_string(),
+        color,
+    })
+}
+
+// INTERACT_WORD msg_type 1 is a user entering the room, 2 a follow (3 is a share)
+fn parse_interact_word(message: &Value) -> Option<DanmakuEvent> {
+    let data = message.get("data")?;
+    let user = data_user(data, "fans_medal");
+    match data.get("msg_type").and_then(|t| t.as_i64())? {
+        1 => Some(DanmakuEvent::Enter { user }),
+        2 => Some(DanmakuEvent::Follow { user }),
+        _ => None,
+    }
+}
+
+// SEND_GIFT data{giftId, giftName, num, total_coin, coin_type, medal_info, ...}
+// Each SEND_GIFT is final, so every event is its own repeat end.
+fn parse_send_gift(message: &Value) -> Option<DanmakuEvent> {
+    let data = message.get("data")?;
+    let count = value_to_i64(data.get("num")).unwrap_or(1);
+    // Only paid (gold) gifts carry revenue; silver gifts are free
+    let value = value_to_i64(data.get("total_coin"))
+        .filter(|_| data.get("coin_type").and_then(|t| t.as_str()) == Some("gold"));
+    Some(DanmakuEvent::Gift {
+        user: data_user(data, "medal_info"),
+        gift_id: value_to_string(data.get("giftId")),
+        gift_name: value_to_string(data.get("giftName"))?,
+        count,
+        icon_url: data
+            .get("gift_info")
+            .and_then(|info| value_to_string(info.get("img_basic"))),
+        combo_count: count,
+        repeat_end: true,
+        value,
+    })
+}
+
+// LIKE_INFO_V3_CLICK is sent once per user who taps like
+fn parse_like_click(message: &Value) -> Option<DanmakuEvent> {
+    let data = message.get("data")?;
+    Some(DanmakuEvent::Like {
+        user: Some(data_user(data, "fans_medal")),
+        count: 1,
+    })
+}
+
+// WATCHED_CHANGE data{num, text_large: "1.2万人看过"}
+fn parse_watched_change(message: &Value) -> Option<DanmakuEvent> {
+    let data = message.get("data")?;
+    Some(DanmakuEvent::RoomStats {
+        viewer_count: value_to_i64(data.get("num")),
+        viewer_count_text: value_to_string(data.get("text_large")),
+    })
+}
+
+// WARNING / CUT_OFF: a moderator warned or cut the room; `msg` is the reason shown to viewers
+fn parse_admin_notice(message: &Value, prefix: &str) -> Option<DanmakuEvent> {
+    let reason = value_to_string(message.get("msg")).unwrap_or_default();
+    Some(DanmakuEvent::System {
+        content: format!("{}{}", prefix, reason),
+    })
+}
+
+// Returns the event for a decoded OP_MESSAGE body, or None for commands we don't handle
+pub fn parse_message(body: &[u8]) -> Option<DanmakuEvent> {
+    let message: Value = serde_json::from_slice(body).ok()?;
+    match base_cmd(&message)? {
+        "DANMU_MSG" => parse_danmu_msg(&message),
+        "INTERACT_WORD" => parse_interact_word(&message),
+        "SEND_GIFT" => parse_send_gift(&message),
+        "LIKE_INFO_V3_CLICK" => parse_like_click(&message),
+        "WATCHED_CHANGE" => parse_watched_change(&message),
+        "LIVE" => Some(DanmakuEvent::LiveStatusChanged { is_live: true }),
+        "PREPARING" => Some(DanmakuEvent::LiveStatusChanged { is_live: false }),
+        "WARNING" => parse_admin_notice(&message, "直播间收到警告: "),
+        "CUT_OFF" => parse_admin_notice(&message, "直播被切断: "),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_danmu_msg() {
+        let Some(DanmakuEvent::Chat {
+            user,
+            content,
+            color,
+        }) = parse_message(include_bytes!("fixtures/danmu_msg.json"))
+        else {
+            panic!("expected a chat event");
+        };
+        assert_eq!(content, "主播晚上好");
+        assert_eq!(color, None);
+        assert_eq!(user.uid.as_deref(), Some("12345678"));
+        assert_eq!(user.nickname, "测试用户");
+        assert_eq!(user.level, Some(15));
+        assert_eq!(user.badge_name.as_deref(), Some("小粉丝"));
+        assert_eq!(user.badge_level, Some(21));
+    }
+
+    #[test]
+    fn keeps_non_default_danmu_colours() {
+        let text = include_str!("fixtures/danmu_msg.json").replacen(
+            "[0,1,25,16777215,",
+            "[0,1,25,16772431,",
+            1,
+        );
+        let Some(DanmakuEvent::Chat { color, .. }) = parse_message(text.as_bytes()) else {
+            panic!("expected a chat event");
+        };
+        assert_eq!(color.as_deref(), Some("#ffed4f"));
+    }
+
+    #[test]
+    fn parses_send_gift() {
+        let Some(DanmakuEvent::Gift {
+            user,
+            gift_id,
+            gift_name,
+            count,
+            icon_url,
+            combo_count,
+            repeat_end,
+            value,
+        }) = parse_message(include_bytes!("fixtures/send_gift.json"))
+        else {
+            panic!("expected a gift event");
+        };
+        assert_eq!(user.nickname, "测试用户");
+        assert_eq!(user.badge_name.as_deref(), Some("小粉丝"));
+        assert_eq!(user.badge_level, Some(12));
+        assert_eq!(gift_id.as_deref(), Some("31036"));
+        assert_eq!(gift_name, "小花花");
+        assert_eq!((count, combo_count, repeat_end), (3, 3, true));
+        assert!(icon_url.unwrap().ends_with(".png"));
+        assert_eq!(value, Some(300));
+    }
+
+    #[test]
+    fn silver_gifts_have_no_value() {
+        let text = include_str!("fixtures/send_gift.json").replacen(
+            r#""coin_type":"gold""#,
+            r#""coin_type":"silver""#,
+            1,
+        );
+        assert!(matches!(
+            parse_message(text.as_bytes()),
+            Some(DanmakuEvent::Gift { value: None, .. })
+        ));
+    }
+
+    #[test]
+    fn parses_interact_word() {
+        let Some(DanmakuEvent::Enter { user }) =
+            parse_message(include_bytes!("fixtures/interact_word.json"))
+        else {
+            panic!("expected an enter event");
+        };
+        assert_eq!(user.uid.as_deref(), Some("23456789"));
+        assert_eq!(user.nickname, "路过的观众");
+        // An unlit, empty medal is no badge
+        assert_eq!(user.badge_name, None);
+        assert_eq!(user.badge_level, None);
+
+        let follow = include_str!("fixtures/interact_word.json").replacen(
+            r#""msg_type":1"#,
+            r#""msg_type":2"#,
+            1,
+        );
+        assert!(matches!(
+            parse_message(follow.as_bytes()),
+            Some(DanmakuEvent::Follow { .. })
+        ));
+    }
+
+    #[test]
+    fn parses_watched_change() {
+        let Some(DanmakuEvent::RoomStats {
+            viewer_count,
+            viewer_count_text,
+        }) = parse_message(include_bytes!("fixtures/watched_change.json"))
+        else {
+            panic!("expected a room stats event");
+        };
+        assert_eq!(viewer_count, Some(12345));
+        assert_eq!(viewer_count_text.as_deref(), Some("1.2万人看过"));
+    }
+
+    #[test]
+    fn ignores_unknown_commands() {
+        assert!(parse_message(br#"{"cmd":"ONLINE_RANK_COUNT","data":{"count":5}}"#).is_none());
+        assert!(parse_message(b"not json").is_none());
+    }
+}
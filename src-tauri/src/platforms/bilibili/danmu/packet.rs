@@ -0,0 +1,281 @@
+use flate2::read::ZlibDecoder;
+use std::io::Read;
+
+// Every packet starts with a 16-byte big-endian header:
+// total length (u32), header length (u16), protocol version (u16), operation (u32), sequence (u32)
+pub const HEADER_LEN: usize = 16;
+
+pub const OP_HEARTBEAT: u32 = 2;
+pub const OP_HEARTBEAT_REPLY: u32 = 3; // Body is the popularity value as a u32
+pub const OP_MESSAGE: u32 = 5;
+pub const OP_AUTH: u32 = 7;
+pub const OP_AUTH_REPLY: u32 = 8;
+
+pub const PROTOVER_INT: u16 = 1; // Heartbeat and auth packets
+pub const PROTOVER_ZLIB: u16 = 2;
+pub const PROTOVER_BROTLI: u16 = 3;
+
+// The server batches a few KiB per frame; anything near these limits is garbage or hostile
+const MAX_DECOMPRESSED_LEN: u64 = 16 * 1024 * 1024;
+const MAX_NESTING: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    pub protover: u16,
+    pub op: u32,
+    pub body: Vec<u8>,
+}
+
+pub fn encode_packet(op: u32, body: &[u8]) -> Vec<u8> {
+    let total_len = HEADER_LEN + body.len();
+    let mut result = Vec::with_capacity(total_len);
+    result.extend_from_slice(&(total_len as u32).to_be_bytes());
+    result.extend_from_slice(&(HEADER_LEN as u16).to_be_bytes());
+    result.extend_from_slice(&PROTOVER_INT.to_be_bytes());
+    result.extend_from_slice(&op.to_be_bytes());
+    result.extend_from_slice(&1u32.to_be_bytes());
+    result.extend_from_slice(body);
+    result
+}
+
+pub fn heartbeat_packet() -> Vec<u8> {
+    // The web client sends this exact body; the server ignores it
+    encode_packet(OP_HEARTBEAT, b"[object Object]")
+}
+
+fn decompress(protover: u16, body: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    // One byte past the limit is enough to tell it was exceeded
+    let limit = MAX_DECOMPRESSED_LEN + 1;
+    match protover {
+        PROTOVER_ZLIB => ZlibDecoder::new(body)
+            .take(limit)
+            .read_to_end(&mut out)
+            .map_err(|e| format!("zlib decompress failed: {}", e))?,
+        PROTOVER_BROTLI => brotli::Decompressor::new(body, 4096)
+            .take(limit)
+            .read_to_end(&mut out)
+            .map_err(|e| format!("brotli decompress failed: {}", e))?,
+        _ => return Err(format!("unsupported compressed protover {}", protover)),
+    };
+    if out.len() as u64 > MAX_DECOMPRESSED_LEN {
+        return Err(format!(
+            "compressed packet unpacks to more than {} bytes",
+            MAX_DECOMPRESSED_LEN
+        ));
+    }
+    Ok(out)
+}
+
+// Splits one WebSocket frame into packets. Compressed message packets wrap several
+// plain packets, so they are unpacked recursively and never returned themselves.
+// A bad header or body is reported after the packets decoded before it.
+pub fn decode_packets(data: &[u8]) -> (Vec<Packet>, Option<String>) {
+    let mut packets = Vec::new();
+    let error = decode_into(data, 0, &mut packets).err();
+    (packets, error)
+}
+
+fn decode_into(data: &[u8], depth: usize, packets: &mut Vec<Packet>) -> Result<(), String> {
+    let mut offset = 0;
+
+    while offset + HEADER_LEN <= data.len() {
+        let header = &data[offset..offset + HEADER_LEN];
+        let total_len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let header_len = u16::from_be_bytes([header[4], header[5]]) as usize;
+        let protover = u16::from_be_bytes([header[6], header[7]]);
+        let op = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+
+        if total_len < header_len || header_len < HEADER_LEN || offset + total_len > data.len() {
+            return Err(format!(
+                "malformed packet at offset {}: total_len={}, header_len={}, frame_len={}",
+                offset,
+                total_len,
+                header_len,
+                data.len()
+            ));
+        }
+
+        let body = &data[offset + header_len..offset + total_len];
+        if op == OP_MESSAGE && (protover == PROTOVER_ZLIB || protover == PROTOVER_BROTLI) {
+            if depth >= MAX_NESTING {
+                return Err(format!(
+                    "compressed packets nested more than {} deep",
+                    MAX_NESTING
+                ));
+            }
+            decode_into(&decompress(protover, body)?, depth + 1, packets)?;
+        } else {
+            packets.push(Packet {
+                protover,
+                op,
+                body: body.to_vec(),
+            });
+        }
+
+        offset += total_len;
+    }
+
+    Ok(())
+}
+
+// Wraps plain packets in a compressed OP_MESSAGE packet, as the server batches them
+#[cfg(test)]
+pub fn compressed_frame(protover: u16, packets: &[Vec<u8>]) -> Vec<u8> {
+    use flate2::write::ZlibEncoder;
+    use std::io::Write;
+
+    let inner = packets.concat();
+    let body = match protover {
+        PROTOVER_ZLIB => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&inner).unwrap();
+            encoder.finish().unwrap()
+        }
+        _ => {
+            let mut out = Vec::new();
+            {
+                let mut writer = brotli::CompressorWriter::new(&mut out, 4096, 5, 22);
+                writer.write_all(&inner).unwrap();
+            }
+            out
+        }
+    };
+    let mut frame = encode_packet(OP_MESSAGE, &body);
+    frame[6..8].copy_from_slice(&protover.to_be_bytes());
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(body: &[u8]) -> Vec<u8> {
+        let mut packet = encode_packet(OP_MESSAGE, body);
+        packet[6..8].copy_from_slice(&0u16.to_be_bytes());
+        packet
+    }
+
+    const DANMU: &[u8] = include_bytes!("fixtures/danmu_msg.json");
+    const GIFT: &[u8] = include_bytes!("fixtures/send_gift.json");
+
+    // Every packet of a frame that should decode cleanly
+    fn decode(frame: &[u8]) -> Vec<Packet> {
+        let (packets, error) = decode_packets(frame);
+        assert_eq!(error, None);
+        packets
+    }
+
+    #[test]
+    fn heartbeat_round_trips() {
+        let packets = decode(&heartbeat_packet());
+        assert_eq!(
+            packets,
+            vec![Packet {
+                protover: PROTOVER_INT,
+                op: OP_HEARTBEAT,
+                body: b"[object Object]".to_vec(),
+            }]
+        );
+    }
+
+    #[test]
+    fn unpacks_zlib_frames() {
+        let frame = compressed_frame(PROTOVER_ZLIB, &[message(DANMU), message(GIFT)]);
+        let packets = decode(&frame);
+        assert_eq!(packets.len(), 2);
+        assert!(packets.iter().all(|p| p.op == OP_MESSAGE));
+        assert_eq!(packets[0].body, DANMU);
+        assert_eq!(packets[1].body, GIFT);
+    }
+
+    #[test]
+    fn unpacks_brotli_frames() {
+        let frame = compressed_frame(PROTOVER_BROTLI, &[message(GIFT), message(DANMU)]);
+        let packets = decode(&frame);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].body, GIFT);
+        assert_eq!(packets[1].body, DANMU);
+    }
+
+    #[test]
+    fn keeps_packets_around_a_compressed_one() {
+        let mut frame = encode_packet(OP_HEARTBEAT_REPLY, &42u32.to_be_bytes());
+        frame.extend(compressed_frame(PROTOVER_BROTLI, &[message(DANMU)]));
+        frame.extend(encode_packet(OP_AUTH_REPLY, br#"{"code":0}"#));
+        let ops: Vec<u32> = decode(&frame).iter().map(|p| p.op).collect();
+        assert_eq!(ops, vec![OP_HEARTBEAT_REPLY, OP_MESSAGE, OP_AUTH_REPLY]);
+    }
+
+    #[test]
+    fn rejects_truncated_packets() {
+        let frame = message(DANMU);
+        let (packets, error) = decode_packets(&frame[..frame.len() - 1]);
+        assert!(packets.is_empty() && error.is_some());
+
+        let mut bad_header = message(DANMU);
+        bad_header[4..6].copy_from_slice(&4u16.to_be_bytes());
+        let (packets, error) = decode_packets(&bad_header);
+        assert!(packets.is_empty() && error.is_some());
+    }
+
+    #[test]
+    fn rejects_corrupt_compressed_bodies() {
+        let mut frame = compressed_frame(PROTOVER_ZLIB, &[message(DANMU)]);
+        let last = frame.len() - 1;
+        frame[HEADER_LEN..last].fill(0xff);
+        assert!(decode_packets(&frame).1.is_some());
+    }
+
+    #[test]
+    fn keeps_packets_before_a_bad_inner_header() {
+        let mut bad_header = message(GIFT);
+        bad_header[4..6].copy_from_slice(&4u16.to_be_bytes());
+        let mut frame = encode_packet(OP_HEARTBEAT_REPLY, &42u32.to_be_bytes());
+        frame.extend(compressed_frame(
+            PROTOVER_ZLIB,
+            &[message(DANMU), bad_header, message(DANMU)],
+        ));
+        frame.extend(encode_packet(OP_AUTH_REPLY, br#"{"code":0}"#));
+
+        let (packets, error) = decode_packets(&frame);
+        assert!(error.unwrap().contains("malformed packet"));
+        let ops: Vec<u32> = packets.iter().map(|p| p.op).collect();
+        assert_eq!(ops, vec![OP_HEARTBEAT_REPLY, OP_MESSAGE]);
+        assert_eq!(packets[1].body, DANMU);
+    }
+
+    #[test]
+    fn refuses_oversized_decompression() {
+        // Zeros compress at least a thousandfold, so this is small on the wire
+        let huge = message(&vec![0; MAX_DECOMPRESSED_LEN as usize]);
+        for protover in [PROTOVER_ZLIB, PROTOVER_BROTLI] {
+            let (packets, error) =
+                decode_packets(&compressed_frame(protover, std::slice::from_ref(&huge)));
+            assert!(packets.is_empty());
+            assert!(error.unwrap().contains("more than"));
+        }
+
+        // Right at the limit is still fine
+        let fits = message(&vec![0; MAX_DECOMPRESSED_LEN as usize - HEADER_LEN]);
+        let packets = decode(&compressed_frame(PROTOVER_ZLIB, &[fits]));
+        assert_eq!(
+            packets[0].body.len(),
+            MAX_DECOMPRESSED_LEN as usize - HEADER_LEN
+        );
+    }
+
+    #[test]
+    fn refuses_deeply_nested_compression() {
+        let mut frame = message(DANMU);
+        for _ in 0..MAX_NESTING {
+            frame = compressed_frame(PROTOVER_ZLIB, &[frame]);
+        }
+        assert_eq!(decode(&frame)[0].body, DANMU);
+
+        let frame = compressed_frame(PROTOVER_BROTLI, &[frame]);
+        let (packets, error) = decode_packets(&frame);
+        assert!(packets.is_empty());
+        assert!(error.unwrap().contains("nested"));
+    }
+}
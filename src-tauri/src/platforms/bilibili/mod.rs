@@ -0,0 +1,11 @@
+pub mod area_list;
+pub mod danmu;
+pub mod models;
+pub mod platform;
+pub mod room_info;
+pub mod search;
+pub mod stream_url;
+pub mod utils;
+
+pub use self::platform::BilibiliPlatform;
+pub use self::stream_url::get_bilibili_stream_url;
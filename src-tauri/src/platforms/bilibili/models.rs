@@ -0,0 +1,269 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+// --- Common envelope: every live.bilibili.com API answers { code, message, data } ---
+
+#[derive(Debug, Deserialize)]
+pub struct BilibiliApiResponse<T> {
+    pub code: i32,
+    #[serde(default)]
+    pub message: String,
+    pub data: Option<T>,
+}
+
+// Bilibili mixes numbers and strings for ids (e.g. area ids in Area/getList are strings)
+pub fn string_or_number<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Value::deserialize(deserializer)? {
+        Value::String(s) => Ok(s),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Null => Ok(String::new()),
+        other => Err(serde::de::Error::custom(format!(
+            "expected string or number, got {}",
+            other
+        ))),
+    }
+}
+
+// --- Area tree (room/v1/Area/getList) ---
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliParentArea {
+    pub name: String,
+    #[serde(default)]
+    pub list: Vec<BilibiliArea>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliArea {
+    #[serde(deserialize_with = "string_or_number")]
+    pub id: String,
+    #[serde(deserialize_with = "string_or_number")]
+    pub parent_id: String,
+    pub name: String,
+    #[serde(default)]
+    pub pic: Option<String>,
+}
+
+// --- Rooms in an area (room/v3/area/getRoomList) ---
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliAreaRoomListData {
+    #[serde(default)]
+    pub count: i64,
+    #[serde(default)]
+    pub list: Vec<BilibiliAreaRoom>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliAreaRoom {
+    pub roomid: i64,
+    pub title: String,
+    pub uname: String,
+    #[serde(default)]
+    pub online: i64,
+    #[serde(default)]
+    pub face: Option<String>,
+    #[serde(default)]
+    pub user_cover: Option<String>,
+    #[serde(default)]
+    pub system_cover: Option<String>,
+}
+
+// --- Room info (xlive/web-room/v1/index/getH5InfoByRoom) ---
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliH5RoomInfoData {
+    pub room_info: BilibiliH5RoomInfo,
+    pub anchor_info: Option<BilibiliAnchorInfo>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliH5RoomInfo {
+    #[serde(default)]
+    pub title: Option<String>,
+    pub live_status: i32, // 0 offline, 1 live, 2 replay loop
+    #[serde(default)]
+    pub live_start_time: i64, // Seconds since the epoch; 0 when offline
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliAnchorInfo {
+    pub base_info: Option<BilibiliAnchorBaseInfo>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliAnchorBaseInfo {
+    pub uname: Option<String>,
+    pub face: Option<String>,
+}
+
+// --- Real room id lookup (room/v1/Room/room_init) ---
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliRoomInitData {
+    pub room_id: i64,
+}
+
+// --- Play info (xlive/web-room/v2/index/getRoomPlayInfo) ---
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliRoomPlayInfoData {
+    pub room_id: i64,
+    pub live_status: i32,
+    pub playurl_info: Option<BilibiliPlayUrlInfo>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliPlayUrlInfo {
+    pub playurl: BilibiliPlayUrl,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliPlayUrl {
+    #[serde(default)]
+    pub g_qn_desc: Vec<BilibiliQnDesc>,
+    #[serde(default)]
+    pub stream: Vec<BilibiliStream>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliQnDesc {
+    pub qn: u32,
+    pub desc: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliStream {
+    pub protocol_name: String, // "http_stream" (FLV) or "http_hls"
+    #[serde(default)]
+    pub format: Vec<BilibiliStreamFormat>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliStreamFormat {
+    pub format_name: String, // "flv", "ts" or "fmp4"
+    #[serde(default)]
+    pub codec: Vec<BilibiliStreamCodec>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliStreamCodec {
+    pub codec_name: String, // "avc" or "hevc"
+    pub current_qn: u32,
+    #[serde(default)]
+    pub accept_qn: Vec<u32>,
+    pub base_url: String,
+    #[serde(default)]
+    pub url_info: Vec<BilibiliUrlInfo>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliUrlInfo {
+    pub host: String,
+    #[serde(default)]
+    pub extra: String,
+}
+
+// --- Danmaku server info (xlive/web-room/v1/index/getDanmuInfo) ---
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliDanmuInfoData {
+    pub token: String,
+    #[serde(default)]
+    pub host_list: Vec<BilibiliDanmuHost>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliDanmuHost {
+    pub host: String,
+    #[serde(default)]
+    pub wss_port: u16,
+}
+
+// --- buvid3 for anonymous requests (api.bilibili.com/x/frontend/finger/spi) ---
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliFingerSpiData {
+    pub b_3: String,
+}
+
+// --- Live search (api.bilibili.com/x/web-interface/search/type?search_type=live) ---
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct BilibiliLiveSearchData {
+    #[serde(default, deserialize_with = "empty_as_default")]
+    pub result: BilibiliLiveSearchResult,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct BilibiliLiveSearchResult {
+    #[serde(default, deserialize_with = "empty_as_default")]
+    pub live_room: Vec<BilibiliSearchLiveRoom>,
+    #[serde(default, deserialize_with = "empty_as_default")]
+    pub live_user: Vec<BilibiliSearchLiveUser>,
+}
+
+// Rooms whose title matched; `title` and `uname` wrap the match in <em class="keyword">
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliSearchLiveRoom {
+    pub roomid: i64,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub uname: String,
+    #[serde(default)]
+    pub uface: String,
+    #[serde(default)]
+    pub user_cover: String,
+    #[serde(default)]
+    pub cover: String,
+    #[serde(default)]
+    pub online: i64,
+}
+
+// Anchors whose name matched; urls are protocol-relative ("//i0.hdslb.com/...")
+#[derive(Debug, Deserialize, Clone)]
+pub struct BilibiliSearchLiveUser {
+    pub roomid: i64,
+    #[serde(default)]
+    pub uname: String,
+    #[serde(default)]
+    pub uface: String,
+    #[serde(default)]
+    pub live_status: i32, // 0 offline, 1 live
+}
+
+// With no hits the search API sends `null` or `[]` where an object or list is expected
+fn empty_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: serde::de::DeserializeOwned + Default,
+{
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(T::default()),
+        Value::Array(items) if items.is_empty() => Ok(T::default()),
+        other => serde_json::from_value(other).map_err(serde::de::Error::custom),
+    }
+}
+
+// --- Public structs for the Tauri command result ---
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BilibiliQuality {
+    pub qn: u32,
+    pub desc: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BilibiliStreamInfo {
+    #[serde(rename = "roomId")]
+    pub room_id: String, // Real (long) room id
+    pub url: String,
+    pub format: String, // "flv" or "m3u8"
+    #[serde(rename = "currentQn")]
+    pub current_qn: u32,
+    pub qualities: Vec<BilibiliQuality>,
+}
@@ -0,0 +1,90 @@
+use async_trait::async_trait;
+use tauri::AppHandle;
+use tokio::sync::oneshot;
+
+use super::area_list::{fetch_area_rooms, fetch_bilibili_areas};
+use super::danmu::client::run_bilibili_danmaku;
+use super::room_info::fetch_bilibili_room_info;
+use super::search::search_bilibili_anchors;
+use super::stream_url::resolve_bilibili_stream;
+use super::utils::BILIBILI_LIVE_REFERER;
+use crate::platforms::common::types_rust::{
+    CommonCategoryGroupRust, CommonLiveRoomRust, CommonRoomInfoRust, CommonRoomListRust,
+    CommonStreamInfoRust, RoomListQueryRust, StreamProtocol, SupportedPlatformRust,
+};
+use crate::platforms::common::LivePlatform;
+use crate::proxy::streams::ProxyStreamHeaders;
+
+pub struct BilibiliPlatform;
+
+#[async_trait]
+impl LivePlatform for BilibiliPlatform {
+    fn platform(&self) -> SupportedPlatformRust {
+        SupportedPlatformRust::Bilibili
+    }
+
+    async fn fetch_categories(&self) -> Result<Vec<CommonCategoryGroupRust>, String> {
+        fetch_bilibili_areas().await
+    }
+
+    async fn fetch_rooms(&self, query: &RoomListQueryRust) -> Result<CommonRoomListRust, String> {
+        // Without a parent the category id is taken as a parent area, listing all of its sub areas
+        match &query.parent_category_id {
+            Some(parent_area_id) => {
+                fetch_area_rooms(
+                    parent_area_id,
+                    &query.category_id,
+                    query.page,
+                    query.page_size,
+                )
+                .await
+            }
+            None => fetch_area_rooms(&query.category_id, "0", query.page, query.page_size).await,
+        }
+    }
+
+    async fn fetch_room_info(&self, room_id: &str) -> Result<CommonRoomInfoRust, String> {
+        fetch_bilibili_room_info(room_id).await
+    }
+
+    // `quality` is a Bilibili qn (e.g. "10000" for 原画, "400" for 蓝光)
+    async fn resolve_stream(
+        &self,
+        room_id: &str,
+        quality: Option<&str>,
+    ) -> Result<CommonStreamInfoRust, String> {
+        let qn = quality
+            .map(|q| {
+                q.parse::<u32>()
+                    .map_err(|_| format!("Invalid Bilibili quality: {}", q))
+            })
+            .transpose()?;
+        let info = resolve_bilibili_stream(room_id, qn, StreamProtocol::Flv).await?;
+        Ok(CommonStreamInfoRust {
+            room_id: room_id.to_string(),
+            url: info.url,
+            format: info.format,
+            platform: SupportedPlatformRust::Bilibili,
+            headers: ProxyStreamHeaders {
+                referer: Some(BILIBILI_LIVE_REFERER.to_string()),
+                ..Default::default()
+            },
+        })
+    }
+
+    async fn search(&self, keyword: &str) -> Result<Vec<CommonLiveRoomRust>, String> {
+        search_bilibili_anchors(keyword).await
+    }
+
+    async fn run_danmaku(
+        &self,
+        app_handle: AppHandle,
+        room_id: String,
+        stop_rx: oneshot::Receiver<()>,
+    ) -> Result<(), String> {
+        let stop_signal = async move {
+            let _ = stop_rx.await;
+        };
+        run_bilibili_danmaku(app_handle, room_id, stop_signal).await
+    }
+}
@@ -0,0 +1,39 @@
+use super::models::{BilibiliH5RoomInfoData, BilibiliRoomInitData};
+use super::utils::{bilibili_http_client, get_bilibili_data, BILIBILI_LIVE_API};
+use crate::platforms::common::types_rust::{CommonRoomInfoRust, SupportedPlatformRust};
+
+pub fn h5_room_info_to_common(room_id: &str, data: BilibiliH5RoomInfoData) -> CommonRoomInfoRust {
+    let base_info = data.anchor_info.and_then(|a| a.base_info);
+    let is_live = data.room_info.live_status == 1; // 2 is a replay loop, not a live broadcast
+    CommonRoomInfoRust {
+        room_id: room_id.to_string(),
+        title: data.room_info.title,
+        anchor_name: base_info.as_ref().and_then(|b| b.uname.clone()),
+        avatar_url: base_info.and_then(|b| b.face).filter(|s| !s.is_empty()),
+        is_live,
+        live_started_at: Some(data.room_info.live_start_time * 1000)
+            .filter(|started_at| is_live && *started_at > 0),
+        platform: SupportedPlatformRust::Bilibili,
+    }
+}
+
+pub async fn fetch_bilibili_room_info(room_id: &str) -> Result<CommonRoomInfoRust, String> {
+    let http_client = bilibili_http_client()?;
+    let url = format!(
+        "{}/xlive/web-room/v1/index/getH5InfoByRoom?room_id={}",
+        BILIBILI_LIVE_API, room_id
+    );
+    let data: BilibiliH5RoomInfoData = get_bilibili_data(&http_client, &url).await?;
+    Ok(h5_room_info_to_common(room_id, data))
+}
+
+// Short ids (e.g. 6) work for most HTTP APIs but the danmaku server only accepts the real id
+pub async fn resolve_real_room_id(room_id: &str) -> Result<i64, String> {
+    let http_client = bilibili_http_client()?;
+    let url = format!(
+        "{}/room/v1/Room/room_init?id={}",
+        BILIBILI_LIVE_API, room_id
+    );
+    let data: BilibiliRoomInitData = get_bilibili_data(&http_client, &url).await?;
+    Ok(data.room_id)
+}
@@ -0,0 +1,143 @@
+use percent_encoding::{percent_encode, NON_ALPHANUMERIC};
+use reqwest::header::COOKIE;
+use std::collections::HashMap;
+
+use super::models::{BilibiliLiveSearchData, BilibiliSearchLiveRoom};
+use super::utils::{bilibili_http_client, fetch_buvid3, format_viewer_count, get_bilibili_data};
+use crate::platforms::common::types_rust::{CommonLiveRoomRust, SupportedPlatformRust};
+
+// Search highlights the matched keyword with <em class="keyword">...</em>
+fn strip_tags(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => result.push(c),
+            _ => {}
+        }
+    }
+    result
+}
+
+fn absolute_url(url: &str) -> Option<String> {
+    match url {
+        "" => None,
+        _ if url.starts_with("//") => Some(format!("https:{}", url)),
+        _ => Some(url.to_string()),
+    }
+}
+
+fn live_room_to_common(room: &BilibiliSearchLiveRoom) -> CommonLiveRoomRust {
+    CommonLiveRoomRust {
+        room_id: room.roomid.to_string(),
+        title: strip_tags(&room.title),
+        anchor_name: strip_tags(&room.uname),
+        avatar_url: absolute_url(&room.uface),
+        cover_url: absolute_url(&room.user_cover).or_else(|| absolute_url(&room.cover)),
+        viewer_count: Some(format_viewer_count(room.online)),
+        is_live: true, // live_room only lists rooms that are on air
+        platform: SupportedPlatformRust::Bilibili,
+    }
+}
+
+// Anchors whose name matched come first, like Douyu's anchor search, filled in from their
+// room when it is also among the title matches; the remaining title matches follow.
+pub fn search_results_to_rooms(data: BilibiliLiveSearchData) -> Vec<CommonLiveRoomRust> {
+    let result = data.result;
+    let mut live_rooms: HashMap<i64, &BilibiliSearchLiveRoom> = result
+        .live_room
+        .iter()
+        .map(|room| (room.roomid, room))
+        .collect();
+
+    let mut rooms: Vec<CommonLiveRoomRust> = result
+        .live_user
+        .iter()
+        .filter(|user| user.roomid != 0)
+        .map(|user| match live_rooms.remove(&user.roomid) {
+            Some(room) => live_room_to_common(room),
+            None => CommonLiveRoomRust {
+                room_id: user.roomid.to_string(),
+                title: String::new(),
+                anchor_name: strip_tags(&user.uname),
+                avatar_url: absolute_url(&user.uface),
+                cover_url: None,
+                viewer_count: None,
+                is_live: user.live_status == 1,
+                platform: SupportedPlatformRust::Bilibili,
+            },
+        })
+        .collect();
+
+    rooms.extend(
+        result
+            .live_room
+            .iter()
+            .filter(|room| live_rooms.contains_key(&room.roomid))
+            .map(live_room_to_common),
+    );
+    rooms
+}
+
+pub async fn search_bilibili_anchors(keyword: &str) -> Result<Vec<CommonLiveRoomRust>, String> {
+    let buvid = fetch_buvid3().await?;
+    let mut http_client = bilibili_http_client()?;
+    http_client.insert_header(COOKIE, &format!("buvid3={}", buvid))?;
+    let url = format!(
+        "https://api.bilibili.com/x/web-interface/search/type?search_type=live&keyword={}&page=1",
+        percent_encode(keyword.as_bytes(), NON_ALPHANUMERIC)
+    );
+    let data: BilibiliLiveSearchData = get_bilibili_data(&http_client, &url).await?;
+    Ok(search_results_to_rooms(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platforms::bilibili::utils::parse_bilibili_response;
+
+    #[test]
+    fn lists_anchors_then_title_matches() {
+        let data: BilibiliLiveSearchData =
+            parse_bilibili_response(include_str!("fixtures/live_search.json")).unwrap();
+        let rooms = search_results_to_rooms(data);
+        let ids: Vec<&str> = rooms.iter().map(|r| r.room_id.as_str()).collect();
+        assert_eq!(ids, vec!["21452505", "1000002", "7734200"]);
+
+        // Matched anchor with a live room: filled in from the room
+        assert_eq!(rooms[0].anchor_name, "测试主播");
+        assert_eq!(rooms[0].title, "周末一起打游戏");
+        assert_eq!(rooms[0].viewer_count.as_deref(), Some("12.3万"));
+        assert_eq!(
+            rooms[0].avatar_url.as_deref(),
+            Some("https://i2.hdslb.com/bfs/face/0123456789abcdef0123456789abcdef01234567.jpg")
+        );
+        assert!(rooms[0].is_live);
+
+        // Matched anchor who is offline
+        assert_eq!(rooms[1].anchor_name, "测试员小王");
+        assert!(!rooms[1].is_live);
+        assert_eq!(rooms[1].viewer_count, None);
+
+        // Title match: highlight stripped, key frame as cover
+        assert_eq!(rooms[2].title, "测试新麦克风");
+        assert!(rooms[2]
+            .cover_url
+            .as_deref()
+            .unwrap()
+            .starts_with("https://"));
+    }
+
+    #[test]
+    fn accepts_empty_results() {
+        let data: BilibiliLiveSearchData =
+            parse_bilibili_response(include_str!("fixtures/live_search_empty.json")).unwrap();
+        assert!(search_results_to_rooms(data).is_empty());
+
+        let data: BilibiliLiveSearchData =
+            serde_json::from_str(r#"{"seid":"1","page":1,"result":[]}"#).unwrap();
+        assert!(search_results_to_rooms(data).is_empty());
+    }
+}
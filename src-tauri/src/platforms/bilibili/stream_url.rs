@@ -0,0 +1,205 @@
+use super::models::{
+    BilibiliPlayUrl, BilibiliQuality, BilibiliRoomPlayInfoData, BilibiliStreamCodec,
+    BilibiliStreamInfo,
+};
+use super::utils::{bilibili_http_client, get_bilibili_data, BILIBILI_LIVE_API};
+use crate::platforms::common::types_rust::StreamProtocol;
+
+// 10000 is 原画; the API falls back to the best quality the room offers below the requested one
+pub const DEFAULT_BILIBILI_QN: u32 = 10000;
+
+// (protocol_name, format_name) pairs in order of preference
+fn candidates(protocol: StreamProtocol) -> [(&'static str, &'static str); 3] {
+    match protocol {
+        StreamProtocol::Flv => [
+            ("http_stream", "flv"),
+            ("http_hls", "ts"),
+            ("http_hls", "fmp4"),
+        ],
+        StreamProtocol::Hls => [
+            ("http_hls", "ts"),
+            ("http_hls", "fmp4"),
+            ("http_stream", "flv"),
+        ],
+    }
+}
+
+fn find_codec<'a>(
+    play_url: &'a BilibiliPlayUrl,
+    protocol_name: &str,
+    format_name: &str,
+) -> Option<&'a BilibiliStreamCodec> {
+    let codecs = &play_url
+        .stream
+        .iter()
+        .find(|s| s.protocol_name == protocol_name)?
+        .format
+        .iter()
+        .find(|f| f.format_name == format_name)?
+        .codec;
+    // HEVC is not playable by mpegts.js in most WebViews, so only use it as a last resort
+    codecs
+        .iter()
+        .find(|c| c.codec_name == "avc")
+        .or_else(|| codecs.first())
+        .filter(|c| !c.url_info.is_empty())
+}
+
+// Picks a stream from getRoomPlayInfo's data
+pub fn select_stream(
+    room_id: &str,
+    data: &BilibiliRoomPlayInfoData,
+    protocol: StreamProtocol,
+) -> Result<BilibiliStreamInfo, String> {
+    if data.live_status != 1 {
+        return Err("主播未开播".to_string());
+    }
+    let play_url = &data
+        .playurl_info
+        .as_ref()
+        .ok_or_else(|| format!("Bilibili room {} has no play url info", room_id))?
+        .playurl;
+
+    let (format_name, codec) = candidates(protocol)
+        .iter()
+        .find_map(|(protocol_name, format_name)| {
+            find_codec(play_url, protocol_name, format_name).map(|c| (*format_name, c))
+        })
+        .ok_or_else(|| format!("No playable stream found for Bilibili room {}", room_id))?;
+
+    let url_info = &codec.url_info[0];
+    let url = format!("{}{}{}", url_info.host, codec.base_url, url_info.extra);
+
+    let qualities = play_url
+        .g_qn_desc
+        .iter()
+        .filter(|q| codec.accept_qn.is_empty() || codec.accept_qn.contains(&q.qn))
+        .map(|q| BilibiliQuality {
+            qn: q.qn,
+            desc: q.desc.clone(),
+        })
+        .collect();
+
+    Ok(BilibiliStreamInfo {
+        room_id: data.room_id.to_string(),
+        url,
+        format: if format_name == "flv" { "flv" } else { "m3u8" }.to_string(),
+        current_qn: codec.current_qn,
+        qualities,
+    })
+}
+
+pub async fn resolve_bilibili_stream(
+    room_id: &str,
+    qn: Option<u32>,
+    protocol: StreamProtocol,
+) -> Result<BilibiliStreamInfo, String> {
+    let http_client = bilibili_http_client()?;
+    let url = format!(
+        "{}/xlive/web-room/v2/index/getRoomPlayInfo?room_id={}&protocol=0,1&format=0,1,2&codec=0,1&qn={}&platform=web&ptype=8&dolby=5&panorama=1",
+        BILIBILI_LIVE_API,
+        room_id,
+        qn.unwrap_or(DEFAULT_BILIBILI_QN)
+    );
+    let data: BilibiliRoomPlayInfoData = get_bilibili_data(&http_client, &url).await?;
+    select_stream(room_id, &data, protocol)
+}
+
+#[tauri::command]
+pub async fn get_bilibili_stream_url(
+    room_id: String,
+    qn: Option<u32>,
+    format: Option<String>,
+) -> Result<BilibiliStreamInfo, String> {
+    let protocol = StreamProtocol::from_format(format.as_deref());
+    resolve_bilibili_stream(&room_id, qn, protocol).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platforms::bilibili::utils::parse_bilibili_response;
+
+    fn play_info() -> BilibiliRoomPlayInfoData {
+        parse_bilibili_response(include_str!("fixtures/room_play_info.json")).unwrap()
+    }
+
+    fn drop_stream(data: &mut BilibiliRoomPlayInfoData, protocol_name: &str, format_name: &str) {
+        let play_url = &mut data.playurl_info.as_mut().unwrap().playurl;
+        for stream in play_url.stream.iter_mut() {
+            if stream.protocol_name == protocol_name {
+                stream.format.retain(|f| f.format_name != format_name);
+            }
+        }
+    }
+
+    #[test]
+    fn prefers_flv() {
+        let info = select_stream("6", &play_info(), StreamProtocol::Flv).unwrap();
+        assert_eq!(info.room_id, "21452505");
+        assert_eq!(info.format, "flv");
+        assert_eq!(info.current_qn, 10000);
+        assert!(info
+            .url
+            .starts_with("https://cn-gdfs-ct-01-01.bilivideo.com/live-bvc/138240/live_434334701_1234567.flv?expires="));
+        // Dolby is only offered for HEVC, so this codec doesn't list it
+        let qns: Vec<u32> = info.qualities.iter().map(|q| q.qn).collect();
+        assert_eq!(qns, vec![10000, 400, 250, 150]);
+        assert_eq!(info.qualities[0].desc, "原画");
+    }
+
+    #[test]
+    fn prefers_hls_when_asked() {
+        let info = select_stream("6", &play_info(), StreamProtocol::Hls).unwrap();
+        assert_eq!(info.format, "m3u8");
+        assert!(info.url.contains("/index.m3u8?"));
+        assert!(info.url.contains("trid=1003abc"));
+    }
+
+    #[test]
+    fn falls_back_to_hls_without_flv() {
+        let mut data = play_info();
+        drop_stream(&mut data, "http_stream", "flv");
+        let info = select_stream("6", &data, StreamProtocol::Flv).unwrap();
+        assert_eq!(info.format, "m3u8");
+        assert!(info.url.contains("trid=1003abc"));
+    }
+
+    #[test]
+    fn skips_hevc_when_avc_is_offered() {
+        let mut data = play_info();
+        drop_stream(&mut data, "http_stream", "flv");
+        drop_stream(&mut data, "http_hls", "ts");
+        let info = select_stream("6", &data, StreamProtocol::Flv).unwrap();
+        assert!(!info.url.contains("prohevc"));
+        assert!(info.url.contains("trid=1007abd"));
+    }
+
+    #[test]
+    fn rejects_offline_rooms() {
+        let mut data = play_info();
+        data.live_status = 0;
+        assert!(select_stream("6", &data, StreamProtocol::Flv).is_err());
+
+        let mut data = play_info();
+        data.playurl_info = None;
+        assert!(select_stream("6", &data, StreamProtocol::Flv).is_err());
+    }
+
+    #[test]
+    fn reads_the_format_argument() {
+        assert_eq!(
+            StreamProtocol::from_format(Some("M3U8")),
+            StreamProtocol::Hls
+        );
+        assert_eq!(
+            StreamProtocol::from_format(Some("hls")),
+            StreamProtocol::Hls
+        );
+        assert_eq!(
+            StreamProtocol::from_format(Some("flv")),
+            StreamProtocol::Flv
+        );
+        assert_eq!(StreamProtocol::from_format(None), StreamProtocol::Flv);
+    }
+}
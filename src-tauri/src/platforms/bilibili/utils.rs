@@ -0,0 +1,61 @@
+use reqwest::header::{ORIGIN, REFERER};
+use serde::de::DeserializeOwned;
+
+use super::models::{BilibiliApiResponse, BilibiliFingerSpiData};
+use crate::platforms::common::http_client::HttpClient;
+
+pub const BILIBILI_LIVE_API: &str = "https://api.live.bilibili.com";
+pub const BILIBILI_LIVE_REFERER: &str = "https://live.bilibili.com/";
+const BILIBILI_LIVE_ORIGIN: &str = "https://live.bilibili.com";
+
+// live.bilibili.com APIs reject requests without a matching Referer/Origin
+pub fn bilibili_http_client() -> Result<HttpClient, String> {
+    let mut http_client =
+        HttpClient::new().map_err(|e| format!("Failed to create HttpClient: {}", e))?;
+    http_client.insert_header(REFERER, BILIBILI_LIVE_REFERER)?;
+    http_client.insert_header(ORIGIN, BILIBILI_LIVE_ORIGIN)?;
+    Ok(http_client)
+}
+
+// Parses the { code, message, data } envelope; a non-zero code becomes the error
+pub fn parse_bilibili_response<T: DeserializeOwned>(text: &str) -> Result<T, String> {
+    let response: BilibiliApiResponse<T> = serde_json::from_str(text)
+        .map_err(|e| format!("Failed to parse Bilibili response: {}", e))?;
+    if response.code != 0 {
+        return Err(format!(
+            "Bilibili API error {}: {}",
+            response.code, response.message
+        ));
+    }
+    response
+        .data
+        .ok_or_else(|| "Bilibili response has no data".to_string())
+}
+
+pub async fn get_bilibili_data<T: DeserializeOwned>(
+    http_client: &HttpClient,
+    url: &str,
+) -> Result<T, String> {
+    let text = http_client.get_text(url).await?;
+    parse_bilibili_response(&text)
+}
+
+// Anonymous requests to risk-controlled APIs (danmaku info, search) need a buvid3 cookie
+pub async fn fetch_buvid3() -> Result<String, String> {
+    let http_client = bilibili_http_client()?;
+    let data: BilibiliFingerSpiData = get_bilibili_data(
+        &http_client,
+        "https://api.bilibili.com/x/frontend/finger/spi",
+    )
+    .await?;
+    Ok(data.b_3)
+}
+
+// Same display style as Douyu's `hn` (e.g. "10.2万")
+pub fn format_viewer_count(count: i64) -> String {
+    if count >= 10_000 {
+        format!("{:.1}万", count as f64 / 10_000.0)
+    } else {
+        count.to_string()
+    }
+}
@@ -0,0 +1,136 @@
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use super::danmaku_supervisor::DanmakuConnectionState;
+use super::types_rust::SupportedPlatformRust;
+
+// The sender of a danmaku event, as far as the platform tells us
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct DanmakuUser {
+    pub uid: Option<String>,
+    pub nickname: String,
+    pub level: Option<i64>,
+    #[serde(rename = "badgeName")]
+    pub badge_name: Option<String>, // Fan badge / medal name
+    #[serde(rename = "badgeLevel")]
+    pub badge_level: Option<i64>,
+}
+
+// Everything a danmaku listener can report, independent of the platform.
+// Serialized with a `type` tag, e.g. { "type": "chat", "user": {...}, "content": "..." }
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum DanmakuEvent {
+    Chat {
+        user: DanmakuUser,
+        content: String,
+        color: Option<String>, // "#rrggbb"; None for the default colour
+    },
+    Gift {
+        user: DanmakuUser,
+        #[serde(rename = "giftId")]
+        gift_id: Option<String>,
+        #[serde(rename = "giftName")]
+        gift_name: String,
+        count: i64, // Gifts added by this event; combos only count what is new since the last update
+        #[serde(rename = "iconUrl")]
+        icon_url: Option<String>,
+        #[serde(rename = "comboCount")]
+        combo_count: i64, // Running total of the combo this event belongs to
+        #[serde(rename = "repeatEnd")]
+        repeat_end: bool, // Last update of the combo; always true for one-off gifts
+        value: Option<i64>, // Worth of `count` gifts in platform currency (Douyin diamonds, Bilibili gold)
+    },
+    Enter {
+        user: DanmakuUser,
+    },
+    Like {
+        user: Option<DanmakuUser>,
+        count: i64,
+    },
+    Follow {
+        user: DanmakuUser,
+    },
+    // Paid membership bought or renewed (Douyu nobles, Bilibili guards, ...)
+    Noble {
+        user: DanmakuUser,
+        level: i64,
+        name: Option<String>, // Tier name, e.g. "伯爵"
+    },
+    UserLevelUp {
+        user: DanmakuUser,
+        level: i64,
+    },
+    // `user.badge_level` is the new level
+    BadgeLevelUp {
+        user: DanmakuUser,
+    },
+    FansRank {
+        #[serde(rename = "fansCount")]
+        fans_count: Option<i64>, // Fan club members
+        top: Vec<DanmakuUser>, // Highest ranked fans first
+    },
+    // Site-wide announcements that may come from another room
+    Broadcast {
+        content: String,
+        #[serde(rename = "targetRoomId")]
+        target_room_id: Option<String>,
+    },
+    RoomStats {
+        #[serde(rename = "viewerCount")]
+        viewer_count: Option<i64>,
+        #[serde(rename = "viewerCountText")]
+        viewer_count_text: Option<String>, // Display string as given by the platform (e.g. "1.2万")
+    },
+    System {
+        content: String,
+    },
+    LiveStatusChanged {
+        #[serde(rename = "isLive")]
+        is_live: bool,
+    },
+    // Emitted by the listener itself, see danmaku_supervisor
+    ConnectionState {
+        state: DanmakuConnectionState,
+        attempt: u32, // Reconnect attempt; 0 for the first connection
+        #[serde(rename = "retryInMs")]
+        retry_in_ms: Option<u64>,
+        error: Option<String>,
+    },
+}
+
+// What actually goes over the wire: the event plus where and when it came from
+#[derive(Debug, Serialize, Clone)]
+pub struct DanmakuEnvelope {
+    pub platform: SupportedPlatformRust,
+    #[serde(rename = "roomId")]
+    pub room_id: String,
+    pub timestamp: i64, // Milliseconds since the epoch, when the backend received it
+    #[serde(flatten)]
+    pub event: DanmakuEvent,
+}
+
+// One channel per platform and room, so the same room id on two sites never collides
+pub fn danmaku_event_name(platform: SupportedPlatformRust, room_id: &str) -> String {
+    format!("danmaku-{}-{}", platform, room_id)
+}
+
+pub fn emit_danmaku(
+    app_handle: &AppHandle,
+    platform: SupportedPlatformRust,
+    room_id: &str,
+    event: DanmakuEvent,
+) {
+    let envelope = DanmakuEnvelope {
+        platform,
+        room_id: room_id.to_string(),
+        timestamp: chrono::Utc::now().timestamp_millis(),
+        event,
+    };
+    if let Err(e) = app_handle.emit(&danmaku_event_name(platform, room_id), envelope) {
+        eprintln!(
+            "[Danmaku] Failed to emit {} event for room {}: {}",
+            platform, room_id, e
+        );
+    }
+}
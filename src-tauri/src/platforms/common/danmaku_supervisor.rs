@@ -0,0 +1,362 @@
+use rand::Rng;
+use serde::Serialize;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::AppHandle;
+use tokio::sync::watch;
+use tokio::time::{Duration, Instant};
+
+use super::danmaku::{emit_danmaku, DanmakuEvent};
+use super::types_rust::SupportedPlatformRust;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// Consecutive failed attempts before giving up; a session that got connected resets the count
+const MAX_ATTEMPTS: u32 = 10;
+// How long a stopping session gets to close its socket before it is dropped
+const STOP_GRACE: Duration = Duration::from_secs(5);
+// Sessions that stayed up this long count as healthy even if they never marked themselves connected
+const STABLE_SESSION: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DanmakuConnectionState {
+    Connecting,
+    Connected,
+    Reconnecting,
+    Failed,
+}
+
+// Sends an event on the room's danmaku channel
+type EmitFn = Arc<dyn Fn(DanmakuEvent) + Send + Sync>;
+
+// Handed to each connection attempt. Sessions emit through it, call `mark_connected` once
+// logged in, and must return promptly once `stopped` resolves.
+#[derive(Clone)]
+pub struct DanmakuSession {
+    emit: EmitFn,
+    room_id: String,
+    stop_rx: watch::Receiver<bool>,
+    connected: Arc<AtomicBool>,
+    attempt: u32,
+}
+
+impl DanmakuSession {
+    pub fn room_id(&self) -> &str {
+        &self.room_id
+    }
+
+    pub fn emit(&self, event: DanmakuEvent) {
+        (self.emit)(event);
+    }
+
+    pub fn mark_connected(&self) {
+        if !self.connected.swap(true, Ordering::SeqCst) {
+            self.emit(connection_state(
+                DanmakuConnectionState::Connected,
+                self.attempt,
+                None,
+                None,
+            ));
+        }
+    }
+
+    fn was_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
+    }
+
+    pub async fn stopped(&self) {
+        let mut stop_rx = self.stop_rx.clone();
+        let _ = stop_rx.wait_for(|stopped| *stopped).await;
+    }
+}
+
+fn connection_state(
+    state: DanmakuConnectionState,
+    attempt: u32,
+    retry_in: Option<Duration>,
+    error: Option<String>,
+) -> DanmakuEvent {
+    DanmakuEvent::ConnectionState {
+        state,
+        attempt,
+        retry_in_ms: retry_in.map(|d| d.as_millis() as u64),
+        error,
+    }
+}
+
+// What follows a session that ended after `attempt` failed attempts in a row: the next attempt
+// and the delay before it, or None to give up. A session that got connected or stayed up for
+// STABLE_SESSION starts the count over. The delay is exponential backoff (1s, 2s, 4s, ... up
+// to MAX_BACKOFF) with jitter: `jitter` in [0, 1] places it between half and all of that, so
+// rooms dropped together don't all reconnect at the same moment.
+fn next_retry(
+    attempt: u32,
+    connected: bool,
+    lasted: Duration,
+    jitter: f64,
+) -> Option<(u32, Duration)> {
+    let attempt = if connected || lasted >= STABLE_SESSION {
+        1
+    } else {
+        attempt + 1
+    };
+    if attempt > MAX_ATTEMPTS {
+        return None;
+    }
+    let cap = INITIAL_BACKOFF
+        .saturating_mul(1 << (attempt - 1).min(16))
+        .min(MAX_BACKOFF);
+    Some((attempt, cap.mul_f64(0.5 + jitter.clamp(0.0, 1.0) / 2.0)))
+}
+
+// Keeps a danmaku connection alive until `stop_signal` completes. `run_session` performs one
+// full connection (fetch tokens, connect, log in, read until closed); whenever it returns
+// without a stop request, it is retried with backoff. State changes are emitted as
+// `DanmakuEvent::ConnectionState` on the room's danmaku channel.
+pub async fn supervise_danmaku<F, Fut>(
+    app_handle: AppHandle,
+    platform: SupportedPlatformRust,
+    room_id: String,
+    stop_signal: impl Future<Output = ()> + Send,
+    run_session: F,
+) where
+    F: FnMut(DanmakuSession) -> Fut,
+    Fut: Future<Output = Result<(), String>> + Send,
+{
+    let emit: EmitFn = {
+        let room_id = room_id.clone();
+        Arc::new(move |event| emit_danmaku(&app_handle, platform, &room_id, event))
+    };
+    supervise(emit, platform, room_id, stop_signal, run_session).await;
+}
+
+async fn supervise<F, Fut>(
+    emit: EmitFn,
+    platform: SupportedPlatformRust,
+    room_id: String,
+    stop_signal: impl Future<Output = ()> + Send,
+    mut run_session: F,
+) where
+    F: FnMut(DanmakuSession) -> Fut,
+    Fut: Future<Output = Result<(), String>> + Send,
+{
+    let (stop_tx, stop_rx) = watch::channel(false);
+    tokio::pin!(stop_signal);
+
+    let mut attempt: u32 = 0;
+    loop {
+        let session = DanmakuSession {
+            emit: emit.clone(),
+            room_id: room_id.clone(),
+            stop_rx: stop_rx.clone(),
+            connected: Arc::new(AtomicBool::new(false)),
+            attempt,
+        };
+        if attempt == 0 {
+            emit(connection_state(
+                DanmakuConnectionState::Connecting,
+                attempt,
+                None,
+                None,
+            ));
+        }
+
+        let started_at = Instant::now();
+        let session_future = run_session(session.clone());
+        tokio::pin!(session_future);
+        let result = tokio::select! {
+            result = &mut session_future => result,
+            _ = &mut stop_signal => {
+                // Let the session close its socket and background tasks itself
+                let _ = stop_tx.send(true);
+                let _ = tokio::time::timeout(STOP_GRACE, session_future).await;
+                println!("[{} Danmaku {}] Listener stopped.", platform, room_id);
+                return;
+            }
+        };
+
+        let error = match result {
+            Ok(()) => None,
+            Err(e) => {
+                eprintln!(
+                    "[{} Danmaku {}] Connection failed: {}",
+                    platform, room_id, e
+                );
+                Some(e)
+            }
+        };
+
+        let jitter = rand::thread_rng().gen_range(0.0..=1.0);
+        let Some((next, delay)) = next_retry(
+            attempt,
+            session.was_connected(),
+            started_at.elapsed(),
+            jitter,
+        ) else {
+            emit(connection_state(
+                DanmakuConnectionState::Failed,
+                attempt,
+                None,
+                error,
+            ));
+            eprintln!(
+                "[{} Danmaku {}] Giving up after {} attempts.",
+                platform, room_id, MAX_ATTEMPTS
+            );
+            return;
+        };
+        attempt = next;
+
+        emit(connection_state(
+            DanmakuConnectionState::Reconnecting,
+            attempt,
+            Some(delay),
+            error,
+        ));
+        println!(
+            "[{} Danmaku {}] Reconnecting in {:?} (attempt {}).",
+            platform, room_id, delay, attempt
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = &mut stop_signal => {
+                println!("[{} Danmaku {}] Listener stopped.", platform, room_id);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn retry(attempt: u32, jitter: f64) -> Option<(u32, Duration)> {
+        next_retry(attempt, false, Duration::ZERO, jitter)
+    }
+
+    #[test]
+    fn backs_off_exponentially_up_to_the_cap() {
+        let delays: Vec<u64> = (0..8)
+            .map(|attempt| retry(attempt, 1.0).unwrap().1.as_secs())
+            .collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(retry(0, 1.0).unwrap().0, 1);
+        assert_eq!(retry(4, 1.0).unwrap().0, 5);
+    }
+
+    #[test]
+    fn jitters_between_half_and_all_of_the_backoff() {
+        assert_eq!(retry(3, 0.0).unwrap().1, Duration::from_secs(4));
+        assert_eq!(retry(3, 0.5).unwrap().1, Duration::from_secs(6));
+        assert_eq!(retry(3, 1.0).unwrap().1, Duration::from_secs(8));
+        assert_eq!(retry(9, 0.0).unwrap().1, Duration::from_secs(30));
+        // Out of range jitter is clamped rather than trusted
+        assert_eq!(retry(3, -1.0).unwrap().1, Duration::from_secs(4));
+        assert_eq!(retry(3, 2.0).unwrap().1, Duration::from_secs(8));
+    }
+
+    #[test]
+    fn gives_up_after_max_attempts() {
+        assert_eq!(retry(MAX_ATTEMPTS - 1, 1.0).unwrap().0, MAX_ATTEMPTS);
+        assert_eq!(retry(MAX_ATTEMPTS, 1.0), None);
+    }
+
+    #[test]
+    fn healthy_sessions_start_the_count_over() {
+        let first = Some((1, INITIAL_BACKOFF));
+        assert_eq!(next_retry(MAX_ATTEMPTS, true, Duration::ZERO, 1.0), first);
+        assert_eq!(next_retry(MAX_ATTEMPTS, false, STABLE_SESSION, 1.0), first);
+        let short = STABLE_SESSION - Duration::from_secs(1);
+        assert_eq!(next_retry(MAX_ATTEMPTS, false, short, 1.0), None);
+    }
+
+    // The connection states emitted, as (state, attempt)
+    type States = Arc<Mutex<Vec<(DanmakuConnectionState, u32)>>>;
+
+    fn recorder() -> (EmitFn, States) {
+        let states = Arc::new(Mutex::new(Vec::new()));
+        let emit: EmitFn = {
+            let states = states.clone();
+            Arc::new(move |event| {
+                if let DanmakuEvent::ConnectionState { state, attempt, .. } = event {
+                    states.lock().unwrap().push((state, attempt));
+                }
+            })
+        };
+        (emit, states)
+    }
+
+    #[tokio::test]
+    async fn stops_a_running_session() {
+        let (emit, states) = recorder();
+        let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+        let saw_stop = Arc::new(AtomicBool::new(false));
+        let supervisor = tokio::spawn(supervise(
+            emit,
+            SupportedPlatformRust::Douyu,
+            "9999".to_string(),
+            async move {
+                let _ = stop_rx.await;
+            },
+            {
+                let saw_stop = saw_stop.clone();
+                move |session: DanmakuSession| {
+                    let saw_stop = saw_stop.clone();
+                    async move {
+                        session.mark_connected();
+                        session.stopped().await;
+                        saw_stop.store(true, Ordering::SeqCst);
+                        Ok(())
+                    }
+                }
+            },
+        ));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        stop_tx.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), supervisor)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(saw_stop.load(Ordering::SeqCst));
+        assert_eq!(
+            *states.lock().unwrap(),
+            vec![
+                (DanmakuConnectionState::Connecting, 0),
+                (DanmakuConnectionState::Connected, 0),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn stops_while_waiting_to_reconnect() {
+        let (emit, states) = recorder();
+        let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+        let supervisor = tokio::spawn(supervise(
+            emit,
+            SupportedPlatformRust::Douyu,
+            "9999".to_string(),
+            async move {
+                let _ = stop_rx.await;
+            },
+            |_session: DanmakuSession| async { Err("refused".to_string()) },
+        ));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        stop_tx.send(()).unwrap();
+        // Well before the first retry, which is at least half of INITIAL_BACKOFF away
+        tokio::time::timeout(INITIAL_BACKOFF / 4, supervisor)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            *states.lock().unwrap(),
+            vec![
+                (DanmakuConnectionState::Connecting, 0),
+                (DanmakuConnectionState::Reconnecting, 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::AppHandle;
+use tokio::sync::oneshot;
+
+use super::types_rust::{
+    CommonCategoryGroupRust, CommonLiveRoomRust, CommonRoomInfoRust, CommonRoomListRust,
+    CommonStreamInfoRust, RoomListQueryRust, SupportedPlatformRust,
+};
+
+// Everything the app needs from a live streaming site. Each platform module
+// provides one implementation, and the generic `platform_*` commands dispatch
+// to it through the PlatformRegistry.
+//
+// Methods a site does not offer keep the default body, which returns an error
+// naming the platform, so callers can surface it as-is.
+#[async_trait]
+pub trait LivePlatform: Send + Sync {
+    fn platform(&self) -> SupportedPlatformRust;
+
+    async fn fetch_categories(&self) -> Result<Vec<CommonCategoryGroupRust>, String> {
+        Err(format!(
+            "{} does not support category listing",
+            self.platform()
+        ))
+    }
+
+    async fn fetch_rooms(&self, query: &RoomListQueryRust) -> Result<CommonRoomListRust, String>;
+
+    async fn fetch_room_info(&self, room_id: &str) -> Result<CommonRoomInfoRust, String>;
+
+    // Returns the real upstream URL; proxying is left to the caller.
+    async fn resolve_stream(
+        &self,
+        room_id: &str,
+        quality: Option<&str>,
+    ) -> Result<CommonStreamInfoRust, String>;
+
+    async fn search(&self, _keyword: &str) -> Result<Vec<CommonLiveRoomRust>, String> {
+        Err(format!(
+            "{} does not support anchor search",
+            self.platform()
+        ))
+    }
+
+    // Runs the danmaku listener for a room until `stop_rx` fires, reconnecting
+    // through supervise_danmaku when the connection drops. Messages are emitted
+    // to the frontend through `app_handle`.
+    async fn run_danmaku(
+        &self,
+        app_handle: AppHandle,
+        room_id: String,
+        stop_rx: oneshot::Receiver<()>,
+    ) -> Result<(), String>;
+}
+
+// Managed by Tauri; maps each supported platform to its implementation
+#[derive(Default, Clone)]
+pub struct PlatformRegistry {
+    platforms: HashMap<SupportedPlatformRust, Arc<dyn LivePlatform>>,
+}
+
+impl PlatformRegistry {
+    pub fn register(&mut self, platform: Arc<dyn LivePlatform>) {
+        self.platforms.insert(platform.platform(), platform);
+    }
+
+    pub fn get(&self, platform: SupportedPlatformRust) -> Result<Arc<dyn LivePlatform>, String> {
+        self.platforms
+            .get(&platform)
+            .cloned()
+            .ok_or_else(|| format!("Platform {} is not supported yet", platform))
+    }
+
+    pub fn platforms(&self) -> Vec<SupportedPlatformRust> {
+        self.platforms.keys().copied().collect()
+    }
+}
@@ -1,8 +1,16 @@
+pub mod danmaku;
+pub mod danmaku_supervisor;
 pub mod http_client;
+pub mod live_platform;
+pub mod platform_commands;
 pub mod types;
 pub mod types_rust;
 
 // Re-export necessary types to make them available directly under platforms::common::TypeName
-pub use types::DouyinDanmakuState;
+pub use danmaku::{DanmakuEvent, DanmakuUser};
+pub use danmaku_supervisor::{supervise_danmaku, DanmakuSession};
+pub use live_platform::{LivePlatform, PlatformRegistry};
+pub use platform_commands::PlatformDanmakuHandles;
+pub use types::DouyinDanmakuHandles;
 pub use types::GetStreamUrlPayload;
 pub use types::LiveStreamInfo;
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+use tokio::sync::oneshot;
+
+use super::live_platform::PlatformRegistry;
+use super::types_rust::{
+    CommonCategoryGroupRust, CommonLiveRoomRust, CommonRoomInfoRust, CommonRoomListRust,
+    CommonStreamInfoRust, RoomListQueryRust, SupportedPlatformRust,
+};
+
+// Stop signals for danmaku listeners started through platform_start_danmaku,
+// keyed by platform and room so the same room id on two sites does not collide
+#[derive(Default, Clone)]
+pub struct PlatformDanmakuHandles(
+    pub Arc<Mutex<HashMap<(SupportedPlatformRust, String), oneshot::Sender<()>>>>,
+);
+
+#[tauri::command]
+pub fn platform_list(registry: State<'_, PlatformRegistry>) -> Vec<SupportedPlatformRust> {
+    registry.platforms()
+}
+
+#[tauri::command]
+pub async fn platform_fetch_categories(
+    platform: SupportedPlatformRust,
+    registry: State<'_, PlatformRegistry>,
+) -> Result<Vec<CommonCategoryGroupRust>, String> {
+    registry.get(platform)?.fetch_categories().await
+}
+
+#[tauri::command]
+pub async fn platform_fetch_rooms(
+    platform: SupportedPlatformRust,
+    query: RoomListQueryRust,
+    registry: State<'_, PlatformRegistry>,
+) -> Result<CommonRoomListRust, String> {
+    registry.get(platform)?.fetch_rooms(&query).await
+}
+
+#[tauri::command]
+pub async fn platform_fetch_room_info(
+    platform: SupportedPlatformRust,
+    room_id: String,
+    registry: State<'_, PlatformRegistry>,
+) -> Result<CommonRoomInfoRust, String> {
+    registry.get(platform)?.fetch_room_info(&room_id).await
+}
+
+#[tauri::command]
+pub async fn platform_resolve_stream(
+    platform: SupportedPlatformRust,
+    room_id: String,
+    quality: Option<String>,
+    registry: State<'_, PlatformRegistry>,
+) -> Result<CommonStreamInfoRust, String> {
+    registry
+        .get(platform)?
+        .resolve_stream(&room_id, quality.as_deref())
+        .await
+}
+
+#[tauri::command]
+pub async fn platform_search(
+    platform: SupportedPlatformRust,
+    keyword: String,
+    registry: State<'_, PlatformRegistry>,
+) -> Result<Vec<CommonLiveRoomRust>, String> {
+    registry.get(platform)?.search(&keyword).await
+}
+
+#[tauri::command]
+pub async fn platform_start_danmaku(
+    platform: SupportedPlatformRust,
+    room_id: String,
+    app_handle: tauri::AppHandle,
+    registry: State<'_, PlatformRegistry>,
+    handles: State<'_, PlatformDanmakuHandles>,
+) -> Result<(), String> {
+    let live_platform = registry.get(platform)?;
+    let key = (platform, room_id.clone());
+
+    // Restart rather than duplicate if the room is already being listened to
+    if let Some(existing_sender) = handles.0.lock().unwrap().remove(&key) {
+        let _ = existing_sender.send(());
+    }
+
+    let (stop_tx, stop_rx) = oneshot::channel();
+    handles.0.lock().unwrap().insert(key.clone(), stop_tx);
+
+    let handles_clone = handles.inner().clone();
+    tokio::spawn(async move {
+        if let Err(e) = live_platform
+            .run_danmaku(app_handle, room_id.clone(), stop_rx)
+            .await
+        {
+            eprintln!(
+                "[Platform Danmaku] {} listener for room {} failed: {}",
+                platform, room_id, e
+            );
+        }
+        // Drop our entry if it still belongs to this task (it may have been replaced by a restart)
+        let mut lock = handles_clone.0.lock().unwrap();
+        if lock.get(&key).map_or(false, |sender| sender.is_closed()) {
+            lock.remove(&key);
+        }
+    });
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn platform_stop_danmaku(
+    platform: SupportedPlatformRust,
+    room_id: String,
+    handles: State<'_, PlatformDanmakuHandles>,
+) -> Result<(), String> {
+    if let Some(sender) = handles
+        .0
+        .lock()
+        .unwrap()
+        .remove(&(platform, room_id.clone()))
+    {
+        sender.send(()).map_err(|_| {
+            format!(
+                "Failed to stop {} danmaku listener for room {}: receiver dropped.",
+                platform, room_id
+            )
+        })
+    } else {
+        Ok(())
+    }
+}
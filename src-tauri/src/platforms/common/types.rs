@@ -1,4 +1,9 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+use crate::platforms::douyin::stream_quality::DouyinStreamQuality;
 
 // Wrapper for payload like { args: { room_id_str: "..." } }
 // Used by get_douyin_live_stream_url and start_douyin_danmaku_listener
@@ -20,6 +25,9 @@ pub struct RoomIdDetail {
 #[allow(dead_code)]
 pub struct GetStreamUrlArgs {
     pub room_id_str: String,
+    // Preferred quality key (see DouyinStreamQuality); degrades from there when unavailable
+    #[serde(default)]
+    pub quality: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -38,6 +46,9 @@ pub struct LiveStreamInfo {
     pub stream_url: Option<String>,
     pub status: Option<i32>,
     pub error_message: Option<String>,
+    // What the room offers and which of it is playing; empty / None when not resolved
+    pub qualities: Vec<DouyinStreamQuality>,
+    pub quality: Option<String>,
 }
 
 #[derive(Default, Clone)]
@@ -46,11 +57,9 @@ pub struct StreamUrlStore {
     pub url: std::sync::Arc<std::sync::Mutex<String>>,
 }
 
-// Moved from main.rs
-// State for the Douyin Danmaku listener
-#[derive(Default)]
-#[allow(dead_code)]
-pub struct DouyinDanmakuState(pub std::sync::Mutex<Option<tokio::sync::mpsc::Sender<()>>>);
+// State for managing Douyin danmaku listener handles (stop signals), keyed by room id
+#[derive(Default, Clone)]
+pub struct DouyinDanmakuHandles(pub Arc<Mutex<HashMap<String, oneshot::Sender<()>>>>);
 
 // State for the Douyu Danmaku listener
 #[derive(Default)]
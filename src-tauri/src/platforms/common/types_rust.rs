@@ -1,15 +1,39 @@
 use serde::{Deserialize, Serialize};
 
+use crate::proxy::streams::ProxyStreamHeaders;
+
 // Enum mirroring TypeScript SupportedPlatform
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum SupportedPlatformRust {
     #[serde(rename = "douyu")]
     Douyu,
     #[serde(rename = "bilibili")]
     Bilibili,
+    #[serde(rename = "douyin")]
+    Douyin,
+    #[serde(rename = "huya")]
+    Huya,
     // Add other platforms as they are supported
 }
 
+impl SupportedPlatformRust {
+    // Same lowercase name the frontend uses, handy for event names and log prefixes
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SupportedPlatformRust::Douyu => "douyu",
+            SupportedPlatformRust::Bilibili => "bilibili",
+            SupportedPlatformRust::Douyin => "douyin",
+            SupportedPlatformRust::Huya => "huya",
+        }
+    }
+}
+
+impl std::fmt::Display for SupportedPlatformRust {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 // Struct mirroring TypeScript CommonPlatformCategory
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CommonPlatformCategoryRust {
@@ -31,3 +55,98 @@ pub struct CommonCategoryGroupRust {
     pub platform: SupportedPlatformRust,
     pub categories: Vec<CommonPlatformCategoryRust>,
 }
+
+// A single room card as shown in the live lists and search results
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommonLiveRoomRust {
+    #[serde(rename = "roomId")]
+    pub room_id: String,
+    pub title: String,
+    #[serde(rename = "anchorName")]
+    pub anchor_name: String,
+    #[serde(rename = "avatarUrl")]
+    pub avatar_url: Option<String>,
+    #[serde(rename = "coverUrl")]
+    pub cover_url: Option<String>,
+    #[serde(rename = "viewerCount")]
+    pub viewer_count: Option<String>, // Display string as given by the platform (e.g. "10.2万")
+    #[serde(rename = "isLive")]
+    pub is_live: bool,
+    pub platform: SupportedPlatformRust,
+}
+
+// One page of rooms for a category
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommonRoomListRust {
+    pub rooms: Vec<CommonLiveRoomRust>,
+    #[serde(rename = "hasMore")]
+    pub has_more: bool,
+}
+
+// Query for a page of rooms. `parent_category_id` is the `parentId` of the
+// CommonPlatformCategoryRust the frontend selected, passed back unchanged.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RoomListQueryRust {
+    #[serde(rename = "categoryId")]
+    pub category_id: String,
+    #[serde(rename = "parentCategoryId")]
+    pub parent_category_id: Option<String>,
+    pub page: u32, // 0-based
+    #[serde(rename = "pageSize")]
+    pub page_size: u32,
+}
+
+// Room metadata, independent of whether a stream URL was resolved
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommonRoomInfoRust {
+    #[serde(rename = "roomId")]
+    pub room_id: String,
+    pub title: Option<String>,
+    #[serde(rename = "anchorName")]
+    pub anchor_name: Option<String>,
+    #[serde(rename = "avatarUrl")]
+    pub avatar_url: Option<String>,
+    #[serde(rename = "isLive")]
+    pub is_live: bool,
+    #[serde(rename = "liveStartedAt")]
+    pub live_started_at: Option<i64>, // Milliseconds since the epoch; None when offline or not reported
+    pub platform: SupportedPlatformRust,
+}
+
+// A resolved (unproxied) stream URL
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommonStreamInfoRust {
+    #[serde(rename = "roomId")]
+    pub room_id: String,
+    pub url: String,
+    pub format: String, // "flv" or "m3u8"
+    pub platform: SupportedPlatformRust,
+    // What the CDN expects alongside requests for `url`
+    #[serde(default)]
+    pub headers: ProxyStreamHeaders,
+}
+
+// The delivery a stream command was asked for, from its `format` argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamProtocol {
+    Flv,
+    Hls,
+}
+
+impl StreamProtocol {
+    // Anything other than "hls"/"m3u8" keeps the FLV default, which the player handles best
+    pub fn from_format(format: Option<&str>) -> Self {
+        match format.map(|f| f.to_ascii_lowercase()) {
+            Some(f) if f == "hls" || f == "m3u8" => StreamProtocol::Hls,
+            _ => StreamProtocol::Flv,
+        }
+    }
+
+    // The `format` reported back with a resolved stream
+    pub fn format_name(&self) -> &'static str {
+        match self {
+            StreamProtocol::Flv => "flv",
+            StreamProtocol::Hls => "m3u8",
+        }
+    }
+}
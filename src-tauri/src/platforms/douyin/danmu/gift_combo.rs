@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// Combos that haven't been updated for this long are forgotten
+const COMBO_TTL: Duration = Duration::from_secs(60);
+
+struct ComboState {
+    total: u64,
+    ended: bool,
+    last_seen: Instant,
+}
+
+// Douyin resends a GiftMessage for every step of a combo with the running total
+// (and sometimes the same step twice). This keeps the last total per combo so each
+// message can be turned into "how many gifts are new", and duplicates dropped.
+#[derive(Default)]
+pub struct GiftComboTracker {
+    combos: HashMap<String, ComboState>,
+}
+
+impl GiftComboTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns the number of new gifts for this update, or None if it's a duplicate
+    // (no new gifts and not the first end-of-combo message).
+    pub fn update(&mut self, combo_key: &str, total: u64, repeat_end: bool) -> Option<u64> {
+        self.update_at(combo_key, total, repeat_end, Instant::now())
+    }
+
+    fn update_at(
+        &mut self,
+        combo_key: &str,
+        total: u64,
+        repeat_end: bool,
+        now: Instant,
+    ) -> Option<u64> {
+        self.combos
+            .retain(|_, state| now.duration_since(state.last_seen) < COMBO_TTL);
+
+        let state = self
+            .combos
+            .entry(combo_key.to_string())
+            .or_insert(ComboState {
+                total: 0,
+                ended: false,
+                last_seen: now,
+            });
+        state.last_seen = now;
+
+        let delta = total.saturating_sub(state.total);
+        let first_end = repeat_end && !state.ended;
+        state.total = state.total.max(total);
+        state.ended |= repeat_end;
+
+        if delta == 0 && !first_end {
+            return None;
+        }
+        Some(delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_repeated_steps() {
+        let mut tracker = GiftComboTracker::new();
+        assert_eq!(tracker.update("a", 1, false), Some(1));
+        assert_eq!(tracker.update("a", 1, false), None);
+        assert_eq!(tracker.update("a", 3, false), Some(2));
+        assert_eq!(tracker.update("a", 3, false), None);
+        // Other combos keep their own totals
+        assert_eq!(tracker.update("b", 2, false), Some(2));
+    }
+
+    #[test]
+    fn ignores_totals_that_arrive_late() {
+        let mut tracker = GiftComboTracker::new();
+        assert_eq!(tracker.update("a", 5, false), Some(5));
+        assert_eq!(tracker.update("a", 3, false), None);
+        assert_eq!(tracker.update("a", 6, false), Some(1));
+    }
+
+    #[test]
+    fn passes_the_first_end_of_combo_through() {
+        let mut tracker = GiftComboTracker::new();
+        assert_eq!(tracker.update("a", 5, false), Some(5));
+        assert_eq!(tracker.update("a", 5, true), Some(0));
+        assert_eq!(tracker.update("a", 5, true), None);
+        // A one-off gift is new and ended in the same message
+        assert_eq!(tracker.update("b", 1, true), Some(1));
+        assert_eq!(tracker.update("b", 1, true), None);
+    }
+
+    #[test]
+    fn forgets_combos_after_the_ttl() {
+        let mut tracker = GiftComboTracker::new();
+        let start = Instant::now();
+        assert_eq!(tracker.update_at("a", 5, true, start), Some(5));
+        let later = start + COMBO_TTL - Duration::from_secs(1);
+        assert_eq!(tracker.update_at("a", 5, true, later), None);
+        // Seen again just now, so it lasts another TTL from here
+        let expired = later + COMBO_TTL;
+        assert_eq!(tracker.update_at("b", 1, false, expired), Some(1));
+        assert_eq!(tracker.combos.len(), 1);
+        assert_eq!(tracker.update_at("a", 5, true, expired), Some(5));
+    }
+}
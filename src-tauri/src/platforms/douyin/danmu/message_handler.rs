@@ -2,11 +2,12 @@ use flate2::read::GzDecoder;
 use futures_util::{stream::SplitStream, StreamExt};
 use prost::Message as ProstMessage; // For decode/encode
 use std::io::Read;
-use tauri::Emitter;
 use tokio::sync::mpsc::Sender;
-use tokio_tungstenite::tungstenite::protocol::Message as WsMessage; // Import the Emitter trait for app_handle.emit()
+use tokio_tungstenite::tungstenite::protocol::Message as WsMessage;
 
+use crate::platforms::common::DanmakuSession;
 use crate::platforms::douyin::danmu::gen::{PushFrame, Response}; // Removed ::douyin
+use crate::platforms::douyin::danmu::gift_combo::GiftComboTracker;
 use crate::platforms::douyin::danmu::message_parsers;
 use crate::platforms::douyin::danmu::websocket_connection::WsStream; // Corrected path // Corrected path
 
@@ -14,13 +15,13 @@ use crate::platforms::douyin::danmu::websocket_connection::WsStream; // Correcte
 pub async fn handle_received_messages(
     mut read_stream: SplitStream<WsStream>,
     ack_tx: Sender<WsMessage>,
-    app_handle: tauri::AppHandle, // Added AppHandle
-    room_id: String,              // Added room_id parameter
+    session: DanmakuSession, // Emits under the room id the frontend listens on (not the internal one)
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     println!(
         "[Douyin Danmaku] Message handler started for room_id: {}",
-        room_id
+        session.room_id()
     );
+    let mut gift_tracker = GiftComboTracker::new();
     while let Some(message_result) = read_stream.next().await {
         match message_result {
             Ok(ws_msg) => {
@@ -60,32 +61,53 @@ pub async fn handle_received_messages(
                                         }
                                         for msg in response.messages_list {
                                             // println!("  -> Method: {}, Payload Length: {}", msg.method, msg.payload.len());
-                                            let mut danmaku_to_send = None;
-                                            if msg.method == "WebcastChatMessage" {
-                                                match message_parsers::parse_chat_message(
-                                                    &msg.payload,
-                                                    &room_id,
-                                                ) {
-                                                    Ok(Some(chat_payload)) => {
-                                                        danmaku_to_send = Some(chat_payload);
-                                                    }
-                                                    Ok(None) => { /* Not a message to display or ignored */
-                                                    }
-                                                    Err(_e) => { /* Error already logged in parser */
-                                                    }
+                                            let parsed = match msg.method.as_str() {
+                                                "WebcastChatMessage" => {
+                                                    message_parsers::parse_chat_message(
+                                                        &msg.payload,
+                                                    )
                                                 }
-                                            }
-                                            // Add other message types here if needed, similar to ChatMessage
-                                            // else if msg.method == "WebcastMemberMessage" { ... }
-
-                                            if let Some(payload) = danmaku_to_send {
-                                                // Use app_handle.emit for Tauri v2 style global event emitting
-                                                if let Err(e) = app_handle
-                                                    .emit("danmaku-message", payload.clone())
-                                                {
-                                                    // payload needs to be Clone for emit
-                                                    eprintln!("[Douyin Danmaku] Failed to emit danmaku event: {}", e);
+                                                "WebcastGiftMessage" => {
+                                                    message_parsers::parse_gift_message(
+                                                        &msg.payload,
+                                                        &mut gift_tracker,
+                                                    )
+                                                }
+                                                "WebcastMemberMessage" => {
+                                                    message_parsers::parse_member_message(
+                                                        &msg.payload,
+                                                    )
+                                                }
+                                                "WebcastLikeMessage" => {
+                                                    message_parsers::parse_like_message(
+                                                        &msg.payload,
+                                                    )
+                                                }
+                                                "WebcastSocialMessage" => {
+                                                    message_parsers::parse_social_message(
+                                                        &msg.payload,
+                                                    )
+                                                }
+                                                "WebcastRoomStatsMessage" => {
+                                                    message_parsers::parse_room_stats_message(
+                                                        &msg.payload,
+                                                    )
+                                                }
+                                                "WebcastControlMessage" => {
+                                                    message_parsers::parse_control_message(
+                                                        &msg.payload,
+                                                    )
+                                                }
+                                                _ => Ok(None),
+                                            };
+                                            match parsed {
+                                                Ok(Some(event)) => session.emit(event),
+                                                Ok(None) => { /* Not a message to display or ignored */
                                                 }
+                                                Err(e) => eprintln!(
+                                                    "[Douyin Danmaku] Failed to parse {}: {}",
+                                                    msg.method, e
+                                                ),
                                             }
                                         }
                                     }
@@ -1,144 +1,138 @@
-use super::gen::{ChatMessage, LikeMessage, MemberMessage, RoomStatsMessage}; // Updated to directly use types from gen
+use super::gen::{
+    ChatMessage, ControlMessage, GiftMessage, LikeMessage, MemberMessage, RoomStatsMessage,
+    SocialMessage, User,
+}; // Updated to directly use types from gen
+use super::gift_combo::GiftComboTracker;
 use prost::Message as ProstMessage; // For .decode()
-use serde::Serialize; // For an AppHandle.emit_all payload
-use specta; // Keep the main crate import if needed, or just specta::Type
 
-// Define the structure for frontend danmaku messages
-#[derive(Serialize, Clone, Debug, specta::Type)] // Explicitly use specta::Type for derive
-pub struct DanmakuFrontendPayload {
-    pub room_id: String, // Added roomId, ensure it's populated when creating this struct
-    pub user: String,
-    pub content: String,
-    pub user_level: i64,
-    pub fans_club_level: i32,
-    // pub r#type: String, // Example: "chat", "gift", "enter"
-}
+use crate::platforms::common::{DanmakuEvent, DanmakuUser};
 
-// Parser for ChatMessage
-pub fn parse_chat_message(
-    payload: &[u8],
-    current_room_id: &str,
-) -> Result<Option<DanmakuFrontendPayload>, Box<dyn std::error::Error + Send + Sync>> {
-    match ChatMessage::decode(payload) {
-        Ok(chat_msg) => {
-            if let Some(user) = chat_msg.user {
-                // 获取用户等级 (来自 demo)
-                let user_level = user.pay_grade.as_ref().map(|pg| pg.level).unwrap_or(0);
-                // 获取粉丝牌等级 (来自 demo)
-                // 注意：demo中的 fans_club.data.level 路径，确保你的 proto 定义一致
-                // 如果你的 User 结构体中 fans_club 直接是 FansClubData 类型，则不需要 .data
-                // 假设 FansClub 结构体包含一个 Option<FansClubData> 类型的字段 data
-                let fans_club_level = user
-                    .fans_club
-                    .as_ref()
-                    .and_then(|fc| fc.data.as_ref()) // 如果 FansClub 直接是 FansClubData，则为 .map(|fc| fc.level)
-                    .map(|fcd| fcd.level)
-                    .unwrap_or(0);
+type ParseResult = Result<Option<DanmakuEvent>, Box<dyn std::error::Error + Send + Sync>>;
 
-                Ok(Some(DanmakuFrontendPayload {
-                    room_id: current_room_id.to_string(), // Populate room_id
-                    user: user.nick_name.clone(),
-                    content: chat_msg.content.clone(),
-                    user_level,
-                    fans_club_level,
-                    // r#type: "chat".to_string(),
-                }))
-            } else {
-                // 对于没有用户信息的聊天消息 (例如系统消息)，也可能需要发送，但等级为0
-                println!(
-                    "    【聊天msg】Content: {} (no user info)",
-                    chat_msg.content
-                );
-                Ok(Some(DanmakuFrontendPayload {
-                    room_id: current_room_id.to_string(), // Populate room_id
-                    user: "系统".to_string(),             // Or some other placeholder
-                    content: chat_msg.content.clone(),
-                    user_level: 0,
-                    fans_club_level: 0,
-                }))
-            }
-        }
-        Err(e) => {
-            // eprintln!("    【X】Failed to parse ChatMessage in parser: {}", e); // Commented out to suppress error logging as per user request
-            Err(Box::new(e) as Box<dyn std::error::Error + Send + Sync>)
-        }
+// ControlMessage.status 3 means the stream has ended
+const CONTROL_STATUS_LIVE_ENDED: i32 = 3;
+// SocialMessage.action 1 is a follow (shares use other actions)
+const SOCIAL_ACTION_FOLLOW: u64 = 1;
+
+fn to_danmaku_user(user: &User) -> DanmakuUser {
+    // 用户等级来自 pay_grade，粉丝牌来自 fans_club.data
+    let fans_club = user.fans_club.as_ref().and_then(|fc| fc.data.as_ref());
+    DanmakuUser {
+        uid: Some(user.id.to_string()).filter(|id| id != "0"),
+        nickname: user.nick_name.clone(),
+        level: user.pay_grade.as_ref().map(|pg| pg.level),
+        badge_name: fans_club
+            .map(|fcd| fcd.club_name.clone())
+            .filter(|name| !name.is_empty()),
+        badge_level: fans_club.map(|fcd| fcd.level as i64).filter(|l| *l > 0),
     }
 }
 
-// Demo 中此函数返回 Result<(), ...> 并且只打印，这里保持原有返回 Option<DanmakuFrontendPayload> 结构
-// 如果不需要将进场消息发送到前端，可以保持返回 Ok(None)
-#[allow(dead_code)] // ADDED to suppress warning
-pub fn parse_member_message(
-    payload: &[u8],
-    _current_room_id: &str,
-) -> Result<Option<DanmakuFrontendPayload>, Box<dyn std::error::Error + Send + Sync>> {
-    match MemberMessage::decode(payload) {
-        Ok(member_msg) => {
-            if let Some(user) = member_msg.user {
-                let user_level = user.pay_grade.as_ref().map(|pg| pg.level).unwrap_or(0);
-                let fans_club_level = user
-                    .fans_club
-                    .as_ref()
-                    .and_then(|fc| fc.data.as_ref())
-                    .map(|fcd| fcd.level)
-                    .unwrap_or(0);
+// Parser for ChatMessage
+pub fn parse_chat_message(payload: &[u8]) -> ParseResult {
+    let chat_msg = ChatMessage::decode(payload)?;
+    // 对于没有用户信息的聊天消息 (例如系统消息)，以系统身份发送
+    let user = chat_msg
+        .user
+        .as_ref()
+        .map(to_danmaku_user)
+        .unwrap_or_else(|| DanmakuUser {
+            nickname: "系统".to_string(),
+            ..Default::default()
+        });
+    Ok(Some(DanmakuEvent::Chat {
+        user,
+        content: chat_msg.content,
+        color: None,
+    }))
+}
 
-                println!(
-                    "    【进场msg】[用户等级: {}][粉丝牌等级: {}]{} 进入了直播间",
-                    user_level, fans_club_level, user.nick_name
-                );
-                Ok(None) // 当前不发送到前端
-            } else {
-                println!("    【进场msg】MemberMessage without user details.");
-                Ok(None)
-            }
-        }
-        Err(e) => {
-            eprintln!("    【X】Failed to parse MemberMessage in parser: {}", e);
-            Err(Box::new(e) as Box<dyn std::error::Error + Send + Sync>)
-        }
-    }
+// Parser for GiftMessage (礼物消息)
+// 连击礼物会重复推送，repeat_count 为累计数量；由 tracker 去重并换算成本次新增数量
+pub fn parse_gift_message(payload: &[u8], tracker: &mut GiftComboTracker) -> ParseResult {
+    let gift_msg = GiftMessage::decode(payload)?;
+    let gift = gift_msg.gift.as_ref();
+    let is_combo = gift.map(|g| g.combo).unwrap_or(false);
+    let total = gift_msg.repeat_count.max(1) * gift_msg.group_count.max(1);
+    // One-off gifts never get a repeat_end message of their own
+    let repeat_end = !is_combo || gift_msg.repeat_end == 1;
+
+    // group_id identifies one combo run; one-off gifts fall back to the message id
+    let user_id = gift_msg.user.as_ref().map(|u| u.id).unwrap_or(0);
+    let run_id = if is_combo && gift_msg.group_id != 0 {
+        gift_msg.group_id
+    } else {
+        gift_msg.common.as_ref().map(|c| c.msg_id).unwrap_or(0)
+    };
+    let combo_key = format!("{}-{}-{}", user_id, gift_msg.gift_id, run_id);
+    let Some(count) = tracker.update(&combo_key, total, repeat_end) else {
+        return Ok(None);
+    };
+
+    let diamond_count = gift.map(|g| g.diamond_count as i64).unwrap_or(0);
+    Ok(Some(DanmakuEvent::Gift {
+        user: gift_msg
+            .user
+            .as_ref()
+            .map(to_danmaku_user)
+            .unwrap_or_default(),
+        gift_id: Some(gift_msg.gift_id.to_string()),
+        gift_name: gift
+            .map(|g| g.name.clone())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "礼物".to_string()),
+        count: count as i64,
+        icon_url: gift
+            .and_then(|g| g.image.as_ref())
+            .and_then(|image| image.url_list_list.first().cloned()),
+        combo_count: total as i64,
+        repeat_end,
+        value: Some(diamond_count * count as i64),
+    }))
+}
+
+// Parser for MemberMessage (进场消息)
+pub fn parse_member_message(payload: &[u8]) -> ParseResult {
+    let member_msg = MemberMessage::decode(payload)?;
+    Ok(member_msg.user.as_ref().map(|user| DanmakuEvent::Enter {
+        user: to_danmaku_user(user),
+    }))
 }
 
 // Parser for LikeMessage (点赞消息)
-// Demo 中此函数返回 Result<(), ...> 并且只打印
-#[allow(dead_code)] // ADDED to suppress warning
-pub fn parse_like_message(
-    payload: &[u8],
-    _current_room_id: &str,
-) -> Result<Option<DanmakuFrontendPayload>, Box<dyn std::error::Error + Send + Sync>> {
-    match LikeMessage::decode(payload) {
-        Ok(like_msg) => {
-            if let Some(user) = like_msg.user {
-                println!(
-                    "    【点赞msg】{} 点了{}个赞",
-                    user.nick_name, like_msg.count
-                );
-            } else {
-                println!("    【点赞msg】点赞 {} 个 (无用户信息)", like_msg.count);
-            }
-            Ok(None) // 点赞消息通常不直接作为弹幕显示在列表
-        }
-        Err(e) => {
-            eprintln!("    【X】Failed to parse LikeMessage in parser: {}", e);
-            Err(Box::new(e) as Box<dyn std::error::Error + Send + Sync>)
-        }
+pub fn parse_like_message(payload: &[u8]) -> ParseResult {
+    let like_msg = LikeMessage::decode(payload)?;
+    Ok(Some(DanmakuEvent::Like {
+        user: like_msg.user.as_ref().map(to_danmaku_user),
+        count: like_msg.count as i64,
+    }))
+}
+
+// Parser for SocialMessage (关注消息)
+pub fn parse_social_message(payload: &[u8]) -> ParseResult {
+    let social_msg = SocialMessage::decode(payload)?;
+    if social_msg.action != SOCIAL_ACTION_FOLLOW {
+        return Ok(None);
     }
+    Ok(social_msg.user.as_ref().map(|user| DanmakuEvent::Follow {
+        user: to_danmaku_user(user),
+    }))
+}
+
+// Parser for RoomStatsMessage (直播间统计)
+pub fn parse_room_stats_message(payload: &[u8]) -> ParseResult {
+    let stats_msg = RoomStatsMessage::decode(payload)?;
+    Ok(Some(DanmakuEvent::RoomStats {
+        viewer_count: Some(stats_msg.display_value),
+        viewer_count_text: Some(stats_msg.display_long).filter(|s| !s.is_empty()),
+    }))
 }
 
-#[allow(dead_code)] // ADDED to suppress warning
-pub fn parse_room_stats_message(
-    payload: &[u8],
-    _current_room_id: &str,
-) -> Result<Option<DanmakuFrontendPayload>, Box<dyn std::error::Error + Send + Sync>> {
-    match RoomStatsMessage::decode(payload) {
-        Ok(stats_msg) => {
-            println!("    【直播间统计msg】{}", stats_msg.display_long);
-            Ok(None) // 统计信息通常不作为普通弹幕显示
-        }
-        Err(e) => {
-            eprintln!("    【X】Failed to parse RoomStatsMessage in parser: {}", e);
-            Err(Box::new(e) as Box<dyn std::error::Error + Send + Sync>)
-        }
+// Parser for ControlMessage (直播状态)
+pub fn parse_control_message(payload: &[u8]) -> ParseResult {
+    let control_msg = ControlMessage::decode(payload)?;
+    if control_msg.status == CONTROL_STATUS_LIVE_ENDED {
+        return Ok(Some(DanmakuEvent::LiveStatusChanged { is_live: false }));
     }
+    Ok(None)
 }
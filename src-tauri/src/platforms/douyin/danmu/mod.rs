@@ -1,8 +1,10 @@
 // This file makes the 'danmu' directory a module.
 
 pub mod gen;
+pub mod gift_combo;
 pub mod message_handler;
 pub mod message_parsers;
 pub mod signature;
 pub mod web_fetcher;
 pub mod websocket_connection;
+pub mod x_bogus;
@@ -1,13 +1,10 @@
-use deno_core::{FastString, JsRuntime, RuntimeOptions};
+use super::x_bogus;
 use md5::{Digest, Md5};
 use rand::Rng;
 use std::collections::HashMap;
 use url::Url;
 
-// Load sign.js content at compile time
-const SIGN_JS_CONTENT: &str = include_str!("./sign.js");
-
-pub async fn generate_signature(
+pub fn generate_signature(
     wss_url: &str,
 ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
     let parsed_url = Url::parse(wss_url)?;
@@ -40,63 +37,13 @@ pub async fn generate_signature(
         tpl_params_vec.push(format!("{}={}", key_str, value));
     }
     let to_sign_str = tpl_params_vec.join(",");
-    println!("[Rust] String to MD5 for signature: {}", to_sign_str);
 
     // Use md_5 crate for MD5 computation
     let mut hasher = Md5::new();
     hasher.update(to_sign_str.as_bytes());
     let digest_bytes = hasher.finalize();
-    let md5_param = format!("{:x}", digest_bytes);
-    println!("[Rust] MD5 param for signature: {}", md5_param);
-
-    let mut runtime = JsRuntime::new(RuntimeOptions::default());
-
-    let bootstrap_script = r#"
-        globalThis.window = globalThis;
-        globalThis.self = globalThis;
-        globalThis.document = {}; 
-        globalThis.navigator = {
-            userAgent: "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36"
-        };
-    "#;
-    runtime
-        .execute_script("[bootstrap]", FastString::from_static(bootstrap_script))
-        .map_err(|e| {
-            eprintln!("Error during deno_core bootstrap script: {}", e);
-            e
-        })?;
-
-    // Use the embedded sign.js content
-    runtime
-        .execute_script("./sign.js", FastString::from_static(SIGN_JS_CONTENT))
-        .map_err(|e| {
-            eprintln!("Error during deno_core eval of sign.js: {}", e);
-            e
-        })?;
 
-    let call_script = format!("get_sign('{}')", md5_param);
-    // For dynamic strings, convert to String first, then into FastString
-    let fast_call_script = FastString::from(call_script);
-    let result = runtime
-        .execute_script("[call_get_sign]", fast_call_script)
-        .map_err(|e| {
-            eprintln!("Error during deno_core call to get_sign: {}", e);
-            e
-        })?;
-
-    let scope = &mut runtime.handle_scope();
-    let local_value = deno_core::v8::Local::new(scope, result);
-
-    if local_value.is_string() {
-        let signature = local_value.to_rust_string_lossy(scope);
-        println!("[Rust] Final signature: {}", signature);
-        Ok(signature)
-    } else {
-        Err(
-            Box::from("get_sign did not return a string value from deno_core")
-                as Box<dyn std::error::Error + Send + Sync>,
-        )
-    }
+    Ok(x_bogus::frontier_sign(&digest_bytes))
 }
 
 pub fn generate_ms_token(length: usize) -> String {
@@ -116,9 +63,3 @@ pub fn generate_douyin_ms_token() -> String {
     // If variable length is needed, this command could take a length parameter.
     generate_ms_token(107)
 }
-
-// Placeholder for the more complex signature generation if needed later.
-// pub async fn generate_signature(wss_url: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-//     // ... (implementation from demo if required)
-//     unimplemented!();
-// }
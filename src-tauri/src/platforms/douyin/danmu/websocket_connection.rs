@@ -44,7 +44,7 @@ pub async fn connect_and_manage_websocket(
         &internal_ext_original, 
         room_id
     );
-    let signature = signature::generate_signature(&wss_url_str_for_signature).await?;
+    let signature = signature::generate_signature(&wss_url_str_for_signature)?;
     let internal_ext_encoded = urlencoding::encode(&internal_ext_original);
     let final_wss_url_str = format!(
         "{}&signature={}",
@@ -91,7 +91,12 @@ pub async fn connect_and_manage_websocket(
                         break;
                     }
                 }
-                Some(msg_to_send) = rx.recv() => {
+                msg_option = rx.recv() => {
+                    let Some(msg_to_send) = msg_option else {
+                        // Every sender is gone: the session is over, so close our half too
+                        let _ = write.close().await;
+                        break;
+                    };
                     if let Err(e) = write.send(msg_to_send).await {
                         println!("【X】消息发送任务错误: {}, 连接可能已断开", e);
                         // Potentially break or signal error if a crucial message (like ACK) fails
@@ -101,10 +106,6 @@ pub async fn connect_and_manage_websocket(
                         }
                     }
                 }
-                else => {
-                    // Channel closed, no more messages to send from other tasks
-                    break;
-                }
             }
         }
         println!("WebSocket send/heartbeat task ended.");
@@ -0,0 +1,202 @@
+// Native port of the X-Bogus that byted_acrawler's frontierSign returns for the webcast
+// WebSocket, which is what the bundled sign.js used to be evaluated for. The signed stub is
+// an MD5 digest; its MD5 is packed with an empty-body hash, a per-process call counter and a
+// random byte, RC4-encrypted under a random one-byte key and encoded with a custom alphabet.
+
+use md5::{Digest, Md5};
+use rand::Rng;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+const ALPHABET: &[u8; 64] = b"Dkdpgh4ZKsQB80/Mfvw36XI1R25+WUAlEi7NLboqYTOPuzmFjJnryx9HVGcaStCe";
+
+// Bytes 14 and 15 of md5(md5("")), since frontierSign signs no body
+const EMPTY_BODY_HASH: [u8; 2] = [0x45, 0x3f];
+
+// frontierSign counts its calls since the script loaded
+static CALLS: AtomicU32 = AtomicU32::new(0);
+
+// `stub` is the raw digest whose hex form sign.js took as X-MS-STUB
+pub fn frontier_sign(stub: &[u8]) -> String {
+    let call = CALLS.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+    let mut rng = rand::thread_rng();
+    // sign.js takes these as floor(Math.random() * 255), which never reaches 255 either
+    sign_with(
+        stub,
+        call,
+        rng.gen_bool(0.5),
+        rng.gen_range(0..255),
+        rng.gen_range(0..255),
+    )
+}
+
+fn sign_with(stub: &[u8], call: u32, flag: bool, salt: u8, key: u8) -> String {
+    let stub_hash = Md5::digest(stub);
+    let mut payload = vec![
+        (call & 63) as u8,
+        0,
+        1,
+        14,
+        EMPTY_BODY_HASH[0],
+        EMPTY_BODY_HASH[1],
+        stub_hash[14],
+        stub_hash[15],
+        salt,
+    ];
+    let checksum = payload.iter().fold(0, |acc, b| acc ^ b);
+    payload.push(checksum);
+
+    let mut bytes = vec![if flag { 0x50 } else { 0x40 }, key];
+    bytes.extend(rc4(&[key], &payload));
+    encode(&bytes)
+}
+
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut s: Vec<u8> = (0..=255).collect();
+    let mut j = 0u8;
+    for i in 0..256 {
+        j = j.wrapping_add(s[i]).wrapping_add(key[i % key.len()]);
+        s.swap(i, j as usize);
+    }
+    let (mut i, mut j) = (0u8, 0u8);
+    data.iter()
+        .map(|b| {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(s[i as usize]);
+            s.swap(i as usize, j as usize);
+            b ^ s[s[i as usize].wrapping_add(s[j as usize]) as usize]
+        })
+        .collect()
+}
+
+// Base64 over ALPHABET; the signed bytes are always a multiple of three long, so no padding
+fn encode(bytes: &[u8]) -> String {
+    bytes
+        .chunks(3)
+        .flat_map(|chunk| {
+            let n = (chunk[0] as u32) << 16 | (chunk[1] as u32) << 8 | chunk[2] as u32;
+            [18, 12, 6, 0].map(|shift| ALPHABET[((n >> shift) & 63) as usize] as char)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Produced by sign.js, one call after another in a single context, with Math.random
+    // fixed for each call; the flag, salt and key are what it made of those numbers
+    const VECTORS: [(&str, u32, bool, u8, u8, &str); 8] = [
+        (
+            "cfcd208495d565ef66e7dff9f98764da",
+            1,
+            true,
+            236,
+            215,
+            "60UhrnXugkKSfQGl",
+        ),
+        (
+            "c4ca4238a0b923820dcc509a6f75849b",
+            2,
+            false,
+            0,
+            0,
+            "fDpW4KiMGE7IwVPB",
+        ),
+        (
+            "c81e728d9d4c2f636f067f89cc14862c",
+            3,
+            true,
+            254,
+            254,
+            "6MVjhJc1lIAMuIrL",
+        ),
+        (
+            "eccbc87e4b5ce2fe28308fd9f2a7baf3",
+            4,
+            false,
+            108,
+            150,
+            "fsRzL0+P685g+wnO",
+        ),
+        (
+            "44f683a84163b3523afe57c2e008bc8c",
+            63,
+            false,
+            93,
+            29,
+            "fkHlU7eWU5cL5HeB",
+        ),
+        (
+            "03afdbd66e7929b125f8597834fa83a4",
+            64,
+            false,
+            78,
+            238,
+            "f/cuWKNejPSWkSmc",
+        ),
+        (
+            "ea5d2f1c4608232e07d3aa3d998e5135",
+            65,
+            true,
+            137,
+            44,
+            "6drdyXj1IMlu1toR",
+        ),
+        (
+            "14bfa6bb14875e45bba028a21ed38046",
+            70,
+            true,
+            16,
+            222,
+            "60aUhEKtXXM+/flg",
+        ),
+    ];
+
+    fn decode(signature: &str) -> Vec<u8> {
+        let digits: Vec<u32> = signature
+            .bytes()
+            .map(|c| ALPHABET.iter().position(|a| *a == c).unwrap() as u32)
+            .collect();
+        digits
+            .chunks(4)
+            .flat_map(|d| {
+                let n = d[0] << 18 | d[1] << 12 | d[2] << 6 | d[3];
+                [(n >> 16) as u8, (n >> 8) as u8, n as u8]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_sign_js() {
+        for (stub, call, flag, salt, key, expected) in VECTORS {
+            let signature = sign_with(&hex::decode(stub).unwrap(), call, flag, salt, key);
+            assert_eq!(decode(&signature), decode(expected), "call {}", call);
+            assert_eq!(signature, expected);
+        }
+    }
+
+    #[test]
+    fn packs_the_counter_salt_and_checksum() {
+        for (stub, call, flag, salt, key, expected) in VECTORS {
+            let bytes = decode(expected);
+            assert_eq!(bytes[..2], [if flag { 0x50 } else { 0x40 }, key]);
+            let payload = rc4(&[key], &bytes[2..]);
+            let stub_hash = Md5::digest(hex::decode(stub).unwrap());
+            assert_eq!(
+                payload[..9],
+                [
+                    (call % 64) as u8,
+                    0,
+                    1,
+                    14,
+                    0x45,
+                    0x3f,
+                    stub_hash[14],
+                    stub_hash[15],
+                    salt
+                ]
+            );
+            assert_eq!(payload.iter().fold(0, |acc, b| acc ^ b), 0);
+        }
+    }
+}
@@ -1,14 +1,14 @@
-use tauri::Emitter;
-use tokio::sync::mpsc as tokio_mpsc;
+use std::future::Future;
+use tokio::sync::oneshot;
 
-// Assuming DouyinDanmakuState is defined in main.rs (crate root)
-// and common::GetStreamUrlPayload is accessible.
+use crate::platforms::common::types_rust::SupportedPlatformRust;
+use crate::platforms::common::{supervise_danmaku, DanmakuSession, DouyinDanmakuHandles};
 
 #[tauri::command]
 pub async fn start_douyin_danmu_listener(
     payload: crate::platforms::common::GetStreamUrlPayload,
     app_handle: tauri::AppHandle,
-    state: tauri::State<'_, crate::platforms::common::DouyinDanmakuState>,
+    handles: tauri::State<'_, DouyinDanmakuHandles>,
 ) -> Result<(), String> {
     let room_id_or_url = payload.args.room_id_str;
     println!(
@@ -16,101 +16,131 @@ pub async fn start_douyin_danmu_listener(
         room_id_or_url
     );
 
-    let previous_tx = {
-        let mut lock = state.inner().0.lock().unwrap();
-        lock.take()
-    };
-
-    if let Some(tx) = previous_tx {
-        println!("[Douyin Danmaku] Sending shutdown to previous Douyin listener task.");
-        if tx.send(()).await.is_err() {
-            eprintln!("[Douyin Danmaku] Failed to send shutdown. Task might have already completed or panicked.");
-        }
-    }
-
-    if room_id_or_url == "stop_listening" {
+    // If a listener for this room already exists, stop it first; other rooms keep running
+    if let Some(existing_sender) = handles.0.lock().unwrap().remove(&room_id_or_url) {
         println!(
-            "[Douyin Danmaku] Received stop_listening signal. Listener will not be restarted."
+            "[Douyin Danmaku] Stopping previous listener for room {}.",
+            room_id_or_url
         );
-        return Ok(());
+        let _ = existing_sender.send(());
     }
 
-    let (tx_shutdown, mut rx_shutdown) = tokio_mpsc::channel::<()>(1);
-    {
-        let mut lock = state.inner().0.lock().unwrap();
-        *lock = Some(tx_shutdown);
+    let (stop_tx, stop_rx) = oneshot::channel();
+    handles
+        .0
+        .lock()
+        .unwrap()
+        .insert(room_id_or_url.clone(), stop_tx);
+
+    let handles_clone = handles.inner().clone();
+    tokio::spawn(async move {
+        let stop_signal = async move {
+            let _ = stop_rx.await;
+        };
+        let _ = run_douyin_danmaku(app_handle, room_id_or_url.clone(), stop_signal).await;
+        // Drop our entry if it still belongs to this task (it may have been replaced by a restart)
+        let mut lock = handles_clone.0.lock().unwrap();
+        if lock
+            .get(&room_id_or_url)
+            .map_or(false, |sender| sender.is_closed())
+        {
+            lock.remove(&room_id_or_url);
+        }
+    });
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn stop_douyin_danmu_listener(
+    room_id: String,
+    handles: tauri::State<'_, DouyinDanmakuHandles>,
+) -> Result<(), String> {
+    if let Some(sender) = handles.0.lock().unwrap().remove(&room_id) {
+        sender.send(()).map_err(|_| {
+            format!(
+                "Failed to stop Douyin danmaku listener for room {}: receiver dropped.",
+                room_id
+            )
+        })
+    } else {
+        Ok(())
     }
+}
 
-    let app_handle_clone = app_handle.clone();
-    let room_id_str_clone = room_id_or_url.clone();
+// Runs one Douyin danmaku connection until the session is stopped or the connection ends.
+// Every call fetches the room page again, so reconnects get a fresh ttwid, cursor and signature.
+async fn run_douyin_session(session: DanmakuSession) -> Result<(), String> {
+    let room_id_str = session.room_id().to_string();
+    println!(
+        "[Douyin Danmaku] Spawning listener for room: {}",
+        room_id_str
+    );
+
+    let task_result = async {
+        let mut fetcher =
+            crate::platforms::douyin::danmu::web_fetcher::DouyinLiveWebFetcher::new(&room_id_str)?;
+        fetcher
+            .fetch_room_details()
+            .await
+            .map_err(|e| format!("Failed to fetch room details: {}", e))?;
+
+        let actual_room_id = fetcher.get_room_id().await?;
+        let ttwid = fetcher.get_ttwid().await?;
 
-    tokio::spawn(async move {
         println!(
-            "[Douyin Danmaku] Spawning listener for room: {}",
-            room_id_str_clone
+            "[Douyin Danmaku] Using: room_id={}, ttwid={}",
+            actual_room_id, ttwid
         );
 
-        let task_result = async {
-            let mut fetcher = crate::platforms::douyin::danmu::web_fetcher::DouyinLiveWebFetcher::new(&room_id_str_clone)?;
-            fetcher.fetch_room_details().await.map_err(|e| format!("Failed to fetch room details: {}", e))?;
-            
-            let actual_room_id = fetcher.get_room_id().await?;
-            let ttwid = fetcher.get_ttwid().await?;
-            
-            println!("[Douyin Danmaku] Using: room_id={}, ttwid={}", actual_room_id, ttwid);
+        let (read_stream, ack_tx) =
+            crate::platforms::douyin::danmu::websocket_connection::connect_and_manage_websocket(
+                &fetcher,
+                &actual_room_id,
+                &ttwid,
+            )
+            .await?;
 
-            let (read_stream, ack_tx) = 
-                crate::platforms::douyin::danmu::websocket_connection::connect_and_manage_websocket(
-                    &fetcher, 
-                    &actual_room_id, 
-                    &ttwid
-                ).await?;
-            
-            println!("[Douyin Danmaku] WebSocket connected for room: {}", actual_room_id);
+        println!(
+            "[Douyin Danmaku] WebSocket connected for room: {}",
+            actual_room_id
+        );
+        session.mark_connected();
 
-            tokio::select! {
-                res = crate::platforms::douyin::danmu::message_handler::handle_received_messages(
-                    read_stream, 
-                    ack_tx, 
-                    app_handle_clone.clone(),
-                    actual_room_id.clone()
-                ) => {
-                    if let Err(e) = res {
-                        return Err(e); 
-                    }
-                }
-                _ = rx_shutdown.recv() => {
-                    println!("[Douyin Danmaku] Received shutdown signal for room {}.", actual_room_id);
-                }
+        tokio::select! {
+            res = crate::platforms::douyin::danmu::message_handler::handle_received_messages(
+                read_stream,
+                ack_tx,
+                session.clone()
+            ) => {
+                res?;
+                // The handler only returns Ok once the server has closed the socket
+                return Err("Douyin danmaku websocket closed".into());
             }
-            Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
-        }.await;
-
-        if let Err(e) = task_result {
-            eprintln!(
-                "[Douyin Danmaku] Listener task for room {} critically failed: {}",
-                room_id_str_clone, e
-            );
-            let error_payload =
-                crate::platforms::douyin::danmu::message_parsers::DanmakuFrontendPayload {
-                    room_id: room_id_str_clone.clone(),
-                    user: "系统消息".to_string(),
-                    content: format!("弹幕连接发生错误: {}", e),
-                    user_level: 0,
-                    fans_club_level: 0,
-                };
-            if let Err(emit_err) = app_handle.emit("danmaku-message", error_payload) {
-                eprintln!(
-                    "[Douyin Danmaku] Failed to emit error event to frontend: {}",
-                    emit_err
-                );
+            _ = session.stopped() => {
+                println!("[Douyin Danmaku] Received shutdown signal for room {}.", actual_room_id);
             }
-        } else {
-            println!(
-                "[Douyin Danmaku] Listener task for room {} completed.",
-                room_id_str_clone
-            );
         }
-    });
+        Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+    }
+    .await;
+
+    task_result.map_err(|e| e.to_string())
+}
+
+// Keeps a Douyin danmaku connection alive, reconnecting as needed, until `stop_signal` completes.
+// Shared by start_douyin_danmu_listener and DouyinPlatform::run_danmaku.
+pub async fn run_douyin_danmaku(
+    app_handle: tauri::AppHandle,
+    room_id_str: String,
+    stop_signal: impl Future<Output = ()> + Send,
+) -> Result<(), String> {
+    supervise_danmaku(
+        app_handle,
+        SupportedPlatformRust::Douyin,
+        room_id_str,
+        stop_signal,
+        run_douyin_session,
+    )
+    .await;
     Ok(())
 }
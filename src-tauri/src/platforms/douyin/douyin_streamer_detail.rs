@@ -1,18 +1,19 @@
 use crate::platforms::common::http_client::HttpClient;
 use crate::platforms::douyin::models::*;
+use crate::platforms::douyin::stream_quality::{degradation_order, parse_qualities};
 use crate::platforms::douyin::utils::setup_douyin_cookies;
 use reqwest;
 use reqwest::header::REFERER; // For setting referer for the API call
-use tauri::{command, AppHandle, State}; // Added AppHandle and State // 确保 reqwest 被导入
+use tauri::{command, AppHandle}; // Added AppHandle // 确保 reqwest 被导入
                                                                      // use serde::Deserialize; // Removed unused import
                                                                      // use crate::platforms::common::GetStreamUrlPayload; // Will use full path
                                                                      // use crate::platforms::common::LiveStreamInfo; // Will use full path
 
-// Import proxy and store
-use crate::proxy::{start_proxy, ProxyServerHandle};
-use crate::StreamUrlStore;
+// Import proxy
+use crate::platforms::common::types_rust::SupportedPlatformRust;
+use crate::proxy::streams::{register_proxy_stream, ProxyStreamHeaders, ProxyStreamOrigin};
 
-const DOUYIN_API_REFERER: &str = "https://live.douyin.com/";
+pub const DOUYIN_API_REFERER: &str = "https://live.douyin.com/";
 
 // Struct to handle flexible argument deserialization - REMOVED as we use PayloadWrapperForRoomId from common
 // #[derive(Deserialize, Debug)]
@@ -25,35 +26,154 @@ const DOUYIN_API_REFERER: &str = "https://live.douyin.com/";
 #[command]
 pub async fn get_douyin_live_stream_url(
     app_handle: AppHandle,
-    stream_url_store: State<'_, StreamUrlStore>,
-    proxy_server_handle: State<'_, ProxyServerHandle>,
     payload: crate::platforms::common::GetStreamUrlPayload,
 ) -> Result<crate::platforms::common::LiveStreamInfo, String> {
     let room_id_str = payload.args.room_id_str;
+    let (mut stream_info, final_stream_url) =
+        resolve_douyin_live_stream(&room_id_str, payload.args.quality.as_deref()).await?;
 
+    let mut proxied_stream_url: Option<String> = None;
+
+    if let Some(real_url) = &final_stream_url {
+        if !real_url.is_empty() {
+            println!(
+                "[Douyin Live RS] Real stream URL found: {}. Attempting to use proxy.",
+                real_url
+            );
+            // Each room gets its own proxy route, so other rooms keep playing. HLS URLs
+            // from hls_pull_url_map are served as playlists, segments fetched with this Referer.
+            let stream_id = format!("douyin-{}", room_id_str);
+            let headers = ProxyStreamHeaders {
+                referer: Some(DOUYIN_API_REFERER.to_string()),
+                ..Default::default()
+            };
+            // Lets the proxy re-resolve the room if the CDN connection drops
+            let origin = ProxyStreamOrigin {
+                platform: SupportedPlatformRust::Douyin,
+                room_id: room_id_str.clone(),
+                quality: stream_info.quality.clone(),
+            };
+            match register_proxy_stream(
+                &app_handle,
+                &stream_id,
+                real_url.clone(),
+                headers,
+                Some(origin),
+            ) {
+                Ok(p_url) => {
+                    println!(
+                        "[Douyin Live RS] Proxy started successfully. Proxy URL: {}",
+                        p_url
+                    );
+                    proxied_stream_url = Some(p_url);
+                }
+                Err(e) => {
+                    eprintln!("[Douyin Live RS] Failed to start proxy: {}", e);
+                    // stream_url stays None; error_message below tells the frontend
+                }
+            }
+        } else {
+            println!("[Douyin Live RS] Real stream URL is empty. Not attempting proxy.");
+        }
+    } else {
+        println!("[Douyin Live RS] No real stream URL found. Not attempting proxy.");
+    }
+
+    let final_error_message = if proxied_stream_url.is_none() && final_stream_url.is_some() {
+        Some("代理启动失败".to_string())
+    } else {
+        None
+    };
+
+    stream_info.stream_url = proxied_stream_url; // proxied_stream_url is moved here
+    if final_error_message.is_some() {
+        stream_info.error_message = final_error_message; // Use the pre-calculated message
+    }
+    Ok(stream_info)
+}
+
+// An sdk_data FLV URL as the player should get it: pull-flv URLs are used as they are,
+// others are followed one redirect. An Err means this quality isn't being served.
+async fn resolve_flv_url(url: &str) -> Result<String, String> {
+    if url.contains("pull-flv") {
+        return Ok(url.to_string());
+    }
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none()) // 禁止自动重定向
+        .build()
+        .map_err(|e| format!("构建 reqwest 客户端用于重定向失败: {}", e))?;
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("解析重定向请求失败: {}", e))?;
+    if response.status().is_redirection() {
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .filter(|location| !location.is_empty());
+        match location {
+            Some(location) => {
+                println!(
+                    "[Douyin Live RS INFO] 重定向到: {}. 将此作为最终 FLV URL.",
+                    location
+                );
+                Ok(location.to_string())
+            }
+            None => {
+                println!("[Douyin Live RS WARN] 重定向响应中未找到可用的 Location header. 使用原始 FLV URL: {}", url);
+                Ok(url.to_string())
+            }
+        }
+    } else if response.status().is_success() {
+        Ok(url.to_string())
+    } else {
+        Err(format!("状态 {}", response.status()))
+    }
+}
+
+// Fetches room metadata and picks the real (unproxied) stream URL for a Douyin room.
+// Problems the frontend should show (empty id, API errors, ...) come back as
+// LiveStreamInfo.error_message with no URL, matching what the command returns.
+// `preferred_quality` is a key from LiveStreamInfo.qualities; see degradation_order.
+pub async fn resolve_douyin_live_stream(
+    room_id_str: &str,
+    preferred_quality: Option<&str>,
+) -> Result<(crate::platforms::common::LiveStreamInfo, Option<String>), String> {
     if room_id_str.is_empty() {
-        return Ok(crate::platforms::common::LiveStreamInfo {
-            title: None,
-            anchor_name: None,
-            avatar: None,
-            stream_url: None,
-            status: None,
-            error_message: Some("Room ID cannot be empty.".to_string()),
-        });
+        return Ok((
+            crate::platforms::common::LiveStreamInfo {
+                title: None,
+                anchor_name: None,
+                avatar: None,
+                stream_url: None,
+                status: None,
+                error_message: Some("Room ID cannot be empty.".to_string()),
+                qualities: Vec::new(),
+                quality: None,
+            },
+            None,
+        ));
     }
 
     let mut http_client =
         HttpClient::new().map_err(|e| format!("Failed to create HttpClient: {}", e))?;
 
-    if let Err(e) = setup_douyin_cookies(&mut http_client, &room_id_str).await {
-        return Ok(crate::platforms::common::LiveStreamInfo {
-            title: None,
-            anchor_name: None,
-            avatar: None,
-            stream_url: None,
-            status: None,
-            error_message: Some(format!("Cookie setup failed: {}", e)),
-        });
+    if let Err(e) = setup_douyin_cookies(&mut http_client, room_id_str).await {
+        return Ok((
+            crate::platforms::common::LiveStreamInfo {
+                title: None,
+                anchor_name: None,
+                avatar: None,
+                stream_url: None,
+                status: None,
+                error_message: Some(format!("Cookie setup failed: {}", e)),
+                qualities: Vec::new(),
+                quality: None,
+            },
+            None,
+        ));
     }
 
     http_client.insert_header(REFERER, DOUYIN_API_REFERER)?;
@@ -72,14 +192,19 @@ pub async fn get_douyin_live_stream_url(
                 .await
                 .unwrap_or_else(|_| "Failed to get raw error text".to_string());
             // println!("[Douyin Live RS] API request failed. Raw error text (if any): {}", raw_error_text);
-            return Ok(crate::platforms::common::LiveStreamInfo {
-                title: None,
-                anchor_name: None,
-                avatar: None,
-                stream_url: None,
-                status: None,
-                error_message: Some(format!("API request failed: {}. URL: {}", e, api_url)),
-            });
+            return Ok((
+                crate::platforms::common::LiveStreamInfo {
+                    title: None,
+                    anchor_name: None,
+                    avatar: None,
+                    stream_url: None,
+                    status: None,
+                    error_message: Some(format!("API request failed: {}. URL: {}", e, api_url)),
+                    qualities: Vec::new(),
+                    quality: None,
+                },
+                None,
+            ));
         }
     };
 
@@ -96,30 +221,40 @@ pub async fn get_douyin_live_stream_url(
             .and_then(|d| d.prompts.as_ref())
             .cloned()
             .unwrap_or_else(|| "Unknown API error".to_string());
-        return Ok(crate::platforms::common::LiveStreamInfo {
-            title: None,
-            anchor_name: None,
-            avatar: None,
-            stream_url: None,
-            status: None,
-            error_message: Some(format!(
-                "API error (status_code: {}): {}",
-                api_response.status_code, prompts
-            )),
-        });
-    }
-
-    let main_data = match api_response.data {
-        Some(d) => d,
-        None => {
-            return Ok(crate::platforms::common::LiveStreamInfo {
+        return Ok((
+            crate::platforms::common::LiveStreamInfo {
                 title: None,
                 anchor_name: None,
                 avatar: None,
                 stream_url: None,
                 status: None,
-                error_message: Some("API response contained no main 'data' object".to_string()),
-            })
+                error_message: Some(format!(
+                    "API error (status_code: {}): {}",
+                    api_response.status_code, prompts
+                )),
+                qualities: Vec::new(),
+                quality: None,
+            },
+            None,
+        ));
+    }
+
+    let main_data = match api_response.data {
+        Some(d) => d,
+        None => {
+            return Ok((
+                crate::platforms::common::LiveStreamInfo {
+                    title: None,
+                    anchor_name: None,
+                    avatar: None,
+                    stream_url: None,
+                    status: None,
+                    error_message: Some("API response contained no main 'data' object".to_string()),
+                    qualities: Vec::new(),
+                    quality: None,
+                },
+                None,
+            ))
         }
     };
 
@@ -131,19 +266,24 @@ pub async fn get_douyin_live_stream_url(
 
     let current_status = room_data_entry.status;
     if current_status != 2 {
-        return Ok(crate::platforms::common::LiveStreamInfo {
-            title: room_data_entry.title.clone(),
-            anchor_name: main_data.user.as_ref().and_then(|u| u.nickname.clone()),
-            avatar: main_data
-                .user
-                .as_ref()
-                .and_then(|u| u.avatar_thumb.as_ref())
-                .and_then(|at| at.url_list.as_ref())
-                .and_then(|ul| ul.first().cloned()),
-            stream_url: None, // Explicitly None as not live
-            status: Some(current_status),
-            error_message: None, // No error, just not live for streaming purposes. Client can interpret status.
-        });
+        return Ok((
+            crate::platforms::common::LiveStreamInfo {
+                title: room_data_entry.title.clone(),
+                anchor_name: main_data.user.as_ref().and_then(|u| u.nickname.clone()),
+                avatar: main_data
+                    .user
+                    .as_ref()
+                    .and_then(|u| u.avatar_thumb.as_ref())
+                    .and_then(|at| at.url_list.as_ref())
+                    .and_then(|ul| ul.first().cloned()),
+                stream_url: None, // Explicitly None as not live
+                status: Some(current_status),
+                error_message: None, // No error, just not live for streaming purposes. Client can interpret status.
+                qualities: Vec::new(),
+                quality: None,
+            },
+            None,
+        ));
     }
 
     // Only proceed to get stream_url_container if the streamer is live (status == 2)
@@ -155,6 +295,8 @@ pub async fn get_douyin_live_stream_url(
         })?;
 
     let mut final_stream_url: Option<String> = None;
+    let mut qualities = Vec::new();
+    let mut chosen_quality: Option<String> = None;
 
     // Try to get FLV stream from live_core_sdk_data
     if let Some(sdk_data) = &stream_url_container.live_core_sdk_data {
@@ -163,25 +305,30 @@ pub async fn get_douyin_live_stream_url(
                 if !stream_data_str.is_empty() {
                     match serde_json::from_str::<InnerStreamDataWrapper>(stream_data_str) {
                         Ok(inner_wrapper) => {
-                            if let Some(qualities_map) = inner_wrapper.data {
-                                let stream_options = [
-                                    qualities_map.origin.as_ref(),
-                                    qualities_map.hd.as_ref(),
-                                    qualities_map.sd.as_ref(),
-                                ];
-                                for opt_quality_detail in stream_options.iter().flatten() {
-                                    if let Some(links) = &opt_quality_detail.main {
-                                        if let Some(flv_url) =
-                                            links.flv.as_ref().filter(|s| !s.is_empty())
-                                        {
-                                            final_stream_url = Some(flv_url.clone());
-                                            println!("[Douyin Live RS INFO] Found FLV URL from sdk_data: {}", flv_url);
-                                            break; // Found an FLV URL, stop searching in sdk_data
+                            if let Some(data) = inner_wrapper.data {
+                                let parsed = parse_qualities(&data);
+                                // HLS from sdk_data is intentionally skipped, as the
+                                // primary HLS fallback is from hls_pull_url_map.
+                                for pick in degradation_order(&parsed, preferred_quality) {
+                                    match resolve_flv_url(&pick.flv).await {
+                                        Ok(url) => {
+                                            println!(
+                                                "[Douyin Live RS INFO] Found FLV URL from sdk_data at quality {}: {}",
+                                                pick.quality.key, url
+                                            );
+                                            final_stream_url = Some(url);
+                                            chosen_quality = Some(pick.quality.key.clone());
+                                            break;
+                                        }
+                                        Err(e) => {
+                                            println!(
+                                                "[Douyin Live RS WARN] Quality {} is not being served ({}), trying the next one.",
+                                                pick.quality.key, e
+                                            );
                                         }
                                     }
                                 }
-                                // HLS lookup from sdk_data is intentionally omitted here,
-                                // as the primary HLS fallback is from hls_pull_url_map.
+                                qualities = parsed.into_iter().map(|q| q.quality).collect();
                             }
                         }
                         Err(e) => {
@@ -203,79 +350,7 @@ pub async fn get_douyin_live_stream_url(
         println!("[Douyin Live RS INFO] live_core_sdk_data is None in stream_url_container.");
     }
 
-    // If an FLV URL was found from sdk_data, process it.
-    // It might need a redirect if it doesn't contain "pull-flv".
-    if let Some(initial_flv_url_candidate) = final_stream_url.clone() {
-        // 使用 clone 来获取 owned String
-        if initial_flv_url_candidate.contains("pull-flv") {
-            println!("[Douyin Live RS INFO] 初始 FLV URL 来自 sdk_data 已包含 'pull-flv': {}. 将使用此链接.", initial_flv_url_candidate);
-            // final_stream_url 已经是 Some(initial_flv_url_candidate)，无需更改
-        } else {
-            println!(
-                "[Douyin Live RS INFO] 初始 FLV URL ('{}') 不含 'pull-flv'. 尝试解析重定向.",
-                initial_flv_url_candidate
-            );
-
-            // 为重定向解析构建 HTTP 客户端
-            let client_result = reqwest::Client::builder()
-                .redirect(reqwest::redirect::Policy::none()) // 禁止自动重定向
-                .build();
-
-            match client_result {
-                Ok(http_client_for_redirect) => {
-                    match http_client_for_redirect
-                        .get(&initial_flv_url_candidate)
-                        .send()
-                        .await
-                    {
-                        Ok(response) => {
-                            if response.status().is_redirection() {
-                                // 检查是否为重定向状态
-                                if let Some(location_header) =
-                                    response.headers().get(reqwest::header::LOCATION)
-                                {
-                                    if let Ok(redirected_url_str) = location_header.to_str() {
-                                        if !redirected_url_str.is_empty() {
-                                            println!("[Douyin Live RS INFO] 重定向到: {}. 将此作为最终 FLV URL.", redirected_url_str);
-                                            final_stream_url = Some(redirected_url_str.to_string());
-                                        } else {
-                                            println!("[Douyin Live RS WARN] 重定向 Location header 为空. 使用原始 FLV URL: {}", initial_flv_url_candidate);
-                                            final_stream_url =
-                                                Some(initial_flv_url_candidate.to_string());
-                                        }
-                                    } else {
-                                        println!("[Douyin Live RS WARN] 转换 Location header 为字符串失败. 使用原始 FLV URL: {}", initial_flv_url_candidate);
-                                        final_stream_url =
-                                            Some(initial_flv_url_candidate.to_string());
-                                    }
-                                } else {
-                                    println!("[Douyin Live RS WARN] 重定向响应中未找到 Location header. 使用原始 FLV URL: {}", initial_flv_url_candidate);
-                                    final_stream_url = Some(initial_flv_url_candidate.to_string());
-                                }
-                            } else {
-                                // 不是重定向 (可能是成功 200 OK, 或其他错误状态码)
-                                println!("[Douyin Live RS INFO] 请求 '{}' 未重定向 (状态: {}). 使用原始 FLV URL.", initial_flv_url_candidate, response.status());
-                                final_stream_url = Some(initial_flv_url_candidate.to_string());
-                            }
-                        }
-                        Err(e) => {
-                            // 请求发送失败
-                            println!("[Douyin Live RS WARN] 解析 '{}' 的重定向请求失败: {}. 使用原始 FLV URL.", initial_flv_url_candidate, e);
-                            final_stream_url = Some(initial_flv_url_candidate.to_string());
-                        }
-                    }
-                }
-                Err(e) => {
-                    // 构建 HTTP 客户端失败
-                    println!("[Douyin Live RS WARN] 构建 reqwest 客户端用于重定向失败: {}. 使用原始 FLV URL.", e);
-                    final_stream_url = Some(initial_flv_url_candidate.to_string());
-                }
-            }
-        }
-    }
-    // 如果 final_stream_url 在此之后为 None (由于上述逻辑或初始就为None), 则会尝试 HLS
-
-    // If no valid FLV stream (with "pull-flv" or successfully redirected) was found from sdk_data, try HLS stream from hls_pull_url_map
+    // If no quality from sdk_data is being served, try HLS stream from hls_pull_url_map
     if final_stream_url.is_none() {
         println!("[Douyin Live RS INFO] No valid FLV stream from sdk_data, or it was discarded. Attempting HLS from hls_pull_url_map.");
         if let Some(hls_map) = &stream_url_container.hls_pull_url_map {
@@ -304,68 +379,22 @@ pub async fn get_douyin_live_stream_url(
         }
     }
 
-    let mut proxied_stream_url: Option<String> = None;
-
-    if let Some(real_url) = &final_stream_url {
-        if !real_url.is_empty() {
-            println!(
-                "[Douyin Live RS] Real stream URL found: {}. Attempting to use proxy.",
-                real_url
-            );
-            // Set the real URL to the store
-            {
-                // Explicit scope for MutexGuard
-                let mut current_url_in_store = stream_url_store.url.lock().unwrap();
-                *current_url_in_store = real_url.clone();
-                println!(
-                    "[Douyin Live RS] Set stream URL in store: {}",
-                    current_url_in_store
-                );
-            }
-
-            // Start the proxy
-            // Note: start_proxy takes ownership/references of states, so we pass them directly.
-            // We need to clone app_handle if it's used elsewhere after this, but here it's fine.
-            match start_proxy(app_handle, proxy_server_handle, stream_url_store).await {
-                Ok(p_url) => {
-                    println!(
-                        "[Douyin Live RS] Proxy started successfully. Proxy URL: {}",
-                        p_url
-                    );
-                    proxied_stream_url = Some(p_url);
-                }
-                Err(e) => {
-                    eprintln!("[Douyin Live RS] Failed to start proxy: {}", e);
-                    // Fallback: return an error message or try to return the original URL if proxy is not critical
-                    // For now, we'll just mean stream_url remains None in the response.
-                    // Consider how to signal this error to the frontend.
-                    // Setting error_message in LiveStreamInfo might be an option.
-                }
-            }
-        } else {
-            println!("[Douyin Live RS] Real stream URL is empty. Not attempting proxy.");
-        }
-    } else {
-        println!("[Douyin Live RS] No real stream URL found. Not attempting proxy.");
-    }
-
-    let final_error_message = if proxied_stream_url.is_none() && final_stream_url.is_some() {
-        Some("代理启动失败".to_string())
-    } else {
-        None
-    };
-
-    Ok(crate::platforms::common::LiveStreamInfo {
-        title: room_data_entry.title.clone(),
-        anchor_name: main_data.user.as_ref().and_then(|u| u.nickname.clone()),
-        avatar: main_data
-            .user
-            .as_ref()
-            .and_then(|u| u.avatar_thumb.as_ref())
-            .and_then(|at| at.url_list.as_ref())
-            .and_then(|ul| ul.first().cloned()),
-        stream_url: proxied_stream_url, // proxied_stream_url is moved here
-        status: Some(current_status),
-        error_message: final_error_message, // Use the pre-calculated message
-    })
+    Ok((
+        crate::platforms::common::LiveStreamInfo {
+            title: room_data_entry.title.clone(),
+            anchor_name: main_data.user.as_ref().and_then(|u| u.nickname.clone()),
+            avatar: main_data
+                .user
+                .as_ref()
+                .and_then(|u| u.avatar_thumb.as_ref())
+                .and_then(|at| at.url_list.as_ref())
+                .and_then(|ul| ul.first().cloned()),
+            stream_url: None, // Filled in by the caller once the URL is proxied
+            status: Some(current_status),
+            error_message: None,
+            qualities,
+            quality: chosen_quality,
+        },
+        final_stream_url,
+    ))
 }
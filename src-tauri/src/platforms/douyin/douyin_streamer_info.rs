@@ -21,6 +21,8 @@ pub async fn fetch_douyin_streamer_info(
             stream_url: None,
             status: None,
             error_message: Some("Room ID cannot be empty.".to_string()),
+            qualities: Vec::new(),
+            quality: None,
         });
     }
 
@@ -35,6 +37,8 @@ pub async fn fetch_douyin_streamer_info(
             stream_url: None,
             status: None,
             error_message: Some(format!("Cookie setup failed: {}", e)),
+            qualities: Vec::new(),
+            quality: None,
         });
     }
 
@@ -64,6 +68,8 @@ pub async fn fetch_douyin_streamer_info(
                 stream_url: None,
                 status: None,
                 error_message: Some(format!("API request failed: {}. URL: {}", e, api_url)),
+                qualities: Vec::new(),
+                quality: None,
             });
         }
     };
@@ -85,6 +91,8 @@ pub async fn fetch_douyin_streamer_info(
                 "API error (status_code: {}): {}",
                 api_response.status_code, prompts
             )),
+            qualities: Vec::new(),
+            quality: None,
         });
     }
 
@@ -98,6 +106,8 @@ pub async fn fetch_douyin_streamer_info(
                 stream_url: None,
                 status: None,
                 error_message: Some("API response contained no main 'data' object".to_string()),
+                qualities: Vec::new(),
+                quality: None,
             })
         }
     };
@@ -125,5 +135,7 @@ pub async fn fetch_douyin_streamer_info(
         stream_url: None, // Explicitly None, as we are not fetching/proxying the stream
         status: Some(current_status),
         error_message: None, // No stream-specific errors here, API errors handled above.
+        qualities: Vec::new(),
+        quality: None,
     })
 }
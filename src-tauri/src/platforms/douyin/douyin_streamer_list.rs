@@ -86,7 +86,17 @@ pub async fn fetch_douyin_partition_rooms(
     ms_token: String,
 ) -> Result<DouyinLiveListResponse, String> {
     let count: i32 = 15; // Number of items requested per page, explicitly typed as i32
+    fetch_partition_rooms(&partition, &partition_type, offset, count, &ms_token).await
+}
 
+// Shared by the command above and DouyinPlatform::fetch_rooms
+pub async fn fetch_partition_rooms(
+    partition: &str,
+    partition_type: &str,
+    offset: i32,
+    count: i32,
+    ms_token: &str,
+) -> Result<DouyinLiveListResponse, String> {
     // For this test, create a new client, similar to the user's test snippet
     let local_client = reqwest::Client::builder()
         .build()
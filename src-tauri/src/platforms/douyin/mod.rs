@@ -4,14 +4,17 @@ pub mod douyin_streamer_detail;
 pub mod douyin_streamer_info;
 pub mod douyin_streamer_list;
 pub mod models;
+pub mod platform;
+pub mod stream_quality;
 pub mod utils;
 // pub mod parsers; // This line was causing an error, remove it if parsers.rs doesn't exist here
 
 // Re-export based on the actual file structure and main.rs imports
 pub use self::danmu::web_fetcher::fetch_douyin_room_info;
-pub use self::douyin_danmu_listener::start_douyin_danmu_listener;
+pub use self::douyin_danmu_listener::{start_douyin_danmu_listener, stop_douyin_danmu_listener};
 pub use self::douyin_streamer_detail::get_douyin_live_stream_url;
 pub use self::douyin_streamer_info::fetch_douyin_streamer_info;
 pub use self::douyin_streamer_list::fetch_douyin_partition_rooms;
+pub use self::platform::DouyinPlatform;
 // generate_douyin_ms_token is likely re-exported at a higher level (e.g. platforms/mod.rs or main.rs)
 // If it's meant to be from this module, its source file needs to be identified.
@@ -84,18 +84,8 @@ pub struct PullDataContainer {
 // --- Structs for parsing the nested JSON string within stream_data ---
 #[derive(Debug, Deserialize, Clone)]
 pub struct InnerStreamDataWrapper {
-    // The actual qualities map might be nested further, e.g. under a "data" key or direct
-    // Adjust based on actual structure of the stream_data JSON string
-    pub data: Option<StreamQualitiesMap>, // Assuming it's like {"data": { "origin": ...}}
-                                          // Or if it's directly the map: #[serde(flatten)] pub qualities: StreamQualitiesMap,
-}
-
-#[derive(Debug, Deserialize, Clone)]
-pub struct StreamQualitiesMap {
-    pub origin: Option<StreamQualityDetail>,
-    pub sd: Option<StreamQualityDetail>,
-    pub hd: Option<StreamQualityDetail>,
-    // Potentially others: ld, uhd
+    // Keyed by quality: origin, uhd, hd, sd, ld, md, ...
+    pub data: Option<HashMap<String, StreamQualityDetail>>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -108,8 +98,8 @@ pub struct StreamQualityDetail {
 pub struct StreamLinks {
     pub flv: Option<String>,
     pub hls: Option<String>, // Added HLS field based on typical structure
-                             // pub cdn_multi_arc: Option<Value>,
-                             // pub sdk_params: Option<String>,
+    // JSON string with VCodec, resolution, vbitrate, fps, ...
+    pub sdk_params: Option<String>,
 }
 
 // Enum for stream resolutions, if needed for consistent key access
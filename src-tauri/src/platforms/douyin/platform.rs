@@ -0,0 +1,146 @@
+use async_trait::async_trait;
+use tauri::AppHandle;
+use tokio::sync::oneshot;
+
+use super::danmu::signature::generate_ms_token;
+use super::douyin_danmu_listener::run_douyin_danmaku;
+use super::douyin_streamer_detail::{resolve_douyin_live_stream, DOUYIN_API_REFERER};
+use super::douyin_streamer_info::fetch_douyin_streamer_info;
+use super::douyin_streamer_list::fetch_partition_rooms;
+use crate::platforms::common::types::{GetStreamUrlArgs, GetStreamUrlPayload};
+use crate::platforms::common::types_rust::{
+    CommonLiveRoomRust, CommonRoomInfoRust, CommonRoomListRust, CommonStreamInfoRust,
+    RoomListQueryRust, SupportedPlatformRust,
+};
+use crate::platforms::common::LivePlatform;
+use crate::proxy::streams::ProxyStreamHeaders;
+
+// Douyin's category tree ships with the frontend (douyinCategoriesData.ts), so
+// fetch_categories keeps the trait's "not supported" default.
+pub struct DouyinPlatform;
+
+// Category ids are the partition, with the partition type as parent. A full frontend
+// href suffix such as "1_1_1_1010032" is accepted too (partition type is the second-to-last part).
+fn split_partition(query: &RoomListQueryRust) -> Result<(String, String), String> {
+    if let Some(partition_type) = &query.parent_category_id {
+        return Ok((query.category_id.clone(), partition_type.clone()));
+    }
+    let parts: Vec<&str> = query.category_id.split('_').collect();
+    if parts.len() >= 2 {
+        Ok((
+            parts[parts.len() - 1].to_string(),
+            parts[parts.len() - 2].to_string(),
+        ))
+    } else {
+        Err(format!(
+            "Douyin category {} has no partition type",
+            query.category_id
+        ))
+    }
+}
+
+fn room_payload(room_id: &str) -> GetStreamUrlPayload {
+    GetStreamUrlPayload {
+        args: GetStreamUrlArgs {
+            room_id_str: room_id.to_string(),
+            quality: None,
+        },
+    }
+}
+
+#[async_trait]
+impl LivePlatform for DouyinPlatform {
+    fn platform(&self) -> SupportedPlatformRust {
+        SupportedPlatformRust::Douyin
+    }
+
+    async fn fetch_rooms(&self, query: &RoomListQueryRust) -> Result<CommonRoomListRust, String> {
+        let (partition, partition_type) = split_partition(query)?;
+        let count = query.page_size as i32;
+        let offset = query.page as i32 * count;
+        let ms_token = generate_ms_token(107);
+
+        let response =
+            fetch_partition_rooms(&partition, &partition_type, offset, count, &ms_token).await?;
+        let rooms = response
+            .rooms
+            .into_iter()
+            .map(|room| CommonLiveRoomRust {
+                room_id: room.web_rid,
+                title: room.title,
+                anchor_name: room.owner_nickname,
+                avatar_url: Some(room.avatar_url).filter(|s| !s.is_empty()),
+                cover_url: Some(room.cover_url).filter(|s| !s.is_empty()),
+                viewer_count: Some(room.user_count_str),
+                is_live: true, // The partition list only contains live rooms
+                platform: SupportedPlatformRust::Douyin,
+            })
+            .collect();
+
+        Ok(CommonRoomListRust {
+            rooms,
+            has_more: response.has_more,
+        })
+    }
+
+    async fn fetch_room_info(&self, room_id: &str) -> Result<CommonRoomInfoRust, String> {
+        let info = fetch_douyin_streamer_info(room_payload(room_id)).await?;
+        if let Some(error_message) = info.error_message {
+            return Err(error_message);
+        }
+        Ok(CommonRoomInfoRust {
+            room_id: room_id.to_string(),
+            title: info.title,
+            anchor_name: info.anchor_name,
+            avatar_url: info.avatar,
+            is_live: info.status == Some(2), // 2 is live, 4 is replay/offline
+            live_started_at: None,           // The room API doesn't report it
+            platform: SupportedPlatformRust::Douyin,
+        })
+    }
+
+    async fn resolve_stream(
+        &self,
+        room_id: &str,
+        quality: Option<&str>,
+    ) -> Result<CommonStreamInfoRust, String> {
+        let (info, real_url) = resolve_douyin_live_stream(room_id, quality).await?;
+        if let Some(error_message) = info.error_message {
+            return Err(error_message);
+        }
+        let url = real_url.ok_or_else(|| {
+            if info.status == Some(2) {
+                "主播在线，但获取直播流失败".to_string()
+            } else {
+                "主播未开播".to_string()
+            }
+        })?;
+        let format = if url.contains(".m3u8") || url.contains("pull-hls") {
+            "m3u8"
+        } else {
+            "flv"
+        };
+        Ok(CommonStreamInfoRust {
+            room_id: room_id.to_string(),
+            url,
+            format: format.to_string(),
+            platform: SupportedPlatformRust::Douyin,
+            headers: ProxyStreamHeaders {
+                referer: Some(DOUYIN_API_REFERER.to_string()),
+                ..Default::default()
+            },
+        })
+    }
+
+    async fn run_danmaku(
+        &self,
+        app_handle: AppHandle,
+        room_id: String,
+        stop_rx: oneshot::Receiver<()>,
+    ) -> Result<(), String> {
+        let stop_signal = async move {
+            let _ = stop_rx.await;
+        };
+        run_douyin_danmaku(app_handle, room_id, stop_signal).await
+    }
+}
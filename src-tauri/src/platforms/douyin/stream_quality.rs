@@ -0,0 +1,185 @@
+// FLV qualities from live_core_sdk_data's stream_data. Each entry of its `data` map is one
+// quality (origin, uhd, hd, sd, ld, md, plus HEVC variants on some rooms), and its
+// `sdk_params` JSON carries the codec, resolution and bitrate.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::models::StreamQualityDetail;
+
+// Best first; a preferred quality degrades along this order
+const LEVELS: [(&str, &str); 7] = [
+    ("origin", "原画"),
+    ("uhd", "蓝光"),
+    ("hd", "超清"),
+    ("sd", "高清"),
+    ("ld", "标清"),
+    ("md", "流畅"),
+    ("ao", "音频"),
+];
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DouyinStreamQuality {
+    pub key: String, // The stream_data key; what the frontend passes back as the preferred quality
+    pub name: String,
+    pub codec: String,              // "h264" or "h265"
+    pub resolution: Option<String>, // e.g. "1920x1080"
+    pub bitrate_kbps: Option<u64>,
+    pub fps: Option<u64>,
+}
+
+pub struct QualityUrls {
+    pub quality: DouyinStreamQuality,
+    pub flv: String,
+}
+
+impl QualityUrls {
+    fn level(&self) -> usize {
+        level_of(&self.quality.key)
+    }
+
+    fn is_hevc(&self) -> bool {
+        self.quality.codec == "h265"
+    }
+}
+
+fn is_hevc_key(key: &str) -> bool {
+    key.ends_with("_h265") || key.ends_with("_hevc")
+}
+
+// Position in LEVELS, ignoring any codec suffix; unknown keys sort last
+fn level_of(key: &str) -> usize {
+    let base = key.split('_').next().unwrap_or(key);
+    LEVELS
+        .iter()
+        .position(|(level, _)| *level == base)
+        .unwrap_or(LEVELS.len())
+}
+
+pub fn parse_qualities(data: &HashMap<String, StreamQualityDetail>) -> Vec<QualityUrls> {
+    let mut qualities: Vec<QualityUrls> = data
+        .iter()
+        .filter_map(|(key, detail)| {
+            let links = detail.main.as_ref()?;
+            let flv = links.flv.clone().filter(|url| !url.is_empty())?;
+            // sdk_params is a JSON string; anything missing or odd in it is just left unknown
+            let params: Value = links
+                .sdk_params
+                .as_deref()
+                .and_then(|raw| serde_json::from_str(raw).ok())
+                .unwrap_or(Value::Null);
+            let codec = match params["VCodec"].as_str() {
+                Some(codec) if codec.contains("265") || codec.eq_ignore_ascii_case("bytevc1") => {
+                    "h265"
+                }
+                _ if is_hevc_key(key) => "h265",
+                _ => "h264",
+            };
+            let level = level_of(key);
+            let mut name = LEVELS
+                .get(level)
+                .map_or_else(|| key.clone(), |(_, name)| name.to_string());
+            if codec == "h265" {
+                name.push_str(" (HEVC)");
+            }
+            Some(QualityUrls {
+                quality: DouyinStreamQuality {
+                    key: key.clone(),
+                    name,
+                    codec: codec.to_string(),
+                    resolution: params["resolution"].as_str().map(str::to_string),
+                    bitrate_kbps: params["vbitrate"].as_u64().map(|bps| bps / 1000),
+                    fps: params["fps"].as_u64(),
+                },
+                flv,
+            })
+        })
+        .collect();
+    qualities.sort_by(|a, b| {
+        (a.level(), a.is_hevc(), &a.quality.key).cmp(&(b.level(), b.is_hevc(), &b.quality.key))
+    });
+    qualities
+}
+
+// The order to try qualities in: the preferred one, then the same level in the other codec,
+// then lower levels, then higher ones nearest first. A level the room doesn't offer still
+// degrades from where it would be. Without a preference (or an unknown one), best first.
+// H.264 wins ties unless HEVC was asked for, since not every player decodes HEVC.
+pub fn degradation_order<'a>(
+    qualities: &'a [QualityUrls],
+    preferred: Option<&str>,
+) -> Vec<&'a QualityUrls> {
+    let exact = preferred.and_then(|key| qualities.iter().find(|q| q.quality.key == key));
+    let (level, hevc) = match (exact, preferred) {
+        (Some(q), _) => (q.level(), q.is_hevc()),
+        (None, Some(key)) if level_of(key) < LEVELS.len() => (level_of(key), is_hevc_key(key)),
+        _ => (0, false),
+    };
+    let mut order: Vec<&QualityUrls> = qualities.iter().collect();
+    order.sort_by_key(|q| {
+        let below = q.level() >= level;
+        let distance = q.level().abs_diff(level);
+        (
+            exact.is_none_or(|p| p.quality.key != q.quality.key),
+            !below,
+            distance,
+            q.is_hevc() != hevc,
+        )
+    });
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn qualities(keys: &[&str]) -> Vec<QualityUrls> {
+        let data: HashMap<String, StreamQualityDetail> = keys
+            .iter()
+            .map(|key| {
+                let detail = serde_json::json!({
+                    "main": { "flv": format!("https://pull-flv.example.com/{}.flv", key) }
+                });
+                (key.to_string(), serde_json::from_value(detail).unwrap())
+            })
+            .collect();
+        parse_qualities(&data)
+    }
+
+    fn order(qualities: &[QualityUrls], preferred: Option<&str>) -> Vec<String> {
+        degradation_order(qualities, preferred)
+            .iter()
+            .map(|q| q.quality.key.clone())
+            .collect()
+    }
+
+    #[test]
+    fn degrades_from_the_preferred_quality() {
+        let room = qualities(&["origin", "uhd", "hd", "sd", "ld"]);
+        assert_eq!(
+            order(&room, Some("hd")),
+            ["hd", "sd", "ld", "uhd", "origin"]
+        );
+        assert_eq!(order(&room, None), ["origin", "uhd", "hd", "sd", "ld"]);
+        assert_eq!(
+            order(&room, Some("best")),
+            ["origin", "uhd", "hd", "sd", "ld"]
+        );
+    }
+
+    #[test]
+    fn degrades_from_a_level_the_room_lacks() {
+        let room = qualities(&["origin", "sd", "ld"]);
+        assert_eq!(order(&room, Some("hd")), ["sd", "ld", "origin"]);
+    }
+
+    #[test]
+    fn keeps_the_preferred_codec_on_ties() {
+        let room = qualities(&["origin", "sd", "sd_h265"]);
+        assert_eq!(order(&room, Some("sd")), ["sd", "sd_h265", "origin"]);
+        assert_eq!(order(&room, Some("sd_h265")), ["sd_h265", "sd", "origin"]);
+        assert_eq!(order(&room, Some("hd_h265")), ["sd_h265", "sd", "origin"]);
+        assert_eq!(room[2].quality.name, "高清 (HEVC)");
+    }
+}
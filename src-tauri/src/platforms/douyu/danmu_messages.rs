@@ -0,0 +1,428 @@
+use serde::Deserialize;
+
+use super::gift_list::DouyuGiftMap;
+use super::stt::from_str;
+use crate::platforms::common::{DanmakuEvent, DanmakuUser};
+
+// Douyu sends chat colours as a palette index (col@=1..6); 0 / missing is the default
+fn douyu_color(col: Option<String>) -> Option<String> {
+    let color = match col?.as_str() {
+        "1" => "#ff0000",
+        "2" => "#1e87f0",
+        "3" => "#7ac84b",
+        "4" => "#ff7f00",
+        "5" => "#9b39f4",
+        "6" => "#ff69b4",
+        _ => return None,
+    };
+    Some(color.to_string())
+}
+
+fn noble_name(level: i64) -> Option<String> {
+    let name = match level {
+        1 => "骑士",
+        2 => "子爵",
+        3 => "伯爵",
+        4 => "公爵",
+        5 => "国王",
+        6 => "皇帝",
+        7 => "游侠",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+#[derive(Deserialize)]
+struct MessageType {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+// Most messages share uid / nn / level / bnn / bl; anbc has its nickname in unk
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct UserFields {
+    uid: Option<String>,
+    nn: Option<String>,
+    unk: Option<String>,
+    level: Option<i64>,
+    bnn: Option<String>,
+    bl: Option<i64>,
+}
+
+impl From<UserFields> for DanmakuUser {
+    fn from(fields: UserFields) -> Self {
+        DanmakuUser {
+            uid: fields.uid,
+            nickname: fields
+                .nn
+                .or(fields.unk)
+                .unwrap_or_else(|| "unknown".to_string()),
+            level: fields.level,
+            badge_name: fields.bnn,
+            badge_level: fields.bl.filter(|l| *l > 0),
+        }
+    }
+}
+
+fn douyu_user(body: &str) -> DanmakuUser {
+    from_str::<UserFields>(body).unwrap_or_default().into()
+}
+
+// chatmsg: txt is read as text whatever it looks like, col palette index
+#[derive(Deserialize)]
+struct ChatFields {
+    #[serde(default)]
+    txt: String,
+    col: Option<String>,
+}
+
+// dgb: gfid gift id, gfcnt gifts in this send, hits running combo count
+#[derive(Deserialize)]
+struct GiftFields {
+    gfid: Option<String>,
+    gfcnt: Option<i64>,
+    hits: Option<i64>,
+}
+
+// spbc: sn sender, dn receiver, gn gift name, gc count, drid room it happened in
+#[derive(Deserialize)]
+struct GiftBroadcastFields {
+    sn: Option<String>,
+    dn: Option<String>,
+    gn: Option<String>,
+    gc: Option<i64>,
+    drid: Option<String>,
+}
+
+// anbc: nl noble level
+#[derive(Deserialize)]
+struct NobleFields {
+    nl: Option<i64>,
+}
+
+// rss: ss 1 when the stream starts, 0 when it ends
+#[derive(Deserialize)]
+struct RoomStatusFields {
+    ss: Option<i64>,
+}
+
+// frank: fc fan club member count, list = [{uid, nn, lev, bl, ...}] top fans in rank order
+#[derive(Deserialize)]
+struct FansRankFields {
+    fc: Option<i64>,
+    #[serde(default)]
+    list: Vec<FanFields>,
+}
+
+#[derive(Deserialize)]
+struct FanFields {
+    uid: Option<String>,
+    nn: Option<String>,
+    lev: Option<i64>,
+    bl: Option<i64>,
+}
+
+fn parse_chat(body: &str) -> Option<DanmakuEvent> {
+    let chat: ChatFields = from_str(body).ok()?;
+    Some(DanmakuEvent::Chat {
+        user: douyu_user(body),
+        content: chat.txt,
+        color: douyu_color(chat.col),
+    })
+}
+
+fn parse_gift(body: &str, gifts: &DouyuGiftMap) -> Option<DanmakuEvent> {
+    let fields: GiftFields = from_str(body).ok()?;
+    let gift_id = fields.gfid?;
+    let gift = gifts.get(&gift_id);
+    let count = fields.gfcnt.unwrap_or(1);
+    Some(DanmakuEvent::Gift {
+        user: douyu_user(body),
+        gift_name: gift
+            .map(|g| g.name.clone())
+            .unwrap_or_else(|| format!("礼物{}", gift_id)),
+        gift_id: Some(gift_id),
+        count,
+        icon_url: None,
+        combo_count: fields.hits.unwrap_or(count),
+        // Douyu sends each hit separately and never marks the end of a combo
+        repeat_end: true,
+        value: gift.and_then(|g| g.price).map(|price| price * count),
+    })
+}
+
+fn parse_gift_broadcast(body: &str) -> Option<DanmakuEvent> {
+    let fields: GiftBroadcastFields = from_str(body).ok()?;
+    Some(DanmakuEvent::Broadcast {
+        content: format!(
+            "{} 赠送给 {} {}个{}",
+            fields.sn?,
+            fields.dn?,
+            fields.gc.unwrap_or(1),
+            fields.gn?
+        ),
+        target_room_id: fields.drid,
+    })
+}
+
+fn parse_noble(body: &str) -> Option<DanmakuEvent> {
+    let level = from_str::<NobleFields>(body).ok()?.nl?;
+    Some(DanmakuEvent::Noble {
+        user: douyu_user(body),
+        level,
+        name: noble_name(level),
+    })
+}
+
+fn parse_room_status(body: &str) -> Option<DanmakuEvent> {
+    Some(DanmakuEvent::LiveStatusChanged {
+        is_live: from_str::<RoomStatusFields>(body).ok()?.ss? == 1,
+    })
+}
+
+fn parse_user_level_up(body: &str) -> Option<DanmakuEvent> {
+    let user = douyu_user(body);
+    Some(DanmakuEvent::UserLevelUp {
+        level: user.level?,
+        user,
+    })
+}
+
+// blab: lbl old badge level, bl new badge level
+fn parse_badge_level_up(body: &str) -> Option<DanmakuEvent> {
+    let user = douyu_user(body);
+    user.badge_level?;
+    Some(DanmakuEvent::BadgeLevelUp { user })
+}
+
+fn parse_fans_rank(body: &str) -> Option<DanmakuEvent> {
+    let fields: FansRankFields = from_str(body).ok()?;
+    Some(DanmakuEvent::FansRank {
+        fans_count: fields.fc,
+        top: fields
+            .list
+            .into_iter()
+            .map(|fan| {
+                UserFields {
+                    uid: fan.uid,
+                    nn: fan.nn,
+                    level: fan.lev,
+                    bl: fan.bl,
+                    ..UserFields::default()
+                }
+                .into()
+            })
+            .collect(),
+    })
+}
+
+// The `type` of a message body, e.g. "chatmsg"
+pub fn message_type(body: &str) -> Option<String> {
+    from_str::<MessageType>(body)
+        .ok()
+        .map(|message| message.kind)
+}
+
+// Maps one STT message body to a danmaku event; None for types we don't handle
+pub fn parse_douyu_message(body: &str, gifts: &DouyuGiftMap) -> Option<DanmakuEvent> {
+    match message_type(body)?.as_str() {
+        "chatmsg" => parse_chat(body),
+        "uenter" => Some(DanmakuEvent::Enter {
+            user: douyu_user(body),
+        }),
+        "dgb" => parse_gift(body, gifts),
+        "spbc" => parse_gift_broadcast(body),
+        "anbc" => parse_noble(body),
+        "rss" => parse_room_status(body),
+        "upgrade" => parse_user_level_up(body),
+        "blab" => parse_badge_level_up(body),
+        "frank" => parse_fans_rank(body),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platforms::douyu::gift_list::DouyuGift;
+    use crate::platforms::douyu::stt::PacketDecoder;
+    use serde_json::{json, Value};
+
+    fn messages() -> Vec<String> {
+        let (bodies, _) = PacketDecoder::new().push(include_bytes!("fixtures/messages.bin"));
+        bodies
+    }
+
+    // The event as the frontend gets it
+    fn event(index: usize, gifts: &DouyuGiftMap) -> Value {
+        let event = parse_douyu_message(&messages()[index], gifts).unwrap();
+        serde_json::to_value(event).unwrap()
+    }
+
+    #[test]
+    fn parses_chat_and_enter() {
+        let messages = messages();
+        let gifts = DouyuGiftMap::new();
+        let Some(DanmakuEvent::Chat {
+            user,
+            content,
+            color,
+        }) = parse_douyu_message(&messages[0], &gifts)
+        else {
+            panic!("expected a chat event");
+        };
+        assert_eq!(content, "a@=b/c@Sd 看这里 https://www.douyu.com/9999/");
+        assert_eq!(color.as_deref(), Some("#1e87f0"));
+        assert_eq!(user.nickname, "斗鱼用户");
+        assert_eq!(user.level, Some(25));
+        assert_eq!(user.badge_name.as_deref(), Some("鱼丸"));
+        assert_eq!(user.badge_level, Some(12));
+
+        assert!(matches!(
+            parse_douyu_message(&messages[1], &gifts),
+            Some(DanmakuEvent::Enter { user }) if user.nickname == "路过的鱼"
+        ));
+    }
+
+    #[test]
+    fn parses_fans_rank() {
+        let Some(DanmakuEvent::FansRank { fans_count, top }) =
+            parse_douyu_message(&messages()[2], &DouyuGiftMap::new())
+        else {
+            panic!("expected a fans rank event");
+        };
+        assert_eq!(fans_count, Some(12345));
+        let fans: Vec<(&str, Option<i64>, Option<i64>)> = top
+            .iter()
+            .map(|u| (u.nickname.as_str(), u.level, u.badge_level))
+            .collect();
+        assert_eq!(
+            fans,
+            vec![("大粉丝", Some(30), Some(20)), ("二号", Some(18), Some(15))]
+        );
+    }
+
+    #[test]
+    fn parses_room_status() {
+        let gifts = DouyuGiftMap::new();
+        assert_eq!(
+            event(6, &gifts),
+            json!({ "type": "liveStatusChanged", "isLive": true })
+        );
+        assert_eq!(
+            event(7, &gifts),
+            json!({ "type": "liveStatusChanged", "isLive": false })
+        );
+    }
+
+    #[test]
+    fn parses_gifts_with_and_without_the_gift_list() {
+        let user = json!({
+            "uid": "34567890",
+            "nickname": "送礼的鱼",
+            "level": 20,
+            "badgeName": "鱼丸",
+            "badgeLevel": 7,
+        });
+        let mut gifts = DouyuGiftMap::new();
+        gifts.insert(
+            "824".to_string(),
+            DouyuGift {
+                name: "荧光棒".to_string(),
+                price: Some(10),
+            },
+        );
+        assert_eq!(
+            event(3, &gifts),
+            json!({
+                "type": "gift",
+                "user": user,
+                "giftId": "824",
+                "giftName": "荧光棒",
+                "count": 3,
+                "iconUrl": null,
+                "comboCount": 9,
+                "repeatEnd": true,
+                "value": 30,
+            })
+        );
+        assert_eq!(
+            event(3, &DouyuGiftMap::new()),
+            json!({
+                "type": "gift",
+                "user": user,
+                "giftId": "824",
+                "giftName": "礼物824",
+                "count": 3,
+                "iconUrl": null,
+                "comboCount": 9,
+                "repeatEnd": true,
+                "value": null,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_gift_broadcasts() {
+        assert_eq!(
+            event(4, &DouyuGiftMap::new()),
+            json!({
+                "type": "broadcast",
+                "content": "土豪鱼 赠送给 某主播 2个火箭",
+                "targetRoomId": "8888",
+            })
+        );
+    }
+
+    #[test]
+    fn parses_nobles() {
+        assert_eq!(
+            event(5, &DouyuGiftMap::new()),
+            json!({
+                "type": "noble",
+                "user": {
+                    "uid": "45678901",
+                    "nickname": "新晋伯爵",
+                    "level": null,
+                    "badgeName": null,
+                    "badgeLevel": null,
+                },
+                "level": 3,
+                "name": "伯爵",
+            })
+        );
+    }
+
+    #[test]
+    fn parses_level_ups() {
+        let gifts = DouyuGiftMap::new();
+        assert_eq!(
+            event(8, &gifts),
+            json!({
+                "type": "userLevelUp",
+                "user": {
+                    "uid": "56789012",
+                    "nickname": "升级的鱼",
+                    "level": 31,
+                    "badgeName": null,
+                    "badgeLevel": null,
+                },
+                "level": 31,
+            })
+        );
+        assert_eq!(
+            event(9, &gifts),
+            json!({
+                "type": "badgeLevelUp",
+                "user": {
+                    "uid": "67890123",
+                    "nickname": "铁粉",
+                    "level": null,
+                    "badgeName": "鱼丸",
+                    "badgeLevel": 6,
+                },
+            })
+        );
+    }
+}
@@ -1,173 +1,165 @@
 use futures_util::{SinkExt, StreamExt};
-use std::collections::HashMap;
-use tauri::{Emitter, Window};
+use tauri::AppHandle;
 use tokio::sync::mpsc;
 use tokio::sync::oneshot;
 use tokio::time::Duration;
 use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
 use tokio_tungstenite::{connect_async_tls_with_config, tungstenite::Message};
 use url::Url;
 
+use super::danmu_messages::{message_type, parse_douyu_message};
+use super::gift_list::{fetch_douyu_gift_list, DouyuGiftMap};
+use super::stt::{encode_fields, encode_packet, PacketDecoder};
+use crate::platforms::common::types_rust::SupportedPlatformRust;
+use crate::platforms::common::{supervise_danmaku, DanmakuSession};
+
 pub struct DanmakuClient {
     room_id: String,
-    window: Window,
+    app_handle: AppHandle,
     stop_signal_rx: oneshot::Receiver<()>,
 }
 
 impl DanmakuClient {
-    pub fn new(room_id: &str, window: Window, stop_signal_rx: oneshot::Receiver<()>) -> Self {
+    pub fn new(
+        room_id: &str,
+        app_handle: AppHandle,
+        stop_signal_rx: oneshot::Receiver<()>,
+    ) -> Self {
         Self {
             room_id: room_id.to_string(),
-            window,
+            app_handle,
             stop_signal_rx,
         }
     }
 
-    fn encode_msg(&self, msg: &str) -> Vec<u8> {
-        let msg_bytes = msg.as_bytes();
-        let packet_len = msg_bytes.len() + 9;
-
-        let mut result = Vec::new();
-        result.extend_from_slice(&(packet_len as u32).to_le_bytes());
-        result.extend_from_slice(&(packet_len as u32).to_le_bytes());
-        result.extend_from_slice(&689u16.to_le_bytes());
-        result.push(0);
-        result.push(0);
-        result.extend_from_slice(msg_bytes);
-        result.push(0);
-
-        result
+    // Keeps the room's danmaku connection alive, reconnecting (with a fresh login and
+    // joingroup) whenever it drops, until the stop signal fires.
+    pub async fn start(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let stop_rx = std::mem::replace(&mut self.stop_signal_rx, oneshot::channel().1);
+        let stop_signal = async move {
+            let _ = stop_rx.await;
+        };
+        supervise_danmaku(
+            self.app_handle.clone(),
+            SupportedPlatformRust::Douyu,
+            self.room_id.clone(),
+            stop_signal,
+            run_douyu_session,
+        )
+        .await;
+        Ok(())
     }
+}
 
-    pub async fn start(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let url = Url::parse("wss://danmuproxy.douyu.com:8506/")?;
-        let mut request = url.into_client_request()?;
-        request
-            .headers_mut()
-            .insert("Sec-WebSocket-Protocol", "binary".parse()?);
-
-        let (ws_stream, _) = connect_async_tls_with_config(request, None, false, None).await?;
-
-        let (mut write, mut read) = ws_stream.split();
-
-        // 发送登录请求
-        let login_msg = format!("type@=loginreq/roomid@={}/", self.room_id);
-        let login_data = self.encode_msg(&login_msg);
-        write.send(Message::Binary(login_data)).await?;
-
-        // 发送加入房间请求
-        let join_msg = format!("type@=joingroup/rid@={}/gid@=1/", self.room_id);
-        let join_data = self.encode_msg(&join_msg);
-        write.send(Message::Binary(join_data)).await?;
-
-        // 创建消息通道
-        let (tx, mut rx) = mpsc::channel(32);
-
-        // 启动心跳任务
-        let heartbeat_msg = "type@=mrkl/";
-        let heartbeat_data = self.encode_msg(heartbeat_msg);
-        let tx_clone = tx.clone();
-
-        tokio::spawn(async move {
-            loop {
-                tokio::time::sleep(Duration::from_secs(45)).await;
-                if let Err(_) = tx_clone.send(Message::Binary(heartbeat_data.clone())).await {
-                    break;
-                }
+// Runs one Douyu danmaku connection until the session is stopped or the connection ends
+async fn run_douyu_session(session: DanmakuSession) -> Result<(), String> {
+    let room_id = session.room_id().to_string();
+    let gifts: DouyuGiftMap = match fetch_douyu_gift_list(&room_id).await {
+        Ok(gifts) => gifts,
+        Err(e) => {
+            // Gifts still come through, just without names
+            eprintln!("[Douyu Danmaku {}] {}", room_id, e);
+            DouyuGiftMap::new()
+        }
+    };
+
+    let url = Url::parse("wss://danmuproxy.douyu.com:8506/").map_err(|e| e.to_string())?;
+    let mut request = url.into_client_request().map_err(|e| e.to_string())?;
+    request
+        .headers_mut()
+        .insert("Sec-WebSocket-Protocol", HeaderValue::from_static("binary"));
+
+    let (ws_stream, _) = connect_async_tls_with_config(request, None, false, None)
+        .await
+        .map_err(|e| format!("Failed to connect to Douyu danmaku server: {}", e))?;
+
+    let (mut write, mut read) = ws_stream.split();
+
+    // 发送登录请求
+    let login_msg = encode_fields(&[("type", "loginreq"), ("roomid", &room_id)]);
+    let login_data = encode_packet(&login_msg);
+    write
+        .send(Message::Binary(login_data))
+        .await
+        .map_err(|e| format!("Failed to send login: {}", e))?;
+
+    // 发送加入房间请求
+    let join_msg = encode_fields(&[("type", "joingroup"), ("rid", &room_id), ("gid", "1")]);
+    let join_data = encode_packet(&join_msg);
+    write
+        .send(Message::Binary(join_data))
+        .await
+        .map_err(|e| format!("Failed to send joingroup: {}", e))?;
+
+    // 创建消息通道
+    let (tx, mut rx) = mpsc::channel(32);
+
+    // 启动心跳任务
+    let heartbeat_msg = encode_fields(&[("type", "mrkl")]);
+    let heartbeat_data = encode_packet(&heartbeat_msg);
+
+    let heartbeat_task = tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(45)).await;
+            if tx
+                .send(Message::Binary(heartbeat_data.clone()))
+                .await
+                .is_err()
+            {
+                break;
             }
-        });
-
-        // Keep a reference to self.stop_signal_rx to move into tasks
-        // We need to select between receiving a message from the websocket and the stop signal
-        let mut stop_rx = std::mem::replace(&mut self.stop_signal_rx, oneshot::channel().1);
+        }
+    });
 
-        // Message sending task
-        let send_task = tokio::spawn(async move {
-            while let Some(msg_to_send) = rx.recv().await {
-                if let Err(_) = write.send(msg_to_send).await {
-                    break;
-                }
+    // Message sending task
+    let send_task = tokio::spawn(async move {
+        while let Some(msg_to_send) = rx.recv().await {
+            if write.send(msg_to_send).await.is_err() {
+                break;
             }
-        });
-
-        let window = self.window.clone();
-        let room_id_clone = self.room_id.clone();
-
-        // Processing incoming messages
-        loop {
-            tokio::select! {
-                _ = &mut stop_rx => {
-                    eprintln!("[Douyu Danmaku {}] Stop signal received, terminating listener.", room_id_clone);
-                    break;
-                }
-                msg_option = read.next() => {
-                    match msg_option {
-                        Some(Ok(Message::Binary(data))) => {
-                            if data.len() < 13 {
-                                continue;
-                            }
-
-                            let content = String::from_utf8_lossy(&data[12..data.len()-1]);
-                            let mut result = HashMap::new();
-                            for item in content.split('/') {
-                                if item.is_empty() {
-                                    continue;
-                                }
-                                if let Some((key, value)) = item.split_once("@=") {
-                                    result.insert(
-                                        key.to_string(),
-                                        value.replace("@S", "/").replace("@A", "@")
-                                    );
-                                }
+        }
+    });
+
+    let mut decoder = PacketDecoder::new();
+    let stopped = session.stopped();
+    tokio::pin!(stopped);
+
+    // Processing incoming messages
+    let result = loop {
+        tokio::select! {
+            _ = &mut stopped => {
+                println!("[Douyu Danmaku {}] Stop signal received, terminating listener.", room_id);
+                break Ok(());
+            }
+            msg_option = read.next() => {
+                match msg_option {
+                    Some(Ok(Message::Binary(data))) => {
+                        let (bodies, error) = decoder.push(&data);
+                        for body in bodies {
+                            if message_type(&body).as_deref() == Some("loginres") {
+                                session.mark_connected();
                             }
-
-                            let event_name = format!("danmaku-{}", room_id_clone);
-
-                            if result.get("type").map_or(false, |t| t == "chatmsg") {
-                                let unknown = "unknown".to_string();
-                                let empty = "".to_string();
-                                let zero = "0".to_string();
-
-                                let danmaku = serde_json::json!({
-                                    "type": "chatmsg",
-                                    "nickname": result.get("nn").unwrap_or(&unknown),
-                                    "content": result.get("txt").unwrap_or(&empty),
-                                    "level": result.get("level").unwrap_or(&zero),
-                                    "badgeName": result.get("bnn").unwrap_or(&empty),
-                                    "badgeLevel": result.get("bl").unwrap_or(&zero),
-                                    "color": result.get("col").map_or(None, |c| Some(c.to_string())),
-                                    "room_id": room_id_clone.clone()
-                                });
-
-                                let _ = window.emit(&event_name, danmaku);
-                            } else if result.get("type").map_or(false, |t| t == "uenter") {
-                                let unknown = "unknown".to_string();
-                                let empty = "".to_string();
-                                let zero = "0".to_string();
-
-                                let uenter_msg = serde_json::json!({
-                                    "type": "uenter",
-                                    "uid": result.get("uid").unwrap_or(&empty),
-                                    "nickname": result.get("nn").unwrap_or(&unknown),
-                                    "level": result.get("level").unwrap_or(&zero),
-                                    "badgeName": result.get("bnn").unwrap_or(&empty),
-                                    "badgeLevel": result.get("bl").unwrap_or(&zero),
-                                    "room_id": room_id_clone.clone()
-                                });
-                                let _ = window.emit(&event_name, uenter_msg);
+                            if let Some(event) = parse_douyu_message(&body, &gifts) {
+                                session.emit(event);
                             }
                         }
-                        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
-                            eprintln!("[Douyu Danmaku {}] Websocket closed or error, terminating listener.", room_id_clone);
-                            break;
+                        if let Some(e) = error {
+                            eprintln!("[Douyu Danmaku {}] {}", room_id, e);
                         }
-                        _ => {}
                     }
+                    Some(Ok(Message::Close(_))) | None => {
+                        break Err("Douyu danmaku websocket closed".to_string());
+                    }
+                    Some(Err(e)) => {
+                        break Err(format!("Douyu danmaku websocket error: {}", e));
+                    }
+                    _ => {}
                 }
             }
         }
-        send_task.abort();
-        eprintln!("[Douyu Danmaku {}] Listener stopped.", room_id_clone);
-        Ok(())
-    }
+    };
+    heartbeat_task.abort();
+    send_task.abort();
+    result
 }
@@ -1,3 +1,6 @@
+use crate::platforms::common::types_rust::{
+    CommonCategoryGroupRust, CommonPlatformCategoryRust, SupportedPlatformRust,
+};
 use reqwest::header::USER_AGENT;
 use serde::{Deserialize, Serialize};
 use tauri::command;
@@ -153,6 +156,32 @@ pub async fn fetch_categories() -> Result<CategoriesApiResponse, String> {
     }
 }
 
+// Same data as fetch_categories, shaped as common category groups for the LivePlatform trait.
+// Each cate1 becomes a group. Cate2 entries use the shortName as id, which is what the
+// newRecList API expects, and carry no parent_id because the group already expresses that
+// level (cate3 entries from fetch_three_cate use the numeric cate2 id as parent_id).
+pub(crate) async fn fetch_douyu_category_groups() -> Result<Vec<CommonCategoryGroupRust>, String> {
+    let raw_data = fetch_categories_douyu_raw().await?;
+    Ok(raw_data
+        .into_iter()
+        .map(|raw_c1_item| CommonCategoryGroupRust {
+            group_name: raw_c1_item.name,
+            platform: SupportedPlatformRust::Douyu,
+            categories: raw_c1_item
+                .cate2_list
+                .into_iter()
+                .map(|raw_c2_item| CommonPlatformCategoryRust {
+                    id: raw_c2_item.short_name,
+                    name: raw_c2_item.name,
+                    platform: SupportedPlatformRust::Douyu,
+                    icon_url: Some(raw_c2_item.icon).filter(|icon| !icon.is_empty()),
+                    parent_id: None,
+                })
+                .collect(),
+        })
+        .collect())
+}
+
 // Internal function to fetch and parse to the old frontend-specific structure
 async fn fetch_categories_douyu_raw() -> Result<Vec<RawFrontendCate1Item>, String> {
     let client = reqwest::Client::new();
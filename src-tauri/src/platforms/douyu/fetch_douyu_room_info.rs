@@ -7,12 +7,13 @@ use serde_json::Value;
 // Define the structure to be returned to TypeScript
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct DouyuFollowInfo {
-    room_id: String,
-    room_name: Option<String>,
-    nickname: Option<String>,
-    avatar_url: Option<String>,
-    video_loop: Option<i64>,
-    show_status: Option<i64>,
+    pub room_id: String,
+    pub room_name: Option<String>,
+    pub nickname: Option<String>,
+    pub avatar_url: Option<String>,
+    pub video_loop: Option<i64>,
+    pub show_status: Option<i64>,
+    pub show_time: Option<i64>, // Seconds since the epoch when the current broadcast began
 }
 
 #[tauri::command]
@@ -112,6 +113,7 @@ pub async fn fetch_douyu_room_info(room_id: String) -> Result<DouyuFollowInfo, S
         avatar_url: avatar_final_url,
         video_loop: get_i64(room_data, "videoLoop"),
         show_status: get_i64(room_data, "show_status"),
+        show_time: get_i64(room_data, "show_time"),
     };
 
     Ok(info)
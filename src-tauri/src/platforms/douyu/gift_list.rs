@@ -0,0 +1,49 @@
+use serde_json::Value;
+use std::collections::HashMap;
+
+// dgb messages only carry a gift id; names and prices come from the room's gift list
+#[derive(Debug, Clone)]
+pub struct DouyuGift {
+    pub name: String,
+    pub price: Option<i64>, // As listed by Douyu, per gift
+}
+
+pub type DouyuGiftMap = HashMap<String, DouyuGift>;
+
+// { error: 0, data: { giftList: [{ id, name, priceInfo: { price } }] } }
+pub fn parse_gift_list(json: &Value) -> DouyuGiftMap {
+    json.pointer("/data/giftList")
+        .and_then(|list| list.as_array())
+        .map(|list| {
+            list.iter()
+                .filter_map(|gift| {
+                    let id = match gift.get("id")? {
+                        Value::Number(n) => n.to_string(),
+                        Value::String(s) => s.clone(),
+                        _ => return None,
+                    };
+                    let name = gift.get("name")?.as_str()?.to_string();
+                    let price = gift.pointer("/priceInfo/price").and_then(|p| p.as_i64());
+                    Some((id, DouyuGift { name, price }))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub async fn fetch_douyu_gift_list(room_id: &str) -> Result<DouyuGiftMap, String> {
+    let url = format!(
+        "https://gift.douyucdn.cn/api/gift/v3/web/list?rid={}",
+        room_id
+    );
+    let json: Value = reqwest::Client::new()
+        .get(&url)
+        .header("Referer", format!("https://www.douyu.com/{}", room_id))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch Douyu gift list: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Douyu gift list: {}", e))?;
+    Ok(parse_gift_list(&json))
+}
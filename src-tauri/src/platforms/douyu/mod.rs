@@ -1,9 +1,14 @@
+pub mod danmu_messages;
 pub mod danmu_start;
 pub mod fetch_douyu_main_categories;
 pub mod fetch_douyu_room_info;
+pub mod gift_list;
 pub mod live_list;
+pub mod platform;
 pub mod search_anchor;
+mod sign;
 pub mod stream_url;
+pub mod stt;
 pub mod three_cate;
 pub mod types;
 
@@ -11,6 +16,7 @@ pub use fetch_douyu_room_info::*;
 // pub use danmu_start::*; // Removed, direct path access is used
 pub use fetch_douyu_main_categories::*;
 pub use live_list::*;
+pub use platform::DouyuPlatform;
 pub use search_anchor::*;
 pub use stream_url::*;
 pub use three_cate::*;
@@ -0,0 +1,181 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use tauri::AppHandle;
+use tokio::sync::oneshot;
+
+use super::danmu_start::DanmakuClient;
+use super::fetch_douyu_main_categories::fetch_douyu_category_groups;
+use super::live_list::{FrontendLiveListResponse, FrontendStreamer};
+use super::{
+    fetch_douyu_room_info, fetch_live_list, fetch_live_list_for_cate3, get_stream_url_with_quality,
+    perform_anchor_search,
+};
+use crate::platforms::common::types_rust::{
+    CommonCategoryGroupRust, CommonLiveRoomRust, CommonRoomInfoRust, CommonRoomListRust,
+    CommonStreamInfoRust, RoomListQueryRust, SupportedPlatformRust,
+};
+use crate::platforms::common::LivePlatform;
+use crate::proxy::streams::ProxyStreamHeaders;
+
+pub struct DouyuPlatform;
+
+fn streamer_to_common(streamer: FrontendStreamer) -> CommonLiveRoomRust {
+    CommonLiveRoomRust {
+        room_id: streamer.rid,
+        title: streamer.room_name,
+        anchor_name: streamer.nickname,
+        avatar_url: Some(streamer.avatar).filter(|s| !s.is_empty()),
+        cover_url: Some(streamer.room_src).filter(|s| !s.is_empty()),
+        viewer_count: Some(streamer.hn),
+        is_live: streamer.is_live.unwrap_or(true),
+        platform: SupportedPlatformRust::Douyu,
+    }
+}
+
+// The live list commands report failures inside the response body; turn that into a Result
+fn live_list_to_rooms(
+    response: FrontendLiveListResponse,
+) -> Result<(Vec<CommonLiveRoomRust>, u32), String> {
+    if response.error != 0 {
+        return Err(format!(
+            "Douyu live list error {}: {}",
+            response.error,
+            response.msg.unwrap_or_default()
+        ));
+    }
+    let data = response
+        .data
+        .ok_or_else(|| "Douyu live list response has no data".to_string())?;
+    Ok((
+        data.list.into_iter().map(streamer_to_common).collect(),
+        data.total,
+    ))
+}
+
+#[async_trait]
+impl LivePlatform for DouyuPlatform {
+    fn platform(&self) -> SupportedPlatformRust {
+        SupportedPlatformRust::Douyu
+    }
+
+    async fn fetch_categories(&self) -> Result<Vec<CommonCategoryGroupRust>, String> {
+        fetch_douyu_category_groups().await
+    }
+
+    async fn fetch_rooms(&self, query: &RoomListQueryRust) -> Result<CommonRoomListRust, String> {
+        // Only cate3 entries carry a parent id (see fetch_douyu_category_groups)
+        if query.parent_category_id.is_some() {
+            let response = fetch_live_list_for_cate3(
+                query.category_id.clone(),
+                query.page + 1,
+                query.page_size,
+            )
+            .await;
+            let (rooms, _estimated_total) = live_list_to_rooms(response)?;
+            let has_more = rooms.len() as u32 >= query.page_size;
+            Ok(CommonRoomListRust { rooms, has_more })
+        } else {
+            let offset = query.page * query.page_size;
+            let response =
+                fetch_live_list(offset, query.category_id.clone(), query.page_size).await;
+            let (rooms, total) = live_list_to_rooms(response)?;
+            let has_more = offset + (rooms.len() as u32) < total;
+            Ok(CommonRoomListRust { rooms, has_more })
+        }
+    }
+
+    async fn fetch_room_info(&self, room_id: &str) -> Result<CommonRoomInfoRust, String> {
+        let info = fetch_douyu_room_info(room_id.to_string()).await?;
+        // show_status 1 with videoLoop 1 is a replay loop, not a live broadcast
+        let is_live = info.show_status == Some(1) && info.video_loop != Some(1);
+        Ok(CommonRoomInfoRust {
+            room_id: info.room_id,
+            title: info.room_name,
+            anchor_name: info.nickname,
+            avatar_url: info.avatar_url,
+            is_live,
+            live_started_at: info
+                .show_time
+                .filter(|started_at| is_live && *started_at > 0)
+                .map(|started_at| started_at * 1000),
+            platform: SupportedPlatformRust::Douyu,
+        })
+    }
+
+    async fn resolve_stream(
+        &self,
+        room_id: &str,
+        quality: Option<&str>,
+    ) -> Result<CommonStreamInfoRust, String> {
+        // Douyu qualities are the numeric rates from get_stream_qualities
+        let rate = quality.and_then(|quality| quality.parse().ok());
+        let url = get_stream_url_with_quality(room_id, rate, None)
+            .await
+            .map_err(|e| format!("Failed to get stream URL: {}", e))?;
+        Ok(CommonStreamInfoRust {
+            room_id: room_id.to_string(),
+            url,
+            format: "flv".to_string(),
+            platform: SupportedPlatformRust::Douyu,
+            headers: ProxyStreamHeaders::default(),
+        })
+    }
+
+    async fn search(&self, keyword: &str) -> Result<Vec<CommonLiveRoomRust>, String> {
+        let text = perform_anchor_search(keyword)
+            .await
+            .map_err(|e| e.to_string())?;
+        let json: Value = serde_json::from_str(&text)
+            .map_err(|e| format!("Failed to parse Douyu search response: {}", e))?;
+
+        let relate_users = json
+            .get("data")
+            .and_then(|d| d.get("relateUser"))
+            .and_then(|u| u.as_array())
+            .cloned()
+            .unwrap_or_default();
+
+        // type 1 entries are anchors; the rest are plain users
+        let rooms = relate_users
+            .iter()
+            .filter(|item| item.get("type").and_then(|t| t.as_i64()) == Some(1))
+            .filter_map(|item| item.get("anchorInfo"))
+            .map(|anchor| {
+                let get_str =
+                    |key: &str| anchor.get(key).and_then(|v| v.as_str()).map(String::from);
+                let room_id = match anchor.get("rid") {
+                    Some(Value::Number(n)) => n.to_string(),
+                    Some(Value::String(s)) => s.clone(),
+                    _ => String::new(),
+                };
+                let is_live = anchor.get("isLive").and_then(|v| v.as_i64()) == Some(1)
+                    && anchor.get("videoLoop").and_then(|v| v.as_i64()) != Some(1);
+                CommonLiveRoomRust {
+                    room_id,
+                    title: get_str("roomName")
+                        .or_else(|| get_str("description"))
+                        .unwrap_or_default(),
+                    anchor_name: get_str("nickName").unwrap_or_default(),
+                    avatar_url: get_str("avatar"),
+                    cover_url: None,
+                    viewer_count: None, // Search only reports follower counts
+                    is_live,
+                    platform: SupportedPlatformRust::Douyu,
+                }
+            })
+            .filter(|room| !room.room_id.is_empty())
+            .collect();
+
+        Ok(rooms)
+    }
+
+    async fn run_danmaku(
+        &self,
+        app_handle: AppHandle,
+        room_id: String,
+        stop_rx: oneshot::Receiver<()>,
+    ) -> Result<(), String> {
+        let mut client = DanmakuClient::new(&room_id, app_handle, stop_rx);
+        client.start().await.map_err(|e| e.to_string())
+    }
+}
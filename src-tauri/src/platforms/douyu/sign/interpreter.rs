@@ -0,0 +1,1123 @@
+// Evaluates the parsed subset. Values behave as in JavaScript where the signing code can
+// tell: numbers are f64 with 32-bit bitwise operators, strings index by UTF-16 unit, and
+// `+` concatenates as soon as either side is a string.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::lexer::tokenize;
+use super::parser::{parse, Expr, Function, Stmt};
+
+// The signing code runs a few thousand loop iterations; this only stops a runaway script
+const MAX_STEPS: u64 = 20_000_000;
+// It calls a couple of functions deep; deeper recursion would overflow the stack
+const MAX_CALL_DEPTH: usize = 32;
+// Its arrays hold a few hundred items; a longer one is refused rather than allocated
+const MAX_ARRAY_LEN: usize = 1 << 20;
+
+#[derive(Clone, Debug)]
+pub enum Value {
+    Undefined,
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(Rc<str>),
+    Array(Rc<RefCell<Vec<Value>>>),
+    Function(Rc<Function>, Rc<Scope>),
+    Namespace(&'static str),     // String, Math
+    Native(&'static str),        // A global function such as parseInt
+    Method(Box<Value>, Rc<str>), // A string, array or number method bound to its receiver
+}
+
+#[derive(Debug, Default)]
+pub struct Scope {
+    vars: RefCell<HashMap<String, Value>>,
+    parent: Option<Rc<Scope>>,
+}
+
+impl Scope {
+    fn lookup(&self, name: &str) -> Option<Value> {
+        if let Some(value) = self.vars.borrow().get(name) {
+            return Some(value.clone());
+        }
+        self.parent.as_ref()?.lookup(name)
+    }
+
+    fn declare(&self, name: &str, value: Value) {
+        self.vars.borrow_mut().insert(name.to_string(), value);
+    }
+
+    // Sloppy-mode assignment: an undeclared name becomes a global
+    fn assign(self: &Rc<Self>, name: &str, value: Value) {
+        let mut scope = self.clone();
+        loop {
+            if scope.vars.borrow().contains_key(name) || scope.parent.is_none() {
+                scope.declare(name, value);
+                return;
+            }
+            let parent = scope.parent.clone().unwrap();
+            scope = parent;
+        }
+    }
+}
+
+enum Flow {
+    Normal,
+    Return(Value),
+    Break,
+    Continue,
+}
+
+enum Place {
+    Var(String),
+    Property(Value, Value),
+}
+
+pub struct Interpreter {
+    global: Rc<Scope>,
+    steps: u64,
+    call_depth: usize,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self {
+            global: Rc::new(Scope::default()),
+            steps: 0,
+            call_depth: 0,
+        }
+    }
+
+    // Runs a script in the shared global scope and returns its completion value, like
+    // evaluating it in a console
+    pub fn run(&mut self, source: &str) -> Result<Value, String> {
+        let program = parse(&tokenize(source)?)?;
+        let global = self.global.clone();
+        hoist(&program, &global);
+        let mut completion = Value::Undefined;
+        for stmt in &program {
+            if let Stmt::Expr(expr) = stmt {
+                completion = self.eval(expr, &global)?;
+                continue;
+            }
+            match self.exec(stmt, &global)? {
+                Flow::Normal => {}
+                _ => return Err("return, break or continue outside a function".to_string()),
+            }
+        }
+        Ok(completion)
+    }
+
+    fn tick(&mut self) -> Result<(), String> {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return Err("Script ran for too long".to_string());
+        }
+        Ok(())
+    }
+
+    fn exec_block(&mut self, body: &[Stmt], scope: &Rc<Scope>) -> Result<Flow, String> {
+        for stmt in body {
+            match self.exec(stmt, scope)? {
+                Flow::Normal => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn exec(&mut self, stmt: &Stmt, scope: &Rc<Scope>) -> Result<Flow, String> {
+        self.tick()?;
+        match stmt {
+            Stmt::Var(decls) => {
+                for (name, init) in decls {
+                    if let Some(init) = init {
+                        let value = self.eval(init, scope)?;
+                        scope.assign(name, value);
+                    }
+                }
+            }
+            Stmt::Function(_) | Stmt::Empty => {} // Functions are hoisted
+            Stmt::Expr(expr) => {
+                self.eval(expr, scope)?;
+            }
+            Stmt::Block(body) => return self.exec_block(body, scope),
+            Stmt::If(test, then, otherwise) => {
+                if truthy(&self.eval(test, scope)?) {
+                    return self.exec(then, scope);
+                } else if let Some(otherwise) = otherwise {
+                    return self.exec(otherwise, scope);
+                }
+            }
+            Stmt::For(init, test, update, body) => {
+                if let Some(init) = init {
+                    self.exec(init, scope)?;
+                }
+                loop {
+                    if let Some(test) = test {
+                        if !truthy(&self.eval(test, scope)?) {
+                            break;
+                        }
+                    }
+                    match self.exec(body, scope)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Normal | Flow::Continue => {}
+                    }
+                    if let Some(update) = update {
+                        self.eval(update, scope)?;
+                    }
+                }
+            }
+            Stmt::While(test, body) => {
+                while truthy(&self.eval(test, scope)?) {
+                    match self.exec(body, scope)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Normal | Flow::Continue => {}
+                    }
+                }
+            }
+            Stmt::DoWhile(body, test) => loop {
+                match self.exec(body, scope)? {
+                    Flow::Break => break,
+                    Flow::Return(value) => return Ok(Flow::Return(value)),
+                    Flow::Normal | Flow::Continue => {}
+                }
+                if !truthy(&self.eval(test, scope)?) {
+                    break;
+                }
+            },
+            Stmt::Switch(discriminant, cases) => {
+                let value = self.eval(discriminant, scope)?;
+                let mut matched = None;
+                for (i, (test, _)) in cases.iter().enumerate() {
+                    if let Some(test) = test {
+                        if strict_equals(&value, &self.eval(test, scope)?) {
+                            matched = Some(i);
+                            break;
+                        }
+                    }
+                }
+                let start = matched.or_else(|| cases.iter().position(|(test, _)| test.is_none()));
+                if let Some(start) = start {
+                    // Falls through until a break
+                    for (_, body) in &cases[start..] {
+                        match self.exec_block(body, scope)? {
+                            Flow::Normal => {}
+                            Flow::Break => break,
+                            flow => return Ok(flow),
+                        }
+                    }
+                }
+            }
+            Stmt::Return(value) => {
+                let value = match value {
+                    Some(value) => self.eval(value, scope)?,
+                    None => Value::Undefined,
+                };
+                return Ok(Flow::Return(value));
+            }
+            Stmt::Break => return Ok(Flow::Break),
+            Stmt::Continue => return Ok(Flow::Continue),
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn eval(&mut self, expr: &Expr, scope: &Rc<Scope>) -> Result<Value, String> {
+        match expr {
+            Expr::Num(n) => Ok(Value::Num(*n)),
+            Expr::Str(s) => Ok(Value::Str(s.clone())),
+            Expr::Ident(name) => lookup(name, scope),
+            Expr::Array(items) => {
+                let mut values = Vec::with_capacity(items.len());
+                for item in items {
+                    values.push(self.eval(item, scope)?);
+                }
+                Ok(Value::Array(Rc::new(RefCell::new(values))))
+            }
+            Expr::Function(function) => Ok(Value::Function(function.clone(), scope.clone())),
+            Expr::Member(object, property) => {
+                let object = self.eval(object, scope)?;
+                let property = self.eval(property, scope)?;
+                get_property(&object, &property)
+            }
+            Expr::Call(callee, args) => {
+                let callee = self.eval(callee, scope)?;
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    values.push(self.eval(arg, scope)?);
+                }
+                self.call(&callee, values)
+            }
+            Expr::New(constructor, args) => {
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    values.push(self.eval(arg, scope)?);
+                }
+                match constructor.as_ref() {
+                    Expr::Ident(name) if name == "Array" => new_array(values),
+                    _ => Err("Only `new Array` is supported".to_string()),
+                }
+            }
+            Expr::Unary(op, operand) => {
+                if *op == "typeof" {
+                    // typeof is the one place an undeclared name isn't an error
+                    let value = match operand.as_ref() {
+                        Expr::Ident(name) => lookup(name, scope).unwrap_or(Value::Undefined),
+                        operand => self.eval(operand, scope)?,
+                    };
+                    return Ok(Value::Str(type_of(&value).into()));
+                }
+                let value = self.eval(operand, scope)?;
+                Ok(match *op {
+                    "!" => Value::Bool(!truthy(&value)),
+                    "-" => Value::Num(-to_number(&value)),
+                    "+" => Value::Num(to_number(&value)),
+                    "~" => Value::Num(!to_int32(to_number(&value)) as f64),
+                    _ => Value::Undefined, // void
+                })
+            }
+            Expr::Update(op, prefix, target) => {
+                let place = self.place(target, scope)?;
+                let old = to_number(&self.read(&place, scope)?);
+                let new = if *op == "++" { old + 1.0 } else { old - 1.0 };
+                self.write(&place, Value::Num(new), scope)?;
+                Ok(Value::Num(if *prefix { new } else { old }))
+            }
+            Expr::Binary(op, left, right) => {
+                let left = self.eval(left, scope)?;
+                let right = self.eval(right, scope)?;
+                binary(op, &left, &right)
+            }
+            Expr::Logical(op, left, right) => {
+                let left = self.eval(left, scope)?;
+                if truthy(&left) == (*op == "||") {
+                    Ok(left)
+                } else {
+                    self.eval(right, scope)
+                }
+            }
+            Expr::Conditional(test, then, otherwise) => {
+                if truthy(&self.eval(test, scope)?) {
+                    self.eval(then, scope)
+                } else {
+                    self.eval(otherwise, scope)
+                }
+            }
+            Expr::Assign(op, target, value) => {
+                let place = self.place(target, scope)?;
+                let value = if *op == "=" {
+                    self.eval(value, scope)?
+                } else {
+                    let old = self.read(&place, scope)?;
+                    let right = self.eval(value, scope)?;
+                    binary(&op[..op.len() - 1], &old, &right)?
+                };
+                self.write(&place, value.clone(), scope)?;
+                Ok(value)
+            }
+            Expr::Sequence(exprs) => {
+                let mut last = Value::Undefined;
+                for expr in exprs {
+                    last = self.eval(expr, scope)?;
+                }
+                Ok(last)
+            }
+        }
+    }
+
+    fn place(&mut self, target: &Expr, scope: &Rc<Scope>) -> Result<Place, String> {
+        match target {
+            Expr::Ident(name) => Ok(Place::Var(name.clone())),
+            Expr::Member(object, property) => Ok(Place::Property(
+                self.eval(object, scope)?,
+                self.eval(property, scope)?,
+            )),
+            _ => Err("Invalid assignment target".to_string()),
+        }
+    }
+
+    fn read(&self, place: &Place, scope: &Rc<Scope>) -> Result<Value, String> {
+        match place {
+            Place::Var(name) => lookup(name, scope),
+            Place::Property(object, property) => get_property(object, property),
+        }
+    }
+
+    fn write(&self, place: &Place, value: Value, scope: &Rc<Scope>) -> Result<(), String> {
+        match place {
+            Place::Var(name) => {
+                scope.assign(name, value);
+                Ok(())
+            }
+            Place::Property(Value::Array(items), property) => {
+                let mut items = items.borrow_mut();
+                if let Value::Str(name) = property {
+                    if &**name == "length" {
+                        items.resize(array_length(to_number(&value))?, Value::Undefined);
+                        return Ok(());
+                    }
+                }
+                let index = array_index(property).ok_or("Unsupported array property")?;
+                if index >= MAX_ARRAY_LEN {
+                    return Err(format!("Array index {} is out of range", index));
+                }
+                if index >= items.len() {
+                    items.resize(index + 1, Value::Undefined);
+                }
+                items[index] = value;
+                Ok(())
+            }
+            Place::Property(object, _) => {
+                Err(format!("Cannot set properties on {}", type_of(object)))
+            }
+        }
+    }
+
+    fn call(&mut self, callee: &Value, args: Vec<Value>) -> Result<Value, String> {
+        self.tick()?;
+        match callee {
+            Value::Function(function, closure) => {
+                if self.call_depth == MAX_CALL_DEPTH {
+                    return Err("Maximum call stack size exceeded".to_string());
+                }
+                let scope = Rc::new(Scope {
+                    vars: RefCell::default(),
+                    parent: Some(closure.clone()),
+                });
+                for (i, param) in function.params.iter().enumerate() {
+                    scope.declare(param, args.get(i).cloned().unwrap_or(Value::Undefined));
+                }
+                hoist(&function.body, &scope);
+                self.call_depth += 1;
+                let flow = self.exec_block(&function.body, &scope);
+                self.call_depth -= 1;
+                match flow? {
+                    Flow::Return(value) => Ok(value),
+                    _ => Ok(Value::Undefined),
+                }
+            }
+            Value::Native(name) => call_native(name, &args),
+            Value::Method(receiver, name) => call_method(receiver, name, &args),
+            other => Err(format!("{} is not a function", type_of(other))),
+        }
+    }
+}
+
+fn call_method(receiver: &Value, name: &str, args: &[Value]) -> Result<Value, String> {
+    match receiver {
+        Value::Str(s) => string_method(s, name, args),
+        Value::Array(items) => array_method(items, name, args),
+        Value::Num(n) => match name {
+            "toString" => {
+                let radix = args.first().map_or(10.0, to_number) as u32;
+                Ok(Value::Str(number_to_radix_string(*n, radix)?.into()))
+            }
+            _ => Err(format!("Unsupported number method {}", name)),
+        },
+        Value::Bool(b) if name == "toString" => Ok(Value::Str(b.to_string().into())),
+        _ => Err(format!("Unsupported method {}", name)),
+    }
+}
+
+// `var` and function declarations belong to the whole function, wherever they're written
+fn hoist(body: &[Stmt], scope: &Rc<Scope>) {
+    for stmt in body {
+        match stmt {
+            Stmt::Var(decls) => {
+                for (name, _) in decls {
+                    if !scope.vars.borrow().contains_key(name) {
+                        scope.declare(name, Value::Undefined);
+                    }
+                }
+            }
+            Stmt::Function(function) => {
+                if let Some(name) = &function.name {
+                    scope.declare(name, Value::Function(function.clone(), scope.clone()));
+                }
+            }
+            Stmt::Block(inner) => hoist(inner, scope),
+            Stmt::If(_, then, otherwise) => {
+                hoist(std::slice::from_ref(then.as_ref()), scope);
+                if let Some(otherwise) = otherwise {
+                    hoist(std::slice::from_ref(otherwise.as_ref()), scope);
+                }
+            }
+            Stmt::For(init, _, _, body) => {
+                if let Some(init) = init {
+                    hoist(std::slice::from_ref(init.as_ref()), scope);
+                }
+                hoist(std::slice::from_ref(body.as_ref()), scope);
+            }
+            Stmt::While(_, body) | Stmt::DoWhile(body, _) => {
+                hoist(std::slice::from_ref(body.as_ref()), scope)
+            }
+            Stmt::Switch(_, cases) => {
+                for (_, case_body) in cases {
+                    hoist(case_body, scope);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn lookup(name: &str, scope: &Rc<Scope>) -> Result<Value, String> {
+    if let Some(value) = scope.lookup(name) {
+        return Ok(value);
+    }
+    match name {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        "null" => Ok(Value::Null),
+        "undefined" => Ok(Value::Undefined),
+        "NaN" => Ok(Value::Num(f64::NAN)),
+        "Infinity" => Ok(Value::Num(f64::INFINITY)),
+        "String" => Ok(Value::Namespace("String")),
+        "Math" => Ok(Value::Namespace("Math")),
+        "parseInt" => Ok(Value::Native("parseInt")),
+        "parseFloat" => Ok(Value::Native("parseFloat")),
+        "isNaN" => Ok(Value::Native("isNaN")),
+        "Array" => Ok(Value::Native("Array")),
+        _ => Err(format!("{} is not defined", name)),
+    }
+}
+
+// A length JavaScript would reject is an error here too, as is one past MAX_ARRAY_LEN
+fn array_length(len: f64) -> Result<usize, String> {
+    if len < 0.0 || len.fract() != 0.0 || len > u32::MAX as f64 {
+        return Err("Invalid array length".to_string());
+    }
+    if len > MAX_ARRAY_LEN as f64 {
+        return Err(format!("Array length {} is out of range", len));
+    }
+    Ok(len as usize)
+}
+
+fn new_array(args: Vec<Value>) -> Result<Value, String> {
+    let items = match args.as_slice() {
+        [Value::Num(len)] => vec![Value::Undefined; array_length(*len)?],
+        _ => args,
+    };
+    Ok(Value::Array(Rc::new(RefCell::new(items))))
+}
+
+fn array_index(property: &Value) -> Option<usize> {
+    let n = match property {
+        Value::Num(n) => *n,
+        Value::Str(s) => s.parse::<f64>().ok()?,
+        _ => return None,
+    };
+    (n >= 0.0 && n.fract() == 0.0).then_some(n as usize)
+}
+
+fn get_property(object: &Value, property: &Value) -> Result<Value, String> {
+    match object {
+        Value::Str(s) => {
+            if let Some(index) = array_index(property) {
+                let units: Vec<u16> = s.encode_utf16().collect();
+                return Ok(units.get(index).map_or(Value::Undefined, |unit| {
+                    Value::Str(String::from_utf16_lossy(&[*unit]).into())
+                }));
+            }
+            let name = to_string(property);
+            if name == "length" {
+                return Ok(Value::Num(s.encode_utf16().count() as f64));
+            }
+            Ok(Value::Method(Box::new(object.clone()), name.into()))
+        }
+        Value::Array(items) => {
+            if let Some(index) = array_index(property) {
+                return Ok(items
+                    .borrow()
+                    .get(index)
+                    .cloned()
+                    .unwrap_or(Value::Undefined));
+            }
+            let name = to_string(property);
+            if name == "length" {
+                return Ok(Value::Num(items.borrow().len() as f64));
+            }
+            Ok(Value::Method(Box::new(object.clone()), name.into()))
+        }
+        Value::Num(_) | Value::Bool(_) => Ok(Value::Method(
+            Box::new(object.clone()),
+            to_string(property).into(),
+        )),
+        Value::Namespace(namespace) => {
+            let name = to_string(property);
+            let native = match (*namespace, name.as_str()) {
+                ("String", "fromCharCode") => "String.fromCharCode",
+                ("Math", "floor") => "Math.floor",
+                ("Math", "ceil") => "Math.ceil",
+                ("Math", "round") => "Math.round",
+                ("Math", "abs") => "Math.abs",
+                ("Math", "max") => "Math.max",
+                ("Math", "min") => "Math.min",
+                ("Math", "pow") => "Math.pow",
+                ("Math", "PI") => return Ok(Value::Num(std::f64::consts::PI)),
+                _ => return Err(format!("{}.{} is not supported", namespace, name)),
+            };
+            Ok(Value::Native(native))
+        }
+        Value::Undefined | Value::Null => Err(format!(
+            "Cannot read property {} of {}",
+            to_string(property),
+            to_string(object)
+        )),
+        _ => Ok(Value::Undefined),
+    }
+}
+
+fn call_native(name: &str, args: &[Value]) -> Result<Value, String> {
+    let arg = |i: usize| args.get(i).cloned().unwrap_or(Value::Undefined);
+    let num = |i: usize| to_number(&arg(i));
+    Ok(match name {
+        "String.fromCharCode" => {
+            let units: Vec<u16> = args
+                .iter()
+                .map(|v| to_uint32(to_number(v)) as u16)
+                .collect();
+            Value::Str(String::from_utf16_lossy(&units).into())
+        }
+        "parseInt" => {
+            let radix = match arg(1) {
+                Value::Undefined => 0,
+                radix => to_int32(to_number(&radix)),
+            };
+            Value::Num(parse_int(&to_string(&arg(0)), radix))
+        }
+        "parseFloat" => Value::Num(parse_float(&to_string(&arg(0)))),
+        "isNaN" => Value::Bool(num(0).is_nan()),
+        "Array" => new_array(args.to_vec())?,
+        "Math.floor" => Value::Num(num(0).floor()),
+        "Math.ceil" => Value::Num(num(0).ceil()),
+        "Math.round" => Value::Num((num(0) + 0.5).floor()),
+        "Math.abs" => Value::Num(num(0).abs()),
+        "Math.pow" => Value::Num(num(0).powf(num(1))),
+        "Math.max" => Value::Num(args.iter().map(to_number).fold(f64::NEG_INFINITY, f64::max)),
+        "Math.min" => Value::Num(args.iter().map(to_number).fold(f64::INFINITY, f64::min)),
+        _ => return Err(format!("{} is not supported", name)),
+    })
+}
+
+// Resolves a relative index the way slice() and friends do
+fn relative_index(value: Value, len: usize, default: usize) -> usize {
+    if matches!(value, Value::Undefined) {
+        return default;
+    }
+    let n = to_number(&value);
+    let n = if n.is_nan() { 0.0 } else { n.trunc() };
+    if n < 0.0 {
+        (len as f64 + n).max(0.0) as usize
+    } else {
+        n.min(len as f64) as usize
+    }
+}
+
+fn string_method(s: &str, name: &str, args: &[Value]) -> Result<Value, String> {
+    let arg = |i: usize| args.get(i).cloned().unwrap_or(Value::Undefined);
+    let units: Vec<u16> = s.encode_utf16().collect();
+    let len = units.len();
+    let text = |range: &[u16]| Value::Str(String::from_utf16_lossy(range).into());
+    let position = |i: usize| {
+        let n = to_number(&arg(i));
+        if n.is_nan() {
+            0.0
+        } else {
+            n.trunc()
+        }
+    };
+    Ok(match name {
+        "charCodeAt" => {
+            let i = position(0);
+            if i >= 0.0 && (i as usize) < len {
+                Value::Num(units[i as usize] as f64)
+            } else {
+                Value::Num(f64::NAN)
+            }
+        }
+        "charAt" => {
+            let i = position(0);
+            if i >= 0.0 && (i as usize) < len {
+                text(&units[i as usize..i as usize + 1])
+            } else {
+                text(&[])
+            }
+        }
+        "substr" => {
+            let start = relative_index(arg(0), len, 0);
+            let count = match arg(1) {
+                Value::Undefined => len - start,
+                count => to_number(&count).max(0.0).min((len - start) as f64) as usize,
+            };
+            text(&units[start..start + count])
+        }
+        "substring" => {
+            let clamp = |value: Value, default: usize| match value {
+                Value::Undefined => default,
+                value => {
+                    let n = to_number(&value);
+                    if n.is_nan() {
+                        0
+                    } else {
+                        n.max(0.0).min(len as f64) as usize
+                    }
+                }
+            };
+            let (a, b) = (clamp(arg(0), 0), clamp(arg(1), len));
+            text(&units[a.min(b)..a.max(b)])
+        }
+        "slice" => {
+            let start = relative_index(arg(0), len, 0);
+            let end = relative_index(arg(1), len, len);
+            text(&units[start..end.max(start)])
+        }
+        "indexOf" => {
+            let needle = to_string(&arg(0));
+            Value::Num(match s.find(&needle) {
+                Some(byte) => s[..byte].encode_utf16().count() as f64,
+                None => -1.0,
+            })
+        }
+        "split" => {
+            let parts: Vec<Value> = match arg(0) {
+                Value::Undefined => vec![Value::Str(s.into())],
+                separator => {
+                    let separator = to_string(&separator);
+                    if separator.is_empty() {
+                        units.iter().map(|unit| text(&[*unit])).collect()
+                    } else {
+                        s.split(separator.as_str())
+                            .map(|part| Value::Str(part.into()))
+                            .collect()
+                    }
+                }
+            };
+            Value::Array(Rc::new(RefCell::new(parts)))
+        }
+        "replace" => {
+            let pattern = to_string(&arg(0));
+            let replacement = to_string(&arg(1));
+            Value::Str(s.replacen(&pattern, &replacement, 1).into())
+        }
+        "concat" => {
+            let mut out = s.to_string();
+            for value in args {
+                out.push_str(&to_string(value));
+            }
+            Value::Str(out.into())
+        }
+        "toUpperCase" => Value::Str(s.to_uppercase().into()),
+        "toLowerCase" => Value::Str(s.to_lowercase().into()),
+        "toString" | "valueOf" => Value::Str(s.into()),
+        _ => return Err(format!("Unsupported string method {}", name)),
+    })
+}
+
+fn array_method(
+    items: &Rc<RefCell<Vec<Value>>>,
+    name: &str,
+    args: &[Value],
+) -> Result<Value, String> {
+    let arg = |i: usize| args.get(i).cloned().unwrap_or(Value::Undefined);
+    Ok(match name {
+        "push" => {
+            let mut items = items.borrow_mut();
+            items.extend(args.iter().cloned());
+            Value::Num(items.len() as f64)
+        }
+        "pop" => items.borrow_mut().pop().unwrap_or(Value::Undefined),
+        "shift" => {
+            let mut items = items.borrow_mut();
+            if items.is_empty() {
+                Value::Undefined
+            } else {
+                items.remove(0)
+            }
+        }
+        "unshift" => {
+            let mut items = items.borrow_mut();
+            for (i, value) in args.iter().enumerate() {
+                items.insert(i, value.clone());
+            }
+            Value::Num(items.len() as f64)
+        }
+        "reverse" => {
+            items.borrow_mut().reverse();
+            Value::Array(items.clone())
+        }
+        "join" => {
+            let separator = match arg(0) {
+                Value::Undefined => ",".to_string(),
+                separator => to_string(&separator),
+            };
+            Value::Str(join(&items.borrow(), &separator).into())
+        }
+        "toString" => Value::Str(join(&items.borrow(), ",").into()),
+        "slice" => {
+            let items = items.borrow();
+            let start = relative_index(arg(0), items.len(), 0);
+            let end = relative_index(arg(1), items.len(), items.len());
+            Value::Array(Rc::new(RefCell::new(items[start..end.max(start)].to_vec())))
+        }
+        "concat" => {
+            let mut out = items.borrow().clone();
+            for value in args {
+                match value {
+                    Value::Array(more) => out.extend(more.borrow().iter().cloned()),
+                    value => out.push(value.clone()),
+                }
+            }
+            Value::Array(Rc::new(RefCell::new(out)))
+        }
+        "indexOf" => {
+            let needle = arg(0);
+            Value::Num(
+                items
+                    .borrow()
+                    .iter()
+                    .position(|item| strict_equals(item, &needle))
+                    .map_or(-1.0, |i| i as f64),
+            )
+        }
+        _ => return Err(format!("Unsupported array method {}", name)),
+    })
+}
+
+fn join(items: &[Value], separator: &str) -> String {
+    items
+        .iter()
+        .map(|item| match item {
+            Value::Undefined | Value::Null => String::new(),
+            item => to_string(item),
+        })
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+fn binary(op: &str, left: &Value, right: &Value) -> Result<Value, String> {
+    if op == "+" {
+        let (left, right) = (to_primitive(left), to_primitive(right));
+        if matches!(left, Value::Str(_)) || matches!(right, Value::Str(_)) {
+            let mut out = to_string(&left);
+            out.push_str(&to_string(&right));
+            return Ok(Value::Str(out.into()));
+        }
+        return Ok(Value::Num(to_number(&left) + to_number(&right)));
+    }
+    match op {
+        "==" => return Ok(Value::Bool(loose_equals(left, right))),
+        "!=" => return Ok(Value::Bool(!loose_equals(left, right))),
+        "===" => return Ok(Value::Bool(strict_equals(left, right))),
+        "!==" => return Ok(Value::Bool(!strict_equals(left, right))),
+        "<" | ">" | "<=" | ">=" => {
+            let (left, right) = (to_primitive(left), to_primitive(right));
+            let ordering = match (&left, &right) {
+                (Value::Str(a), Value::Str(b)) => Some(a.encode_utf16().cmp(b.encode_utf16())),
+                _ => to_number(&left).partial_cmp(&to_number(&right)),
+            };
+            let Some(ordering) = ordering else {
+                return Ok(Value::Bool(false)); // NaN
+            };
+            return Ok(Value::Bool(match op {
+                "<" => ordering.is_lt(),
+                ">" => ordering.is_gt(),
+                "<=" => ordering.is_le(),
+                _ => ordering.is_ge(),
+            }));
+        }
+        _ => {}
+    }
+    let (a, b) = (to_number(left), to_number(right));
+    let shift = to_uint32(b) & 31;
+    Ok(Value::Num(match op {
+        "-" => a - b,
+        "*" => a * b,
+        "/" => a / b,
+        "%" => a % b,
+        "&" => (to_int32(a) & to_int32(b)) as f64,
+        "|" => (to_int32(a) | to_int32(b)) as f64,
+        "^" => (to_int32(a) ^ to_int32(b)) as f64,
+        "<<" => to_int32(a).wrapping_shl(shift) as f64,
+        ">>" => (to_int32(a) >> shift) as f64,
+        ">>>" => (to_uint32(a) >> shift) as f64,
+        _ => return Err(format!("Unsupported operator {}", op)),
+    }))
+}
+
+fn to_primitive(value: &Value) -> Value {
+    match value {
+        Value::Array(_) | Value::Function(..) | Value::Native(_) | Value::Method(..) => {
+            Value::Str(to_string(value).into())
+        }
+        value => value.clone(),
+    }
+}
+
+fn strict_equals(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Undefined, Value::Undefined) | (Value::Null, Value::Null) => true,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Num(a), Value::Num(b)) => a == b,
+        (Value::Str(a), Value::Str(b)) => a == b,
+        (Value::Array(a), Value::Array(b)) => Rc::ptr_eq(a, b),
+        (Value::Function(a, _), Value::Function(b, _)) => Rc::ptr_eq(a, b),
+        _ => false,
+    }
+}
+
+fn loose_equals(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Undefined | Value::Null, Value::Undefined | Value::Null) => true,
+        (Value::Undefined | Value::Null, _) | (_, Value::Undefined | Value::Null) => false,
+        (Value::Str(_), Value::Str(_))
+        | (Value::Array(_), Value::Array(_))
+        | (Value::Function(..), Value::Function(..)) => strict_equals(left, right),
+        _ => {
+            let (left, right) = (to_primitive(left), to_primitive(right));
+            match (&left, &right) {
+                (Value::Str(a), Value::Str(b)) => a == b,
+                _ => to_number(&left) == to_number(&right),
+            }
+        }
+    }
+}
+
+pub fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Undefined | Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Num(n) => *n != 0.0 && !n.is_nan(),
+        Value::Str(s) => !s.is_empty(),
+        _ => true,
+    }
+}
+
+fn type_of(value: &Value) -> &'static str {
+    match value {
+        Value::Undefined => "undefined",
+        Value::Bool(_) => "boolean",
+        Value::Num(_) => "number",
+        Value::Str(_) => "string",
+        Value::Function(..) | Value::Native(_) | Value::Method(..) => "function",
+        Value::Null | Value::Array(_) | Value::Namespace(_) => "object",
+    }
+}
+
+pub fn to_number(value: &Value) -> f64 {
+    match value {
+        Value::Undefined => f64::NAN,
+        Value::Null => 0.0,
+        Value::Bool(b) => *b as u8 as f64,
+        Value::Num(n) => *n,
+        Value::Str(s) => {
+            let s = s.trim();
+            if s.is_empty() {
+                0.0
+            } else if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+                u64::from_str_radix(hex, 16).map_or(f64::NAN, |n| n as f64)
+            } else {
+                match s {
+                    "Infinity" | "+Infinity" => f64::INFINITY,
+                    "-Infinity" => f64::NEG_INFINITY,
+                    // Rust also accepts "inf" and "nan", JavaScript doesn't
+                    _ if s
+                        .chars()
+                        .any(|c| c.is_ascii_alphabetic() && c != 'e' && c != 'E') =>
+                    {
+                        f64::NAN
+                    }
+                    _ => s.parse().unwrap_or(f64::NAN),
+                }
+            }
+        }
+        Value::Array(_) => to_number(&to_primitive(value)),
+        _ => f64::NAN,
+    }
+}
+
+pub fn to_string(value: &Value) -> String {
+    match value {
+        Value::Undefined => "undefined".to_string(),
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Num(n) => number_to_string(*n),
+        Value::Str(s) => s.to_string(),
+        Value::Array(items) => join(&items.borrow(), ","),
+        Value::Function(..) | Value::Native(_) | Value::Method(..) => {
+            "function () { [native code] }".to_string()
+        }
+        Value::Namespace(name) => format!("[object {}]", name),
+    }
+}
+
+fn to_int32(n: f64) -> i32 {
+    to_uint32(n) as i32
+}
+
+fn to_uint32(n: f64) -> u32 {
+    if !n.is_finite() {
+        return 0;
+    }
+    n.trunc().rem_euclid(4_294_967_296.0) as u32
+}
+
+fn number_to_string(n: f64) -> String {
+    if n.is_nan() {
+        "NaN".to_string()
+    } else if n.is_infinite() {
+        if n > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+    } else if n == 0.0 {
+        "0".to_string()
+    } else if n.fract() == 0.0 && n.abs() < 1e21 {
+        format!("{}", n as i128)
+    } else {
+        // Both print the shortest digits that round-trip; JavaScript switches to exponent
+        // notation outside 1e-6..1e21
+        let exponential = format!("{:e}", n);
+        let (mantissa, exponent) = exponential.split_once('e').unwrap_or((&exponential, "0"));
+        let exponent: i32 = exponent.parse().unwrap_or(0);
+        if (-6..21).contains(&exponent) {
+            format!("{}", n)
+        } else if exponent < 0 {
+            format!("{}e{}", mantissa, exponent)
+        } else {
+            format!("{}e+{}", mantissa, exponent)
+        }
+    }
+}
+
+fn number_to_radix_string(n: f64, radix: u32) -> Result<String, String> {
+    if radix == 10 {
+        return Ok(number_to_string(n));
+    }
+    if !(2..=36).contains(&radix) {
+        return Err("toString() radix must be between 2 and 36".to_string());
+    }
+    if n.fract() != 0.0 || !n.is_finite() || n.abs() >= 1e21 {
+        return Err("Only integers can be converted to another radix".to_string());
+    }
+    let mut magnitude = n.abs() as u128;
+    let mut digits = Vec::new();
+    loop {
+        digits.push(std::char::from_digit((magnitude % radix as u128) as u32, radix).unwrap());
+        magnitude /= radix as u128;
+        if magnitude == 0 {
+            break;
+        }
+    }
+    if n < 0.0 {
+        digits.push('-');
+    }
+    Ok(digits.iter().rev().collect())
+}
+
+fn parse_int(s: &str, radix: i32) -> f64 {
+    let s = s.trim_start();
+    let (negative, s) = match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+    let mut radix = radix as u32;
+    let mut digits = s;
+    if (radix == 0 || radix == 16) && (s.starts_with("0x") || s.starts_with("0X")) {
+        digits = &s[2..];
+        radix = 16;
+    }
+    if radix == 0 {
+        radix = 10;
+    }
+    if !(2..=36).contains(&radix) {
+        return f64::NAN;
+    }
+    let mut value = 0.0;
+    let mut any = false;
+    for c in digits.chars() {
+        let Some(digit) = c.to_digit(radix) else {
+            break;
+        };
+        value = value * radix as f64 + digit as f64;
+        any = true;
+    }
+    if !any {
+        return f64::NAN;
+    }
+    if negative {
+        -value
+    } else {
+        value
+    }
+}
+
+fn parse_float(s: &str) -> f64 {
+    let s = s.trim_start();
+    let mut end = 0;
+    let bytes = s.as_bytes();
+    if end < bytes.len() && (bytes[end] == b'-' || bytes[end] == b'+') {
+        end += 1;
+    }
+    let mut seen_dot = false;
+    let mut seen_exp = false;
+    while end < bytes.len() {
+        match bytes[end] {
+            b'0'..=b'9' => {}
+            b'.' if !seen_dot && !seen_exp => seen_dot = true,
+            b'e' | b'E' if !seen_exp => {
+                seen_exp = true;
+                if end + 1 < bytes.len() && (bytes[end + 1] == b'-' || bytes[end + 1] == b'+') {
+                    end += 1;
+                }
+            }
+            _ => break,
+        }
+        end += 1;
+    }
+    // Drop a dangling exponent or sign the loop took
+    let mut candidate = &s[..end];
+    while !candidate.is_empty() && candidate.parse::<f64>().is_err() {
+        candidate = &candidate[..candidate.len() - 1];
+    }
+    candidate.parse().unwrap_or(f64::NAN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(source: &str) -> Result<Value, String> {
+        Interpreter::new().run(source)
+    }
+
+    #[test]
+    fn refuses_huge_arrays() {
+        assert!(run("var a = []; a.length = 1e12").is_err());
+        assert!(run("var a = []; a[4294967295] = 1").is_err());
+        assert!(run("new Array(1e12)").is_err());
+        assert!(run("Array(-1)").is_err());
+        let ok = run("var a = new Array(3); a[5] = 1; a.length = 8; a.length").unwrap();
+        assert_eq!(to_string(&ok), "8");
+    }
+
+    #[test]
+    fn stops_runaway_recursion() {
+        assert!(run("function f(n) { return f(n + 1); } f(0)").is_err());
+        let ok = run("function f(n) { return n ? n + f(n - 1) : 0; } f(10)").unwrap();
+        assert_eq!(to_string(&ok), "55");
+    }
+
+    #[test]
+    fn refuses_deeply_nested_scripts() {
+        let depth = 100_000;
+        assert!(run(&format!("{}1{}", "(".repeat(depth), ")".repeat(depth))).is_err());
+        assert!(run(&format!("{}1", "!".repeat(depth))).is_err());
+        assert!(run(&format!("{}{}", "{".repeat(depth), "}".repeat(depth))).is_err());
+        assert!(run(&format!("1{}", "+1".repeat(depth))).is_err());
+        assert!(run(&format!("var a; a{}", "=a".repeat(depth))).is_err());
+        let ok = run(&format!("{}1{}", "(".repeat(20), ")".repeat(20))).unwrap();
+        assert_eq!(to_string(&ok), "1");
+    }
+}
@@ -0,0 +1,11 @@
+// The V8 fallback, for pages the interpreter can't handle. Runs on its own engine so one
+// page's globals only ever meet another Douyu page's.
+
+use crate::js_engine::JsEngine;
+use once_cell::sync::Lazy;
+
+static ENGINE: Lazy<JsEngine> = Lazy::new(|| JsEngine::spawn("douyu-sign", Vec::new(), 1));
+
+pub async fn run(scripts: Vec<String>) -> Result<String, String> {
+    ENGINE.eval(scripts).await
+}
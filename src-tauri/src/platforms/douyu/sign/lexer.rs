@@ -0,0 +1,135 @@
+// Tokens for the JavaScript subset the room page's signing code is written in
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Num(f64),
+    Str(String),
+    Ident(String),
+    Punct(&'static str),
+}
+
+// Longest first, so ">>>=" isn't read as ">>" then ">="
+const PUNCTUATORS: [&str; 48] = [
+    ">>>=", "===", "!==", ">>>", "<<=", ">>=", "==", "!=", "<=", ">=", "&&", "||", "++", "--",
+    "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "<<", ">>", "{", "}", "(", ")", "[", "]", ";",
+    ",", "<", ">", "+", "-", "*", "/", "%", "&", "|", "^", "!", "~", "?", ":", "=", ".",
+];
+
+pub fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).map_or(false, |next| next.is_ascii_digit()))
+        {
+            let (num, len) = read_number(&chars[i..])?;
+            tokens.push(Token::Num(num));
+            i += len;
+        } else if c == '"' || c == '\'' {
+            let (s, len) = read_string(&chars[i..])?;
+            tokens.push(Token::Str(s));
+            i += len;
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+            {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else {
+            let punct = PUNCTUATORS
+                .iter()
+                .find(|p| {
+                    p.chars()
+                        .enumerate()
+                        .all(|(j, pc)| chars.get(i + j) == Some(&pc))
+                })
+                .ok_or_else(|| format!("Unexpected character '{}'", c))?;
+            tokens.push(Token::Punct(punct));
+            i += punct.len();
+        }
+    }
+    Ok(tokens)
+}
+
+fn read_number(chars: &[char]) -> Result<(f64, usize), String> {
+    if chars[0] == '0' && matches!(chars.get(1), Some('x') | Some('X')) {
+        let digits: String = chars[2..]
+            .iter()
+            .take_while(|c| c.is_ascii_hexdigit())
+            .collect();
+        let value = u64::from_str_radix(&digits, 16)
+            .map_err(|_| format!("Bad hex literal 0x{}", digits))?;
+        return Ok((value as f64, 2 + digits.len()));
+    }
+    let mut len = 0;
+    while len < chars.len() && (chars[len].is_ascii_digit() || chars[len] == '.') {
+        len += 1;
+    }
+    if len < chars.len() && (chars[len] == 'e' || chars[len] == 'E') {
+        len += 1;
+        if len < chars.len() && (chars[len] == '+' || chars[len] == '-') {
+            len += 1;
+        }
+        while len < chars.len() && chars[len].is_ascii_digit() {
+            len += 1;
+        }
+    }
+    let text: String = chars[..len].iter().collect();
+    let value = text
+        .parse::<f64>()
+        .map_err(|_| format!("Bad number literal {}", text))?;
+    Ok((value, len))
+}
+
+fn read_string(chars: &[char]) -> Result<(String, usize), String> {
+    let quote = chars[0];
+    let mut out = String::new();
+    let mut i = 1;
+    loop {
+        let c = *chars.get(i).ok_or("Unterminated string literal")?;
+        i += 1;
+        if c == quote {
+            return Ok((out, i));
+        }
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let escaped = *chars.get(i).ok_or("Unterminated string literal")?;
+        i += 1;
+        match escaped {
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            'r' => out.push('\r'),
+            'b' => out.push('\u{8}'),
+            'f' => out.push('\u{c}'),
+            'v' => out.push('\u{b}'),
+            '0' => out.push('\0'),
+            'x' | 'u' => {
+                let len = if escaped == 'x' { 2 } else { 4 };
+                let hex: String = chars.get(i..i + len).ok_or("Bad escape")?.iter().collect();
+                let code = u32::from_str_radix(&hex, 16).map_err(|_| "Bad escape")?;
+                out.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                i += len;
+            }
+            '\n' => {} // Line continuation
+            other => out.push(other),
+        }
+    }
+}
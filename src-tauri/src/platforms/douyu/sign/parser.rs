@@ -0,0 +1,539 @@
+// Parses the tokens into statements. Anything outside the subset is an error, which sends
+// signing to the fallback rather than guessing.
+
+use std::rc::Rc;
+
+use super::lexer::Token;
+
+#[derive(Debug)]
+pub enum Stmt {
+    Var(Vec<(String, Option<Expr>)>),
+    Function(Rc<Function>),
+    Expr(Expr),
+    Block(Vec<Stmt>),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    For(Option<Box<Stmt>>, Option<Expr>, Option<Expr>, Box<Stmt>),
+    While(Expr, Box<Stmt>),
+    DoWhile(Box<Stmt>, Expr),
+    Switch(Expr, Vec<(Option<Expr>, Vec<Stmt>)>),
+    Return(Option<Expr>),
+    Break,
+    Continue,
+    Empty,
+}
+
+#[derive(Debug)]
+pub struct Function {
+    pub name: Option<String>,
+    pub params: Vec<String>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug)]
+pub enum Expr {
+    Num(f64),
+    Str(Rc<str>),
+    Ident(String),
+    Array(Vec<Expr>),
+    Function(Rc<Function>),
+    Member(Box<Expr>, Box<Expr>), // object[property]; a.b is parsed as a["b"]
+    Call(Box<Expr>, Vec<Expr>),
+    New(Box<Expr>, Vec<Expr>),
+    Unary(&'static str, Box<Expr>),
+    Update(&'static str, bool, Box<Expr>), // Operator, prefix, target
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Logical(&'static str, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    Assign(&'static str, Box<Expr>, Box<Expr>),
+    Sequence(Vec<Expr>),
+}
+
+// Binary operators by precedence, loosest first
+const BINARY_LEVELS: [&[&str]; 8] = [
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!=", "===", "!=="],
+    &["<", ">", "<=", ">="],
+    &["<<", ">>", ">>>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+// How deep statements and expressions may nest; the signing code stays far below this,
+// and deeper input would overflow the stack here or in the interpreter
+const MAX_NESTING: usize = 100;
+
+const ASSIGN_OPS: [&str; 12] = [
+    "=", "+=", "-=", "*=", "/=", "%=", "<<=", ">>=", ">>>=", "&=", "|=", "^=",
+];
+
+pub fn parse(tokens: &[Token]) -> Result<Vec<Stmt>, String> {
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        depth: 0,
+    };
+    let mut program = Vec::new();
+    while parser.peek().is_some() {
+        program.push(parser.statement()?);
+    }
+    Ok(program)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or("Unexpected end of script")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    // Called once per level of nesting; the caller restores `depth` when it's done
+    fn enter(&mut self) -> Result<(), String> {
+        if self.depth == MAX_NESTING {
+            return Err("Script nested too deeply".to_string());
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn is_punct(&self, punct: &str) -> bool {
+        matches!(self.peek(), Some(Token::Punct(p)) if *p == punct)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(name)) if name == keyword)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if self.is_punct(punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), String> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(format!("Expected '{}', found {:?}", punct, self.peek()))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.next()? {
+            Token::Ident(name) => Ok(name),
+            other => Err(format!("Expected an identifier, found {:?}", other)),
+        }
+    }
+
+    // Semicolons can be left out before } and at the end of the script
+    fn end_statement(&mut self) -> Result<(), String> {
+        if self.eat(";") || self.is_punct("}") || self.peek().is_none() {
+            Ok(())
+        } else {
+            Err(format!("Expected ';', found {:?}", self.peek()))
+        }
+    }
+
+    fn statement(&mut self) -> Result<Stmt, String> {
+        self.enter()?;
+        let stmt = self.statement_inner();
+        self.depth -= 1;
+        stmt
+    }
+
+    fn statement_inner(&mut self) -> Result<Stmt, String> {
+        if self.eat(";") {
+            return Ok(Stmt::Empty);
+        }
+        if self.eat("{") {
+            return Ok(Stmt::Block(self.block_body()?));
+        }
+        let keyword = match self.peek() {
+            Some(Token::Ident(name)) => name.clone(),
+            _ => String::new(),
+        };
+        match keyword.as_str() {
+            "var" | "let" | "const" => {
+                self.pos += 1;
+                let decls = self.var_declarations()?;
+                self.end_statement()?;
+                Ok(Stmt::Var(decls))
+            }
+            "function" => {
+                self.pos += 1;
+                let function = self.function_rest()?;
+                if function.name.is_none() {
+                    return Err("Function declaration without a name".to_string());
+                }
+                Ok(Stmt::Function(function))
+            }
+            "if" => {
+                self.pos += 1;
+                self.expect("(")?;
+                let test = self.expression()?;
+                self.expect(")")?;
+                let then = Box::new(self.statement()?);
+                let otherwise = if self.is_keyword("else") {
+                    self.pos += 1;
+                    Some(Box::new(self.statement()?))
+                } else {
+                    None
+                };
+                Ok(Stmt::If(test, then, otherwise))
+            }
+            "for" => {
+                self.pos += 1;
+                self.expect("(")?;
+                let init = if self.is_punct(";") {
+                    None
+                } else if self.is_keyword("var") || self.is_keyword("let") {
+                    self.pos += 1;
+                    Some(Box::new(Stmt::Var(self.var_declarations()?)))
+                } else {
+                    Some(Box::new(Stmt::Expr(self.expression()?)))
+                };
+                self.expect(";")?;
+                let test = if self.is_punct(";") {
+                    None
+                } else {
+                    Some(self.expression()?)
+                };
+                self.expect(";")?;
+                let update = if self.is_punct(")") {
+                    None
+                } else {
+                    Some(self.expression()?)
+                };
+                self.expect(")")?;
+                Ok(Stmt::For(init, test, update, Box::new(self.statement()?)))
+            }
+            "while" => {
+                self.pos += 1;
+                self.expect("(")?;
+                let test = self.expression()?;
+                self.expect(")")?;
+                Ok(Stmt::While(test, Box::new(self.statement()?)))
+            }
+            "do" => {
+                self.pos += 1;
+                let body = Box::new(self.statement()?);
+                if !self.is_keyword("while") {
+                    return Err("Expected 'while' after do body".to_string());
+                }
+                self.pos += 1;
+                self.expect("(")?;
+                let test = self.expression()?;
+                self.expect(")")?;
+                self.eat(";");
+                Ok(Stmt::DoWhile(body, test))
+            }
+            "switch" => {
+                self.pos += 1;
+                self.expect("(")?;
+                let discriminant = self.expression()?;
+                self.expect(")")?;
+                self.expect("{")?;
+                let mut cases = Vec::new();
+                while !self.eat("}") {
+                    let test = if self.is_keyword("case") {
+                        self.pos += 1;
+                        Some(self.expression()?)
+                    } else if self.is_keyword("default") {
+                        self.pos += 1;
+                        None
+                    } else {
+                        return Err(format!("Expected case, found {:?}", self.peek()));
+                    };
+                    self.expect(":")?;
+                    let mut body = Vec::new();
+                    while !self.is_keyword("case")
+                        && !self.is_keyword("default")
+                        && !self.is_punct("}")
+                    {
+                        body.push(self.statement()?);
+                    }
+                    cases.push((test, body));
+                }
+                Ok(Stmt::Switch(discriminant, cases))
+            }
+            "return" => {
+                self.pos += 1;
+                let value = if self.is_punct(";") || self.is_punct("}") || self.peek().is_none() {
+                    None
+                } else {
+                    Some(self.expression()?)
+                };
+                self.end_statement()?;
+                Ok(Stmt::Return(value))
+            }
+            "break" => {
+                self.pos += 1;
+                self.end_statement()?;
+                Ok(Stmt::Break)
+            }
+            "continue" => {
+                self.pos += 1;
+                self.end_statement()?;
+                Ok(Stmt::Continue)
+            }
+            _ => {
+                let expr = self.expression()?;
+                self.end_statement()?;
+                Ok(Stmt::Expr(expr))
+            }
+        }
+    }
+
+    fn block_body(&mut self) -> Result<Vec<Stmt>, String> {
+        let mut body = Vec::new();
+        while !self.eat("}") {
+            body.push(self.statement()?);
+        }
+        Ok(body)
+    }
+
+    fn var_declarations(&mut self) -> Result<Vec<(String, Option<Expr>)>, String> {
+        let mut decls = Vec::new();
+        loop {
+            let name = self.ident()?;
+            let init = if self.eat("=") {
+                Some(self.assignment()?)
+            } else {
+                None
+            };
+            decls.push((name, init));
+            if !self.eat(",") {
+                return Ok(decls);
+            }
+        }
+    }
+
+    // After the `function` keyword
+    fn function_rest(&mut self) -> Result<Rc<Function>, String> {
+        let name = match self.peek() {
+            Some(Token::Ident(_)) => Some(self.ident()?),
+            _ => None,
+        };
+        self.expect("(")?;
+        let mut params = Vec::new();
+        while !self.eat(")") {
+            params.push(self.ident()?);
+            if !self.is_punct(")") {
+                self.expect(",")?;
+            }
+        }
+        self.expect("{")?;
+        let body = self.block_body()?;
+        Ok(Rc::new(Function { name, params, body }))
+    }
+
+    fn expression(&mut self) -> Result<Expr, String> {
+        let first = self.assignment()?;
+        if !self.is_punct(",") {
+            return Ok(first);
+        }
+        let mut exprs = vec![first];
+        while self.eat(",") {
+            exprs.push(self.assignment()?);
+        }
+        Ok(Expr::Sequence(exprs))
+    }
+
+    fn assignment(&mut self) -> Result<Expr, String> {
+        self.enter()?;
+        let expr = self.assignment_inner();
+        self.depth -= 1;
+        expr
+    }
+
+    fn assignment_inner(&mut self) -> Result<Expr, String> {
+        let target = self.conditional()?;
+        if let Some(Token::Punct(op)) = self.peek() {
+            if let Some(op) = ASSIGN_OPS.iter().find(|assign| *assign == op) {
+                if !matches!(target, Expr::Ident(_) | Expr::Member(..)) {
+                    return Err("Invalid assignment target".to_string());
+                }
+                self.pos += 1;
+                let value = self.assignment()?;
+                return Ok(Expr::Assign(op, Box::new(target), Box::new(value)));
+            }
+        }
+        Ok(target)
+    }
+
+    fn conditional(&mut self) -> Result<Expr, String> {
+        let test = self.logical_or()?;
+        if !self.eat("?") {
+            return Ok(test);
+        }
+        let then = self.assignment()?;
+        self.expect(":")?;
+        let otherwise = self.assignment()?;
+        Ok(Expr::Conditional(
+            Box::new(test),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    // Chains like `a || b || c` nest to the left, one level per operator
+    fn logical_or(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut left = self.logical_and()?;
+        while self.eat("||") {
+            self.enter()?;
+            left = Expr::Logical("||", Box::new(left), Box::new(self.logical_and()?));
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn logical_and(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut left = self.binary(0)?;
+        while self.eat("&&") {
+            self.enter()?;
+            left = Expr::Logical("&&", Box::new(left), Box::new(self.binary(0)?));
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        if level == BINARY_LEVELS.len() {
+            return self.unary();
+        }
+        let depth = self.depth;
+        let mut left = self.binary(level + 1)?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Punct(p)) => BINARY_LEVELS[level].iter().find(|op| *op == p),
+                _ => None,
+            };
+            let Some(op) = op else {
+                self.depth = depth;
+                return Ok(left);
+            };
+            self.pos += 1;
+            self.enter()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.binary(level + 1)?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        self.enter()?;
+        let expr = self.unary_inner();
+        self.depth -= 1;
+        expr
+    }
+
+    fn unary_inner(&mut self) -> Result<Expr, String> {
+        for op in ["!", "-", "+", "~"] {
+            if self.eat(op) {
+                return Ok(Expr::Unary(op, Box::new(self.unary()?)));
+            }
+        }
+        for op in ["typeof", "void"] {
+            if self.is_keyword(op) {
+                self.pos += 1;
+                return Ok(Expr::Unary(op, Box::new(self.unary()?)));
+            }
+        }
+        for op in ["++", "--"] {
+            if self.eat(op) {
+                return Ok(Expr::Update(op, true, Box::new(self.unary()?)));
+            }
+        }
+        let expr = self.call_member()?;
+        for op in ["++", "--"] {
+            if self.eat(op) {
+                return Ok(Expr::Update(op, false, Box::new(expr)));
+            }
+        }
+        Ok(expr)
+    }
+
+    fn call_member(&mut self) -> Result<Expr, String> {
+        let mut expr = if self.is_keyword("new") {
+            self.pos += 1;
+            let constructor = self.primary()?;
+            let args = if self.eat("(") {
+                self.arguments()?
+            } else {
+                Vec::new()
+            };
+            Expr::New(Box::new(constructor), args)
+        } else {
+            self.primary()?
+        };
+        loop {
+            if self.eat(".") {
+                let property = self.ident()?;
+                expr = Expr::Member(Box::new(expr), Box::new(Expr::Str(property.into())));
+            } else if self.eat("[") {
+                let property = self.expression()?;
+                self.expect("]")?;
+                expr = Expr::Member(Box::new(expr), Box::new(property));
+            } else if self.eat("(") {
+                expr = Expr::Call(Box::new(expr), self.arguments()?);
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    // After the opening parenthesis
+    fn arguments(&mut self) -> Result<Vec<Expr>, String> {
+        let mut args = Vec::new();
+        while !self.eat(")") {
+            args.push(self.assignment()?);
+            if !self.is_punct(")") {
+                self.expect(",")?;
+            }
+        }
+        Ok(args)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next()? {
+            Token::Num(n) => Ok(Expr::Num(n)),
+            Token::Str(s) => Ok(Expr::Str(s.into())),
+            Token::Ident(name) if name == "function" => Ok(Expr::Function(self.function_rest()?)),
+            Token::Ident(name) => Ok(Expr::Ident(name)),
+            Token::Punct("(") => {
+                let expr = self.expression()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Punct("[") => {
+                let mut items = Vec::new();
+                while !self.eat("]") {
+                    items.push(self.assignment()?);
+                    if !self.is_punct("]") {
+                        self.expect(",")?;
+                    }
+                }
+                Ok(Expr::Array(items))
+            }
+            other => Err(format!("Unexpected token {:?}", other)),
+        }
+    }
+}
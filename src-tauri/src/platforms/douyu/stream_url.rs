@@ -1,10 +1,13 @@
-use deno_core::{JsRuntime, RuntimeOptions}; // 替换 rquickjs 导入
 use isahc::config::{Configurable, RedirectPolicy};
 use isahc::{http, prelude::*, HttpClient, Request};
-use md5::Digest;
-use regex::Regex;
-use serde::Deserialize;
-use std::time::{SystemTime, UNIX_EPOCH}; // Added for JSON deserialization
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const DEFAULT_CDN: &str = "ws-h5";
+const DEFAULT_RATE: i32 = 0; // 原画
+
+// How long a CDN gets to start answering before the next one is tried
+const CDN_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Deserialize, Debug)]
 struct RoomInfoData {
@@ -20,6 +23,78 @@ struct RoomInfoResponse {
     data: Option<RoomInfoData>,
 }
 
+// One entry of getH5Play's `multirates`; rate 0 is the original quality
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DouyuStreamRate {
+    pub name: String,
+    pub rate: i32,
+    #[serde(default)]
+    pub bit: u32, // kbps
+}
+
+// One entry of getH5Play's `cdnsWithName`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DouyuStreamCdn {
+    pub name: String,
+    pub cdn: String,
+    #[serde(rename = "isH265", default)]
+    pub is_h265: bool,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct H5PlayData {
+    rtmp_url: String,
+    rtmp_live: String,
+    rtmp_cdn: String,
+    rate: i32,
+    multirates: Vec<DouyuStreamRate>,
+    #[serde(rename = "cdnsWithName")]
+    cdns_with_name: Vec<DouyuStreamCdn>,
+}
+
+impl H5PlayData {
+    fn stream_url(&self) -> Result<String, String> {
+        if self.rtmp_url.is_empty() || self.rtmp_live.is_empty() {
+            return Err("No rtmp_url / rtmp_live in getH5Play response".to_string());
+        }
+        Ok(format!("{}/{}", self.rtmp_url, self.rtmp_live))
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DouyuStreamQualities {
+    pub rates: Vec<DouyuStreamRate>,
+    pub cdns: Vec<DouyuStreamCdn>,
+    pub current_rate: i32,
+    pub current_cdn: String,
+}
+
+// getH5Play can refuse the preferred CDN outright; the default line, or no cdn at all
+// (Douyu picks), still gets an answer listing the room's CDNs
+fn requested_cdns(preferred: &str) -> Vec<&str> {
+    let mut requested = vec![preferred];
+    for fallback in [DEFAULT_CDN, ""] {
+        if !requested.contains(&fallback) {
+            requested.push(fallback);
+        }
+    }
+    requested
+}
+
+// CDNs to probe: the one Douyu answered with (its own pick when the requested one isn't
+// available), then the rest it offers for the room
+fn probe_order(answered: &str, offered: &[DouyuStreamCdn]) -> Vec<String> {
+    let mut candidates = vec![answered.to_string()];
+    for other in offered {
+        if !candidates.contains(&other.cdn) {
+            candidates.push(other.cdn.clone());
+        }
+    }
+    candidates
+}
+
 struct DouYu {
     did: String,
     rid: String,
@@ -40,38 +115,38 @@ impl DouYu {
         })
     }
 
-    fn md5(data: &str) -> String {
-        let mut hasher = md5::Md5::new();
-        hasher.update(data.as_bytes());
-        format!("{:x}", hasher.finalize())
-    }
-
-    async fn get_pc_js(&self, cdn: &str, rate: i32) -> Result<String, Box<dyn std::error::Error>> {
+    async fn ensure_live(&self) -> Result<(), Box<dyn std::error::Error>> {
         match self.check_room_status().await {
             Ok(true) => {
                 println!(
                     "[Douyu Stream URL] Room {} is live. Proceeding to fetch stream URL.",
                     self.rid
                 );
+                Ok(())
             }
             Ok(false) => {
                 println!(
                     "[Douyu Stream URL] Room {} is not live. Aborting stream fetch.",
                     self.rid
                 );
-                return Err(Box::new(std::io::Error::new(
+                Err(Box::new(std::io::Error::new(
                     std::io::ErrorKind::Other,
                     "主播未开播",
-                )));
+                )))
             }
             Err(e) => {
                 println!("[Douyu Stream URL] Error checking room status for room {}: {}. Proceeding with caution or returning error.", self.rid, e);
-                return Err(Box::new(std::io::Error::new(
+                Err(Box::new(std::io::Error::new(
                     std::io::ErrorKind::Other,
                     format!("检查房间状态失败: {}", e),
-                )));
+                )))
             }
         }
+    }
+
+    // Form body for getH5Play, minus cdn and rate; one signature serves several requests
+    async fn signed_params(&self) -> Result<String, Box<dyn std::error::Error>> {
+        self.ensure_live().await?;
         // 获取PC网页内容
         let request = Request::builder()
             .method(http::Method::GET)
@@ -81,74 +156,17 @@ impl DouYu {
             .header("Connection", "keep-alive")
             .body(())?;
 
-        let text = self.client.send(request)?.text()?;
-        // 提取JS函数
-        let re = Regex::new(r"(vdwdae325w_64we[\s\S]*function ub98484234[\s\S]*?)function")?;
-        let result = re
-            .captures(&text)
-            .ok_or("Cannot find js function")?
-            .get(1)
-            .ok_or("No capture group")?
-            .as_str();
-
-        let re_eval = Regex::new(r"eval.*?;\}")?;
-        let func_ub9 = re_eval.replace_all(result, "strc;}");
-
-        let mut runtime = JsRuntime::new(RuntimeOptions::default());
-
-        // 将字符串转换为静态字符串
-        let func_ub9_static = String::from(func_ub9);
-        runtime.execute_script("[douyu]", deno_core::FastString::from(func_ub9_static))?;
-        let js_result = runtime.execute_script(
-            "[douyu]",
-            deno_core::FastString::from(String::from("ub98484234()")),
-        )?;
-
-        // 获取 JavaScript 执行结果
-        let res = {
-            let scope = &mut runtime.handle_scope();
-            let result = js_result.open(scope);
-            result.to_rust_string_lossy(scope)
-        };
-
-        // 提取v参数
-        let re = Regex::new(r"v=(\d+)")?;
-        let v = re
-            .captures(&res)
-            .ok_or("v parameter not found")?
-            .get(1)
-            .ok_or("No capture group")?
-            .as_str();
-
-        let t10 = SystemTime::now()
-            .duration_since(UNIX_EPOCH)?
-            .as_secs()
-            .to_string();
-
-        let rb = Self::md5(&format!("{}{}{}{}", self.rid, self.did, &t10, v));
-
-        // 构造签名函数
-        let func_sign = res.replace("return rt;})", "return rt;}");
-        let func_sign = func_sign.replace("(function (", "function sign(");
-        let func_sign = func_sign.replace("CryptoJS.MD5(cb).toString()", &format!("\"{}\"", rb));
-
-        let func_sign_static = String::from(func_sign);
-        runtime.execute_script("[douyu]", deno_core::FastString::from(func_sign_static))?;
-
-        let sign_call = format!("sign(\"{}\", \"{}\", \"{}\");", self.rid, self.did, t10);
-
-        let sign_call_static = String::from(sign_call);
-        let js_params =
-            runtime.execute_script("[douyu]", deno_core::FastString::from(sign_call_static))?;
-
-        // 获取签名结果
-        let mut params = {
-            let scope = &mut runtime.handle_scope();
-            let result = js_params.open(scope);
-            result.to_rust_string_lossy(scope)
-        };
+        let text = self.client.send_async(request).await?.text().await?;
+        Ok(super::sign::sign_params(&text, &self.rid, &self.did).await?)
+    }
 
-        params.push_str(&format!("&cdn={}&rate={}", cdn, rate));
+    async fn get_h5_play(
+        &self,
+        params: &str,
+        cdn: &str,
+        rate: i32,
+    ) -> Result<H5PlayData, Box<dyn std::error::Error>> {
+        let body = format!("{}&cdn={}&rate={}", params, cdn, rate);
 
         // 获取真实URL
         let url = format!("https://www.douyu.com/lapi/live/getH5Play/{}", self.rid);
@@ -158,25 +176,135 @@ impl DouYu {
             .header("Content-Type", "application/x-www-form-urlencoded")
             .header("Origin", "https://www.douyu.com")
             .header("Referer", format!("https://www.douyu.com/{}", self.rid))
-            .body(params)?;
+            .body(body)?;
 
-        let mut response = self.client.send(request)?;
+        let mut response = self.client.send_async(request).await?;
 
-        let json: serde_json::Value = response.json()?;
+        let json: serde_json::Value = response.json().await?;
+        if json["error"].as_i64() != Some(0) {
+            return Err(format!(
+                "getH5Play error {}: {}",
+                json["error"],
+                json["msg"].as_str().unwrap_or_default()
+            )
+            .into());
+        }
+        let data = json.get("data").ok_or("No data field in response")?;
+        Ok(serde_json::from_value(data.clone())?)
+    }
 
-        let data = json["data"]
-            .as_object()
-            .ok_or("No data field in response")?;
-        let rtmp_url = data["rtmp_url"].as_str().ok_or("No rtmp_url field")?;
-        let rtmp_live = data["rtmp_live"].as_str().ok_or("No rtmp_live field")?;
+    // Whether the CDN actually serves the stream; blocked or broken ones fail here
+    async fn probe(&self, url: &str) -> Result<(), String> {
+        let request = Request::builder()
+            .method(http::Method::GET)
+            .uri(url)
+            .timeout(CDN_PROBE_TIMEOUT)
+            .body(())
+            .map_err(|e| e.to_string())?;
+        let response = self
+            .client
+            .send_async(request)
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("status {}", response.status()))
+        }
+    }
 
-        let final_url = format!("{}/{}", rtmp_url, rtmp_live);
+    // The first getH5Play answer along requested_cdns(cdn), noting each refusal in `failures`
+    async fn first_h5_play(
+        &self,
+        params: &str,
+        cdn: &str,
+        rate: i32,
+        failures: &mut Vec<String>,
+    ) -> Option<H5PlayData> {
+        for requested_cdn in requested_cdns(cdn) {
+            match self.get_h5_play(params, requested_cdn, rate).await {
+                Ok(data) => return Some(data),
+                Err(e) => {
+                    let name = if requested_cdn.is_empty() {
+                        "auto"
+                    } else {
+                        requested_cdn
+                    };
+                    eprintln!(
+                        "[Douyu Stream URL] getH5Play with CDN {} failed for room {}: {}",
+                        name, self.rid, e
+                    );
+                    failures.push(format!("{}: {}", name, e));
+                }
+            }
+        }
+        None
+    }
 
-        Ok(final_url)
+    // Tries `cdn` first, then the other CDNs Douyu offers for the room, returning the first
+    // URL that actually serves the stream
+    pub async fn get_real_url(
+        &self,
+        rate: i32,
+        cdn: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let params = self.signed_params().await?;
+        let mut failures = Vec::new();
+        let Some(first) = self.first_h5_play(&params, cdn, rate, &mut failures).await else {
+            return Err(format!("所有线路均不可用 ({})", failures.join("; ")).into());
+        };
+        let candidates = probe_order(&first.rtmp_cdn, &first.cdns_with_name);
+
+        for (i, candidate) in candidates.iter().enumerate() {
+            let url = if i == 0 {
+                first.stream_url()
+            } else {
+                self.get_h5_play(&params, candidate, rate)
+                    .await
+                    .map_err(|e| e.to_string())
+                    .and_then(|data| data.stream_url())
+            };
+            let result = match url {
+                Ok(url) => self.probe(&url).await.map(|_| url),
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(url) => {
+                    if i > 0 {
+                        println!(
+                            "[Douyu Stream URL] Room {} fell back to CDN {}.",
+                            self.rid, candidate
+                        );
+                    }
+                    return Ok(url);
+                }
+                Err(e) => {
+                    eprintln!(
+                        "[Douyu Stream URL] CDN {} failed for room {}: {}",
+                        candidate, self.rid, e
+                    );
+                    failures.push(format!("{}: {}", candidate, e));
+                }
+            }
+        }
+        Err(format!("所有线路均不可用 ({})", failures.join("; ")).into())
     }
 
-    pub async fn get_real_url(&self) -> Result<String, Box<dyn std::error::Error>> {
-        self.get_pc_js("ws-h5", 0).await
+    async fn get_qualities(&self) -> Result<DouyuStreamQualities, Box<dyn std::error::Error>> {
+        let params = self.signed_params().await?;
+        let mut failures = Vec::new();
+        let Some(data) = self
+            .first_h5_play(&params, DEFAULT_CDN, DEFAULT_RATE, &mut failures)
+            .await
+        else {
+            return Err(format!("所有线路均不可用 ({})", failures.join("; ")).into());
+        };
+        Ok(DouyuStreamQualities {
+            rates: data.multirates,
+            cdns: data.cdns_with_name,
+            current_rate: data.rate,
+            current_cdn: data.rtmp_cdn,
+        })
     }
 
     async fn check_room_status(&self) -> Result<bool, Box<dyn std::error::Error>> {
@@ -226,8 +354,59 @@ impl DouYu {
     }
 }
 
-pub async fn get_stream_url(room_id: &str) -> Result<String, Box<dyn std::error::Error>> {
+// `rate` is one of the rates from get_stream_qualities and `cdn` the preferred line; both
+// fall back to Douyu's defaults
+pub async fn get_stream_url_with_quality(
+    room_id: &str,
+    rate: Option<i32>,
+    cdn: Option<&str>,
+) -> Result<String, Box<dyn std::error::Error>> {
     let douyu = DouYu::new(room_id).await?;
-    let url = douyu.get_real_url().await?;
+    let url = douyu
+        .get_real_url(
+            rate.unwrap_or(DEFAULT_RATE),
+            cdn.filter(|cdn| !cdn.is_empty()).unwrap_or(DEFAULT_CDN),
+        )
+        .await?;
     Ok(url) // 直接返回实际的流地址
 }
+
+// The rates and CDN lines a live room offers
+#[tauri::command]
+pub async fn get_stream_qualities(room_id: String) -> Result<DouyuStreamQualities, String> {
+    let douyu = DouYu::new(&room_id).await.map_err(|e| e.to_string())?;
+    douyu.get_qualities().await.map_err(|e| {
+        eprintln!(
+            "[Douyu Stream URL] Failed to get qualities for room {}: {}",
+            room_id, e
+        );
+        format!("Failed to get stream qualities: {}", e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cdn(cdn: &str) -> DouyuStreamCdn {
+        DouyuStreamCdn {
+            name: cdn.to_uppercase(),
+            cdn: cdn.to_string(),
+            is_h265: false,
+        }
+    }
+
+    #[test]
+    fn falls_back_to_the_default_cdn_then_douyus_pick() {
+        assert_eq!(requested_cdns("hs-h5"), ["hs-h5", DEFAULT_CDN, ""]);
+        assert_eq!(requested_cdns(DEFAULT_CDN), [DEFAULT_CDN, ""]);
+        assert_eq!(requested_cdns(""), ["", DEFAULT_CDN]);
+    }
+
+    #[test]
+    fn probes_the_answered_cdn_then_the_rest_once_each() {
+        let offered = [cdn("ws-h5"), cdn("tct-h5"), cdn("hs-h5"), cdn("tct-h5")];
+        assert_eq!(probe_order("hs-h5", &offered), ["hs-h5", "ws-h5", "tct-h5"]);
+        assert_eq!(probe_order("ali-h5", &[]), ["ali-h5"]);
+    }
+}
@@ -0,0 +1,558 @@
+// Douyu's danmaku protocol: STT ("serialized text") messages in little-endian framed packets.
+//
+// Packet: u32 length, u32 length (again), u16 message type, u8 encryption, u8 reserved,
+// then the STT body terminated by a NUL byte. Both lengths count everything after the
+// first length field. A WebSocket frame may hold several packets, or only part of one.
+//
+// STT: `key@=value/key@=value/`, with `@` escaped as `@A` and `/` as `@S`. Values can
+// themselves be escaped STT maps or `item/item/` lists, nested to any depth. Nothing marks a
+// value as nested, and chat text like `a@=b/` escapes to exactly what a nested map would.
+// `deserialize` guesses from the shape of each value; `from_str` into a serde struct decodes
+// each field as whatever the struct declares, so a `String` field always gets the text.
+
+use serde::de::value::{Error as DeError, StringDeserializer};
+use serde::de::{self, DeserializeOwned, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use serde_json::Value;
+use std::str::FromStr;
+
+const HEADER_LEN: usize = 12;
+// Bytes counted by the length field that aren't body: second length, type, encryption, reserved
+const LENGTH_OVERHEAD: usize = 8;
+const MSG_TYPE_CLIENT: u16 = 689;
+// Douyu closes the connection well before this; anything bigger is a desynced stream
+const MAX_PACKET_LEN: usize = 1 << 20;
+
+pub fn escape(raw: &str) -> String {
+    raw.replace('@', "@A").replace('/', "@S")
+}
+
+// One level only; a lone or unknown `@x` is kept as is
+pub fn unescape(raw: &str) -> String {
+    let mut result = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '@' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('A') => result.push('@'),
+            Some('S') => result.push('/'),
+            Some(other) => {
+                result.push('@');
+                result.push(other);
+            }
+            None => result.push('@'),
+        }
+    }
+    result
+}
+
+// Builds a flat STT message; field order is kept since Douyu expects `type` first
+pub fn encode_fields(fields: &[(&str, &str)]) -> String {
+    fields
+        .iter()
+        .map(|(key, value)| format!("{}@={}/", escape(key), escape(value)))
+        .collect()
+}
+
+// Wraps an STT body in a client packet
+pub fn encode_packet(body: &str) -> Vec<u8> {
+    let body_bytes = body.as_bytes();
+    let packet_len = (body_bytes.len() + 1 + LENGTH_OVERHEAD) as u32;
+
+    let mut packet = Vec::with_capacity(HEADER_LEN + body_bytes.len() + 1);
+    packet.extend_from_slice(&packet_len.to_le_bytes());
+    packet.extend_from_slice(&packet_len.to_le_bytes());
+    packet.extend_from_slice(&MSG_TYPE_CLIENT.to_le_bytes());
+    packet.push(0); // Encryption
+    packet.push(0); // Reserved
+    packet.extend_from_slice(body_bytes);
+    packet.push(0);
+    packet
+}
+
+// Reassembles packets from WebSocket frames. Keep one per connection: bytes of a packet
+// split across frames are buffered until the rest arrives.
+#[derive(Default)]
+pub struct PacketDecoder {
+    buffer: Vec<u8>,
+}
+
+impl PacketDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Appends a frame and returns the STT bodies of every packet completed by it. A bad
+    // header drops everything buffered from it on, and is reported after the bodies
+    // decoded before it.
+    pub fn push(&mut self, frame: &[u8]) -> (Vec<String>, Option<String>) {
+        self.buffer.extend_from_slice(frame);
+
+        let mut bodies = Vec::new();
+        let mut offset = 0;
+        while self.buffer.len() - offset >= HEADER_LEN {
+            let header = &self.buffer[offset..offset + HEADER_LEN];
+            let packet_len =
+                u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+            let packet_len_again =
+                u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+            if packet_len != packet_len_again
+                || !(LENGTH_OVERHEAD..=MAX_PACKET_LEN).contains(&packet_len)
+            {
+                // Nothing after a bad header can be trusted
+                self.buffer.clear();
+                return (
+                    bodies,
+                    Some(format!(
+                        "Invalid Douyu packet header (lengths {} / {})",
+                        packet_len, packet_len_again
+                    )),
+                );
+            }
+
+            let total_len = packet_len + 4;
+            if self.buffer.len() - offset < total_len {
+                break; // Rest of the packet is in a later frame
+            }
+
+            let body = &self.buffer[offset + HEADER_LEN..offset + total_len];
+            let body = body.strip_suffix(&[0]).unwrap_or(body);
+            bodies.push(String::from_utf8_lossy(body).into_owned());
+            offset += total_len;
+        }
+
+        self.buffer.drain(..offset);
+        (bodies, None)
+    }
+}
+
+// `/` only appears escaped inside an item, so splitting on it separates the items of this level
+fn items(raw: &str) -> impl Iterator<Item = &str> {
+    raw.split('/').filter(|item| !item.is_empty())
+}
+
+// One map level as unescaped (key, value) pairs; nested values come out escaped one level less
+fn parse_map(raw: &str) -> Vec<(String, String)> {
+    items(raw)
+        .map(|item| {
+            // An escaped key has no `@=`, so the first one ends it
+            let (key, value) = item.split_once("@=").unwrap_or((item, ""));
+            (unescape(key), unescape(value))
+        })
+        .collect()
+}
+
+fn parse_list(raw: &str) -> Vec<String> {
+    items(raw).map(unescape).collect()
+}
+
+// Decodes a message body, guessing which values are nested: one ending in `/` is a list, or a
+// map if every item has a key. Anything else stays a string, numbers included. Text that may
+// look nested, such as chat, should be read with `from_str` instead.
+#[allow(dead_code)] // The listener reads typed fields; this is for inspecting messages
+pub fn deserialize(raw: &str) -> Value {
+    Value::deserialize(SttDeserializer::new(raw)).unwrap_or_default()
+}
+
+// Decodes a message body into `T`. Fields are decoded as declared: `String` keeps the text
+// as sent, structs and maps parse a nested map, sequences a nested list, and numbers and bools
+// are parsed from their text. Empty values count as missing for `Option` fields.
+pub fn from_str<T: DeserializeOwned>(raw: &str) -> Result<T, String> {
+    T::deserialize(SttDeserializer::new(raw)).map_err(|e| format!("Invalid STT message: {}", e))
+}
+
+// One STT value, unescaped down to its own level
+struct SttDeserializer {
+    raw: String,
+}
+
+impl SttDeserializer {
+    fn new(raw: &str) -> Self {
+        Self {
+            raw: raw.to_string(),
+        }
+    }
+
+    fn parse<T: FromStr>(&self) -> Result<T, DeError>
+    where
+        T::Err: std::fmt::Display,
+    {
+        self.raw
+            .parse()
+            .map_err(|e| de::Error::custom(format!("{:?}: {}", self.raw, e)))
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for SttDeserializer {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        if !self.raw.ends_with('/') {
+            visitor.visit_string(self.raw)
+        } else if items(&self.raw).all(|item| item.contains("@=")) {
+            self.deserialize_map(visitor)
+        } else {
+            self.deserialize_seq(visitor)
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self.raw.as_str() {
+            "1" | "true" => visitor.visit_bool(true),
+            "0" | "false" => visitor.visit_bool(false),
+            other => Err(de::Error::custom(format!("{:?} is not a bool", other))),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_string(self.raw)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_string(self.raw)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_byte_buf(self.raw.into_bytes())
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_byte_buf(self.raw.into_bytes())
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        if self.raw.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_seq(Items(parse_list(&self.raw).into_iter()))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_map(Entries {
+            entries: parse_map(&self.raw).into_iter(),
+            value: None,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.deserialize_map(visitor)
+    }
+
+    // Unit variants only, named by the text
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        let text: StringDeserializer<DeError> = self.raw.into_deserializer();
+        visitor.visit_enum(text)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_string(self.raw)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_unit()
+    }
+}
+
+struct Items(std::vec::IntoIter<String>);
+
+impl<'de> SeqAccess<'de> for Items {
+    type Error = DeError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, DeError> {
+        self.0
+            .next()
+            .map(|item| seed.deserialize(SttDeserializer { raw: item }))
+            .transpose()
+    }
+}
+
+struct Entries {
+    entries: std::vec::IntoIter<(String, String)>,
+    value: Option<String>,
+}
+
+impl<'de> MapAccess<'de> for Entries {
+    type Error = DeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, DeError> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.value = Some(value);
+        let key: StringDeserializer<DeError> = key.into_deserializer();
+        seed.deserialize(key).map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, DeError> {
+        let raw = self.value.take().unwrap_or_default();
+        seed.deserialize(SttDeserializer { raw })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One packet of each message type we map, as the server batches them into one frame
+    const FRAME: &[u8] = include_bytes!("fixtures/messages.bin");
+
+    fn message_type(body: &str) -> String {
+        deserialize(body)["type"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn decodes_several_packets_in_one_frame() {
+        let (bodies, error) = PacketDecoder::new().push(FRAME);
+        assert_eq!(error, None);
+        let types: Vec<String> = bodies.iter().map(|b| message_type(b)).collect();
+        assert_eq!(
+            types,
+            vec![
+                "chatmsg", "uenter", "frank", "dgb", "spbc", "anbc", "rss", "rss", "upgrade",
+                "blab"
+            ]
+        );
+    }
+
+    #[test]
+    fn reassembles_packets_split_across_frames() {
+        for chunk_len in [1, 5, 13, 100] {
+            let mut decoder = PacketDecoder::new();
+            let mut bodies = Vec::new();
+            for chunk in FRAME.chunks(chunk_len) {
+                let (decoded, error) = decoder.push(chunk);
+                assert_eq!(error, None);
+                bodies.extend(decoded);
+            }
+            assert_eq!(bodies, PacketDecoder::new().push(FRAME).0);
+        }
+    }
+
+    #[test]
+    fn keeps_packets_before_a_bad_header() {
+        let mut frame = FRAME.to_vec();
+        frame.extend_from_slice(&[0xFF; HEADER_LEN]);
+        let mut decoder = PacketDecoder::new();
+        let (bodies, error) = decoder.push(&frame);
+        assert_eq!(bodies.len(), 10);
+        assert!(error.is_some());
+
+        // The bad bytes are dropped, so the next frame decodes cleanly
+        let (bodies, error) = decoder.push(&encode_packet("type@=mrkl/"));
+        assert_eq!(bodies, vec!["type@=mrkl/"]);
+        assert_eq!(error, None);
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Fan {
+        nn: String,
+        ic: Option<String>,
+        lev: u32,
+    }
+
+    #[derive(Deserialize)]
+    struct FansRank {
+        fc: i64,
+        list: Vec<Fan>,
+    }
+
+    #[derive(Deserialize)]
+    struct Chat {
+        txt: String,
+        ic: String,
+        lk: Option<String>,
+        level: u32,
+        missing: Option<u32>,
+    }
+
+    #[test]
+    fn keeps_chat_text_as_text() {
+        let (bodies, _) = PacketDecoder::new().push(FRAME);
+        let chat: Chat = from_str(&bodies[0]).unwrap();
+        assert_eq!(chat.txt, "a@=b/c@Sd 看这里 https://www.douyu.com/9999/");
+        assert_eq!(chat.ic, "avatar_v3/202310/0123456789abcdef");
+        assert_eq!(chat.lk, None);
+        assert_eq!(chat.level, 25);
+        assert_eq!(chat.missing, None);
+
+        #[derive(Deserialize)]
+        struct Text {
+            txt: String,
+        }
+        for text in [
+            "a@=b/",
+            "@S",
+            "@A=",
+            "/",
+            "@",
+            "x@=y/z@=w//",
+            "表情[emot:dy101]/",
+        ] {
+            let message: Text =
+                from_str(&encode_fields(&[("type", "chatmsg"), ("txt", text)])).unwrap();
+            assert_eq!(message.txt, text);
+        }
+    }
+
+    #[test]
+    fn reads_nested_lists_and_maps_into_structs() {
+        let (bodies, _) = PacketDecoder::new().push(FRAME);
+        let frank: FansRank = from_str(&bodies[2]).unwrap();
+        assert_eq!(frank.fc, 12345);
+        assert_eq!(
+            frank.list,
+            vec![
+                Fan {
+                    nn: "大粉丝".to_string(),
+                    ic: Some("avanew/face/201901/01".to_string()),
+                    lev: 30,
+                },
+                Fan {
+                    nn: "二号".to_string(),
+                    ic: None,
+                    lev: 18,
+                },
+            ]
+        );
+
+        // Lists of scalars and of lists, a map of maps, and an empty list
+        let nested: std::collections::HashMap<String, Vec<Vec<String>>> =
+            from_str("a@=x@AS@AAA@AS@Sy@AS@S/").unwrap();
+        assert_eq!(nested["a"], vec![vec!["x", "@"], vec!["y"]]);
+        let maps: std::collections::HashMap<String, std::collections::HashMap<String, u8>> =
+            from_str("m@=k@A=1@Sj@A=2@S/").unwrap();
+        assert_eq!(maps["m"]["j"], 2);
+        let empty: std::collections::HashMap<String, Vec<String>> = from_str("l@=/").unwrap();
+        assert!(empty["l"].is_empty());
+    }
+
+    #[test]
+    fn guesses_nesting_when_decoding_to_a_value() {
+        let (bodies, _) = PacketDecoder::new().push(FRAME);
+        let frank = deserialize(&bodies[2]);
+        assert_eq!(frank["fc"], "12345");
+        assert_eq!(frank["list"][0]["nn"], "大粉丝");
+        assert_eq!(frank["list"][0]["ic"], "avanew/face/201901/01");
+        assert_eq!(frank["list"][1]["lev"], "18");
+        assert_eq!(frank["list"][1]["ic"], "");
+
+        assert_eq!(
+            deserialize("a@=x/b@=p@Sq@S/c@=k@A=v@S/d@=x@AS@AAA@AS@S/"),
+            serde_json::json!({ "a": "x", "b": ["p", "q"], "c": { "k": "v" }, "d": [["x", "@"]] })
+        );
+        // Which is why chat text is read into a String instead
+        let chat = deserialize(&encode_fields(&[("txt", "a@=b/")]));
+        assert_eq!(chat["txt"], serde_json::json!({ "a": "b" }));
+    }
+
+    #[test]
+    fn rejects_values_that_dont_fit_the_struct() {
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Level {
+            level: u32,
+        }
+        assert!(from_str::<Level>("level@=abc/").is_err());
+        assert!(from_str::<Level>("level@=-1/").is_err());
+        assert!(from_str::<Level>("type@=chatmsg/").is_err());
+    }
+
+    #[test]
+    fn unescapes_one_level() {
+        assert_eq!(unescape("a@Sb@Ac"), "a/b@c");
+        assert_eq!(unescape("@AS@AA"), "@S@A");
+        assert_eq!(unescape("trailing@"), "trailing@");
+        assert_eq!(unescape("@x"), "@x");
+        for raw in ["", "@", "/", "@@//", "@A@S", "a@=b/"] {
+            assert_eq!(unescape(&escape(raw)), raw);
+        }
+    }
+}
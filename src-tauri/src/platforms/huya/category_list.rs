@@ -0,0 +1,104 @@
+use serde_json::Value;
+
+use super::utils::{get_huya_data, get_i64, get_str, huya_http_client};
+use crate::platforms::common::types_rust::{
+    CommonCategoryGroupRust, CommonLiveRoomRust, CommonPlatformCategoryRust, CommonRoomListRust,
+    SupportedPlatformRust,
+};
+
+// Huya's top-level sections and their bussType ids, as ordered on the site
+const HUYA_BUSS_TYPES: [(u32, &str); 4] = [
+    (1, "网游竞技"),
+    (2, "单机热游"),
+    (3, "手游休闲"),
+    (8, "娱乐天地"),
+];
+
+pub fn parse_buss_games(group_name: &str, data: &Value) -> CommonCategoryGroupRust {
+    let categories = data
+        .as_array()
+        .map(|games| {
+            games
+                .iter()
+                .filter_map(|game| {
+                    let gid = get_str(game, "gid")?;
+                    Some(CommonPlatformCategoryRust {
+                        icon_url: Some(format!(
+                            "https://huyaimg.msstatic.com/cdnimage/game/{}-MS.jpg",
+                            gid
+                        )),
+                        id: gid,
+                        name: get_str(game, "gameFullName")?,
+                        platform: SupportedPlatformRust::Huya,
+                        parent_id: None,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    CommonCategoryGroupRust {
+        group_name: group_name.to_string(),
+        platform: SupportedPlatformRust::Huya,
+        categories,
+    }
+}
+
+pub async fn fetch_huya_categories() -> Result<Vec<CommonCategoryGroupRust>, String> {
+    let http_client = huya_http_client()?;
+    let mut groups = Vec::with_capacity(HUYA_BUSS_TYPES.len());
+    for (buss_type, group_name) in HUYA_BUSS_TYPES {
+        let url = format!(
+            "https://live.cdn.huya.com/liveconfig/game/bussLive?bussType={}",
+            buss_type
+        );
+        let data = get_huya_data(&http_client, &url).await?;
+        groups.push(parse_buss_games(group_name, &data));
+    }
+    Ok(groups)
+}
+
+// Parses getLiveListByPage's `data`; `page` is the 0-based page that was requested
+pub fn parse_live_list_page(data: &Value, page: u32) -> CommonRoomListRust {
+    let total_page = get_i64(data, "totalPage").unwrap_or(0);
+    let rooms: Vec<CommonLiveRoomRust> = data
+        .get("datas")
+        .and_then(|d| d.as_array())
+        .map(|datas| {
+            datas
+                .iter()
+                .filter_map(|room| {
+                    Some(CommonLiveRoomRust {
+                        room_id: get_str(room, "profileRoom")?,
+                        title: get_str(room, "introduction")
+                            .or_else(|| get_str(room, "roomName"))
+                            .unwrap_or_default(),
+                        anchor_name: get_str(room, "nick").unwrap_or_default(),
+                        avatar_url: get_str(room, "avatar180"),
+                        cover_url: get_str(room, "screenshot"),
+                        viewer_count: get_str(room, "totalCount"),
+                        is_live: true, // The live list only contains live rooms
+                        platform: SupportedPlatformRust::Huya,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    CommonRoomListRust {
+        has_more: (page as i64 + 1) < total_page && !rooms.is_empty(),
+        rooms,
+    }
+}
+
+// The page size is fixed by Huya (120 rooms), so callers' page_size is not forwarded
+pub async fn fetch_huya_game_rooms(game_id: &str, page: u32) -> Result<CommonRoomListRust, String> {
+    let http_client = huya_http_client()?;
+    let url = format!(
+        "https://www.huya.com/cache.php?m=LiveList&do=getLiveListByPage&tagAll=0&gameId={}&page={}",
+        game_id,
+        page + 1 // Huya pages are 1-indexed
+    );
+    let data = get_huya_data(&http_client, &url).await?;
+    Ok(parse_live_list_page(&data, page))
+}
@@ -0,0 +1,141 @@
+use futures_util::{SinkExt, StreamExt};
+use std::future::Future;
+use tauri::AppHandle;
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+use super::message_parser::{parse_frame, CMD_HEARTBEAT_REQ, CMD_REGISTER_REQ};
+use crate::platforms::common::types_rust::SupportedPlatformRust;
+use crate::platforms::common::{supervise_danmaku, DanmakuSession};
+use crate::platforms::huya::room_info::fetch_huya_room_profile;
+use crate::platforms::huya::tars::TarsWriter;
+
+const HUYA_DANMU_URL: &str = "wss://cdnws.api.huya.com:443";
+const HEARTBEAT_INTERVAL_SECS: u64 = 60;
+
+// WebSocketCommand{iCmdType 0, vData 1}
+fn encode_command(cmd_type: i64, data: &[u8]) -> Vec<u8> {
+    let mut command = TarsWriter::new();
+    command.write_int(0, cmd_type);
+    command.write_bytes(1, data);
+    command.into_bytes()
+}
+
+// WSUserInfo for an anonymous viewer: lUid(0) is the presenter's yyid, then
+// bAnonymous, sGuid, sToken, lTid, lSid, lGroupId, lGroupType
+fn register_packet(yyid: i64, top_sid: i64, sub_sid: i64) -> Vec<u8> {
+    let mut user_info = TarsWriter::new();
+    user_info.write_int(0, yyid);
+    user_info.write_bool(1, true);
+    user_info.write_string(2, "");
+    user_info.write_string(3, "");
+    user_info.write_int(4, top_sid);
+    user_info.write_int(5, sub_sid);
+    user_info.write_int(6, 0);
+    user_info.write_int(7, 0);
+    encode_command(CMD_REGISTER_REQ, &user_info.into_bytes())
+}
+
+// Runs one Huya danmaku connection until the session is stopped or the connection ends;
+// reconnecting is up to supervise_danmaku. Events are emitted on `danmaku-huya-{room_id}`.
+async fn run_huya_session(session: DanmakuSession) -> Result<(), String> {
+    let room_id = session.room_id().to_string();
+    let profile = fetch_huya_room_profile(&room_id).await?;
+    if profile.yyid == 0 {
+        return Err(format!("Huya room {} has no presenter id", room_id));
+    }
+
+    let (ws_stream, _) = connect_async(HUYA_DANMU_URL)
+        .await
+        .map_err(|e| format!("Failed to connect to Huya danmaku server: {}", e))?;
+    let (mut write, mut read) = ws_stream.split();
+
+    write
+        .send(Message::Binary(register_packet(
+            profile.yyid,
+            profile.top_sid,
+            profile.sub_sid,
+        )))
+        .await
+        .map_err(|e| format!("Failed to send register packet: {}", e))?;
+    // Huya doesn't acknowledge the registration; a sent register packet is as good as it gets
+    session.mark_connected();
+
+    // Outgoing messages go through a channel so the heartbeat task doesn't need the sink
+    let (tx, mut rx) = mpsc::channel::<Message>(8);
+    let heartbeat_task = tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(HEARTBEAT_INTERVAL_SECS)).await;
+            if tx
+                .send(Message::Binary(encode_command(CMD_HEARTBEAT_REQ, &[])))
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+    let send_task = tokio::spawn(async move {
+        while let Some(msg_to_send) = rx.recv().await {
+            if write.send(msg_to_send).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let stopped = session.stopped();
+    tokio::pin!(stopped);
+
+    let result = loop {
+        tokio::select! {
+            _ = &mut stopped => {
+                println!("[Huya Danmaku {}] Stop signal received, terminating listener.", room_id);
+                break Ok(());
+            }
+            msg_option = read.next() => {
+                match msg_option {
+                    Some(Ok(Message::Binary(data))) => match parse_frame(&data) {
+                        Ok(events) => {
+                            for event in events {
+                                session.emit(event);
+                            }
+                        }
+                        Err(e) => {
+                            eprintln!("[Huya Danmaku {}] Failed to decode frame: {}", room_id, e);
+                        }
+                    },
+                    Some(Ok(Message::Close(_))) | None => {
+                        break Err("Huya danmaku websocket closed".to_string());
+                    }
+                    Some(Err(e)) => {
+                        break Err(format!("Huya danmaku websocket error: {}", e));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    };
+
+    heartbeat_task.abort();
+    send_task.abort();
+    result
+}
+
+// Keeps a Huya danmaku connection alive, reconnecting as needed, until `stop_signal` completes
+pub async fn run_huya_danmaku(
+    app_handle: AppHandle,
+    room_id: String,
+    stop_signal: impl Future<Output = ()> + Send,
+) -> Result<(), String> {
+    supervise_danmaku(
+        app_handle,
+        SupportedPlatformRust::Huya,
+        room_id,
+        stop_signal,
+        run_huya_session,
+    )
+    .await;
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use crate::platforms::common::{DanmakuEvent, DanmakuUser};
+use crate::platforms::huya::tars::{TarsStruct, TarsValue};
+
+// WebSocketCommand.iCmdType values we send or handle
+pub const CMD_REGISTER_REQ: i64 = 1;
+pub const CMD_MSG_PUSH: i64 = 7;
+pub const CMD_HEARTBEAT_REQ: i64 = 20;
+pub const CMD_MSG_PUSH_V2: i64 = 22;
+
+// Push message URIs
+const URI_MESSAGE_NOTICE: i64 = 1400; // Chat
+const URI_VIP_ENTER_BANNER: i64 = 6110; // Noble/VIP entering the room
+const URI_ATTENDEE_COUNT: i64 = 8006; // AttendeeCountNotice{iAttendeeCount 0}
+
+const DECORATION_APP_BADGE: i64 = 10400;
+
+// MessageNotice: tUserInfo(0){lUid 0, sNickName 2}, sContent(3), tBulletFormat(6){iFontColor 0},
+// vDecorationPrefix(8) = [DecorationInfo{iAppId 0, vData 2}]
+fn parse_message_notice(notice: &TarsStruct) -> Option<DanmakuEvent> {
+    let content = notice.get_string(3)?;
+    let user_info = notice.get_struct(0);
+    let nickname = user_info.and_then(|u| u.get_string(2)).unwrap_or("unknown");
+    let uid = user_info
+        .and_then(|u| u.get_i64(0))
+        .map(|uid| uid.to_string());
+
+    // Fan badge: BadgeInfo{sBadgeName 3, iBadgeLevel 4} inside a decoration with app id 10400
+    let badge = notice
+        .get_list(8)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|item| match item {
+            TarsValue::Struct(decoration) => Some(decoration),
+            _ => None,
+        })
+        .find(|decoration| decoration.get_i64(0) == Some(DECORATION_APP_BADGE))
+        .and_then(|decoration| TarsStruct::decode(decoration.get_bytes(2)?).ok());
+    let badge_name = badge
+        .as_ref()
+        .and_then(|b| b.get_string(3))
+        .filter(|name| !name.is_empty())
+        .map(|name| name.to_string());
+    let badge_level = badge.as_ref().and_then(|b| b.get_i64(4));
+
+    // -1 means the default colour; leave it to the frontend's per-user colour
+    let color = notice
+        .get_struct(6)
+        .and_then(|format| format.get_i64(0))
+        .filter(|c| *c >= 0)
+        .map(|c| format!("#{:06x}", c));
+
+    Some(DanmakuEvent::Chat {
+        user: DanmakuUser {
+            uid,
+            nickname: nickname.to_string(),
+            level: None,
+            badge_name,
+            badge_level,
+        },
+        content: content.to_string(),
+        color,
+    })
+}
+
+// VipEnterBanner: lUid(0), sNickName(1)
+fn parse_vip_enter(banner: &TarsStruct) -> Option<DanmakuEvent> {
+    Some(DanmakuEvent::Enter {
+        user: DanmakuUser {
+            uid: banner.get_i64(0).map(|uid| uid.to_string()),
+            nickname: banner.get_string(1)?.to_string(),
+            ..Default::default()
+        },
+    })
+}
+
+fn parse_attendee_count(notice: &TarsStruct) -> Option<DanmakuEvent> {
+    Some(DanmakuEvent::RoomStats {
+        viewer_count: notice.get_i64(0),
+        viewer_count_text: None,
+    })
+}
+
+fn parse_push(uri: i64, msg: &[u8]) -> Option<DanmakuEvent> {
+    let body = TarsStruct::decode(msg).ok()?;
+    match uri {
+        URI_MESSAGE_NOTICE => parse_message_notice(&body),
+        URI_VIP_ENTER_BANNER => parse_vip_enter(&body),
+        URI_ATTENDEE_COUNT => parse_attendee_count(&body),
+        _ => None,
+    }
+}
+
+// Decodes one WebSocketCommand frame into danmaku events.
+// V1 pushes carry one message (WSPushMessage{iUri 1, sMsg 2}); V2 pushes carry a
+// list of WSMsgItem{iUri 0, sMsg 1} in WSPushMessage_V2's field 1.
+pub fn parse_frame(frame: &[u8]) -> Result<Vec<DanmakuEvent>, String> {
+    let command = TarsStruct::decode(frame)?;
+    let data = command.get_bytes(1).unwrap_or_default();
+
+    let events = match command.get_i64(0).unwrap_or(0) {
+        CMD_MSG_PUSH => {
+            let push = TarsStruct::decode(data)?;
+            match (push.get_i64(1), push.get_bytes(2)) {
+                (Some(uri), Some(msg)) => parse_push(uri, msg).into_iter().collect(),
+                _ => Vec::new(),
+            }
+        }
+        CMD_MSG_PUSH_V2 => {
+            let push = TarsStruct::decode(data)?;
+            push.get_list(1)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|item| match item {
+                    TarsValue::Struct(item) => parse_push(item.get_i64(0)?, item.get_bytes(1)?),
+                    _ => None,
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    };
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_message_notice_push() {
+        let events = parse_frame(include_bytes!("fixtures/message_notice_push.bin")).unwrap();
+        let [DanmakuEvent::Chat {
+            user,
+            content,
+            color,
+        }] = events.as_slice()
+        else {
+            panic!("expected one chat event, got {:?}", events);
+        };
+        assert_eq!(content, "主播好厉害");
+        assert_eq!(color.as_deref(), Some("#ffa500"));
+        assert_eq!(user.uid.as_deref(), Some("1234567890"));
+        assert_eq!(user.nickname, "虎牙用户");
+        assert_eq!(user.badge_name.as_deref(), Some("小虎牙"));
+        assert_eq!(user.badge_level, Some(12));
+    }
+
+    #[test]
+    fn parses_v2_pushes() {
+        let events = parse_frame(include_bytes!("fixtures/push_v2.bin")).unwrap();
+        assert_eq!(events.len(), 2, "{:?}", events);
+        assert!(matches!(
+            &events[0],
+            DanmakuEvent::Enter { user }
+                if user.nickname == "贵族观众" && user.uid.as_deref() == Some("2233445566")
+        ));
+        assert!(matches!(
+            events[1],
+            DanmakuEvent::RoomStats {
+                viewer_count: Some(45678),
+                viewer_count_text: None
+            }
+        ));
+    }
+
+    #[test]
+    fn ignores_other_commands() {
+        // WebSocketCommand{iCmdType 20 (heartbeat)}
+        assert!(parse_frame(&[0x00, 0x14]).unwrap().is_empty());
+        let frame = include_bytes!("fixtures/message_notice_push.bin");
+        assert!(parse_frame(&frame[..frame.len() / 2]).is_err());
+    }
+}
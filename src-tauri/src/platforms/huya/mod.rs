@@ -0,0 +1,11 @@
+pub mod category_list;
+pub mod danmu;
+pub mod models;
+pub mod platform;
+pub mod room_info;
+pub mod stream_url;
+pub mod tars;
+pub mod utils;
+
+pub use self::platform::HuyaPlatform;
+pub use self::stream_url::get_huya_stream_url;
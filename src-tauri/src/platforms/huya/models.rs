@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+// One CDN line from profileRoom's stream.baseSteamInfoList
+#[derive(Debug, Clone, Default)]
+pub struct HuyaStreamLine {
+    pub cdn_type: String,
+    pub stream_name: String,
+    pub flv_url: String,
+    pub flv_url_suffix: String,
+    pub flv_anti_code: String,
+    pub hls_url: String,
+    pub hls_url_suffix: String,
+    pub hls_anti_code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HuyaBitRate {
+    #[serde(rename = "bitRate")]
+    pub bit_rate: i64, // 0 is the original quality
+    pub name: String,
+}
+
+// Everything the module needs from the profileRoom API
+#[derive(Debug, Clone, Default)]
+pub struct HuyaRoomProfile {
+    pub room_id: String,
+    pub title: Option<String>,
+    pub anchor_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub is_live: bool,
+    // Milliseconds since the epoch
+    pub live_started_at: Option<i64>,
+    pub yyid: i64, // Presenter id used by the danmaku server
+    pub top_sid: i64,
+    pub sub_sid: i64,
+    pub lines: Vec<HuyaStreamLine>,
+    pub bit_rates: Vec<HuyaBitRate>,
+}
+
+// --- Public struct for the Tauri command result ---
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HuyaStreamInfo {
+    #[serde(rename = "roomId")]
+    pub room_id: String,
+    pub url: String,
+    pub format: String, // "flv" or "m3u8"
+    #[serde(rename = "cdnType")]
+    pub cdn_type: String,
+    #[serde(rename = "currentBitRate")]
+    pub current_bit_rate: i64,
+    pub qualities: Vec<HuyaBitRate>,
+}
@@ -0,0 +1,70 @@
+use async_trait::async_trait;
+use tauri::AppHandle;
+use tokio::sync::oneshot;
+
+use super::category_list::{fetch_huya_categories, fetch_huya_game_rooms};
+use super::danmu::client::run_huya_danmaku;
+use super::room_info::fetch_huya_room_info;
+use super::stream_url::resolve_huya_stream;
+use crate::platforms::common::types_rust::{
+    CommonCategoryGroupRust, CommonRoomInfoRust, CommonRoomListRust, CommonStreamInfoRust,
+    RoomListQueryRust, StreamProtocol, SupportedPlatformRust,
+};
+use crate::platforms::common::LivePlatform;
+use crate::proxy::streams::ProxyStreamHeaders;
+
+pub struct HuyaPlatform;
+
+#[async_trait]
+impl LivePlatform for HuyaPlatform {
+    fn platform(&self) -> SupportedPlatformRust {
+        SupportedPlatformRust::Huya
+    }
+
+    async fn fetch_categories(&self) -> Result<Vec<CommonCategoryGroupRust>, String> {
+        fetch_huya_categories().await
+    }
+
+    // Category ids are Huya game ids (gid); the list is flat so parent ids are unused
+    async fn fetch_rooms(&self, query: &RoomListQueryRust) -> Result<CommonRoomListRust, String> {
+        fetch_huya_game_rooms(&query.category_id, query.page).await
+    }
+
+    async fn fetch_room_info(&self, room_id: &str) -> Result<CommonRoomInfoRust, String> {
+        fetch_huya_room_info(room_id).await
+    }
+
+    // `quality` is a Huya bit rate (e.g. "4000" for 超清); "0" or none is the original quality
+    async fn resolve_stream(
+        &self,
+        room_id: &str,
+        quality: Option<&str>,
+    ) -> Result<CommonStreamInfoRust, String> {
+        let bit_rate = quality
+            .map(|q| {
+                q.parse::<i64>()
+                    .map_err(|_| format!("Invalid Huya quality: {}", q))
+            })
+            .transpose()?;
+        let info = resolve_huya_stream(room_id, bit_rate, StreamProtocol::Flv).await?;
+        Ok(CommonStreamInfoRust {
+            room_id: room_id.to_string(),
+            url: info.url,
+            format: info.format,
+            platform: SupportedPlatformRust::Huya,
+            headers: ProxyStreamHeaders::default(),
+        })
+    }
+
+    async fn run_danmaku(
+        &self,
+        app_handle: AppHandle,
+        room_id: String,
+        stop_rx: oneshot::Receiver<()>,
+    ) -> Result<(), String> {
+        let stop_signal = async move {
+            let _ = stop_rx.await;
+        };
+        run_huya_danmaku(app_handle, room_id, stop_signal).await
+    }
+}
@@ -0,0 +1,104 @@
+use serde_json::Value;
+
+use super::models::{HuyaBitRate, HuyaRoomProfile, HuyaStreamLine};
+use super::utils::{get_huya_data, get_i64, get_str, huya_http_client};
+use crate::platforms::common::types_rust::{CommonRoomInfoRust, SupportedPlatformRust};
+
+fn parse_stream_line(line: &Value) -> HuyaStreamLine {
+    HuyaStreamLine {
+        cdn_type: get_str(line, "sCdnType").unwrap_or_default(),
+        stream_name: get_str(line, "sStreamName").unwrap_or_default(),
+        flv_url: get_str(line, "sFlvUrl").unwrap_or_default(),
+        flv_url_suffix: get_str(line, "sFlvUrlSuffix").unwrap_or_else(|| "flv".to_string()),
+        flv_anti_code: get_str(line, "sFlvAntiCode").unwrap_or_default(),
+        hls_url: get_str(line, "sHlsUrl").unwrap_or_default(),
+        hls_url_suffix: get_str(line, "sHlsUrlSuffix").unwrap_or_else(|| "m3u8".to_string()),
+        hls_anti_code: get_str(line, "sHlsAntiCode").unwrap_or_default(),
+    }
+}
+
+fn parse_bit_rates(list: &Value) -> Vec<HuyaBitRate> {
+    list.as_array()
+        .map(|rates| {
+            rates
+                .iter()
+                .filter_map(|rate| {
+                    Some(HuyaBitRate {
+                        bit_rate: get_i64(rate, "iBitRate")?,
+                        name: get_str(rate, "sDisplayName")?,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Parses profileRoom's `data`. `stream` is an empty string rather than an object when
+// the room is offline, so every stream field is optional.
+pub fn parse_profile_room(room_id: &str, data: &Value) -> HuyaRoomProfile {
+    let profile_info = data.get("profileInfo").unwrap_or(&Value::Null);
+    let live_data = data.get("liveData").unwrap_or(&Value::Null);
+    let stream = data.get("stream").unwrap_or(&Value::Null);
+
+    let stream_lines: Vec<&Value> = stream
+        .get("baseSteamInfoList")
+        .and_then(|l| l.as_array())
+        .map(|l| l.iter().collect())
+        .unwrap_or_default();
+    let first_line = stream_lines.first().copied().unwrap_or(&Value::Null);
+
+    // Prefer the rate list attached to the stream; liveData carries the same list as a JSON string
+    let mut bit_rates = parse_bit_rates(
+        stream
+            .get("flv")
+            .and_then(|f| f.get("rateArray"))
+            .unwrap_or(&Value::Null),
+    );
+    if bit_rates.is_empty() {
+        if let Some(raw) = live_data.get("bitRateInfo").and_then(|b| b.as_str()) {
+            bit_rates = parse_bit_rates(&serde_json::from_str(raw).unwrap_or(Value::Null));
+        }
+    }
+
+    let is_live = data.get("liveStatus").and_then(|s| s.as_str()) == Some("ON");
+    HuyaRoomProfile {
+        room_id: room_id.to_string(),
+        title: get_str(live_data, "introduction").or_else(|| get_str(live_data, "roomName")),
+        anchor_name: get_str(profile_info, "nick"),
+        avatar_url: get_str(profile_info, "avatar180"),
+        is_live,
+        // startTime is in seconds and stays set after the broadcast ends
+        live_started_at: get_i64(live_data, "startTime")
+            .filter(|started_at| is_live && *started_at > 0)
+            .map(|started_at| started_at * 1000),
+        yyid: get_i64(profile_info, "yyid").unwrap_or(0),
+        top_sid: get_i64(first_line, "lChannelId").unwrap_or(0),
+        sub_sid: get_i64(first_line, "lSubChannelId").unwrap_or(0),
+        lines: stream_lines.into_iter().map(parse_stream_line).collect(),
+        bit_rates,
+    }
+}
+
+// `room_id` is the numeric room number shown in the URL (www.huya.com/<room_id>)
+pub async fn fetch_huya_room_profile(room_id: &str) -> Result<HuyaRoomProfile, String> {
+    let http_client = huya_http_client()?;
+    let url = format!(
+        "https://mp.huya.com/cache.php?m=Live&do=profileRoom&roomid={}",
+        room_id
+    );
+    let data = get_huya_data(&http_client, &url).await?;
+    Ok(parse_profile_room(room_id, &data))
+}
+
+pub async fn fetch_huya_room_info(room_id: &str) -> Result<CommonRoomInfoRust, String> {
+    let profile = fetch_huya_room_profile(room_id).await?;
+    Ok(CommonRoomInfoRust {
+        room_id: profile.room_id,
+        title: profile.title,
+        anchor_name: profile.anchor_name,
+        avatar_url: profile.avatar_url,
+        is_live: profile.is_live,
+        live_started_at: profile.live_started_at,
+        platform: SupportedPlatformRust::Huya,
+    })
+}
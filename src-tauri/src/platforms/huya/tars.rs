@@ -0,0 +1,391 @@
+use std::collections::BTreeMap;
+
+// Minimal Tars (TUP/JCE) codec, enough for Huya's danmaku WebSocket.
+//
+// Each field starts with a head byte: the high nibble is the tag and the low
+// nibble the type; tags >= 15 put 0xF0 | type first and the tag in the next byte.
+// Integers are big-endian and always written in the smallest type that fits.
+const TYPE_INT8: u8 = 0;
+const TYPE_INT16: u8 = 1;
+const TYPE_INT32: u8 = 2;
+const TYPE_INT64: u8 = 3;
+const TYPE_FLOAT: u8 = 4;
+const TYPE_DOUBLE: u8 = 5;
+const TYPE_STRING1: u8 = 6;
+const TYPE_STRING4: u8 = 7;
+const TYPE_MAP: u8 = 8;
+const TYPE_LIST: u8 = 9;
+const TYPE_STRUCT_BEGIN: u8 = 10;
+const TYPE_STRUCT_END: u8 = 11;
+const TYPE_ZERO: u8 = 12;
+const TYPE_SIMPLE_LIST: u8 = 13; // byte array
+                                 // Huya's messages nest a few levels at most; this only guards against hostile nesting
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TarsValue {
+    Int(i64),
+    Float(f32),
+    Double(f64),
+    String(String),
+    Map(Vec<(TarsValue, TarsValue)>),
+    List(Vec<TarsValue>),
+    Struct(TarsStruct),
+    Bytes(Vec<u8>),
+}
+
+// A decoded struct, fields keyed by tag. Huya adds fields over time, so unknown tags are kept.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TarsStruct {
+    pub fields: BTreeMap<u8, TarsValue>,
+}
+
+impl TarsStruct {
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        TarsReader::new(data).read_struct_fields(false, 0)
+    }
+
+    pub fn get(&self, tag: u8) -> Option<&TarsValue> {
+        self.fields.get(&tag)
+    }
+
+    pub fn get_i64(&self, tag: u8) -> Option<i64> {
+        match self.get(tag)? {
+            TarsValue::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn get_string(&self, tag: u8) -> Option<&str> {
+        match self.get(tag)? {
+            TarsValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn get_struct(&self, tag: u8) -> Option<&TarsStruct> {
+        match self.get(tag)? {
+            TarsValue::Struct(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn get_list(&self, tag: u8) -> Option<&[TarsValue]> {
+        match self.get(tag)? {
+            TarsValue::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    // Nested payloads are usually a byte array holding another encoded struct
+    pub fn get_bytes(&self, tag: u8) -> Option<&[u8]> {
+        match self.get(tag)? {
+            TarsValue::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+}
+
+pub struct TarsReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> TarsReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.pos + len > self.buf.len() {
+            return Err(format!(
+                "tars: need {} bytes at offset {}, only {} left",
+                len,
+                self.pos,
+                self.buf.len() - self.pos
+            ));
+        }
+        let slice = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_head(&mut self) -> Result<(u8, u8), String> {
+        let byte = self.read_u8()?;
+        let value_type = byte & 0x0F;
+        let mut tag = byte >> 4;
+        if tag == 15 {
+            tag = self.read_u8()?;
+        }
+        Ok((tag, value_type))
+    }
+
+    // Lengths of maps, lists and byte arrays are themselves encoded as a tagged int
+    fn read_length(&mut self, depth: usize) -> Result<usize, String> {
+        let (_, value_type) = self.read_head()?;
+        match self.read_value(value_type, depth + 1)? {
+            TarsValue::Int(len) if len >= 0 => Ok(len as usize),
+            other => Err(format!("tars: invalid length {:?}", other)),
+        }
+    }
+
+    fn read_value(&mut self, value_type: u8, depth: usize) -> Result<TarsValue, String> {
+        if depth > MAX_DEPTH {
+            return Err("tars: data nested too deeply".to_string());
+        }
+        let value = match value_type {
+            TYPE_INT8 => TarsValue::Int(self.read_u8()? as i8 as i64),
+            TYPE_INT16 => {
+                let b = self.take(2)?;
+                TarsValue::Int(i16::from_be_bytes([b[0], b[1]]) as i64)
+            }
+            TYPE_INT32 => {
+                let b = self.take(4)?;
+                TarsValue::Int(i32::from_be_bytes([b[0], b[1], b[2], b[3]]) as i64)
+            }
+            TYPE_INT64 => {
+                let b = self.take(8)?;
+                TarsValue::Int(i64::from_be_bytes(b.try_into().unwrap()))
+            }
+            TYPE_FLOAT => {
+                let b = self.take(4)?;
+                TarsValue::Float(f32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            }
+            TYPE_DOUBLE => {
+                let b = self.take(8)?;
+                TarsValue::Double(f64::from_be_bytes(b.try_into().unwrap()))
+            }
+            TYPE_STRING1 => {
+                let len = self.read_u8()? as usize;
+                TarsValue::String(String::from_utf8_lossy(self.take(len)?).into_owned())
+            }
+            TYPE_STRING4 => {
+                let b = self.take(4)?;
+                let len = u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize;
+                TarsValue::String(String::from_utf8_lossy(self.take(len)?).into_owned())
+            }
+            TYPE_MAP => {
+                let len = self.read_length(depth)?;
+                let mut entries = Vec::with_capacity(len.min(1024));
+                for _ in 0..len {
+                    let (_, key_type) = self.read_head()?;
+                    let key = self.read_value(key_type, depth + 1)?;
+                    let (_, value_type) = self.read_head()?;
+                    let value = self.read_value(value_type, depth + 1)?;
+                    entries.push((key, value));
+                }
+                TarsValue::Map(entries)
+            }
+            TYPE_LIST => {
+                let len = self.read_length(depth)?;
+                let mut items = Vec::with_capacity(len.min(1024));
+                for _ in 0..len {
+                    let (_, item_type) = self.read_head()?;
+                    items.push(self.read_value(item_type, depth + 1)?);
+                }
+                TarsValue::List(items)
+            }
+            TYPE_STRUCT_BEGIN => TarsValue::Struct(self.read_struct_fields(true, depth + 1)?),
+            TYPE_ZERO => TarsValue::Int(0),
+            TYPE_SIMPLE_LIST => {
+                let _element_head = self.read_u8()?; // Always a byte (type 0)
+                let len = self.read_length(depth)?;
+                TarsValue::Bytes(self.take(len)?.to_vec())
+            }
+            other => return Err(format!("tars: unknown type {}", other)),
+        };
+        Ok(value)
+    }
+
+    // Nested structs end with a STRUCT_END head; a top-level struct simply runs to the end of the buffer
+    fn read_struct_fields(&mut self, nested: bool, depth: usize) -> Result<TarsStruct, String> {
+        let mut result = TarsStruct::default();
+        loop {
+            if !nested && self.pos >= self.buf.len() {
+                break;
+            }
+            let (tag, value_type) = self.read_head()?;
+            if value_type == TYPE_STRUCT_END {
+                break;
+            }
+            let value = self.read_value(value_type, depth)?;
+            result.fields.insert(tag, value);
+        }
+        Ok(result)
+    }
+}
+
+#[derive(Default)]
+pub struct TarsWriter {
+    buf: Vec<u8>,
+}
+
+impl TarsWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    fn write_head(&mut self, tag: u8, value_type: u8) {
+        if tag < 15 {
+            self.buf.push((tag << 4) | value_type);
+        } else {
+            self.buf.push(0xF0 | value_type);
+            self.buf.push(tag);
+        }
+    }
+
+    pub fn write_int(&mut self, tag: u8, value: i64) {
+        if value == 0 {
+            self.write_head(tag, TYPE_ZERO);
+        } else if let Ok(v) = i8::try_from(value) {
+            self.write_head(tag, TYPE_INT8);
+            self.buf.push(v as u8);
+        } else if let Ok(v) = i16::try_from(value) {
+            self.write_head(tag, TYPE_INT16);
+            self.buf.extend_from_slice(&v.to_be_bytes());
+        } else if let Ok(v) = i32::try_from(value) {
+            self.write_head(tag, TYPE_INT32);
+            self.buf.extend_from_slice(&v.to_be_bytes());
+        } else {
+            self.write_head(tag, TYPE_INT64);
+            self.buf.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    pub fn write_bool(&mut self, tag: u8, value: bool) {
+        self.write_int(tag, value as i64);
+    }
+
+    pub fn write_string(&mut self, tag: u8, value: &str) {
+        let bytes = value.as_bytes();
+        if bytes.len() <= u8::MAX as usize {
+            self.write_head(tag, TYPE_STRING1);
+            self.buf.push(bytes.len() as u8);
+        } else {
+            self.write_head(tag, TYPE_STRING4);
+            self.buf
+                .extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        }
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub fn write_bytes(&mut self, tag: u8, value: &[u8]) {
+        self.write_head(tag, TYPE_SIMPLE_LIST);
+        self.write_head(0, TYPE_INT8);
+        self.write_int(0, value.len() as i64);
+        self.buf.extend_from_slice(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_written_fields() {
+        let long = "弹幕".repeat(100);
+        let mut writer = TarsWriter::new();
+        for (tag, value) in [0, 1, -1, 300, -70_000, 1 << 40].into_iter().enumerate() {
+            writer.write_int(tag as u8, value);
+        }
+        writer.write_bool(6, true);
+        writer.write_string(7, "huya");
+        writer.write_string(8, &long);
+        writer.write_bytes(9, &[1, 2, 3]);
+        writer.write_int(200, 42);
+        let decoded = TarsStruct::decode(&writer.into_bytes()).unwrap();
+
+        let ints: Vec<i64> = (0..7).map(|tag| decoded.get_i64(tag).unwrap()).collect();
+        assert_eq!(ints, vec![0, 1, -1, 300, -70_000, 1 << 40, 1]);
+        assert_eq!(decoded.get_string(7), Some("huya"));
+        assert_eq!(decoded.get_string(8), Some(long.as_str()));
+        assert_eq!(decoded.get_bytes(9), Some(&[1u8, 2, 3][..]));
+        assert_eq!(decoded.get_i64(200), Some(42));
+    }
+
+    #[test]
+    fn writes_the_smallest_int_type() {
+        let encoded = |value| {
+            let mut writer = TarsWriter::new();
+            writer.write_int(1, value);
+            writer.into_bytes()
+        };
+        assert_eq!(encoded(0), vec![0x1C]);
+        assert_eq!(encoded(-2), vec![0x10, 0xFE]);
+        assert_eq!(encoded(300), vec![0x11, 0x01, 0x2C]);
+        assert_eq!(encoded(1 << 20), vec![0x12, 0x00, 0x10, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn reads_nested_lists_maps_and_structs() {
+        // {0: [ {0: 5, 1: "a"}, {} ], 1: { "k": [7] }}
+        let data = [
+            0x09, 0x00, 0x02, // tag 0 list, length 2
+            0x0A, 0x00, 0x05, 0x16, 0x01, b'a', 0x0B, // struct {0: 5, 1: "a"}
+            0x0A, 0x0B, // empty struct
+            0x18, 0x00, 0x01, // tag 1 map, length 1
+            0x06, 0x01, b'k', // key "k"
+            0x19, 0x00, 0x01, 0x00, 0x07, // value [7]
+        ];
+        let decoded = TarsStruct::decode(&data).unwrap();
+
+        let list = decoded.get_list(0).unwrap();
+        let TarsValue::Struct(first) = &list[0] else {
+            panic!("expected a struct, got {:?}", list[0]);
+        };
+        assert_eq!(first.get_i64(0), Some(5));
+        assert_eq!(first.get_string(1), Some("a"));
+        assert_eq!(list[1], TarsValue::Struct(TarsStruct::default()));
+
+        assert_eq!(
+            decoded.get(1),
+            Some(&TarsValue::Map(vec![(
+                TarsValue::String("k".to_string()),
+                TarsValue::List(vec![TarsValue::Int(7)])
+            )]))
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let mut writer = TarsWriter::new();
+        writer.write_string(0, "truncated");
+        writer.write_int(1, 1 << 40);
+        let bytes = writer.into_bytes();
+        for len in 1..bytes.len() {
+            if len != 11 {
+                assert!(TarsStruct::decode(&bytes[..len]).is_err(), "len {}", len);
+            }
+        }
+        // An unterminated nested struct
+        assert!(TarsStruct::decode(&[0x0A, 0x00, 0x05]).is_err());
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        let nested = |depth: usize| {
+            let mut data = vec![0x0A; depth];
+            data.extend(vec![0x0B; depth]);
+            data
+        };
+        // The outermost struct is the one being decoded, so it doesn't count
+        assert!(TarsStruct::decode(&nested(MAX_DEPTH + 1)).is_ok());
+        assert!(TarsStruct::decode(&nested(MAX_DEPTH + 2)).is_err());
+
+        // Lists of lists, far deeper than the stack would allow
+        let lists: Vec<u8> = [0x09, 0x00, 0x01].repeat(100_000);
+        assert!(TarsStruct::decode(&lists).is_err());
+        // A length that is itself a deeply nested struct
+        let mut length = vec![0x09];
+        length.extend(vec![0x0A; 100_000]);
+        assert!(TarsStruct::decode(&length).is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use reqwest::header::REFERER;
+use serde_json::Value;
+
+use crate::platforms::common::http_client::HttpClient;
+
+const HUYA_REFERER: &str = "https://www.huya.com/";
+
+pub fn huya_http_client() -> Result<HttpClient, String> {
+    let mut http_client =
+        HttpClient::new().map_err(|e| format!("Failed to create HttpClient: {}", e))?;
+    http_client.insert_header(REFERER, HUYA_REFERER)?;
+    Ok(http_client)
+}
+
+// Huya's cache.php APIs answer { status: 200, message, data }; anything else is an error
+pub fn parse_huya_response(text: &str) -> Result<Value, String> {
+    let json: Value =
+        serde_json::from_str(text).map_err(|e| format!("Failed to parse Huya response: {}", e))?;
+    let status = json.get("status").and_then(value_to_i64).unwrap_or(-1);
+    if status != 200 {
+        let message = json
+            .get("message")
+            .and_then(|m| m.as_str())
+            .unwrap_or("unknown error");
+        return Err(format!("Huya API error {}: {}", status, message));
+    }
+    json.get("data")
+        .cloned()
+        .ok_or_else(|| "Huya response has no data".to_string())
+}
+
+pub async fn get_huya_data(http_client: &HttpClient, url: &str) -> Result<Value, String> {
+    let text = http_client.get_text(url).await?;
+    parse_huya_response(&text)
+}
+
+// Huya returns ids and counts as either numbers or strings depending on the endpoint
+pub fn value_to_i64(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+pub fn value_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+pub fn get_str(value: &Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(value_to_string)
+        .filter(|s| !s.is_empty())
+}
+
+pub fn get_i64(value: &Value, key: &str) -> Option<i64> {
+    value.get(key).and_then(value_to_i64)
+}
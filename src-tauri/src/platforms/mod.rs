@@ -1,6 +1,20 @@
+pub mod bilibili;
 pub mod common;
 pub mod douyin;
 pub mod douyu;
+pub mod huya;
+
+use std::sync::Arc;
+
+// Every platform with a LivePlatform implementation; adding a site means registering it here
+pub fn build_platform_registry() -> common::PlatformRegistry {
+    let mut registry = common::PlatformRegistry::default();
+    registry.register(Arc::new(douyu::DouyuPlatform));
+    registry.register(Arc::new(douyin::DouyinPlatform));
+    registry.register(Arc::new(bilibili::BilibiliPlatform));
+    registry.register(Arc::new(huya::HuyaPlatform));
+    registry
+}
 
 // pub use douyu::*; // Removed to avoid ambiguity and encourage explicit paths
 // pub use common::*; // Removed for consistency
@@ -0,0 +1,457 @@
+// Time-shift buffer for a proxied FLV stream. With it on, one capture task per stream reads
+// the upstream (through the failover relay) and keeps the last few minutes of tags in
+// memory, indexed by keyframe. Every player connection is served from the buffer: from the
+// newest keyframe, or from `offset` seconds back, then following it live. A paused player
+// simply stops reading and carries on from where it was, as long as that's still buffered.
+
+use actix_web::web::Bytes;
+use futures_util::{Stream, StreamExt};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{watch, Notify};
+
+use super::failover::{relay_flv, UpstreamSource};
+use super::routes::open_upstream;
+use super::StreamStatsStore;
+use crate::media::flv::{FlvItem, FlvReader, FlvStatsCollector, FlvTag, TAG_SCRIPT, TAG_VIDEO};
+
+#[cfg(not(test))]
+const MAX_BUFFER_BYTES: usize = 512 * 1024 * 1024;
+#[cfg(test)]
+const MAX_BUFFER_BYTES: usize = 64 * 1024;
+// Largest chunk handed to a player at once when it's catching up
+#[cfg(not(test))]
+const MAX_READ_BATCH: usize = 256 * 1024;
+#[cfg(test)]
+const MAX_READ_BATCH: usize = 1024;
+// Audio-only streams have no keyframes; start points are indexed this far apart instead
+const AUDIO_INDEX_INTERVAL_MS: u32 = 1000;
+
+struct BufferedTag {
+    timestamp: u32,
+    data: Bytes, // Encoded tag, PreviousTagSize included
+}
+
+// The metadata and sequence headers in effect, as (tag type, body)
+type Configs = Arc<Vec<(u8, Vec<u8>)>>;
+
+struct StartPoint {
+    seq: u64,
+    timestamp: u32,
+    configs: Configs,
+}
+
+#[derive(Default)]
+struct DvrState {
+    header: Option<Vec<u8>>,
+    expect_video: bool,
+    configs: Configs,
+    tags: VecDeque<BufferedTag>,
+    first_seq: u64, // Sequence number of tags[0]
+    start_points: VecDeque<StartPoint>,
+    bytes: usize,
+    ended: bool,
+}
+
+impl DvrState {
+    fn end_seq(&self) -> u64 {
+        self.first_seq + self.tags.len() as u64
+    }
+
+    // The last start point at or before `offset_ms` back from the newest tag, or the oldest
+    // one when the buffer doesn't reach that far
+    fn start_point(&self, offset_ms: u64) -> Option<&StartPoint> {
+        let live = self.tags.back()?.timestamp as u64;
+        let target = live.saturating_sub(offset_ms);
+        self.start_points
+            .iter()
+            .rev()
+            .find(|point| point.timestamp as u64 <= target)
+            .or_else(|| self.start_points.front())
+    }
+
+    fn evict(&mut self, window_ms: u32) {
+        let newest = self.tags.back().map_or(0, |tag| tag.timestamp);
+        while let Some(oldest) = self.tags.front() {
+            if newest.saturating_sub(oldest.timestamp) <= window_ms
+                && self.bytes <= MAX_BUFFER_BYTES
+            {
+                break;
+            }
+            self.bytes -= oldest.data.len();
+            self.tags.pop_front();
+            self.first_seq += 1;
+        }
+        while self
+            .start_points
+            .front()
+            .map_or(false, |point| point.seq < self.first_seq)
+        {
+            self.start_points.pop_front();
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct DvrBuffer {
+    state: Arc<Mutex<DvrState>>,
+    updated: Arc<Notify>,
+    window_ms: u32,
+}
+
+impl DvrBuffer {
+    pub fn new(window_secs: u32) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(DvrState {
+                expect_video: true,
+                ..DvrState::default()
+            })),
+            updated: Arc::new(Notify::new()),
+            window_ms: window_secs.saturating_mul(1000),
+        }
+    }
+
+    // How far back the buffer currently reaches
+    pub fn buffered_secs(&self) -> u32 {
+        let state = self.state.lock().unwrap();
+        match (state.tags.front(), state.tags.back()) {
+            (Some(oldest), Some(newest)) => {
+                newest.timestamp.saturating_sub(oldest.timestamp) / 1000
+            }
+            _ => 0,
+        }
+    }
+
+    fn push(&self, item: FlvItem) {
+        let mut state = self.state.lock().unwrap();
+        let tag = match item {
+            FlvItem::Header(header) => {
+                // The relay only ever passes on the first one
+                if state.header.is_none() {
+                    state.expect_video = header.has_video;
+                    state.header = Some(header.bytes);
+                }
+                return;
+            }
+            FlvItem::Tag(tag) => tag,
+        };
+
+        if tag.tag_type == TAG_SCRIPT || tag.is_sequence_header() {
+            let mut configs: Vec<_> = state
+                .configs
+                .iter()
+                .filter(|(tag_type, _)| *tag_type != tag.tag_type)
+                .cloned()
+                .collect();
+            configs.push((tag.tag_type, tag.body.clone()));
+            state.configs = Arc::new(configs);
+        } else {
+            let starts = if state.expect_video {
+                tag.tag_type == TAG_VIDEO && tag.is_video_keyframe()
+            } else {
+                state.start_points.back().map_or(true, |point| {
+                    tag.timestamp.saturating_sub(point.timestamp) >= AUDIO_INDEX_INTERVAL_MS
+                })
+            };
+            if starts {
+                let point = StartPoint {
+                    seq: state.end_seq(),
+                    timestamp: tag.timestamp,
+                    configs: state.configs.clone(),
+                };
+                state.start_points.push_back(point);
+            }
+        }
+
+        let data = Bytes::from(tag.encode(tag.timestamp));
+        state.bytes += data.len();
+        state.tags.push_back(BufferedTag {
+            timestamp: tag.timestamp,
+            data,
+        });
+        state.evict(self.window_ms);
+        drop(state);
+        self.updated.notify_waiters();
+    }
+
+    fn finish(&self) {
+        self.state.lock().unwrap().ended = true;
+        self.updated.notify_waiters();
+    }
+
+    // What a player at `next` should get: the header and configs first, then tags
+    async fn read(&self, next: &mut Option<u64>, offset_ms: u64) -> Option<Bytes> {
+        loop {
+            // Created before looking, so an update in between isn't missed
+            let updated = self.updated.notified();
+            {
+                let state = self.state.lock().unwrap();
+                match *next {
+                    None => {
+                        if let (Some(header), Some(point)) =
+                            (&state.header, state.start_point(offset_ms))
+                        {
+                            let mut out = header.clone();
+                            for (tag_type, body) in point.configs.iter() {
+                                let config = FlvTag {
+                                    tag_type: *tag_type,
+                                    timestamp: point.timestamp,
+                                    offset: 0,
+                                    body: body.clone(),
+                                };
+                                out.extend(config.encode(point.timestamp));
+                            }
+                            *next = Some(point.seq);
+                            return Some(Bytes::from(out));
+                        }
+                    }
+                    Some(mut seq) => {
+                        // Fell out of the buffer (a long pause); skip ahead to a keyframe
+                        if seq < state.first_seq {
+                            seq = state
+                                .start_points
+                                .front()
+                                .map_or(state.end_seq(), |point| point.seq);
+                        }
+                        let mut out = Vec::new();
+                        let start = (seq - state.first_seq) as usize;
+                        for tag in state.tags.range(start.min(state.tags.len())..) {
+                            if out.len() >= MAX_READ_BATCH {
+                                break;
+                            }
+                            out.extend_from_slice(&tag.data);
+                            seq += 1;
+                        }
+                        *next = Some(seq);
+                        if !out.is_empty() {
+                            return Some(Bytes::from(out));
+                        }
+                    }
+                }
+                if state.ended {
+                    return None;
+                }
+            }
+            updated.await;
+        }
+    }
+
+    // A player connection starting `offset_secs` behind live
+    pub fn reader(&self, offset_secs: u64) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+        let offset_ms = offset_secs.saturating_mul(1000);
+        futures_util::stream::unfold((self.clone(), None), move |(buffer, mut next)| async move {
+            let chunk = buffer.read(&mut next, offset_ms).await?;
+            Some((Ok(chunk), (buffer, next)))
+        })
+    }
+}
+
+// Fills the buffer until the stream is removed or replaced, or the relay gives up
+pub fn spawn_capture(
+    buffer: DvrBuffer,
+    source: UpstreamSource,
+    stats: StreamStatsStore,
+    mut closed_rx: watch::Receiver<()>,
+) {
+    tauri::async_runtime::spawn(async move {
+        let upstream_response =
+            match open_upstream(&source.url, &source.headers, &source.client).await {
+                Ok(upstream_response) => upstream_response,
+                Err(_) => {
+                    // open_upstream has logged why
+                    buffer.finish();
+                    return;
+                }
+            };
+        *stats.0.lock().unwrap() = Some(FlvStatsCollector::new());
+
+        let closed = async move { while closed_rx.changed().await.is_ok() {} };
+        let mut chunks = Box::pin(relay_flv(upstream_response, source).take_until(closed));
+        let mut reader = FlvReader::new();
+        while let Some(Ok(chunk)) = chunks.next().await {
+            stats.record(&chunk);
+            match reader.push(&chunk) {
+                Ok(items) => items.into_iter().for_each(|item| buffer.push(item)),
+                Err(e) => {
+                    eprintln!("[Rust/proxy dvr] Failed to parse the relayed stream: {}", e);
+                    break;
+                }
+            }
+        }
+        buffer.finish();
+        println!("[Rust/proxy dvr] Capture ended.");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::flv::{FlvHeader, TAG_AUDIO};
+
+    const FLV_HEADER: [u8; 13] = [b'F', b'L', b'V', 1, 5, 0, 0, 0, 9, 0, 0, 0, 0];
+    const FRAME_MS: usize = 40;
+
+    fn tag(tag_type: u8, timestamp: u32, body: Vec<u8>) -> FlvItem {
+        FlvItem::Tag(FlvTag {
+            tag_type,
+            timestamp,
+            offset: 0,
+            body,
+        })
+    }
+
+    // Header, metadata and sequence headers
+    fn start(buffer: &DvrBuffer) {
+        buffer.push(FlvItem::Header(FlvHeader {
+            has_video: true,
+            bytes: FLV_HEADER.to_vec(),
+        }));
+        buffer.push(tag(TAG_SCRIPT, 0, vec![2, 0, 10]));
+        buffer.push(tag(TAG_VIDEO, 0, vec![0x17, 0, 0, 0, 0, 1, 0x64, 0, 0x28]));
+        buffer.push(tag(TAG_AUDIO, 0, vec![0xaf, 0, 0x12, 0x10]));
+    }
+
+    // 25 fps video with a keyframe every second, and audio alongside
+    fn push_stream(buffer: &DvrBuffer, from_ms: u32, to_ms: u32, frame_bytes: usize) {
+        for timestamp in (from_ms..to_ms).step_by(FRAME_MS) {
+            let frame_type = if timestamp % 1000 == 0 { 0x17 } else { 0x27 };
+            let mut body = vec![frame_type, 1, 0, 0, 0];
+            body.resize(frame_bytes, 0);
+            buffer.push(tag(TAG_VIDEO, timestamp, body));
+            buffer.push(tag(TAG_AUDIO, timestamp, vec![0xaf, 1, 0x21]));
+        }
+    }
+
+    // The tags a player got, as (type, timestamp, sequence header, keyframe); `chunks`
+    // start with the FLV header unless `resumed`
+    fn received(chunks: &[Bytes], resumed: bool) -> Vec<(u8, u32, bool, bool)> {
+        let mut data = if resumed {
+            FLV_HEADER.to_vec()
+        } else {
+            Vec::new()
+        };
+        for chunk in chunks {
+            data.extend_from_slice(chunk);
+        }
+        let items = FlvReader::new().push(&data).unwrap();
+        assert!(matches!(items[0], FlvItem::Header(_)));
+        items[1..]
+            .iter()
+            .map(|item| match item {
+                FlvItem::Tag(tag) => (
+                    tag.tag_type,
+                    tag.timestamp,
+                    tag.tag_type == TAG_SCRIPT || tag.is_sequence_header(),
+                    tag.is_video_keyframe(),
+                ),
+                FlvItem::Header(_) => panic!("a second FLV header"),
+            })
+            .collect()
+    }
+
+    async fn read_all(buffer: &DvrBuffer, offset_secs: u64) -> Vec<Bytes> {
+        buffer
+            .reader(offset_secs)
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn starts_at_the_keyframe_before_the_offset() {
+        let buffer = DvrBuffer::new(60);
+        start(&buffer);
+        push_stream(&buffer, 0, 10_000, 16);
+        buffer.finish();
+
+        // Live is at 9960 ms
+        for (offset_secs, keyframe) in [(0, 9000), (3, 6000), (9, 0), (600, 0)] {
+            let tags = received(&read_all(&buffer, offset_secs).await, false);
+            // Configs first, stamped with the start point, then the keyframe
+            assert_eq!(
+                tags[..4],
+                [
+                    (TAG_SCRIPT, keyframe, true, false),
+                    (TAG_VIDEO, keyframe, true, true),
+                    (TAG_AUDIO, keyframe, true, false),
+                    (TAG_VIDEO, keyframe, false, true),
+                ]
+            );
+            // Then everything from there to live, once and in order
+            let video: Vec<u32> = tags[3..]
+                .iter()
+                .filter(|tag| tag.0 == TAG_VIDEO)
+                .map(|tag| tag.1)
+                .collect();
+            let expected: Vec<u32> = (keyframe..10_000).step_by(FRAME_MS).collect();
+            assert_eq!(video, expected);
+        }
+        assert_eq!(buffer.buffered_secs(), 9);
+    }
+
+    #[tokio::test]
+    async fn catches_up_in_batches_then_follows_live() {
+        let buffer = DvrBuffer::new(60);
+        start(&buffer);
+        push_stream(&buffer, 0, 3000, 64);
+        let reader = tokio::spawn({
+            let buffer = buffer.clone();
+            async move { read_all(&buffer, 1).await }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        push_stream(&buffer, 3000, 5000, 64);
+        buffer.finish();
+
+        let chunks = reader.await.unwrap();
+        // A tag may push a batch past the limit, but never by more than one tag
+        assert!(chunks[1..]
+            .iter()
+            .all(|chunk| chunk.len() < MAX_READ_BATCH + 100));
+        let tags = received(&chunks, false);
+        // 1960 ms back from live at 2960 ms
+        assert_eq!(tags[3], (TAG_VIDEO, 1000, false, true));
+        let timestamps: Vec<u32> = tags[3..].iter().map(|tag| tag.1).collect();
+        let expected: Vec<u32> = (1000..5000)
+            .step_by(FRAME_MS)
+            .flat_map(|timestamp| [timestamp, timestamp])
+            .collect();
+        assert_eq!(timestamps, expected);
+    }
+
+    #[tokio::test]
+    async fn evicts_by_window_and_size() {
+        let buffer = DvrBuffer::new(2);
+        start(&buffer);
+        push_stream(&buffer, 0, 5000, 16);
+        {
+            let state = buffer.state.lock().unwrap();
+            assert_eq!(state.tags.front().unwrap().timestamp, 2960);
+            assert_eq!(state.start_points.front().unwrap().timestamp, 3000);
+        }
+        assert_eq!(buffer.buffered_secs(), 2);
+
+        // An hour's window, but the frames outgrow MAX_BUFFER_BYTES long before that
+        let buffer = DvrBuffer::new(3600);
+        start(&buffer);
+        push_stream(&buffer, 0, 10_000, 1000);
+        let (first_seq, oldest_keyframe) = {
+            let state = buffer.state.lock().unwrap();
+            let bytes: usize = state.tags.iter().map(|tag| tag.data.len()).sum();
+            assert_eq!(state.bytes, bytes);
+            assert!(bytes <= MAX_BUFFER_BYTES && bytes > MAX_BUFFER_BYTES - 1100);
+            let point = state.start_points.front().unwrap();
+            assert!(point.seq >= state.first_seq);
+            (state.first_seq, point.timestamp)
+        };
+        assert!(first_seq > 0);
+        buffer.finish();
+        let tags = received(&read_all(&buffer, 3600).await, false);
+        assert_eq!(tags[3], (TAG_VIDEO, oldest_keyframe, false, true));
+
+        // A player paused since the start resumes at the oldest keyframe still buffered
+        let mut next = Some(0);
+        let chunk = buffer.read(&mut next, 0).await.unwrap();
+        let tags = received(&[chunk], true);
+        assert_eq!(tags[0], (TAG_VIDEO, oldest_keyframe, false, true));
+    }
+}
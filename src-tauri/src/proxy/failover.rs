@@ -0,0 +1,422 @@
+// Keeps an FLV stream going when its upstream drops. The relay parses what the CDN sends
+// and hands the player whole tags only, so a connection that breaks mid-tag leaves nothing
+// half-written. When the upstream ends, errors or stalls, the stream is re-resolved
+// through the platform it came from (or the same URL is retried) and the new connection
+// is spliced in: its FLV header is dropped, nothing is sent until a keyframe, the codec
+// sequence headers are repeated, and timestamps carry on from where the old stream
+// stopped. The player's connection never notices.
+
+use actix_web::web::Bytes;
+use futures_util::stream::{self, BoxStream};
+use futures_util::{Stream, StreamExt};
+use reqwest::Client;
+use std::time::Duration;
+
+use super::routes::open_upstream;
+use super::streams::{ProxyStreamHeaders, ProxyStreamOrigin};
+use crate::media::flv::{FlvItem, FlvReader, FlvTag, TAG_AUDIO, TAG_VIDEO};
+use crate::platforms::common::PlatformRegistry;
+
+// Attempts since a tag last reached the player; an upstream that accepts the connection
+// and then drops it before sending anything usable still uses one up
+const MAX_RECONNECT_ATTEMPTS: u32 = 5;
+// Waited before each attempt, times the attempt number
+#[cfg(not(test))]
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+#[cfg(test)]
+const RECONNECT_DELAY: Duration = Duration::from_millis(10);
+// Live CDNs send data continuously; this long without any counts as a drop
+const UPSTREAM_READ_TIMEOUT: Duration = Duration::from_secs(15);
+// Gap left between the last tag of the old stream and the first of the new one
+const SPLICE_GAP_MS: i64 = 40;
+
+// Where a stream comes from and how to get it again
+pub struct UpstreamSource {
+    pub url: String,
+    pub headers: ProxyStreamHeaders,
+    pub origin: Option<ProxyStreamOrigin>,
+    pub client: Client,
+    pub platforms: PlatformRegistry,
+    // Told about a re-resolved URL, so later player connections start from it
+    pub on_resolved: Box<dyn Fn(&str) + Send + Sync>,
+}
+
+impl UpstreamSource {
+    async fn resolve_url(&self) -> Result<String, String> {
+        let Some(origin) = &self.origin else {
+            return Ok(self.url.clone());
+        };
+        let stream = self
+            .platforms
+            .get(origin.platform)?
+            .resolve_stream(&origin.room_id, origin.quality.as_deref())
+            .await?;
+        if stream.format != "flv" {
+            return Err(format!(
+                "{} room {} now resolves to a {} stream",
+                origin.platform, origin.room_id, stream.format
+            ));
+        }
+        Ok(stream.url)
+    }
+}
+
+struct Relay {
+    source: UpstreamSource,
+    upstream: Option<BoxStream<'static, reqwest::Result<Bytes>>>,
+    reader: FlvReader,
+    header_sent: bool,
+    expect_video: bool,
+    // Set after a reconnect until the new stream reaches a point the player can resume at
+    waiting_for_start: bool,
+    configs: Vec<FlvTag>, // Sequence headers seen while waiting, one per tag type
+    timestamp_offset: i64,
+    resume_from: u32, // Tags before this (after rebasing) would go backwards; dropped
+    last_timestamp: Option<u32>,
+    failed_attempts: u32,
+    finished: bool,
+}
+
+impl Relay {
+    fn new(source: UpstreamSource) -> Self {
+        Self {
+            source,
+            upstream: None,
+            reader: FlvReader::new(),
+            header_sent: false,
+            expect_video: true,
+            waiting_for_start: false,
+            configs: Vec::new(),
+            timestamp_offset: 0,
+            resume_from: 0,
+            last_timestamp: None,
+            failed_attempts: 0,
+            finished: false,
+        }
+    }
+
+    async fn next_chunk(&mut self) -> Option<Result<Bytes, actix_web::Error>> {
+        loop {
+            if self.finished {
+                return None;
+            }
+            let Some(upstream) = self.upstream.as_mut() else {
+                if let Err(e) = self.reconnect().await {
+                    eprintln!("[Rust/proxy failover] {}", e);
+                    self.finished = true;
+                    return Some(Err(actix_web::error::ErrorBadGateway(e)));
+                }
+                continue;
+            };
+
+            match tokio::time::timeout(UPSTREAM_READ_TIMEOUT, upstream.next()).await {
+                Ok(Some(Ok(chunk))) => match self.splice(&chunk) {
+                    Ok(out) if out.is_empty() => {}
+                    Ok(out) => return Some(Ok(Bytes::from(out))),
+                    Err(e) => {
+                        eprintln!("[Rust/proxy failover] Upstream sent invalid FLV: {}", e);
+                        self.upstream = None;
+                    }
+                },
+                Ok(Some(Err(e))) => {
+                    eprintln!("[Rust/proxy failover] Error reading from upstream: {}", e);
+                    self.upstream = None;
+                }
+                Ok(None) => {
+                    println!("[Rust/proxy failover] Upstream ended the stream.");
+                    self.upstream = None;
+                }
+                Err(_) => {
+                    eprintln!("[Rust/proxy failover] Upstream stopped sending data.");
+                    self.upstream = None;
+                }
+            }
+        }
+    }
+
+    fn start_upstream(&mut self, upstream_response: reqwest::Response) {
+        self.upstream = Some(upstream_response.bytes_stream().boxed());
+        self.restart_reader();
+    }
+
+    // Readies `splice` for the bytes of a new connection
+    fn restart_reader(&mut self) {
+        self.reader = FlvReader::new();
+        // The first connection passes straight through; later ones have to be spliced
+        self.waiting_for_start = self.header_sent;
+        self.configs.clear();
+    }
+
+    async fn reconnect(&mut self) -> Result<(), String> {
+        while self.failed_attempts < MAX_RECONNECT_ATTEMPTS {
+            self.failed_attempts += 1;
+            let attempt = self.failed_attempts;
+            tokio::time::sleep(RECONNECT_DELAY * attempt).await;
+            let url = match self.source.resolve_url().await {
+                Ok(url) => url,
+                Err(e) => {
+                    eprintln!(
+                        "[Rust/proxy failover] Attempt {} failed to re-resolve the stream: {}",
+                        attempt, e
+                    );
+                    continue;
+                }
+            };
+            // open_upstream logs its own failures
+            if let Ok(upstream_response) =
+                open_upstream(&url, &self.source.headers, &self.source.client).await
+            {
+                println!(
+                    "[Rust/proxy failover] Reconnected to upstream on attempt {}.",
+                    attempt
+                );
+                if url != self.source.url {
+                    (self.source.on_resolved)(&url);
+                    self.source.url = url;
+                }
+                self.start_upstream(upstream_response);
+                return Ok(());
+            }
+        }
+        Err(format!(
+            "Gave up reconnecting to upstream after {} attempts",
+            MAX_RECONNECT_ATTEMPTS
+        ))
+    }
+
+    // Turns an upstream chunk into the bytes to send the player
+    fn splice(&mut self, chunk: &[u8]) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        for item in self.reader.push(chunk)? {
+            let tag = match item {
+                // A second header would restart the stream for the player
+                FlvItem::Header(header) => {
+                    if !self.header_sent {
+                        out.extend_from_slice(&header.bytes);
+                        self.header_sent = true;
+                        self.expect_video = header.has_video;
+                    }
+                    continue;
+                }
+                FlvItem::Tag(tag) => tag,
+            };
+
+            if self.waiting_for_start {
+                if tag.is_sequence_header() {
+                    self.configs
+                        .retain(|config| config.tag_type != tag.tag_type);
+                    self.configs.push(tag);
+                    continue;
+                }
+                let starts = if self.expect_video {
+                    tag.tag_type == TAG_VIDEO && tag.is_video_keyframe()
+                } else {
+                    tag.tag_type == TAG_AUDIO
+                };
+                if !starts {
+                    continue;
+                }
+                let resume_at = self
+                    .last_timestamp
+                    .map_or(0, |last| last as i64 + SPLICE_GAP_MS);
+                self.timestamp_offset = resume_at - tag.timestamp as i64;
+                self.resume_from = resume_at as u32;
+                self.waiting_for_start = false;
+                // The new connection may have different codec parameters
+                for config in std::mem::take(&mut self.configs) {
+                    out.extend(config.encode(self.resume_from));
+                }
+                println!(
+                    "[Rust/proxy failover] Spliced in the new upstream at {} ms.",
+                    resume_at
+                );
+            }
+
+            let timestamp =
+                (tag.timestamp as i64 + self.timestamp_offset).clamp(0, u32::MAX as i64) as u32;
+            if timestamp < self.resume_from {
+                continue;
+            }
+            self.last_timestamp = Some(self.last_timestamp.map_or(timestamp, |t| t.max(timestamp)));
+            self.failed_attempts = 0;
+            out.extend(tag.encode(timestamp));
+        }
+        Ok(out)
+    }
+}
+
+// The player-facing body for an FLV stream whose first upstream connection is open
+pub fn relay_flv(
+    upstream_response: reqwest::Response,
+    source: UpstreamSource,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    let mut relay = Relay::new(source);
+    relay.start_upstream(upstream_response);
+    stream::unfold(relay, |mut relay| async move {
+        let chunk = relay.next_chunk().await?;
+        Some((chunk, relay))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const FLV_HEADER: [u8; 13] = [b'F', b'L', b'V', 1, 1, 0, 0, 0, 9, 0, 0, 0, 0];
+
+    // Answers every request with 200, sends `body` and closes; returns the URL and a
+    // count of the connections it took
+    async fn serve(body: Vec<u8>) -> (String, Arc<AtomicU32>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/live.flv", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicU32::new(0));
+        let counter = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut request = [0u8; 1024];
+                let _ = socket.read(&mut request).await;
+                let _ = socket
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: video/x-flv\r\nConnection: close\r\n\r\n")
+                    .await;
+                let _ = socket.write_all(&body).await;
+            }
+        });
+        (url, connections)
+    }
+
+    fn source(url: &str) -> UpstreamSource {
+        UpstreamSource {
+            url: url.to_string(),
+            headers: ProxyStreamHeaders::default(),
+            origin: None,
+            client: Client::new(),
+            platforms: PlatformRegistry::default(),
+            on_resolved: Box::new(|_| {}),
+        }
+    }
+
+    async fn relay(url: &str) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+        let source = source(url);
+        let response = open_upstream(url, &source.headers, &source.client)
+            .await
+            .ok()
+            .unwrap();
+        relay_flv(response, source)
+    }
+
+    fn tag(tag_type: u8, timestamp: u32, body: &[u8]) -> Vec<u8> {
+        FlvTag {
+            tag_type,
+            timestamp,
+            offset: 0,
+            body: body.to_vec(),
+        }
+        .encode(timestamp)
+    }
+
+    const AVC_CONFIG: [u8; 9] = [0x17, 0, 0, 0, 0, 1, 0x64, 0, 0x28];
+    const NEW_AVC_CONFIG: [u8; 9] = [0x17, 0, 0, 0, 0, 1, 0x64, 0, 0x1f];
+    const AAC_CONFIG: [u8; 4] = [0xaf, 0, 0x12, 0x10];
+    const KEYFRAME: [u8; 10] = [0x17, 1, 0, 0, 0, 0, 0, 0, 1, 0x65];
+    const INTER_FRAME: [u8; 10] = [0x27, 1, 0, 0, 0, 0, 0, 0, 1, 0x41];
+    const AAC_FRAME: [u8; 3] = [0xaf, 1, 0x21];
+
+    #[test]
+    fn splices_a_new_upstream_after_the_old_one() {
+        let mut first = FLV_HEADER.to_vec();
+        first.extend(tag(TAG_VIDEO, 0, &AVC_CONFIG));
+        first.extend(tag(TAG_AUDIO, 0, &AAC_CONFIG));
+        first.extend(tag(TAG_VIDEO, 1000, &KEYFRAME));
+        first.extend(tag(TAG_AUDIO, 1010, &AAC_FRAME));
+        first.extend(tag(TAG_VIDEO, 1033, &INTER_FRAME));
+        first.extend(tag(TAG_AUDIO, 1040, &AAC_FRAME));
+
+        // A new connection to a stream whose clock started elsewhere, joined mid-GOP
+        let mut second = FLV_HEADER.to_vec();
+        second.extend(tag(TAG_VIDEO, 50000, &NEW_AVC_CONFIG));
+        second.extend(tag(TAG_AUDIO, 50000, &AAC_CONFIG));
+        second.extend(tag(TAG_VIDEO, 50000, &INTER_FRAME));
+        second.extend(tag(TAG_AUDIO, 50010, &AAC_FRAME));
+        second.extend(tag(TAG_VIDEO, 50100, &KEYFRAME));
+        second.extend(tag(TAG_AUDIO, 50110, &AAC_FRAME));
+        second.extend(tag(TAG_VIDEO, 50133, &INTER_FRAME));
+
+        let mut relay = Relay::new(source("http://127.0.0.1/live.flv"));
+        relay.restart_reader();
+        let mut out = relay.splice(&first).unwrap();
+        relay.restart_reader();
+        // Split mid-tag, the way a TCP stream arrives
+        for chunk in second.chunks(7) {
+            out.extend(relay.splice(chunk).unwrap());
+        }
+
+        let items = FlvReader::new().push(&out).unwrap();
+        let headers = items
+            .iter()
+            .filter(|item| matches!(item, FlvItem::Header(_)))
+            .count();
+        assert_eq!(headers, 1);
+        let tags: Vec<(u8, u32, &[u8])> = items
+            .iter()
+            .filter_map(|item| match item {
+                FlvItem::Tag(tag) => Some((tag.tag_type, tag.timestamp, tag.body.as_slice())),
+                FlvItem::Header(_) => None,
+            })
+            .collect();
+        // The old stream passes through untouched
+        assert_eq!(tags[5], (TAG_AUDIO, 1040, &AAC_FRAME[..]));
+        let resume_at = 1040 + SPLICE_GAP_MS as u32;
+        assert_eq!(
+            tags[6..],
+            [
+                (TAG_VIDEO, resume_at, &NEW_AVC_CONFIG[..]),
+                (TAG_AUDIO, resume_at, &AAC_CONFIG[..]),
+                (TAG_VIDEO, resume_at, &KEYFRAME[..]),
+                (TAG_AUDIO, resume_at + 10, &AAC_FRAME[..]),
+                (TAG_VIDEO, resume_at + 33, &INTER_FRAME[..]),
+            ]
+        );
+        assert_eq!(tags.len(), 11);
+        assert!(tags.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_upstreams_that_never_send_a_tag() {
+        let (url, connections) = serve(FLV_HEADER.to_vec()).await;
+        let chunks: Vec<_> =
+            tokio::time::timeout(Duration::from_secs(30), relay(&url).await.collect())
+                .await
+                .expect("the relay should give up");
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].as_ref().unwrap().as_ref(), &FLV_HEADER);
+        assert!(chunks[1].is_err());
+        assert_eq!(
+            connections.load(Ordering::SeqCst),
+            1 + MAX_RECONNECT_ATTEMPTS
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_reconnecting_while_tags_get_through() {
+        let keyframe = FlvTag {
+            tag_type: TAG_VIDEO,
+            timestamp: 0,
+            offset: 0,
+            body: vec![0x17, 1, 0, 0, 0, 0, 0, 0, 1, 0x65],
+        };
+        let mut body = FLV_HEADER.to_vec();
+        body.extend(keyframe.encode(0));
+        let (url, connections) = serve(body).await;
+
+        // Each connection yields a chunk or two, so this outlasts the attempt budget
+        let rounds = MAX_RECONNECT_ATTEMPTS as usize * 4;
+        let chunks: Vec<_> = relay(&url).await.take(rounds).collect().await;
+        assert!(chunks.iter().all(Result::is_ok));
+        assert!(connections.load(Ordering::SeqCst) > 1 + MAX_RECONNECT_ATTEMPTS);
+    }
+}
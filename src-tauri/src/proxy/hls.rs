@@ -0,0 +1,286 @@
+// HLS through the proxy. Playlists are fetched fresh on every request and have each URI
+// rewritten to a local route; segments are fetched with the stream's headers and the most
+// recent ones are kept in memory. Rewritten URIs carry a number rather than the upstream
+// URL, so the routes only ever fetch what one of the stream's playlists pointed at.
+
+use actix_web::web::Bytes;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+// Live playlists keep re-listing the same URIs; older ones age out in insertion order
+const MAX_KNOWN_URIS: usize = 1024;
+const MAX_CACHED_SEGMENTS: usize = 24;
+const MAX_CACHE_BYTES: usize = 64 * 1024 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HlsResource {
+    Playlist,
+    Segment, // Media segments, init sections and keys
+}
+
+#[derive(Default)]
+struct KnownUris {
+    ids: HashMap<String, u64>,
+    urls: HashMap<u64, (String, HlsResource)>,
+    order: VecDeque<u64>,
+    next_id: u64,
+}
+
+#[derive(Clone)]
+pub struct CachedSegment {
+    pub content_type: Option<String>,
+    pub data: Bytes,
+}
+
+#[derive(Default)]
+struct SegmentCache {
+    entries: HashMap<u64, CachedSegment>,
+    order: VecDeque<u64>,
+    bytes: usize,
+}
+
+#[derive(Default, Clone)]
+pub struct HlsState {
+    uris: Arc<Mutex<KnownUris>>,
+    cache: Arc<Mutex<SegmentCache>>,
+}
+
+impl HlsState {
+    // The same URL always gets the same id while it's remembered, so segments that stay
+    // in a live playlist across refreshes keep hitting the cache
+    fn uri_id(&self, url: String, kind: HlsResource) -> u64 {
+        let mut uris = self.uris.lock().unwrap();
+        if let Some(id) = uris.ids.get(&url) {
+            return *id;
+        }
+        let id = uris.next_id;
+        uris.next_id += 1;
+        uris.ids.insert(url.clone(), id);
+        uris.urls.insert(id, (url, kind));
+        uris.order.push_back(id);
+        while uris.order.len() > MAX_KNOWN_URIS {
+            if let Some(old) = uris.order.pop_front() {
+                if let Some((old_url, _)) = uris.urls.remove(&old) {
+                    uris.ids.remove(&old_url);
+                }
+            }
+        }
+        id
+    }
+
+    pub fn lookup(&self, id: u64, kind: HlsResource) -> Option<String> {
+        let uris = self.uris.lock().unwrap();
+        match uris.urls.get(&id) {
+            Some((url, known_kind)) if *known_kind == kind => Some(url.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn cached(&self, id: u64) -> Option<CachedSegment> {
+        self.cache.lock().unwrap().entries.get(&id).cloned()
+    }
+
+    pub fn cache(&self, id: u64, segment: CachedSegment) {
+        let mut cache = self.cache.lock().unwrap();
+        if segment.data.len() > MAX_CACHE_BYTES || cache.entries.contains_key(&id) {
+            return;
+        }
+        cache.bytes += segment.data.len();
+        cache.entries.insert(id, segment);
+        cache.order.push_back(id);
+        while cache.order.len() > MAX_CACHED_SEGMENTS || cache.bytes > MAX_CACHE_BYTES {
+            let Some(old) = cache.order.pop_front() else {
+                break;
+            };
+            if let Some(evicted) = cache.entries.remove(&old) {
+                cache.bytes -= evicted.data.len();
+            }
+        }
+    }
+}
+
+// Judged by the path, since CDN URLs carry long query strings
+pub fn is_hls_url(url: &str) -> bool {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed.path().ends_with(".m3u8"),
+        Err(_) => url.contains(".m3u8"),
+    }
+}
+
+fn local_path(stream_id: &str, id: u64, kind: HlsResource) -> String {
+    match kind {
+        HlsResource::Playlist => format!("/stream/{}/playlist/{}.m3u8", stream_id, id),
+        HlsResource::Segment => format!("/stream/{}/segment/{}", stream_id, id),
+    }
+}
+
+// Keeps URIs the proxy can't fetch (data:, skd:// and the like) as they are
+fn rewrite_uri(
+    uri: &str,
+    kind: HlsResource,
+    playlist_url: &reqwest::Url,
+    stream_id: &str,
+    state: &HlsState,
+) -> String {
+    match playlist_url.join(uri) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {
+            local_path(stream_id, state.uri_id(url.to_string(), kind), kind)
+        }
+        _ => uri.to_string(),
+    }
+}
+
+// Rewrites the URI="..." attribute of a tag line, if it has one
+fn rewrite_tag(
+    line: &str,
+    playlist_url: &reqwest::Url,
+    stream_id: &str,
+    state: &HlsState,
+) -> String {
+    const URI_ATTR: &str = "URI=\"";
+    let Some(start) = line.find(URI_ATTR).map(|i| i + URI_ATTR.len()) else {
+        return line.to_string();
+    };
+    let Some(len) = line[start..].find('"') else {
+        return line.to_string();
+    };
+    // Renditions and I-frame playlists are playlists; keys, init sections and parts are
+    // fetched like segments
+    let kind = if line.starts_with("#EXT-X-MEDIA:")
+        || line.starts_with("#EXT-X-I-FRAME-STREAM-INF:")
+        || line.starts_with("#EXT-X-RENDITION-REPORT:")
+    {
+        HlsResource::Playlist
+    } else {
+        HlsResource::Segment
+    };
+    let uri = rewrite_uri(
+        &line[start..start + len],
+        kind,
+        playlist_url,
+        stream_id,
+        state,
+    );
+    format!("{}{}{}", &line[..start], uri, &line[start + len..])
+}
+
+// Points every URI in a master or media playlist at the proxy. `playlist_url` is where
+// the playlist was actually fetched from, after redirects, since relative URIs resolve
+// against it.
+pub fn rewrite_playlist(
+    body: &str,
+    playlist_url: &reqwest::Url,
+    stream_id: &str,
+    state: &HlsState,
+) -> String {
+    // In a master playlist the URI lines are variant playlists, otherwise media segments
+    let line_kind = if body.contains("#EXT-X-STREAM-INF") {
+        HlsResource::Playlist
+    } else {
+        HlsResource::Segment
+    };
+    let mut out = String::with_capacity(body.len());
+    for line in body.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with('#') {
+            out.push_str(&rewrite_tag(line, playlist_url, stream_id, state));
+        } else if line.trim().is_empty() {
+            out.push_str(line);
+        } else {
+            out.push_str(&rewrite_uri(
+                line.trim(),
+                line_kind,
+                playlist_url,
+                stream_id,
+                state,
+            ));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(raw: &str) -> reqwest::Url {
+        reqwest::Url::parse(raw).unwrap()
+    }
+
+    #[test]
+    fn rewrites_master_playlists() {
+        let state = HlsState::default();
+        let playlist_url = url("https://cdn.example.com/live/room/master.m3u8?token=abc");
+        let out = rewrite_playlist(
+            include_str!("fixtures/master.m3u8"),
+            &playlist_url,
+            "s1",
+            &state,
+        );
+        assert_eq!(out, include_str!("fixtures/master.rewritten.m3u8"));
+        let upstream: Vec<Option<String>> = (0..4)
+            .map(|id| state.lookup(id, HlsResource::Playlist))
+            .collect();
+        assert_eq!(
+            upstream,
+            [
+                "https://cdn.example.com/live/room/audio/index.m3u8?token=abc",
+                "https://cdn.example.com/live/room/1080p/index.m3u8?token=abc&expires=1760000000",
+                "https://cdn2.example.com/live/720p.m3u8?sign=xyz",
+                "https://cdn.example.com/live/iframes.m3u8",
+            ]
+            .map(|u| Some(u.to_string()))
+        );
+        // Ids only resolve for the kind of resource they were handed out for
+        assert_eq!(state.lookup(1, HlsResource::Segment), None);
+    }
+
+    #[test]
+    fn rewrites_media_playlists() {
+        let state = HlsState::default();
+        let playlist_url = url("https://cdn.example.com/live/room/1080p/index.m3u8?token=abc");
+        let out = rewrite_playlist(
+            include_str!("fixtures/media.m3u8"),
+            &playlist_url,
+            "s1",
+            &state,
+        );
+        assert_eq!(out, include_str!("fixtures/media.rewritten.m3u8"));
+        let upstream: Vec<Option<String>> = (0..5)
+            .map(|id| state.lookup(id, HlsResource::Segment))
+            .collect();
+        assert_eq!(
+            upstream,
+            [
+                "https://cdn.example.com/live/room/keys/k1.key?kid=7",
+                "https://cdn.example.com/live/room/1080p/init.mp4",
+                "https://cdn.example.com/live/room/1080p/seg1500.m4s?txspiseq=1",
+                "https://cdn3.example.com/live/room/seg1501.m4s",
+                "https://cdn4.example.com/live/room/seg1502.m4s?a=1&b=2",
+            ]
+            .map(|u| Some(u.to_string()))
+        );
+        assert_eq!(state.lookup(5, HlsResource::Segment), None);
+
+        // A refresh keeps the ids of segments still listed
+        let refreshed = "#EXTM3U\r\n#EXTINF:2.000,\r\nseg1500.m4s?txspiseq=1\r\n#EXTINF:2.000,\r\nseg1503.m4s\r\n";
+        assert_eq!(
+            rewrite_playlist(refreshed, &playlist_url, "s1", &state),
+            "#EXTM3U\n#EXTINF:2.000,\n/stream/s1/segment/2\n#EXTINF:2.000,\n/stream/s1/segment/5\n"
+        );
+    }
+
+    #[test]
+    fn leaves_tags_without_a_uri_alone() {
+        let state = HlsState::default();
+        let playlist_url = url("https://cdn.example.com/live/index.m3u8");
+        for line in [
+            "#EXT-X-PROGRAM-DATE-TIME:2025-10-01T12:00:00.000+08:00",
+            "#EXT-X-KEY:METHOD=NONE",
+            "#EXT-X-MAP:URI=\"unterminated",
+        ] {
+            assert_eq!(rewrite_tag(line, &playlist_url, "s1", &state), line);
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use actix_web::{dev::ServerHandle, web, App, HttpServer};
+// use tokio::sync::Mutex as TokioMutex; // Not used here
+use reqwest::Client; // Changed from awc::Client
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Instant;
+use tauri::{AppHandle, Manager, State}; // Removed unused async_runtime
+
+use crate::media::flv::{FlvStatsCollector, FlvStreamStats};
+use crate::platforms::common::PlatformRegistry;
+// Changed path: StreamUrlStore is now at the crate root (main.rs)
+use crate::StreamUrlStore;
+
+mod dvr;
+mod failover;
+mod hls;
+mod routes;
+pub mod settings;
+pub mod streams;
+mod traffic;
+
+pub use settings::ProxySettingsStore;
+pub use streams::ProxyStreamRegistry;
+use traffic::StreamTraffic;
+
+pub struct RunningProxy {
+    handle: ServerHandle,
+    port: u16,
+}
+
+// Define a struct to hold the server handle in a Tauri managed state
+#[derive(Default)]
+pub struct ProxyServerHandle(pub StdMutex<Option<RunningProxy>>);
+
+// Stats for a stream going through the proxy, parsed from the bytes the player gets
+#[derive(Default, Clone)]
+pub struct StreamStatsStore(pub Arc<StdMutex<Option<FlvStatsCollector>>>);
+
+impl StreamStatsStore {
+    fn record(&self, chunk: &[u8]) {
+        if let Some(collector) = self.0.lock().unwrap().as_mut() {
+            collector.push(chunk);
+        }
+    }
+}
+
+// What /health reports about the running server
+struct ServerInfo {
+    port: u16,
+    started_at: Instant,
+}
+
+// The preferred port when it's free, otherwise whatever the OS hands out, so a busy port
+// or a second instance of the app doesn't stop the proxy from starting
+fn bind_listener(preferred_port: Option<u16>) -> Result<TcpListener, String> {
+    if let Some(port) = preferred_port {
+        match TcpListener::bind(("127.0.0.1", port)) {
+            Ok(listener) => return Ok(listener),
+            Err(e) => eprintln!(
+                "[Rust/proxy.rs] Preferred port {} is unavailable ({}), using a free one.",
+                port, e
+            ),
+        }
+    }
+    TcpListener::bind(("127.0.0.1", 0))
+        .map_err(|e| format!("[Rust/proxy.rs] Failed to bind a free port: {}", e))
+}
+
+// Starts the server unless it's already running, and returns its base URL. Streams are
+// looked up per request, so one server serves whatever is registered at the time.
+// Must be called from within the async runtime.
+fn ensure_proxy_server(app_handle: &AppHandle) -> Result<String, String> {
+    let server_state = app_handle.state::<ProxyServerHandle>();
+    // Held until the new server is stored, so concurrent callers can't start two
+    let mut running = server_state.0.lock().unwrap();
+    if let Some(proxy) = running.as_ref() {
+        return Ok(format!("http://127.0.0.1:{}", proxy.port));
+    }
+
+    let preferred_port = app_handle.state::<ProxySettingsStore>().preferred_port();
+    let listener = bind_listener(preferred_port).map_err(|e| {
+        eprintln!("{}", e);
+        e
+    })?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| format!("[Rust/proxy.rs] Failed to read the bound address: {}", e))?
+        .port();
+    // Cloned into every worker; all of these are Arc based and Send + Sync
+    let stream_url_data_for_actix =
+        web::Data::new(app_handle.state::<StreamUrlStore>().inner().clone());
+    let stats_data_for_actix =
+        web::Data::new(app_handle.state::<StreamStatsStore>().inner().clone());
+    let registry_data_for_actix =
+        web::Data::new(app_handle.state::<ProxyStreamRegistry>().inner().clone());
+    // For re-resolving streams whose upstream drops
+    let platforms_for_actix =
+        web::Data::new(app_handle.state::<PlatformRegistry>().inner().clone());
+    // The /live.flv stream's traffic; starts over with each server
+    let legacy_traffic_for_actix = web::Data::new(StreamTraffic::default());
+    let server_info_for_actix = web::Data::new(ServerInfo {
+        port,
+        started_at: Instant::now(),
+    });
+
+    let server = match HttpServer::new(move || {
+        // Create reqwest::Client inside the closure for each worker thread
+        let app_data_reqwest_client = web::Data::new(Client::new()); // Changed to reqwest::Client
+        App::new()
+            .app_data(stream_url_data_for_actix.clone())
+            .app_data(app_data_reqwest_client) // Provide reqwest client
+            .app_data(stats_data_for_actix.clone())
+            .app_data(registry_data_for_actix.clone())
+            .app_data(legacy_traffic_for_actix.clone())
+            .app_data(server_info_for_actix.clone())
+            .app_data(platforms_for_actix.clone())
+            .wrap(actix_cors::Cors::permissive())
+            .route("/health", web::get().to(routes::health_handler))
+            .route("/streams", web::get().to(routes::streams_handler))
+            .route("/live.flv", web::get().to(routes::flv_proxy_handler))
+            .route("/live.mp4", web::get().to(routes::mp4_proxy_handler))
+            .route(
+                "/stream/{id}.flv",
+                web::get().to(routes::stream_flv_handler),
+            )
+            .route(
+                "/stream/{id}.mp4",
+                web::get().to(routes::stream_mp4_handler),
+            )
+            .route(
+                "/stream/{id}.m3u8",
+                web::get().to(routes::stream_hls_handler),
+            )
+            .route(
+                "/stream/{id}/playlist/{n}.m3u8",
+                web::get().to(routes::hls_playlist_handler),
+            )
+            .route(
+                "/stream/{id}/segment/{n}",
+                web::get().to(routes::hls_segment_handler),
+            )
+    })
+    .listen(listener)
+    {
+        Ok(srv) => srv,
+        Err(e) => {
+            let err_msg = format!("[Rust/proxy.rs] Failed to listen on port {}: {}", port, e);
+            eprintln!("{}", err_msg);
+            return Err(err_msg);
+        }
+    }
+    .run();
+
+    *running = Some(RunningProxy {
+        handle: server.handle(),
+        port,
+    });
+
+    // Use tauri::async_runtime::spawn directly
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = server.await {
+            eprintln!("[Rust/proxy.rs] Proxy server run error: {}", e);
+        } else {
+            println!("[Rust/proxy.rs] Proxy server on port {} shut down.", port);
+        }
+    });
+
+    println!("[Rust/proxy.rs] Proxy server listening on port {}.", port);
+    Ok(format!("http://127.0.0.1:{}", port))
+}
+
+// Serves whatever StreamUrlStore holds at /live.flv and returns that URL, with the port the
+// server actually bound. Registered streams (streams::add_proxy_stream) keep playing; only
+// the single global URL is shared.
+#[tauri::command]
+pub async fn start_proxy(
+    app_handle: AppHandle,
+    stream_url_store: State<'_, StreamUrlStore>,
+) -> Result<String, String> {
+    let current_stream_url = stream_url_store.url.lock().unwrap().clone();
+
+    if current_stream_url.is_empty() {
+        return Err("Stream URL is not set in store. Cannot start proxy.".to_string());
+    }
+
+    let base_url = ensure_proxy_server(&app_handle)?;
+    Ok(format!("{}/live.flv", base_url))
+}
+
+// Shuts the server down, ending every proxied stream
+#[tauri::command]
+pub async fn stop_proxy(
+    server_handle_state: State<'_, ProxyServerHandle>,
+    stats_store: State<'_, StreamStatsStore>,
+    registry: State<'_, ProxyStreamRegistry>,
+) -> Result<(), String> {
+    *stats_store.0.lock().unwrap() = None;
+    registry.clear();
+    // Ensure MutexGuard is dropped before .await
+    let proxy_to_stop = { server_handle_state.0.lock().unwrap().take() };
+
+    if let Some(proxy) = proxy_to_stop {
+        proxy.handle.stop(false).await; // Changed to non-graceful shutdown
+        println!("[Rust/proxy.rs] stop_proxy: Initiated non-graceful shutdown.");
+    } else {
+        println!("[Rust/proxy.rs] stop_proxy command: No proxy server was running or handle already taken.");
+    }
+    Ok(())
+}
+
+// Codec, resolution, fps, bitrate and recent keyframes of a proxied stream: the one
+// registered as `stream_id`, or the /live.flv stream when it's omitted. None when nothing
+// is playing.
+#[tauri::command]
+pub fn get_stream_stats(
+    stream_id: Option<String>,
+    stats_store: State<'_, StreamStatsStore>,
+    registry: State<'_, ProxyStreamRegistry>,
+) -> Option<FlvStreamStats> {
+    let stats = match stream_id {
+        Some(id) => registry.stats(&id)?,
+        None => stats_store.inner().clone(),
+    };
+    let collector = stats.0.lock().unwrap();
+    collector.as_ref().map(FlvStatsCollector::snapshot)
+}
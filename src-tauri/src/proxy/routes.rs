@@ -0,0 +1,526 @@
+// Request handlers: the single-stream /live.flv and /live.mp4 routes backed by
+// StreamUrlStore, the /stream/{id}.flv and /stream/{id}.mp4 routes backed by the
+// ProxyStreamRegistry, HLS playlists and segments for registered .m3u8 streams, and the
+// /health and /streams JSON endpoints.
+
+use actix_web::web::Bytes;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use futures_util::future::{self, BoxFuture};
+use futures_util::{FutureExt, Stream, StreamExt, TryStreamExt};
+use reqwest::Client; // Changed from awc::Client
+use serde::{Deserialize, Serialize};
+
+use super::failover::{relay_flv, UpstreamSource};
+use super::hls::{is_hls_url, rewrite_playlist, CachedSegment, HlsResource};
+use super::streams::{ProxyStreamHeaders, ProxyStreamRegistry, StreamConnection};
+use super::traffic::{StreamTraffic, TrafficSnapshot};
+use super::{ServerInfo, StreamStatsStore};
+use crate::media::flv::{FlvReader, FlvStatsCollector};
+use crate::media::fmp4::Fmp4Remuxer;
+use crate::platforms::common::PlatformRegistry;
+// Changed path: StreamUrlStore is now at the crate root (main.rs)
+use crate::StreamUrlStore;
+
+#[derive(Clone, Copy)]
+enum OutputFormat {
+    Flv,
+    Mp4,
+}
+
+// Connects to a stream, playlist or segment URL; on failure, the Err is the response for
+// the player
+pub async fn open_upstream(
+    url: &str,
+    headers: &ProxyStreamHeaders,
+    client: &Client,
+) -> Result<reqwest::Response, HttpResponse> {
+    match headers.apply(client.get(url)).send().await {
+        Ok(upstream_response) => {
+            if upstream_response.status().is_success() {
+                Ok(upstream_response)
+            } else {
+                let status_from_reqwest = upstream_response.status(); // Renamed for clarity
+                let error_text = upstream_response
+                    .text()
+                    .await
+                    .unwrap_or_else(|e| format!("Failed to read error body from upstream: {}", e));
+                eprintln!(
+                    "[Rust/proxy.rs handler] Upstream request to {} failed with status: {}. Body: {}",
+                    url,
+                    status_from_reqwest, // Use the renamed variable for logging
+                    error_text
+                );
+                // Convert reqwest::StatusCode to actix_web::http::StatusCode
+                let actix_status_code =
+                    actix_web::http::StatusCode::from_u16(status_from_reqwest.as_u16())
+                        .unwrap_or(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR);
+
+                Err(HttpResponse::build(actix_status_code).body(format!(
+                    "Error fetching {} from upstream (reqwest). Status: {}. Details: {}",
+                    url,
+                    status_from_reqwest, // Log original reqwest status code
+                    error_text
+                )))
+            }
+        }
+        Err(e) => {
+            eprintln!(
+                "[Rust/proxy.rs handler] Failed to send request to upstream {} with reqwest: {}",
+                url, e
+            );
+            Err(HttpResponse::InternalServerError().body(format!(
+                "Error connecting to upstream {} with reqwest: {}",
+                url, e
+            )))
+        }
+    }
+}
+
+// FLV chunks in, fragmented MP4 out; chunks that complete no fragment are skipped
+fn remux_to_mp4(
+    chunks: impl Stream<Item = Result<Bytes, actix_web::Error>>,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    let mut reader = FlvReader::new();
+    let mut remuxer = Fmp4Remuxer::new();
+    chunks
+        .and_then(move |chunk| {
+            let remuxed = reader.push(&chunk).and_then(|items| {
+                let mut out = Vec::new();
+                for item in &items {
+                    out.extend(remuxer.push(item)?);
+                }
+                Ok(out)
+            });
+            future::ready(remuxed.map(Bytes::from).map_err(|e| {
+                eprintln!("[Rust/proxy.rs handler] Failed to remux stream: {}", e);
+                actix_web::error::ErrorInternalServerError(e)
+            }))
+        })
+        .try_filter(|bytes| future::ready(!bytes.is_empty()))
+}
+
+// Counts what the player is sent, and the connection itself while the body is alive
+fn count_traffic(
+    body: impl Stream<Item = Result<Bytes, actix_web::Error>>,
+    traffic: StreamTraffic,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    let connection = traffic.connect();
+    body.inspect_ok(move |bytes| {
+        let _connection = &connection;
+        traffic.record(bytes.len());
+    })
+}
+
+fn respond(
+    format: OutputFormat,
+    chunks: impl Stream<Item = Result<Bytes, actix_web::Error>> + 'static,
+    traffic: StreamTraffic,
+    closed: BoxFuture<'static, ()>,
+) -> HttpResponse {
+    match format {
+        OutputFormat::Flv => HttpResponse::Ok()
+            .content_type("video/x-flv")
+            .streaming(count_traffic(chunks.take_until(closed), traffic)),
+        // For players that do better with MSE (or a plain <video> element) than with
+        // flv.js, e.g. for HEVC
+        OutputFormat::Mp4 => HttpResponse::Ok()
+            .content_type("video/mp4")
+            .streaming(count_traffic(
+                remux_to_mp4(chunks).take_until(closed),
+                traffic,
+            )),
+    }
+}
+
+// Relays the upstream body, reconnecting when it drops, until the relay gives up or
+// `closed` completes
+fn stream_response(
+    upstream_response: reqwest::Response,
+    source: UpstreamSource,
+    format: OutputFormat,
+    stats: StreamStatsStore,
+    traffic: StreamTraffic,
+    closed: BoxFuture<'static, ()>,
+) -> HttpResponse {
+    // Each player connection starts a fresh stream, so the stats start over too
+    *stats.0.lock().unwrap() = Some(FlvStatsCollector::new());
+
+    let chunks = relay_flv(upstream_response, source).inspect_ok(move |chunk| stats.record(chunk));
+    respond(format, chunks, traffic, closed)
+}
+
+async fn serve_legacy_stream(
+    format: OutputFormat,
+    stream_url_store: &StreamUrlStore,
+    client: &Client,
+    platforms: &PlatformRegistry,
+    stats_store: &StreamStatsStore,
+    traffic: &StreamTraffic,
+) -> HttpResponse {
+    let url = stream_url_store.url.lock().unwrap().clone();
+    if url.is_empty() {
+        return HttpResponse::NotFound().body("Stream URL is not set or empty.");
+    }
+    let headers = ProxyStreamHeaders::default();
+    match open_upstream(&url, &headers, client).await {
+        Ok(upstream_response) => stream_response(
+            upstream_response,
+            // No room to re-resolve from here, so reconnects retry the same URL
+            UpstreamSource {
+                url,
+                headers,
+                origin: None,
+                client: client.clone(),
+                platforms: platforms.clone(),
+                on_resolved: Box::new(|_| {}),
+            },
+            format,
+            stats_store.clone(),
+            traffic.clone(),
+            future::pending().boxed(),
+        ),
+        Err(error_response) => error_response,
+    }
+}
+
+async fn serve_registered_stream(
+    format: OutputFormat,
+    id: &str,
+    registry: &ProxyStreamRegistry,
+    client: &Client,
+    platforms: &PlatformRegistry,
+    offset_secs: Option<u64>,
+) -> HttpResponse {
+    let Some(connection) = registry.connect(id) else {
+        return HttpResponse::NotFound().body(format!("No proxied stream with id '{}'.", id));
+    };
+    if is_hls_url(&connection.url) {
+        return HttpResponse::BadRequest().body(format!(
+            "'{}' is an HLS stream; play /stream/{}.m3u8 instead.",
+            id, id
+        ));
+    }
+    // Ends the response when the stream is removed or replaced
+    let mut closed_rx = connection.closed_rx.clone();
+    let closed = async move { while closed_rx.changed().await.is_ok() {} };
+
+    if let Some(buffer) = &connection.dvr {
+        let chunks = buffer.reader(offset_secs.unwrap_or(0));
+        return respond(format, chunks, connection.traffic, closed.boxed());
+    }
+    if offset_secs.is_some() {
+        return HttpResponse::BadRequest().body(
+            "Time-shift is off; set dvrSeconds in the proxy settings and re-add the stream.",
+        );
+    }
+    match open_upstream(&connection.url, &connection.headers, client).await {
+        Ok(upstream_response) => {
+            let source =
+                registry.upstream_source(id, &connection, client.clone(), platforms.clone());
+            stream_response(
+                upstream_response,
+                source,
+                format,
+                connection.stats,
+                connection.traffic,
+                closed.boxed(),
+            )
+        }
+        Err(error_response) => error_response,
+    }
+}
+
+// Fetches a playlist and returns it with every URI pointing back at the proxy
+async fn serve_playlist(
+    stream_id: &str,
+    url: &str,
+    connection: &StreamConnection,
+    client: &Client,
+) -> HttpResponse {
+    let upstream_response = match open_upstream(url, &connection.headers, client).await {
+        Ok(upstream_response) => upstream_response,
+        Err(error_response) => return error_response,
+    };
+    // Relative URIs resolve against where the playlist ended up after redirects
+    let playlist_url = upstream_response.url().clone();
+    let body = match upstream_response.text().await {
+        Ok(body) => body,
+        Err(e) => {
+            eprintln!(
+                "[Rust/proxy.rs handler] Failed to read playlist {}: {}",
+                url, e
+            );
+            return HttpResponse::BadGateway().body(format!("Failed to read playlist: {}", e));
+        }
+    };
+    if !body.trim_start().starts_with("#EXTM3U") {
+        return HttpResponse::BadGateway().body("Upstream did not return an HLS playlist.");
+    }
+
+    let playlist = rewrite_playlist(&body, &playlist_url, stream_id, &connection.hls);
+    connection.traffic.record(playlist.len());
+    HttpResponse::Ok()
+        .content_type("application/vnd.apple.mpegurl")
+        .insert_header(("Cache-Control", "no-cache")) // Live playlists change every few seconds
+        .body(playlist)
+}
+
+fn segment_response(segment: CachedSegment) -> HttpResponse {
+    let mut response = HttpResponse::Ok();
+    if let Some(content_type) = segment.content_type {
+        response.content_type(content_type);
+    }
+    response.body(segment.data)
+}
+
+fn connect_hls(id: &str, registry: &ProxyStreamRegistry) -> Result<StreamConnection, HttpResponse> {
+    match registry.connect(id) {
+        Some(connection) if is_hls_url(&connection.url) => Ok(connection),
+        Some(_) => Err(HttpResponse::BadRequest().body(format!(
+            "'{}' is not an HLS stream; play /stream/{}.flv instead.",
+            id, id
+        ))),
+        None => Err(HttpResponse::NotFound().body(format!("No proxied stream with id '{}'.", id))),
+    }
+}
+
+// Your actual proxy logic - this is a simplified placeholder
+pub async fn flv_proxy_handler(
+    _req: HttpRequest,
+    stream_url_store: web::Data<StreamUrlStore>,
+    client: web::Data<Client>, // Changed to reqwest::Client
+    platforms: web::Data<PlatformRegistry>,
+    stats_store: web::Data<StreamStatsStore>,
+    traffic: web::Data<StreamTraffic>,
+) -> impl Responder {
+    serve_legacy_stream(
+        OutputFormat::Flv,
+        &stream_url_store,
+        &client,
+        &platforms,
+        &stats_store,
+        &traffic,
+    )
+    .await
+}
+
+pub async fn mp4_proxy_handler(
+    _req: HttpRequest,
+    stream_url_store: web::Data<StreamUrlStore>,
+    client: web::Data<Client>,
+    platforms: web::Data<PlatformRegistry>,
+    stats_store: web::Data<StreamStatsStore>,
+    traffic: web::Data<StreamTraffic>,
+) -> impl Responder {
+    serve_legacy_stream(
+        OutputFormat::Mp4,
+        &stream_url_store,
+        &client,
+        &platforms,
+        &stats_store,
+        &traffic,
+    )
+    .await
+}
+
+// `offset` is how many seconds behind live to start, as a negative number (-120 is two
+// minutes back); it needs the stream's time-shift buffer
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    offset: Option<i64>,
+}
+
+impl StreamQuery {
+    fn offset_secs(&self) -> Option<u64> {
+        self.offset.map(|offset| offset.min(0).unsigned_abs())
+    }
+}
+
+pub async fn stream_flv_handler(
+    id: web::Path<String>,
+    query: web::Query<StreamQuery>,
+    registry: web::Data<ProxyStreamRegistry>,
+    client: web::Data<Client>,
+    platforms: web::Data<PlatformRegistry>,
+) -> impl Responder {
+    serve_registered_stream(
+        OutputFormat::Flv,
+        &id,
+        &registry,
+        &client,
+        &platforms,
+        query.offset_secs(),
+    )
+    .await
+}
+
+pub async fn stream_mp4_handler(
+    id: web::Path<String>,
+    query: web::Query<StreamQuery>,
+    registry: web::Data<ProxyStreamRegistry>,
+    client: web::Data<Client>,
+    platforms: web::Data<PlatformRegistry>,
+) -> impl Responder {
+    serve_registered_stream(
+        OutputFormat::Mp4,
+        &id,
+        &registry,
+        &client,
+        &platforms,
+        query.offset_secs(),
+    )
+    .await
+}
+
+// The stream's top-level playlist: a master playlist, or the media playlist itself
+pub async fn stream_hls_handler(
+    id: web::Path<String>,
+    registry: web::Data<ProxyStreamRegistry>,
+    client: web::Data<Client>,
+) -> impl Responder {
+    let connection = match connect_hls(&id, &registry) {
+        Ok(connection) => connection,
+        Err(error_response) => return error_response,
+    };
+    let url = connection.url.clone();
+    serve_playlist(&id, &url, &connection, &client).await
+}
+
+// Variant and rendition playlists a rewritten playlist pointed at
+pub async fn hls_playlist_handler(
+    path: web::Path<(String, u64)>,
+    registry: web::Data<ProxyStreamRegistry>,
+    client: web::Data<Client>,
+) -> impl Responder {
+    let (id, n) = path.into_inner();
+    let connection = match connect_hls(&id, &registry) {
+        Ok(connection) => connection,
+        Err(error_response) => return error_response,
+    };
+    let Some(url) = connection.hls.lookup(n, HlsResource::Playlist) else {
+        return HttpResponse::NotFound().body("Unknown or expired playlist.");
+    };
+    serve_playlist(&id, &url, &connection, &client).await
+}
+
+pub async fn hls_segment_handler(
+    path: web::Path<(String, u64)>,
+    registry: web::Data<ProxyStreamRegistry>,
+    client: web::Data<Client>,
+) -> impl Responder {
+    let (id, n) = path.into_inner();
+    let connection = match connect_hls(&id, &registry) {
+        Ok(connection) => connection,
+        Err(error_response) => return error_response,
+    };
+    let Some(url) = connection.hls.lookup(n, HlsResource::Segment) else {
+        return HttpResponse::NotFound().body("Unknown or expired segment.");
+    };
+    if let Some(segment) = connection.hls.cached(n) {
+        connection.traffic.record(segment.data.len());
+        return segment_response(segment);
+    }
+
+    let upstream_response = match open_upstream(&url, &connection.headers, &client).await {
+        Ok(upstream_response) => upstream_response,
+        Err(error_response) => return error_response,
+    };
+    let content_type = upstream_response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let data = match upstream_response.bytes().await {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!(
+                "[Rust/proxy.rs handler] Failed to read segment {}: {}",
+                url, e
+            );
+            return HttpResponse::BadGateway().body(format!("Failed to read segment: {}", e));
+        }
+    };
+    let segment = CachedSegment { content_type, data };
+    connection.hls.cache(n, segment.clone());
+    connection.traffic.record(segment.data.len());
+    segment_response(segment)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StreamSummary {
+    id: String,
+    path: String,
+    // Just the host; the full URL usually carries auth tokens
+    upstream_host: Option<String>,
+    #[serde(flatten)]
+    traffic: TrafficSnapshot,
+    // How far back the time-shift buffer reaches; absent when it's off
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dvr_buffered_secs: Option<u32>,
+}
+
+fn upstream_host(url: &str) -> Option<String> {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+}
+
+// Every stream the proxy can serve right now; /live.flv shows up as "live" while it's set
+fn stream_summaries(
+    stream_url_store: &StreamUrlStore,
+    legacy_traffic: &StreamTraffic,
+    registry: &ProxyStreamRegistry,
+) -> Vec<StreamSummary> {
+    let mut summaries = Vec::new();
+    let legacy_url = stream_url_store.url.lock().unwrap().clone();
+    if !legacy_url.is_empty() {
+        summaries.push(StreamSummary {
+            id: "live".to_string(),
+            path: "/live.flv".to_string(),
+            upstream_host: upstream_host(&legacy_url),
+            traffic: legacy_traffic.snapshot(),
+            dvr_buffered_secs: None,
+        });
+    }
+    for (id, url, traffic, dvr_buffered_secs) in registry.list() {
+        summaries.push(StreamSummary {
+            path: format!(
+                "/stream/{}.{}",
+                id,
+                if is_hls_url(&url) { "m3u8" } else { "flv" }
+            ),
+            id,
+            upstream_host: upstream_host(&url),
+            traffic,
+            dvr_buffered_secs,
+        });
+    }
+    summaries
+}
+
+pub async fn health_handler(
+    server_info: web::Data<ServerInfo>,
+    stream_url_store: web::Data<StreamUrlStore>,
+    registry: web::Data<ProxyStreamRegistry>,
+) -> impl Responder {
+    let has_legacy_stream = !stream_url_store.url.lock().unwrap().is_empty();
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "ok",
+        "port": server_info.port,
+        "uptimeSecs": server_info.started_at.elapsed().as_secs(),
+        "streams": registry.list().len() + has_legacy_stream as usize,
+    }))
+}
+
+pub async fn streams_handler(
+    stream_url_store: web::Data<StreamUrlStore>,
+    legacy_traffic: web::Data<StreamTraffic>,
+    registry: web::Data<ProxyStreamRegistry>,
+) -> impl Responder {
+    HttpResponse::Ok().json(stream_summaries(
+        &stream_url_store,
+        &legacy_traffic,
+        &registry,
+    ))
+}
@@ -0,0 +1,86 @@
+// Proxy options the user can change, saved as proxy_settings.json in the app data directory.
+// They apply the next time the server starts.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager, State};
+
+const SETTINGS_FILE: &str = "proxy_settings.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProxySettings {
+    // Tried first; when unset or taken, the OS picks a free port
+    pub preferred_port: Option<u16>,
+    // How much of each FLV stream to keep for time-shifting (/stream/{id}.flv?offset=-120).
+    // Unset or 0 turns it off. Applies to streams added afterwards.
+    pub dvr_seconds: Option<u32>,
+}
+
+#[derive(Default, Clone)]
+pub struct ProxySettingsStore(pub Arc<Mutex<ProxySettings>>);
+
+impl ProxySettingsStore {
+    pub fn preferred_port(&self) -> Option<u16> {
+        self.0
+            .lock()
+            .unwrap()
+            .preferred_port
+            .filter(|port| *port != 0)
+    }
+
+    pub fn dvr_seconds(&self) -> Option<u32> {
+        self.0.lock().unwrap().dvr_seconds.filter(|secs| *secs != 0)
+    }
+}
+
+fn settings_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join(SETTINGS_FILE))
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))
+}
+
+// Called once at startup, before anything can start the server
+pub fn load_proxy_settings(app_handle: &AppHandle) {
+    let Ok(path) = settings_path(app_handle) else {
+        return;
+    };
+    let settings = match std::fs::read_to_string(&path) {
+        Ok(raw) => serde_json::from_str(&raw).unwrap_or_else(|e| {
+            eprintln!("[Rust/proxy] Ignoring invalid {}: {}", path.display(), e);
+            ProxySettings::default()
+        }),
+        Err(_) => ProxySettings::default(), // Nothing saved yet
+    };
+    *app_handle.state::<ProxySettingsStore>().0.lock().unwrap() = settings;
+}
+
+#[tauri::command]
+pub fn get_proxy_settings(store: State<'_, ProxySettingsStore>) -> ProxySettings {
+    store.0.lock().unwrap().clone()
+}
+
+#[tauri::command]
+pub async fn set_proxy_settings(
+    settings: ProxySettings,
+    app_handle: AppHandle,
+    store: State<'_, ProxySettingsStore>,
+) -> Result<(), String> {
+    let path = settings_path(&app_handle)?;
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let json = serde_json::to_string_pretty(&settings)
+        .map_err(|e| format!("Failed to serialize proxy settings: {}", e))?;
+    tokio::fs::write(&path, json)
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    *store.0.lock().unwrap() = settings;
+    Ok(())
+}
@@ -0,0 +1,293 @@
+// Streams the proxy serves at /stream/{id}.flv (and .mp4), or /stream/{id}.m3u8 for HLS,
+// each with its own upstream URL and request headers, so several rooms can play at once
+// through one server.
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::watch;
+
+use super::dvr::{spawn_capture, DvrBuffer};
+use super::failover::UpstreamSource;
+use super::hls::{is_hls_url, HlsState};
+use super::traffic::{StreamTraffic, TrafficSnapshot};
+use super::ProxySettingsStore;
+use super::StreamStatsStore;
+use crate::platforms::common::http_client::DEFAULT_USER_AGENT;
+use crate::platforms::common::types_rust::SupportedPlatformRust;
+use crate::platforms::common::PlatformRegistry;
+
+const MAX_STREAM_ID_LEN: usize = 64;
+
+// Tells registrations apart, so a reconnect can't update a stream that was replaced meanwhile
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+// Sent with every upstream request for the stream; CDNs often check Referer and Cookie
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProxyStreamHeaders {
+    pub referer: Option<String>,
+    pub cookie: Option<String>,
+    pub user_agent: Option<String>, // Defaults to a desktop Chrome UA
+}
+
+impl ProxyStreamHeaders {
+    pub fn apply(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let mut request = request.header(
+            "User-Agent",
+            self.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT),
+        );
+        if let Some(referer) = &self.referer {
+            request = request.header("Referer", referer);
+        }
+        if let Some(cookie) = &self.cookie {
+            request = request.header("Cookie", cookie);
+        }
+        request
+    }
+}
+
+// The room a stream belongs to; when the upstream drops, the stream is re-resolved
+// through this platform instead of retrying a URL that has likely expired
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyStreamOrigin {
+    pub platform: SupportedPlatformRust,
+    pub room_id: String,
+    #[serde(default)]
+    pub quality: Option<String>,
+}
+
+struct ProxiedStream {
+    url: String,
+    headers: ProxyStreamHeaders,
+    origin: Option<ProxyStreamOrigin>,
+    generation: u64,
+    stats: StreamStatsStore,
+    traffic: StreamTraffic,
+    hls: HlsState,
+    dvr: Option<DvrBuffer>,
+    // Never sent on; dropping it with the entry ends the stream's open connections
+    closed_tx: watch::Sender<()>,
+}
+
+// What a request handler needs to serve one connection of a stream
+pub struct StreamConnection {
+    pub url: String,
+    pub headers: ProxyStreamHeaders,
+    pub origin: Option<ProxyStreamOrigin>,
+    pub generation: u64,
+    pub stats: StreamStatsStore,
+    pub traffic: StreamTraffic,
+    pub hls: HlsState,
+    pub dvr: Option<DvrBuffer>,
+    pub closed_rx: watch::Receiver<()>,
+}
+
+#[derive(Default, Clone)]
+pub struct ProxyStreamRegistry(Arc<Mutex<HashMap<String, ProxiedStream>>>);
+
+impl ProxyStreamRegistry {
+    // Replaces any stream already registered under `id`, closing its connections
+    fn insert(
+        &self,
+        id: String,
+        url: String,
+        headers: ProxyStreamHeaders,
+        origin: Option<ProxyStreamOrigin>,
+        dvr: Option<DvrBuffer>,
+    ) {
+        let (closed_tx, _) = watch::channel(());
+        self.0.lock().unwrap().insert(
+            id,
+            ProxiedStream {
+                url,
+                headers,
+                origin,
+                generation: NEXT_GENERATION.fetch_add(1, Ordering::Relaxed),
+                stats: StreamStatsStore::default(),
+                traffic: StreamTraffic::default(),
+                hls: HlsState::default(),
+                dvr,
+                closed_tx,
+            },
+        );
+    }
+
+    fn remove(&self, id: &str) -> bool {
+        self.0.lock().unwrap().remove(id).is_some()
+    }
+
+    pub fn connect(&self, id: &str) -> Option<StreamConnection> {
+        let streams = self.0.lock().unwrap();
+        let stream = streams.get(id)?;
+        Some(StreamConnection {
+            url: stream.url.clone(),
+            headers: stream.headers.clone(),
+            origin: stream.origin.clone(),
+            generation: stream.generation,
+            stats: stream.stats.clone(),
+            traffic: stream.traffic.clone(),
+            hls: stream.hls.clone(),
+            dvr: stream.dvr.clone(),
+            closed_rx: stream.closed_tx.subscribe(),
+        })
+    }
+
+    // Where a connection's upstream comes from, for the failover relay; a URL it gets
+    // re-resolved to is kept for later connections
+    pub fn upstream_source(
+        &self,
+        id: &str,
+        connection: &StreamConnection,
+        client: Client,
+        platforms: PlatformRegistry,
+    ) -> UpstreamSource {
+        let (registry, id, generation) = (self.clone(), id.to_string(), connection.generation);
+        UpstreamSource {
+            url: connection.url.clone(),
+            headers: connection.headers.clone(),
+            origin: connection.origin.clone(),
+            client,
+            platforms,
+            on_resolved: Box::new(move |url| registry.update_url(&id, generation, url)),
+        }
+    }
+
+    // Records a URL the stream was re-resolved to, unless it has been replaced since
+    fn update_url(&self, id: &str, generation: u64, url: &str) {
+        if let Some(stream) = self.0.lock().unwrap().get_mut(id) {
+            if stream.generation == generation {
+                stream.url = url.to_string();
+            }
+        }
+    }
+
+    pub fn stats(&self, id: &str) -> Option<StreamStatsStore> {
+        self.0.lock().unwrap().get(id).map(|s| s.stats.clone())
+    }
+
+    // (id, upstream URL, traffic, seconds time-shift buffered) of every registered stream,
+    // sorted by id
+    pub fn list(&self) -> Vec<(String, String, TrafficSnapshot, Option<u32>)> {
+        let mut streams: Vec<_> = self
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, s)| {
+                (
+                    id.clone(),
+                    s.url.clone(),
+                    s.traffic.snapshot(),
+                    s.dvr.as_ref().map(DvrBuffer::buffered_secs),
+                )
+            })
+            .collect();
+        streams.sort_by(|a, b| a.0.cmp(&b.0));
+        streams
+    }
+
+    pub fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
+
+// Ids end up in URLs and logs; keep them to something that needs no escaping
+fn validate_stream_id(id: &str) -> Result<(), String> {
+    let valid = !id.is_empty()
+        && id.len() <= MAX_STREAM_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid stream id '{}': use 1-{} letters, digits, '-' or '_'",
+            id, MAX_STREAM_ID_LEN
+        ))
+    }
+}
+
+// Registers (or replaces) a stream and makes sure the server is up; returns its FLV URL,
+// or its playlist URL for an HLS stream
+pub fn register_proxy_stream(
+    app_handle: &AppHandle,
+    id: &str,
+    url: String,
+    headers: ProxyStreamHeaders,
+    origin: Option<ProxyStreamOrigin>,
+) -> Result<String, String> {
+    validate_stream_id(id)?;
+    if url.is_empty() {
+        return Err("Stream URL is empty.".to_string());
+    }
+    let base_url = super::ensure_proxy_server(app_handle)?;
+    let is_hls = is_hls_url(&url);
+    let extension = if is_hls { "m3u8" } else { "flv" };
+    // Time-shifting needs one connection filling a buffer for every player to read from
+    let dvr = match app_handle.state::<ProxySettingsStore>().dvr_seconds() {
+        Some(secs) if !is_hls => Some(DvrBuffer::new(secs)),
+        _ => None,
+    };
+    let registry = app_handle.state::<ProxyStreamRegistry>();
+    registry.insert(id.to_string(), url, headers, origin, dvr.clone());
+    if let (Some(buffer), Some(connection)) = (dvr, registry.connect(id)) {
+        let source = registry.upstream_source(
+            id,
+            &connection,
+            app_handle.state::<Client>().inner().clone(),
+            app_handle.state::<PlatformRegistry>().inner().clone(),
+        );
+        spawn_capture(buffer, source, connection.stats, connection.closed_rx);
+    }
+    println!("[Rust/proxy] Registered {} stream '{}'", extension, id);
+    Ok(format!("{}/stream/{}.{}", base_url, id, extension))
+}
+
+#[tauri::command]
+pub async fn add_proxy_stream(
+    id: String,
+    url: String,
+    headers: Option<ProxyStreamHeaders>,
+    origin: Option<ProxyStreamOrigin>,
+    app_handle: AppHandle,
+) -> Result<String, String> {
+    register_proxy_stream(&app_handle, &id, url, headers.unwrap_or_default(), origin)
+}
+
+#[tauri::command]
+pub fn remove_proxy_stream(
+    id: String,
+    registry: State<'_, ProxyStreamRegistry>,
+) -> Result<(), String> {
+    if registry.remove(&id) {
+        println!("[Rust/proxy] Removed stream '{}'", id);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_default_user_agent_and_platform_headers() {
+        let headers = ProxyStreamHeaders {
+            referer: Some("https://live.bilibili.com/".to_string()),
+            ..Default::default()
+        };
+        let request = headers
+            .apply(Client::new().get("https://example.com/live.flv"))
+            .build()
+            .unwrap();
+        let sent = request.headers();
+        assert_eq!(sent["User-Agent"], DEFAULT_USER_AGENT);
+        assert_eq!(sent["Referer"], "https://live.bilibili.com/");
+        assert!(sent.get("Cookie").is_none());
+    }
+}
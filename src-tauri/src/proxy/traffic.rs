@@ -0,0 +1,76 @@
+// How much of a stream the proxy has relayed to players, and how fast, for /streams
+
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Throughput is measured over windows of this length
+const RATE_WINDOW: Duration = Duration::from_secs(2);
+
+#[derive(Default)]
+struct Counters {
+    connections: usize,
+    bytes_sent: u64,
+    window_start: Option<Instant>,
+    window_bytes: u64,
+    rate_bps: f64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TrafficSnapshot {
+    pub connections: usize,
+    pub bytes_sent: u64,
+    pub throughput_kbps: u64,
+}
+
+#[derive(Default, Clone)]
+pub struct StreamTraffic(Arc<Mutex<Counters>>);
+
+impl StreamTraffic {
+    // Counts a player connection for as long as the returned guard lives
+    pub fn connect(&self) -> ConnectionGuard {
+        self.0.lock().unwrap().connections += 1;
+        ConnectionGuard(self.clone())
+    }
+
+    pub fn record(&self, bytes: usize) {
+        let now = Instant::now();
+        let mut counters = self.0.lock().unwrap();
+        counters.bytes_sent += bytes as u64;
+        counters.window_bytes += bytes as u64;
+        let start = *counters.window_start.get_or_insert(now);
+        let elapsed = now.duration_since(start);
+        if elapsed >= RATE_WINDOW {
+            counters.rate_bps = counters.window_bytes as f64 * 8.0 / elapsed.as_secs_f64();
+            counters.window_start = Some(now);
+            counters.window_bytes = 0;
+        }
+    }
+
+    pub fn snapshot(&self) -> TrafficSnapshot {
+        let counters = self.0.lock().unwrap();
+        // A window that never closed means the stream stalled
+        let stalled = counters
+            .window_start
+            .map_or(true, |start| start.elapsed() >= RATE_WINDOW * 2);
+        TrafficSnapshot {
+            connections: counters.connections,
+            bytes_sent: counters.bytes_sent,
+            throughput_kbps: if stalled {
+                0
+            } else {
+                (counters.rate_bps / 1000.0).round() as u64
+            },
+        }
+    }
+}
+
+pub struct ConnectionGuard(StreamTraffic);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut counters = (self.0).0.lock().unwrap();
+        counters.connections = counters.connections.saturating_sub(1);
+    }
+}
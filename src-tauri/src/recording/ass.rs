@@ -0,0 +1,255 @@
+// Scrolling ASS subtitles from a danmaku recording.
+//
+// Every comment crosses the screen right to left in `scroll_duration_secs`, so longer text
+// moves faster. Comments are placed in horizontal lanes; a lane is only reused once the
+// previous comment has fully entered the screen and the new one can't catch up with it
+// before it leaves. Comments that find no free lane are dropped rather than overlapped.
+
+use serde::Deserialize;
+use std::fmt::Write;
+
+use super::danmaku_export::RecordedSession;
+
+// Vertical space per lane, as a multiple of the font size
+const LINE_HEIGHT: f64 = 1.2;
+// Minimum horizontal gap between comments sharing a lane, as a multiple of the font size
+const LANE_GAP: f64 = 1.0;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AssExportOptions {
+    pub font_name: String,
+    pub font_size: u32,            // In script pixels (see width / height)
+    pub scroll_duration_secs: f64, // Time to cross the screen; lower is faster
+    pub width: u32,                // PlayResX / PlayResY; players scale to the video
+    pub height: u32,
+    pub area: f64,    // Fraction of the screen height, from the top, used for lanes
+    pub opacity: f64, // 0.0 (invisible) to 1.0 (opaque)
+}
+
+impl Default for AssExportOptions {
+    fn default() -> Self {
+        Self {
+            font_name: "Microsoft YaHei".to_string(),
+            font_size: 42,
+            scroll_duration_secs: 10.0,
+            width: 1920,
+            height: 1080,
+            area: 0.8,
+            opacity: 0.8,
+        }
+    }
+}
+
+// The last comment placed in a lane
+struct LaneOccupant {
+    start: f64,
+    width: f64,
+    speed: f64,
+}
+
+// ASS has no text metrics; CJK and other wide characters take about a full em, ASCII half
+fn estimate_text_width(text: &str, font_size: f64) -> f64 {
+    text.chars()
+        .map(|c| if c.is_ascii() { 0.5 } else { 1.0 })
+        .sum::<f64>()
+        * font_size
+}
+
+fn ass_time(secs: f64) -> String {
+    let centis = (secs.max(0.0) * 100.0).round() as u64;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        centis / 360_000,
+        centis / 6_000 % 60,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+// ASS colours are &HBBGGRR&
+fn ass_color(rgb: u32) -> String {
+    format!(
+        "&H{:02X}{:02X}{:02X}&",
+        rgb & 0xFF,
+        (rgb >> 8) & 0xFF,
+        (rgb >> 16) & 0xFF
+    )
+}
+
+// Braces start override blocks and backslashes escapes; there is no quoting, so swap in
+// full-width lookalikes
+fn escape_ass_text(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\\' => '＼',
+            '{' => '｛',
+            '}' => '｝',
+            '\r' | '\n' => ' ',
+            c => c,
+        })
+        .collect()
+}
+
+// Style lines are comma-separated with no quoting, so a comma in the font name would shift
+// every field after it
+fn style_font_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, ',' | '\r' | '\n'))
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+// Returns the script and the number of comments dropped for lack of a free lane
+pub fn to_ass(session: &RecordedSession, options: &AssExportOptions) -> (String, usize) {
+    let font_size = options.font_size.max(1) as f64;
+    let screen_width = options.width.max(1) as f64;
+    let duration = options.scroll_duration_secs.max(1.0);
+    let line_height = font_size * LINE_HEIGHT;
+    let lane_count =
+        ((options.height as f64 * options.area.clamp(0.0, 1.0)) / line_height).max(1.0) as usize;
+    let alpha = ((1.0 - options.opacity.clamp(0.0, 1.0)) * 255.0).round() as u8;
+
+    let mut script = String::new();
+    let _ = write!(
+        script,
+        "[Script Info]\n\
+         ; Generated from a danmaku recording\n\
+         Title: {title}\n\
+         ScriptType: v4.00+\n\
+         PlayResX: {width}\n\
+         PlayResY: {height}\n\
+         WrapStyle: 2\n\
+         ScaledBorderAndShadow: yes\n\
+         \n\
+         [V4+ Styles]\n\
+         Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+         Style: Danmaku,{font},{size},&H{alpha:02X}FFFFFF,&H{alpha:02X}FFFFFF,&H{alpha:02X}000000,&H{alpha:02X}000000,0,0,0,0,100,100,0,0,1,1.5,0,7,0,0,0,1\n\
+         \n\
+         [Events]\n\
+         Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+        title = escape_ass_text(session.title.as_deref().unwrap_or("Danmaku")),
+        width = options.width,
+        height = options.height,
+        font = style_font_name(&options.font_name),
+        size = options.font_size,
+        alpha = alpha,
+    );
+
+    let mut lanes: Vec<Option<LaneOccupant>> = (0..lane_count).map(|_| None).collect();
+    let mut dropped = 0;
+    for comment in &session.comments {
+        let start = comment.offset_ms as f64 / 1000.0;
+        let width = estimate_text_width(&comment.content, font_size);
+        let speed = (screen_width + width) / duration;
+
+        let free_lane = lanes.iter().position(|lane| match lane {
+            None => true,
+            Some(last) => {
+                // The previous comment's tail is on screen with room to spare...
+                let tail_clear = last.start + (last.width + font_size * LANE_GAP) / last.speed;
+                // ...and this one won't reach the left edge before the previous one is gone
+                let no_catch_up = last.start + duration - screen_width / speed;
+                start >= tail_clear && start >= no_catch_up
+            }
+        });
+        let Some(lane) = free_lane else {
+            dropped += 1;
+            continue;
+        };
+        lanes[lane] = Some(LaneOccupant {
+            start,
+            width,
+            speed,
+        });
+
+        let y = (lane as f64 * line_height).round() as i64;
+        let color = comment
+            .color
+            .map(|rgb| format!("\\c{}", ass_color(rgb)))
+            .unwrap_or_default();
+        let _ = writeln!(
+            script,
+            "Dialogue: 0,{},{},Danmaku,,0,0,0,,{{\\move({},{},{},{}){}}}{}",
+            ass_time(start),
+            ass_time(start + duration),
+            options.width,
+            y,
+            -(width.ceil() as i64),
+            y,
+            color,
+            escape_ass_text(&comment.content)
+        );
+    }
+
+    (script, dropped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording::danmaku_export::RecordedComment;
+
+    fn comment(offset_ms: i64, content: &str, color: Option<u32>) -> RecordedComment {
+        RecordedComment {
+            offset_ms,
+            content: content.to_string(),
+            color,
+            uid: None,
+            timestamp: 0,
+        }
+    }
+
+    // Three lanes on a small screen, so comments have to compete for them
+    fn options() -> AssExportOptions {
+        AssExportOptions {
+            font_name: "Noto Sans, CJK".to_string(),
+            font_size: 20,
+            scroll_duration_secs: 8.0,
+            width: 640,
+            height: 360,
+            area: 0.2,
+            opacity: 0.5,
+        }
+    }
+
+    #[test]
+    fn formats_times_and_colours() {
+        assert_eq!(ass_time(0.0), "0:00:00.00");
+        assert_eq!(ass_time(-3.0), "0:00:00.00");
+        assert_eq!(ass_time(3725.5), "1:02:05.50");
+        assert_eq!(ass_time(59.999), "0:01:00.00");
+        assert_eq!(ass_color(0xFF8000), "&H0080FF&");
+        assert_eq!(ass_color(0x123456), "&H563412&");
+        assert_eq!(escape_ass_text("{\\b1}a\nb"), "｛＼b1｝a b");
+        assert_eq!(style_font_name(" Noto Sans, CJK\n"), "Noto Sans CJK");
+    }
+
+    #[test]
+    fn places_comments_in_free_lanes() {
+        let session = RecordedSession {
+            title: Some("测试 {直播}".to_string()),
+            comments: vec![
+                comment(0, "first", None),
+                // Lane 0 is still busy with the first comment's tail
+                comment(500, "第二条弹幕", None),
+                comment(600, "{\\an8}注入", Some(0xFF8000)),
+                // Every lane is busy
+                comment(700, "dropped", None),
+                // Long gone by now
+                comment(9000, "later", None),
+                comment(12000, "a", None),
+                // Clear of the short comment's tail, but fast enough to catch up with it
+                comment(
+                    12500,
+                    "a long comment that moves much faster than that",
+                    None,
+                ),
+            ],
+        };
+        let (script, dropped) = to_ass(&session, &options());
+        assert_eq!(dropped, 1);
+        assert_eq!(script, include_str!("fixtures/danmaku.ass"));
+    }
+}
@@ -0,0 +1,527 @@
+// Watches configured rooms and records them whenever they are live.
+//
+// Every POLL_INTERVAL (or right after the rules change) each enabled rule's room is checked.
+// A room that goes live gets a session directory `<output>/auto/<platform>_<room>/<start>/`
+// holding the stream recording and, if the rule asks for it, the danmaku recording. The
+// session ends when the room goes offline; a session that hits the rule's max duration stops
+// recording and waits for the next broadcast. Only the newest `keepLast` session directories
+// of a room are kept. Rules are saved to `<app data>/auto_record.json`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::Notify;
+use tokio::time::{Duration, Instant};
+
+use super::danmaku_recorder::set_danmaku_recording;
+use super::stream_recorder::{
+    start_stream_recording, stop_stream_recording, RecordingContainer, StreamRecorderHandles,
+    StreamRecordingOptions,
+};
+use crate::platforms::common::platform_commands::{platform_start_danmaku, platform_stop_danmaku};
+use crate::platforms::common::types_rust::SupportedPlatformRust;
+use crate::platforms::common::PlatformRegistry;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+const RULES_FILE: &str = "auto_record.json";
+const SESSION_DIR_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AutoRecordRule {
+    pub platform: SupportedPlatformRust,
+    pub room_id: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub quality: Option<String>,
+    #[serde(default)]
+    pub record_danmaku: bool,
+    #[serde(default)]
+    pub max_duration_secs: Option<u64>, // Per broadcast; recording resumes at the next one
+    #[serde(default)]
+    pub keep_last: Option<usize>, // Session directories kept for the room; None keeps all
+    #[serde(default)]
+    pub filename_template: Option<String>,
+    #[serde(default)]
+    pub output_dir: Option<String>, // Defaults to <Videos>/DTV
+    #[serde(default)]
+    pub container: RecordingContainer,
+}
+
+// The configured rules, shared between the commands and the watcher task
+#[derive(Default, Clone)]
+pub struct AutoRecordHandles {
+    rules: Arc<Mutex<Vec<AutoRecordRule>>>,
+    rules_changed: Arc<Notify>,
+}
+
+// A broadcast currently being recorded
+struct AutoRecordSession {
+    dir: PathBuf,
+    started_at: Instant,
+    started_listener: bool, // We started the danmaku listener, so we stop it too
+    capped: bool,           // Hit max_duration_secs; wait for the room to go offline
+}
+
+type RoomKey = (SupportedPlatformRust, String);
+
+// Live as the room view shows it, which each platform decides in its fetch_room_info
+async fn check_live(
+    app_handle: &AppHandle,
+    platform: SupportedPlatformRust,
+    room_id: &str,
+) -> Result<bool, String> {
+    app_handle
+        .state::<PlatformRegistry>()
+        .get(platform)?
+        .fetch_room_info(room_id)
+        .await
+        .map(|info| info.is_live)
+}
+
+fn sanitize_dir_component(raw: &str) -> String {
+    raw.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn room_dir(app_handle: &AppHandle, rule: &AutoRecordRule) -> Result<PathBuf, String> {
+    let base = match rule
+        .output_dir
+        .as_deref()
+        .filter(|dir| !dir.trim().is_empty())
+    {
+        Some(dir) => PathBuf::from(dir),
+        None => app_handle
+            .path()
+            .video_dir()
+            .map_err(|e| format!("Failed to resolve the videos directory: {}", e))?
+            .join("DTV"),
+    };
+    Ok(base.join("auto").join(format!(
+        "{}_{}",
+        rule.platform,
+        sanitize_dir_component(&rule.room_id)
+    )))
+}
+
+// Deletes all but the newest `keep` session directories. Only directories named like a
+// session are touched, in case the output directory is shared with other files.
+async fn prune_sessions(room_dir: &Path, keep: usize) -> Result<(), String> {
+    let mut entries = tokio::fs::read_dir(room_dir)
+        .await
+        .map_err(|e| format!("Failed to list {}: {}", room_dir.display(), e))?;
+    let mut sessions = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let is_dir = entry.file_type().await.map_or(false, |t| t.is_dir());
+        if is_dir && chrono::NaiveDateTime::parse_from_str(&name, SESSION_DIR_FORMAT).is_ok() {
+            sessions.push(name);
+        }
+    }
+    // Names are timestamps, so they sort oldest first
+    sessions.sort();
+    let excess = sessions.len().saturating_sub(keep);
+    for name in sessions.into_iter().take(excess) {
+        let path = room_dir.join(&name);
+        println!("[Auto Record] Removing old recording {}", path.display());
+        tokio::fs::remove_dir_all(&path)
+            .await
+            .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+fn has_danmaku_listener(
+    app_handle: &AppHandle,
+    platform: SupportedPlatformRust,
+    room_id: &str,
+) -> bool {
+    crate::list_active_danmaku_listeners(app_handle.state(), app_handle.state(), app_handle.state())
+        .iter()
+        .any(|listener| listener.platform == platform && listener.room_id == room_id)
+}
+
+// Starts (or restarts, after the stream dropped) the recordings for a live room
+async fn start_recordings(
+    app_handle: &AppHandle,
+    rule: &AutoRecordRule,
+    session: &mut AutoRecordSession,
+) -> Result<(), String> {
+    let dir = session.dir.display().to_string();
+    let options = StreamRecordingOptions {
+        filename_template: rule.filename_template.clone(),
+        quality: rule.quality.clone(),
+        output_dir: Some(dir.clone()),
+        container: rule.container,
+        ..Default::default()
+    };
+    start_stream_recording(
+        rule.platform,
+        rule.room_id.clone(),
+        Some(options),
+        app_handle.clone(),
+        app_handle.state(),
+        app_handle.state(),
+    )
+    .await?;
+
+    if rule.record_danmaku {
+        // The recorder only captures events, so make sure a listener is producing them. If
+        // the player is already listening to the room, share its listener.
+        if !has_danmaku_listener(app_handle, rule.platform, &rule.room_id) {
+            platform_start_danmaku(
+                rule.platform,
+                rule.room_id.clone(),
+                app_handle.clone(),
+                app_handle.state(),
+                app_handle.state(),
+            )
+            .await?;
+            session.started_listener = true;
+        }
+        set_danmaku_recording(
+            rule.platform,
+            rule.room_id.clone(),
+            true,
+            Some(dir),
+            app_handle.clone(),
+            app_handle.state(),
+            app_handle.state(),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn stop_recordings(
+    app_handle: &AppHandle,
+    platform: SupportedPlatformRust,
+    room_id: &str,
+    session: &mut AutoRecordSession,
+) {
+    if let Err(e) = stop_stream_recording(platform, room_id.to_string(), app_handle.state()).await {
+        eprintln!("[Auto Record] {}", e);
+    }
+    let _ = set_danmaku_recording(
+        platform,
+        room_id.to_string(),
+        false,
+        None,
+        app_handle.clone(),
+        app_handle.state(),
+        app_handle.state(),
+    )
+    .await;
+    if session.started_listener {
+        let _ = platform_stop_danmaku(platform, room_id.to_string(), app_handle.state()).await;
+        session.started_listener = false;
+    }
+}
+
+// Creates a session directory and runs `start` in it. Older sessions are only pruned once
+// it succeeds; a session that fails to start takes its directory with it, so rooms that
+// keep failing don't push real recordings out.
+async fn open_session_dir<T, F, Fut>(
+    room_dir: &Path,
+    keep_last: Option<usize>,
+    start: F,
+) -> Result<T, String>
+where
+    F: FnOnce(PathBuf) -> Fut,
+    Fut: Future<Output = Result<T, String>>,
+{
+    let dir = room_dir.join(chrono::Local::now().format(SESSION_DIR_FORMAT).to_string());
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let started = match start(dir.clone()).await {
+        Ok(started) => started,
+        Err(e) => {
+            if let Err(remove_error) = tokio::fs::remove_dir_all(&dir).await {
+                eprintln!(
+                    "[Auto Record] Failed to remove {}: {}",
+                    dir.display(),
+                    remove_error
+                );
+            }
+            return Err(e);
+        }
+    };
+    if let Some(keep) = keep_last {
+        // The new session counts towards the limit
+        if let Err(e) = prune_sessions(room_dir, keep.max(1)).await {
+            eprintln!("[Auto Record] {}", e);
+        }
+    }
+    Ok(started)
+}
+
+async fn begin_session(
+    app_handle: &AppHandle,
+    rule: &AutoRecordRule,
+) -> Result<AutoRecordSession, String> {
+    let room_dir = room_dir(app_handle, rule)?;
+    open_session_dir(&room_dir, rule.keep_last, |dir| async move {
+        let mut session = AutoRecordSession {
+            dir,
+            started_at: Instant::now(),
+            started_listener: false,
+            capped: false,
+        };
+        if let Err(e) = start_recordings(app_handle, rule, &mut session).await {
+            stop_recordings(app_handle, rule.platform, &rule.room_id, &mut session).await;
+            return Err(e);
+        }
+        Ok(session)
+    })
+    .await
+}
+
+// One pass over the rules: start, cap, resume and end sessions as rooms change state
+async fn poll_rooms(
+    app_handle: &AppHandle,
+    rules: &[AutoRecordRule],
+    sessions: &mut HashMap<RoomKey, AutoRecordSession>,
+) {
+    // Sessions whose rule was removed or disabled
+    let stale: Vec<RoomKey> = sessions
+        .keys()
+        .filter(|(platform, room_id)| {
+            !rules
+                .iter()
+                .any(|rule| rule.enabled && rule.platform == *platform && rule.room_id == *room_id)
+        })
+        .cloned()
+        .collect();
+    for key in stale {
+        if let Some(mut session) = sessions.remove(&key) {
+            stop_recordings(app_handle, key.0, &key.1, &mut session).await;
+        }
+    }
+
+    for rule in rules.iter().filter(|rule| rule.enabled) {
+        let key = (rule.platform, rule.room_id.clone());
+        let is_live = match check_live(app_handle, rule.platform, &rule.room_id).await {
+            Ok(is_live) => is_live,
+            Err(e) => {
+                // Keep whatever is running; one failed check doesn't mean the room went offline
+                eprintln!(
+                    "[Auto Record] Failed to check {} room {}: {}",
+                    rule.platform, rule.room_id, e
+                );
+                continue;
+            }
+        };
+
+        match (is_live, sessions.get_mut(&key)) {
+            (true, None) => {
+                println!(
+                    "[Auto Record] {} room {} is live, starting recording.",
+                    rule.platform, rule.room_id
+                );
+                match begin_session(app_handle, rule).await {
+                    Ok(session) => {
+                        sessions.insert(key, session);
+                    }
+                    Err(e) => eprintln!(
+                        "[Auto Record] Failed to record {} room {}: {}",
+                        rule.platform, rule.room_id, e
+                    ),
+                }
+            }
+            (true, Some(session)) if !session.capped => {
+                let max_duration = rule.max_duration_secs.map(Duration::from_secs);
+                if max_duration.map_or(false, |max| session.started_at.elapsed() >= max) {
+                    println!(
+                        "[Auto Record] {} room {} reached its max duration.",
+                        rule.platform, rule.room_id
+                    );
+                    stop_recordings(app_handle, rule.platform, &rule.room_id, session).await;
+                    session.capped = true;
+                } else if !app_handle
+                    .state::<StreamRecorderHandles>()
+                    .is_recording(rule.platform, &rule.room_id)
+                {
+                    // Still live but the recording ended (CDN dropped us); pick it up again
+                    if let Err(e) = start_recordings(app_handle, rule, session).await {
+                        eprintln!(
+                            "[Auto Record] Failed to resume {} room {}: {}",
+                            rule.platform, rule.room_id, e
+                        );
+                    }
+                }
+            }
+            (false, Some(_)) => {
+                println!(
+                    "[Auto Record] {} room {} went offline.",
+                    rule.platform, rule.room_id
+                );
+                if let Some(mut session) = sessions.remove(&key) {
+                    stop_recordings(app_handle, rule.platform, &rule.room_id, &mut session).await;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn rules_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join(RULES_FILE))
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))
+}
+
+async fn load_rules(app_handle: &AppHandle) -> Vec<AutoRecordRule> {
+    let Ok(path) = rules_path(app_handle) else {
+        return Vec::new();
+    };
+    match tokio::fs::read_to_string(&path).await {
+        Ok(raw) => serde_json::from_str(&raw).unwrap_or_else(|e| {
+            eprintln!("[Auto Record] Ignoring invalid {}: {}", path.display(), e);
+            Vec::new()
+        }),
+        Err(_) => Vec::new(), // No rules saved yet
+    }
+}
+
+// Loads the saved rules and runs the watcher for the lifetime of the app
+pub fn spawn_auto_recorder(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let handles = app_handle.state::<AutoRecordHandles>().inner().clone();
+        *handles.rules.lock().unwrap() = load_rules(&app_handle).await;
+
+        let mut sessions = HashMap::new();
+        loop {
+            let rules = handles.rules.lock().unwrap().clone();
+            poll_rooms(&app_handle, &rules, &mut sessions).await;
+            tokio::select! {
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = handles.rules_changed.notified() => {}
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_auto_record_rules(handles: State<'_, AutoRecordHandles>) -> Vec<AutoRecordRule> {
+    handles.rules.lock().unwrap().clone()
+}
+
+// Replaces all rules, saves them and re-checks every room right away
+#[tauri::command]
+pub async fn set_auto_record_rules(
+    rules: Vec<AutoRecordRule>,
+    app_handle: AppHandle,
+    handles: State<'_, AutoRecordHandles>,
+) -> Result<(), String> {
+    let path = rules_path(&app_handle)?;
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let json = serde_json::to_string_pretty(&rules)
+        .map_err(|e| format!("Failed to serialize auto record rules: {}", e))?;
+    tokio::fs::write(&path, json)
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    *handles.rules.lock().unwrap() = rules;
+    handles.rules_changed.notify_one();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD_SESSIONS: [&str; 3] = ["20240101-200000", "20240102-200000", "20240103-200000"];
+
+    // A room directory holding three finished sessions and an unrelated file
+    async fn room_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("dtv-auto-record-{}-{}", name, std::process::id()));
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        for session in OLD_SESSIONS {
+            tokio::fs::create_dir_all(dir.join(session)).await.unwrap();
+        }
+        tokio::fs::write(dir.join("notes.txt"), "keep me")
+            .await
+            .unwrap();
+        dir
+    }
+
+    async fn entries(dir: &Path) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut entries = tokio::fs::read_dir(dir).await.unwrap();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn failed_start_keeps_old_sessions() {
+        let dir = room_dir("failed").await;
+        for _ in 0..3 {
+            let result: Result<(), String> =
+                open_session_dir(&dir, Some(1), |session_dir| async move {
+                    assert!(session_dir.is_dir());
+                    Err("Only FLV streams can be recorded".to_string())
+                })
+                .await;
+            assert!(result.is_err());
+        }
+        assert_eq!(
+            entries(&dir).await,
+            [
+                "20240101-200000",
+                "20240102-200000",
+                "20240103-200000",
+                "notes.txt"
+            ]
+        );
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn started_session_prunes_the_oldest() {
+        let dir = room_dir("started").await;
+        let session_dir =
+            open_session_dir(&dir, Some(2), |session_dir| async move { Ok(session_dir) })
+                .await
+                .unwrap();
+        let new_session = session_dir
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .into_owned();
+        assert_eq!(
+            entries(&dir).await,
+            [
+                "20240103-200000".to_string(),
+                new_session,
+                "notes.txt".to_string()
+            ]
+        );
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -0,0 +1,133 @@
+// Bilibili's danmaku XML, which most danmaku-aware players (and converters like
+// DanmakuFactory) accept: <i> ... <d p="time,mode,size,color,sent,pool,uid hash,id">text</d></i>
+//
+// p: time in seconds from the start of the video, mode 1 (scrolling), font size 25 (normal),
+// colour as a decimal RGB integer, send time in epoch seconds, pool 0 (normal), a hash of the
+// sender's uid and a row id.
+
+use std::fmt::Write;
+
+use super::danmaku_export::RecordedSession;
+
+const MODE_SCROLL: u8 = 1;
+const FONT_SIZE_NORMAL: u8 = 25;
+const DEFAULT_COLOR: u32 = 0xFFFFFF;
+
+// Bilibili publishes a CRC32 of the uid rather than the uid itself; any stable hash does
+fn uid_hash(uid: Option<&str>) -> String {
+    let mut crc: u32 = 0xFFFF_FFFF;
+    for byte in uid.unwrap_or("0").bytes() {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    format!("{:08x}", !crc)
+}
+
+// Escapes markup and drops characters XML 1.0 doesn't allow at all
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(' '),
+            c if (c as u32) < 0x20 || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn to_bilibili_xml(session: &RecordedSession) -> String {
+    let mut xml = String::new();
+    let _ = write!(
+        xml,
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <i>\n\
+         <chatserver>chat.bilibili.com</chatserver>\n\
+         <chatid>0</chatid>\n\
+         <mission>0</mission>\n\
+         <maxlimit>{}</maxlimit>\n\
+         <state>0</state>\n\
+         <real_name>0</real_name>\n\
+         <source>k-v</source>\n",
+        session.comments.len()
+    );
+
+    for (row_id, comment) in session.comments.iter().enumerate() {
+        let _ = writeln!(
+            xml,
+            "<d p=\"{:.3},{},{},{},{},0,{},{}\">{}</d>",
+            comment.offset_ms as f64 / 1000.0,
+            MODE_SCROLL,
+            FONT_SIZE_NORMAL,
+            comment.color.unwrap_or(DEFAULT_COLOR),
+            comment.timestamp / 1000,
+            uid_hash(comment.uid.as_deref()),
+            row_id,
+            escape_xml(&comment.content)
+        );
+    }
+
+    xml.push_str("</i>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording::danmaku_export::RecordedComment;
+
+    #[test]
+    fn hashes_uids_with_crc32() {
+        assert_eq!(uid_hash(Some("123456789")), "cbf43926"); // The standard check value
+        assert_eq!(uid_hash(Some("")), "00000000");
+        assert_eq!(uid_hash(None), uid_hash(Some("0")));
+        assert_eq!(uid_hash(None), "f4dbdf21");
+    }
+
+    #[test]
+    fn escapes_markup_and_drops_invalid_characters() {
+        assert_eq!(
+            escape_xml("<b>\"Tom\" & 'Jerry'</b>"),
+            "&lt;b&gt;&quot;Tom&quot; &amp; &apos;Jerry&apos;&lt;/b&gt;"
+        );
+        assert_eq!(escape_xml("a\tb\nc\u{1}d\u{FFFE}e弹幕"), "a b cde弹幕");
+    }
+
+    #[test]
+    fn writes_one_d_per_comment() {
+        let session = RecordedSession {
+            title: Some("直播".to_string()),
+            comments: vec![
+                RecordedComment {
+                    offset_ms: 1500,
+                    content: "hello".to_string(),
+                    color: None,
+                    uid: Some("123456789".to_string()),
+                    timestamp: 1_760_000_001_234,
+                },
+                RecordedComment {
+                    offset_ms: 61_005,
+                    content: "<3 & 弹幕".to_string(),
+                    color: Some(0xFF8000),
+                    uid: None,
+                    timestamp: 1_760_000_060_999,
+                },
+            ],
+        };
+        assert_eq!(
+            to_bilibili_xml(&session),
+            include_str!("fixtures/danmaku.xml")
+        );
+    }
+}
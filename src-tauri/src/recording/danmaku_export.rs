@@ -0,0 +1,127 @@
+// Converts a recording made by danmaku_recorder into formats video players can overlay:
+// ASS subtitles (see ass.rs) and Bilibili-style XML (see bilibili_xml.rs).
+
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+
+use super::ass::{to_ass, AssExportOptions};
+use super::bilibili_xml::to_bilibili_xml;
+
+// A chat message from a recording, positioned on the recording's timeline
+pub struct RecordedComment {
+    pub offset_ms: i64,
+    pub content: String,
+    pub color: Option<u32>, // 0xRRGGBB; None for the default (white)
+    pub uid: Option<String>,
+    pub timestamp: i64, // Milliseconds since the epoch, when it was received
+}
+
+pub struct RecordedSession {
+    pub title: Option<String>,
+    pub comments: Vec<RecordedComment>, // Sorted by offset
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum DanmakuExportFormat {
+    Ass,
+    Xml,
+}
+
+// Chat colours are recorded as "#rrggbb"
+fn parse_color(color: &str) -> Option<u32> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok()
+}
+
+fn parse_comment(record: &Value) -> Option<RecordedComment> {
+    let content = record.get("content")?.as_str()?.trim();
+    if content.is_empty() {
+        return None;
+    }
+    Some(RecordedComment {
+        offset_ms: record.get("offsetMs")?.as_i64()?.max(0),
+        content: content.to_string(),
+        color: record
+            .get("color")
+            .and_then(|c| c.as_str())
+            .and_then(parse_color)
+            .filter(|c| *c != 0xFFFFFF),
+        uid: record
+            .pointer("/user/uid")
+            .and_then(|uid| uid.as_str())
+            .map(|uid| uid.to_string()),
+        timestamp: record
+            .get("timestamp")
+            .and_then(|t| t.as_i64())
+            .unwrap_or(0),
+    })
+}
+
+// Reads the chat out of a .jsonl recording. Other event types and lines that don't parse
+// (e.g. a line cut short when the app was killed) are skipped.
+pub fn parse_recording(raw: &str) -> RecordedSession {
+    let mut title = None;
+    let mut comments = Vec::new();
+    for line in raw.lines().filter(|line| !line.trim().is_empty()) {
+        let Ok(record) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        match record.get("type").and_then(|t| t.as_str()) {
+            Some("session") => {
+                title = record
+                    .get("title")
+                    .and_then(|t| t.as_str())
+                    .map(|t| t.to_string());
+            }
+            Some("chat") => comments.extend(parse_comment(&record)),
+            _ => {}
+        }
+    }
+    comments.sort_by_key(|comment| comment.offset_ms);
+    RecordedSession { title, comments }
+}
+
+// Writes the export next to the recording (same name, .ass / .xml) and returns its path
+#[tauri::command]
+pub async fn export_danmaku_recording(
+    path: String,
+    format: DanmakuExportFormat,
+    ass_options: Option<AssExportOptions>,
+) -> Result<String, String> {
+    let input = Path::new(&path);
+    if input.extension().and_then(|ext| ext.to_str()) != Some("jsonl") {
+        return Err(format!("{} is not a danmaku recording (.jsonl)", path));
+    }
+    let raw = tokio::fs::read_to_string(input)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let session = parse_recording(&raw);
+
+    let (contents, extension) = match format {
+        DanmakuExportFormat::Ass => {
+            let options = ass_options.unwrap_or_default();
+            let (contents, dropped) = to_ass(&session, &options);
+            if dropped > 0 {
+                println!(
+                    "[Danmaku Export] {} of {} comments in {} had no free lane and were left out",
+                    dropped,
+                    session.comments.len(),
+                    path
+                );
+            }
+            (contents, "ass")
+        }
+        DanmakuExportFormat::Xml => (to_bilibili_xml(&session), "xml"),
+    };
+
+    let output = input.with_extension(extension);
+    tokio::fs::write(&output, contents)
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+    Ok(output.display().to_string())
+}
@@ -0,0 +1,307 @@
+// Records a room's danmaku to `<platform>_<room>_<start>.jsonl`, by default under
+// `<app data>/danmaku`.
+//
+// The first line is a `session` header (platform, room, title, anchor, recording start and,
+// when the platform reports it, stream start); every following line is one DanmakuEnvelope as
+// emitted to the frontend, plus `offsetMs` from the start of the recording and, with a known
+// stream start, `streamOffsetMs` from the start of the broadcast. The recorder subscribes to the room's danmaku event channel, so it
+// captures whichever listener is running for the room, and records nothing while none is.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, EventId, Listener, Manager, State};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{Duration, MissedTickBehavior};
+
+use crate::platforms::common::danmaku::danmaku_event_name;
+use crate::platforms::common::types_rust::SupportedPlatformRust;
+use crate::platforms::common::PlatformRegistry;
+
+// Written lines are flushed at least this often, so little is lost if the app is closed
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+struct ActiveRecording {
+    event_id: EventId,
+    path: PathBuf,
+    stop_tx: oneshot::Sender<()>,
+}
+
+// Running danmaku recordings, keyed by platform and room
+#[derive(Default, Clone)]
+pub struct DanmakuRecorderHandles(
+    Arc<Mutex<HashMap<(SupportedPlatformRust, String), ActiveRecording>>>,
+);
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionHeader {
+    #[serde(rename = "type")]
+    record_type: &'static str,
+    platform: SupportedPlatformRust,
+    room_id: String,
+    title: Option<String>,
+    anchor_name: Option<String>,
+    started_at: i64, // Milliseconds since the epoch; `offsetMs` is relative to this
+    stream_started_at: Option<i64>, // Same, for the broadcast; `streamOffsetMs` is relative to this
+}
+
+// Room ids can be URLs (Douyin accepts either), so keep file names to a safe subset
+fn sanitize_file_component(raw: &str) -> String {
+    raw.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// Turns an emitted envelope into a recording line; None for payloads that aren't envelopes
+fn recording_line(payload: &str, header: &SessionHeader) -> Option<String> {
+    let mut envelope: Value = serde_json::from_str(payload).ok()?;
+    let timestamp = envelope.get("timestamp")?.as_i64()?;
+    let fields = envelope.as_object_mut()?;
+    fields.insert(
+        "offsetMs".to_string(),
+        Value::from(timestamp - header.started_at),
+    );
+    if let Some(stream_started_at) = header.stream_started_at {
+        fields.insert(
+            "streamOffsetMs".to_string(),
+            Value::from(timestamp - stream_started_at),
+        );
+    }
+    let mut line = serde_json::to_string(&envelope).ok()?;
+    line.push('\n');
+    Some(line)
+}
+
+async fn write_recording(
+    path: &Path,
+    header: SessionHeader,
+    mut payload_rx: mpsc::UnboundedReceiver<String>,
+    mut stop_rx: oneshot::Receiver<()>,
+) -> Result<(), String> {
+    let file = tokio::fs::File::create(path)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut writer = BufWriter::new(file);
+
+    let mut header_line = serde_json::to_string(&header)
+        .map_err(|e| format!("Failed to serialize recording header: {}", e))?;
+    header_line.push('\n');
+    writer
+        .write_all(header_line.as_bytes())
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    let mut flush_interval = tokio::time::interval(FLUSH_INTERVAL);
+    flush_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            payload = payload_rx.recv() => {
+                let Some(payload) = payload else { break };
+                if let Some(line) = recording_line(&payload, &header) {
+                    writer
+                        .write_all(line.as_bytes())
+                        .await
+                        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                }
+            }
+            _ = flush_interval.tick() => {
+                writer
+                    .flush()
+                    .await
+                    .map_err(|e| format!("Failed to flush {}: {}", path.display(), e))?;
+            }
+            _ = &mut stop_rx => {
+                // Keep whatever was already queued when recording was turned off
+                while let Ok(payload) = payload_rx.try_recv() {
+                    if let Some(line) = recording_line(&payload, &header) {
+                        writer
+                            .write_all(line.as_bytes())
+                            .await
+                            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                    }
+                }
+                break;
+            }
+        }
+    }
+
+    writer
+        .flush()
+        .await
+        .map_err(|e| format!("Failed to flush {}: {}", path.display(), e))
+}
+
+async fn start_recording(
+    app_handle: AppHandle,
+    platform: SupportedPlatformRust,
+    room_id: String,
+    output_dir: Option<String>,
+    registry: &PlatformRegistry,
+) -> Result<ActiveRecording, String> {
+    let dir = match output_dir.filter(|dir| !dir.trim().is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => app_handle
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("Failed to resolve app data directory: {}", e))?
+            .join("danmaku"),
+    };
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let now = chrono::Local::now();
+    let path = dir.join(format!(
+        "{}_{}_{}.jsonl",
+        platform,
+        sanitize_file_component(&room_id),
+        now.format("%Y%m%d-%H%M%S")
+    ));
+
+    // Title, anchor and stream start are informational only; record without them if the lookup fails
+    let room_info = match registry.get(platform) {
+        Ok(live_platform) => live_platform.fetch_room_info(&room_id).await.ok(),
+        Err(_) => None,
+    };
+    let header = SessionHeader {
+        record_type: "session",
+        platform,
+        room_id: room_id.clone(),
+        title: room_info.as_ref().and_then(|info| info.title.clone()),
+        anchor_name: room_info.as_ref().and_then(|info| info.anchor_name.clone()),
+        started_at: now.timestamp_millis(),
+        stream_started_at: room_info.and_then(|info| info.live_started_at),
+    };
+
+    let (payload_tx, payload_rx) = mpsc::unbounded_channel();
+    let (stop_tx, stop_rx) = oneshot::channel();
+    let event_id = app_handle.listen(danmaku_event_name(platform, &room_id), move |event| {
+        let _ = payload_tx.send(event.payload().to_string());
+    });
+
+    let task_path = path.clone();
+    tokio::spawn(async move {
+        if let Err(e) = write_recording(&task_path, header, payload_rx, stop_rx).await {
+            eprintln!(
+                "[Danmaku Recorder] {} recording for room {} failed: {}",
+                platform, room_id, e
+            );
+            app_handle.unlisten(event_id);
+        }
+    });
+
+    Ok(ActiveRecording {
+        event_id,
+        path,
+        stop_tx,
+    })
+}
+
+// Turns danmaku recording for a room on or off. Returns the file being written while on.
+#[tauri::command]
+pub async fn set_danmaku_recording(
+    platform: SupportedPlatformRust,
+    room_id: String,
+    enabled: bool,
+    output_dir: Option<String>, // Defaults to <app data>/danmaku
+    app_handle: AppHandle,
+    registry: State<'_, PlatformRegistry>,
+    recorders: State<'_, DanmakuRecorderHandles>,
+) -> Result<Option<String>, String> {
+    let key = (platform, room_id.clone());
+
+    if !enabled {
+        if let Some(recording) = recorders.0.lock().unwrap().remove(&key) {
+            app_handle.unlisten(recording.event_id);
+            let _ = recording.stop_tx.send(());
+            println!(
+                "[Danmaku Recorder] Stopped {} room {}, saved to {}",
+                platform,
+                room_id,
+                recording.path.display()
+            );
+        }
+        return Ok(None);
+    }
+
+    // Already recording (and the writer is still alive): keep the current file
+    if let Some(recording) = recorders.0.lock().unwrap().get(&key) {
+        if !recording.stop_tx.is_closed() {
+            return Ok(Some(recording.path.display().to_string()));
+        }
+    }
+
+    let recording = start_recording(
+        app_handle.clone(),
+        platform,
+        room_id.clone(),
+        output_dir,
+        &registry,
+    )
+    .await?;
+    let path = recording.path.display().to_string();
+    println!(
+        "[Danmaku Recorder] Recording {} room {} to {}",
+        platform, room_id, path
+    );
+    // A concurrent start for the same room may have won the race; stop its file in favour of ours
+    if let Some(previous) = recorders.0.lock().unwrap().insert(key, recording) {
+        app_handle.unlisten(previous.event_id);
+        let _ = previous.stop_tx.send(());
+    }
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(stream_started_at: Option<i64>) -> SessionHeader {
+        SessionHeader {
+            record_type: "session",
+            platform: SupportedPlatformRust::Bilibili,
+            room_id: "6".to_string(),
+            title: None,
+            anchor_name: None,
+            started_at: 1_700_000_060_000,
+            stream_started_at,
+        }
+    }
+
+    fn line(payload: &str, header: &SessionHeader) -> Value {
+        serde_json::from_str(&recording_line(payload, header).unwrap()).unwrap()
+    }
+
+    const PAYLOAD: &str = r#"{"timestamp":1700000065000,"type":"chat","content":"hi"}"#;
+
+    #[test]
+    fn offsets_from_recording_and_stream_start() {
+        let record = line(PAYLOAD, &header(Some(1_700_000_000_000)));
+        assert_eq!(record["offsetMs"], 5_000);
+        assert_eq!(record["streamOffsetMs"], 65_000);
+        assert_eq!(record["content"], "hi");
+    }
+
+    #[test]
+    fn omits_the_stream_offset_when_unknown() {
+        let record = line(PAYLOAD, &header(None));
+        assert_eq!(record["offsetMs"], 5_000);
+        assert!(record.get("streamOffsetMs").is_none());
+    }
+
+    #[test]
+    fn skips_payloads_that_are_not_envelopes() {
+        assert!(recording_line("\"connected\"", &header(None)).is_none());
+        assert!(recording_line(r#"{"type":"chat"}"#, &header(None)).is_none());
+    }
+}
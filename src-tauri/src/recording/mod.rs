@@ -0,0 +1,10 @@
+pub mod ass;
+pub mod auto_record;
+pub mod bilibili_xml;
+pub mod danmaku_export;
+pub mod danmaku_recorder;
+pub mod stream_recorder;
+
+pub use auto_record::AutoRecordHandles;
+pub use danmaku_recorder::DanmakuRecorderHandles;
+pub use stream_recorder::StreamRecorderHandles;
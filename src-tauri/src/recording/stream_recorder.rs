@@ -0,0 +1,775 @@
+// Records a room's live stream to FLV or MP4 files, by default under `<Videos>/DTV`.
+//
+// The recorder opens its own upstream connection (resolved through the PlatformRegistry)
+// rather than teeing the player's proxy, so a room can be recorded without being watched
+// and keeps recording when the player switches rooms. Files can be split by size and/or
+// duration; files start and split on video keyframes, and every file begins with the FLV
+// header, metadata and codec sequence headers so each one plays on its own. Progress is
+// emitted on RECORDING_STATUS_EVENT about once a second. MP4 files are fragmented MP4
+// remuxed from the FLV tags, with an index and duration written when each file is closed.
+
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::fs::File;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt, BufWriter};
+use tokio::sync::oneshot;
+use tokio::time::{Duration, Instant, MissedTickBehavior};
+
+use crate::media::flv::{FlvHeader, FlvItem, FlvReader, FlvTag, TAG_AUDIO, TAG_SCRIPT, TAG_VIDEO};
+use crate::media::fmp4::Fmp4Remuxer;
+use crate::platforms::common::types_rust::SupportedPlatformRust;
+use crate::platforms::common::PlatformRegistry;
+use crate::proxy::streams::ProxyStreamHeaders;
+
+pub const RECORDING_STATUS_EVENT: &str = "recording-status";
+const DEFAULT_FILENAME_TEMPLATE: &str = "{platform}_{anchor}_{date}";
+const STATUS_INTERVAL: Duration = Duration::from_secs(1);
+// Live CDNs send data continuously; this long without any means the stream is gone
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct StreamRecordingOptions {
+    // Placeholders: {platform} {room} {anchor} {title} {date} {segment}
+    pub filename_template: Option<String>,
+    pub max_segment_bytes: Option<u64>,
+    pub max_segment_secs: Option<u64>,
+    pub quality: Option<String>,
+    pub output_dir: Option<String>, // Defaults to <Videos>/DTV
+    pub container: RecordingContainer,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordingContainer {
+    #[default]
+    Flv,
+    Mp4, // Fragmented, remuxed on the fly; indexed on close so it seeks like a normal file
+}
+
+impl RecordingContainer {
+    fn extension(self) -> &'static str {
+        match self {
+            RecordingContainer::Flv => "flv",
+            RecordingContainer::Mp4 => "mp4",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+enum RecordingState {
+    Recording,
+    Stopped,
+    Failed,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct RecordingStatus {
+    platform: SupportedPlatformRust,
+    room_id: String,
+    state: RecordingState,
+    path: Option<String>, // File currently (or last) written
+    segment: u32,         // 1-based; 0 until the first file is opened
+    bytes_written: u64,   // Across all segments
+    duration_ms: u64,
+    bitrate_kbps: f64, // Over the last status interval
+    error: Option<String>,
+}
+
+// Stop signals for running stream recordings, keyed by platform and room
+#[derive(Default, Clone)]
+pub struct StreamRecorderHandles(
+    Arc<Mutex<HashMap<(SupportedPlatformRust, String), oneshot::Sender<()>>>>,
+);
+
+impl StreamRecorderHandles {
+    pub fn is_recording(&self, platform: SupportedPlatformRust, room_id: &str) -> bool {
+        self.0
+            .lock()
+            .unwrap()
+            .get(&(platform, room_id.to_string()))
+            .map_or(false, |sender| !sender.is_closed())
+    }
+}
+
+// Values substituted into the filename template
+struct FilenameContext {
+    platform: SupportedPlatformRust,
+    room_id: String,
+    anchor: String,
+    title: String,
+}
+
+// Keeps characters Windows / macOS reject out of file names; CJK names are left alone
+fn sanitize_filename(raw: &str) -> String {
+    raw.chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn render_filename(
+    template: &str,
+    context: &FilenameContext,
+    started_at: chrono::DateTime<chrono::Local>,
+    segment: u32,
+    extension: &str,
+) -> String {
+    let rendered = template
+        .replace("{platform}", context.platform.as_str())
+        .replace("{room}", &sanitize_filename(&context.room_id))
+        .replace("{anchor}", &sanitize_filename(&context.anchor))
+        .replace("{title}", &sanitize_filename(&context.title))
+        .replace("{date}", &started_at.format("%Y%m%d-%H%M%S").to_string())
+        .replace("{segment}", &segment.to_string());
+    // The template must not point outside the recordings directory
+    let name = sanitize_filename(&rendered);
+    // Templates may carry either extension; the container decides which one the file gets
+    let name = strip_media_extension(&name);
+    format!("{}.{}", name, extension)
+}
+
+// Drops a trailing .flv or .mp4 in any letter case. Only ASCII is compared, so the cut can't
+// land inside a multi-byte character the way lowercasing first could.
+fn strip_media_extension(name: &str) -> &str {
+    for extension in [".flv", ".mp4"] {
+        let Some(split) = name.len().checked_sub(extension.len()) else {
+            continue;
+        };
+        if name.is_char_boundary(split) && name[split..].eq_ignore_ascii_case(extension) {
+            return &name[..split];
+        }
+    }
+    name
+}
+
+// Appends _1, _2, ... if a file with the same name already exists (e.g. two splits in a second)
+fn unused_path(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
+    }
+    let (stem, extension) = name.rsplit_once('.').unwrap_or((name, ""));
+    (1..)
+        .map(|n| dir.join(format!("{}_{}.{}", stem, n, extension)))
+        .find(|path| !path.exists())
+        .unwrap_or(path)
+}
+
+// Writes FLV items to the current segment, opening a new file when a split is due
+struct SegmentWriter {
+    dir: PathBuf,
+    template: String,
+    context: FilenameContext,
+    max_segment_bytes: Option<u64>,
+    max_segment_ms: Option<u64>,
+    container: RecordingContainer,
+    remuxer: Option<Fmp4Remuxer>, // MP4 only, one per file
+    config_changed: bool,         // MP4 only: codec parameters changed, start a new file
+
+    file: Option<BufWriter<File>>,
+    path: Option<PathBuf>,
+    segment: u32,
+    segment_bytes: u64,
+    base_timestamp: u32, // Stream timestamp of the segment's first tag, so each file starts at 0
+    total_bytes: u64,
+
+    // Replayed at the start of every segment
+    header: Option<FlvHeader>,
+    metadata: Option<FlvTag>,
+    video_sequence_header: Option<FlvTag>,
+    audio_sequence_header: Option<FlvTag>,
+}
+
+impl SegmentWriter {
+    fn new(dir: PathBuf, context: FilenameContext, options: &StreamRecordingOptions) -> Self {
+        Self {
+            dir,
+            template: options
+                .filename_template
+                .clone()
+                .filter(|t| !t.trim().is_empty())
+                .unwrap_or_else(|| DEFAULT_FILENAME_TEMPLATE.to_string()),
+            context,
+            max_segment_bytes: options.max_segment_bytes.filter(|max| *max > 0),
+            max_segment_ms: options
+                .max_segment_secs
+                .filter(|max| *max > 0)
+                .map(|secs| secs * 1000),
+            container: options.container,
+            remuxer: None,
+            config_changed: false,
+            file: None,
+            path: None,
+            segment: 0,
+            segment_bytes: 0,
+            base_timestamp: 0,
+            total_bytes: 0,
+            header: None,
+            metadata: None,
+            video_sequence_header: None,
+            audio_sequence_header: None,
+        }
+    }
+
+    fn split_due(&self, tag: &FlvTag) -> bool {
+        // Only cut where the next file can start decoding
+        if !tag.is_video_keyframe() || tag.is_sequence_header() {
+            return false;
+        }
+        if self.config_changed {
+            return true;
+        }
+        let too_big = self
+            .max_segment_bytes
+            .map_or(false, |max| self.segment_bytes >= max);
+        let too_long = self.max_segment_ms.map_or(false, |max| {
+            tag.timestamp.saturating_sub(self.base_timestamp) as u64 >= max
+        });
+        too_big || too_long
+    }
+
+    async fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let file = self.file.as_mut().ok_or("No recording file open")?;
+        file.write_all(bytes)
+            .await
+            .map_err(|e| format!("Failed to write recording: {}", e))?;
+        self.segment_bytes += bytes.len() as u64;
+        self.total_bytes += bytes.len() as u64;
+        Ok(())
+    }
+
+    async fn open_segment(&mut self, base_timestamp: u32) -> Result<(), String> {
+        self.close().await?;
+
+        let header = self
+            .header
+            .clone()
+            .ok_or("FLV tag arrived before the FLV header")?;
+        self.segment += 1;
+        let name = render_filename(
+            &self.template,
+            &self.context,
+            chrono::Local::now(),
+            self.segment,
+            self.container.extension(),
+        );
+        let path = unused_path(&self.dir, &name);
+        let file = File::create(&path)
+            .await
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        println!("[Stream Recorder] Writing {}", path.display());
+
+        self.file = Some(BufWriter::new(file));
+        self.path = Some(path);
+        self.segment_bytes = 0;
+        self.base_timestamp = base_timestamp;
+        self.config_changed = false;
+
+        let replayed = [
+            &self.metadata,
+            &self.video_sequence_header,
+            &self.audio_sequence_header,
+        ];
+        let mut start = Vec::new();
+        match self.container {
+            RecordingContainer::Flv => {
+                start.extend_from_slice(&header.bytes);
+                for tag in replayed.into_iter().flatten() {
+                    start.extend(tag.encode(0));
+                }
+            }
+            RecordingContainer::Mp4 => {
+                // The init segment comes out with the first frame, once the codecs are known
+                let mut remuxer = Fmp4Remuxer::new();
+                remuxer.push(&FlvItem::Header(header))?;
+                for tag in replayed.into_iter().flatten() {
+                    remuxer.push_tag(tag)?;
+                }
+                self.remuxer = Some(remuxer);
+            }
+        }
+        self.write_bytes(&start).await
+    }
+
+    async fn write_tag(&mut self, tag: &FlvTag) -> Result<(), String> {
+        let bytes = match self.remuxer.as_mut() {
+            Some(remuxer) => remuxer.push_tag(tag)?,
+            None => tag.encode(tag.timestamp.saturating_sub(self.base_timestamp)),
+        };
+        self.write_bytes(&bytes).await
+    }
+
+    async fn write_item(&mut self, item: FlvItem) -> Result<(), String> {
+        let tag = match item {
+            FlvItem::Header(header) => {
+                self.header.get_or_insert(header);
+                return Ok(());
+            }
+            FlvItem::Tag(tag) => tag,
+        };
+
+        if self.file.is_none() {
+            // Joining mid-GOP gives frames nothing can decode, so hold the first file back
+            // until a keyframe; metadata and sequence headers seen meanwhile get replayed
+            let audio_only = self.header.as_ref().map_or(false, |h| !h.has_video);
+            let keyframe = tag.is_video_keyframe() && !tag.is_sequence_header();
+            let config = tag.tag_type == TAG_SCRIPT || tag.is_sequence_header();
+            if config || !(keyframe || audio_only) {
+                self.remember(tag);
+                return Ok(());
+            }
+            self.open_segment(tag.timestamp).await?;
+        } else if self.split_due(&tag) {
+            self.open_segment(tag.timestamp).await?;
+        } else if self.remuxer.is_some() && self.is_new_config(&tag) {
+            // An MP4 has one set of codec parameters; the next keyframe starts a new file
+            self.config_changed = true;
+            self.remember(tag);
+            return Ok(());
+        }
+        self.write_tag(&tag).await?;
+        self.remember(tag);
+        Ok(())
+    }
+
+    fn is_new_config(&self, tag: &FlvTag) -> bool {
+        let current = match tag.tag_type {
+            TAG_VIDEO if tag.is_sequence_header() => &self.video_sequence_header,
+            TAG_AUDIO if tag.is_sequence_header() => &self.audio_sequence_header,
+            _ => return false,
+        };
+        current
+            .as_ref()
+            .map_or(false, |current| current.body != tag.body)
+    }
+
+    // Keeps the tags open_segment replays
+    fn remember(&mut self, tag: FlvTag) {
+        match tag.tag_type {
+            TAG_SCRIPT if self.metadata.is_none() => self.metadata = Some(tag),
+            TAG_VIDEO if tag.is_sequence_header() => self.video_sequence_header = Some(tag),
+            TAG_AUDIO if tag.is_sequence_header() => self.audio_sequence_header = Some(tag),
+            _ => {}
+        }
+    }
+
+    async fn close(&mut self) -> Result<(), String> {
+        let mut patches = Vec::new();
+        if let Some(mut remuxer) = self.remuxer.take() {
+            let trailer = remuxer.finish();
+            self.write_bytes(&trailer.bytes).await?;
+            patches = trailer.patches;
+        }
+        if let Some(mut file) = self.file.take() {
+            file.flush()
+                .await
+                .map_err(|e| format!("Failed to flush recording: {}", e))?;
+            // Fill in the MP4 duration now that it's known
+            let file = file.get_mut();
+            for (offset, bytes) in patches {
+                file.seek(SeekFrom::Start(offset))
+                    .await
+                    .map_err(|e| format!("Failed to finalize recording: {}", e))?;
+                file.write_all(&bytes)
+                    .await
+                    .map_err(|e| format!("Failed to finalize recording: {}", e))?;
+            }
+            file.flush()
+                .await
+                .map_err(|e| format!("Failed to flush recording: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+struct StatusReporter {
+    app_handle: AppHandle,
+    platform: SupportedPlatformRust,
+    room_id: String,
+    started_at: Instant,
+    last_report: Instant,
+    last_bytes: u64,
+}
+
+impl StatusReporter {
+    fn report(&mut self, writer: &SegmentWriter, state: RecordingState, error: Option<String>) {
+        let elapsed = self.last_report.elapsed().as_secs_f64();
+        let bitrate_kbps = if elapsed > 0.0 {
+            (writer.total_bytes - self.last_bytes) as f64 * 8.0 / 1000.0 / elapsed
+        } else {
+            0.0
+        };
+        self.last_report = Instant::now();
+        self.last_bytes = writer.total_bytes;
+
+        let status = RecordingStatus {
+            platform: self.platform,
+            room_id: self.room_id.clone(),
+            state,
+            path: writer.path.as_ref().map(|p| p.display().to_string()),
+            segment: writer.segment,
+            bytes_written: writer.total_bytes,
+            duration_ms: self.started_at.elapsed().as_millis() as u64,
+            bitrate_kbps,
+            error,
+        };
+        if let Err(e) = self.app_handle.emit(RECORDING_STATUS_EVENT, status) {
+            eprintln!("[Stream Recorder] Failed to emit recording status: {}", e);
+        }
+    }
+}
+
+// Copies the stream into segments until it ends, fails or `stop_rx` fires
+async fn record_stream(
+    stream_url: &str,
+    headers: &ProxyStreamHeaders,
+    writer: &mut SegmentWriter,
+    reporter: &mut StatusReporter,
+    stop_rx: &mut oneshot::Receiver<()>,
+) -> Result<(), String> {
+    let response = headers
+        .apply(reqwest::Client::new().get(stream_url))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to connect to upstream: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Upstream returned {}", response.status()));
+    }
+
+    let mut stream = response.bytes_stream();
+    let mut reader = FlvReader::new();
+    let mut status_interval = tokio::time::interval(STATUS_INTERVAL);
+    status_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_data = Instant::now();
+    loop {
+        tokio::select! {
+            chunk = stream.next() => {
+                let chunk = match chunk {
+                    // The CDN closes the connection when the broadcast ends
+                    None => return Ok(()),
+                    Some(Err(e)) => return Err(format!("Upstream stream error: {}", e)),
+                    Some(Ok(chunk)) => chunk,
+                };
+                last_data = Instant::now();
+                for item in reader.push(&chunk)? {
+                    writer.write_item(item).await?;
+                }
+            }
+            _ = tokio::time::sleep_until(last_data + READ_TIMEOUT) => {
+                return Err("Upstream stopped sending data".to_string());
+            }
+            _ = status_interval.tick() => {
+                reporter.report(writer, RecordingState::Recording, None);
+            }
+            _ = &mut *stop_rx => return Ok(()),
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn start_stream_recording(
+    platform: SupportedPlatformRust,
+    room_id: String,
+    options: Option<StreamRecordingOptions>,
+    app_handle: AppHandle,
+    registry: State<'_, PlatformRegistry>,
+    recorders: State<'_, StreamRecorderHandles>,
+) -> Result<(), String> {
+    let options = options.unwrap_or_default();
+    let live_platform = registry.get(platform)?;
+
+    let stream = live_platform
+        .resolve_stream(&room_id, options.quality.as_deref())
+        .await?;
+    if stream.format != "flv" {
+        return Err(format!(
+            "Only FLV streams can be recorded; {} room {} is {}",
+            platform, room_id, stream.format
+        ));
+    }
+
+    // Only used for file names, so carry on with the room id if the lookup fails
+    let room_info = live_platform.fetch_room_info(&room_id).await.ok();
+    let context = FilenameContext {
+        platform,
+        room_id: room_id.clone(),
+        anchor: room_info
+            .as_ref()
+            .and_then(|info| info.anchor_name.clone())
+            .unwrap_or_else(|| room_id.clone()),
+        title: room_info.and_then(|info| info.title).unwrap_or_default(),
+    };
+
+    let dir = match options
+        .output_dir
+        .as_deref()
+        .filter(|dir| !dir.trim().is_empty())
+    {
+        Some(dir) => PathBuf::from(dir),
+        None => app_handle
+            .path()
+            .video_dir()
+            .map_err(|e| format!("Failed to resolve the videos directory: {}", e))?
+            .join("DTV"),
+    };
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let key = (platform, room_id.clone());
+    // Restart rather than record the same room twice
+    if let Some(existing_sender) = recorders.0.lock().unwrap().remove(&key) {
+        let _ = existing_sender.send(());
+    }
+    let (stop_tx, mut stop_rx) = oneshot::channel();
+    recorders.0.lock().unwrap().insert(key.clone(), stop_tx);
+
+    let mut writer = SegmentWriter::new(dir, context, &options);
+    let mut reporter = StatusReporter {
+        app_handle,
+        platform,
+        room_id: room_id.clone(),
+        started_at: Instant::now(),
+        last_report: Instant::now(),
+        last_bytes: 0,
+    };
+
+    let recorders_clone = recorders.inner().clone();
+    tokio::spawn(async move {
+        let mut result = record_stream(
+            &stream.url,
+            &stream.headers,
+            &mut writer,
+            &mut reporter,
+            &mut stop_rx,
+        )
+        .await;
+        let closed = writer.close().await;
+        if result.is_ok() {
+            result = closed;
+        }
+
+        match result {
+            Ok(()) => {
+                println!("[Stream Recorder] {} room {} stopped.", platform, room_id);
+                reporter.report(&writer, RecordingState::Stopped, None);
+            }
+            Err(e) => {
+                eprintln!(
+                    "[Stream Recorder] {} room {} failed: {}",
+                    platform, room_id, e
+                );
+                reporter.report(&writer, RecordingState::Failed, Some(e));
+            }
+        }
+
+        // Drop our entry if it still belongs to this task (it may have been replaced by a restart)
+        drop(stop_rx);
+        let mut lock = recorders_clone.0.lock().unwrap();
+        if lock.get(&key).map_or(false, |sender| sender.is_closed()) {
+            lock.remove(&key);
+        }
+    });
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn stop_stream_recording(
+    platform: SupportedPlatformRust,
+    room_id: String,
+    recorders: State<'_, StreamRecorderHandles>,
+) -> Result<(), String> {
+    if let Some(sender) = recorders
+        .0
+        .lock()
+        .unwrap()
+        .remove(&(platform, room_id.clone()))
+    {
+        sender.send(()).map_err(|_| {
+            format!(
+                "Failed to stop {} recording for room {}: it has already ended.",
+                platform, room_id
+            )
+        })
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    // 1080p30 AVC with keyframes at 0, 990 and 1980 ms, plus AAC
+    const SAMPLE: &[u8] = include_bytes!("../media/flv/fixtures/sample.flv");
+
+    fn context(title: &str) -> FilenameContext {
+        FilenameContext {
+            platform: SupportedPlatformRust::Douyu,
+            room_id: "9999".to_string(),
+            anchor: "主播/小明".to_string(),
+            title: title.to_string(),
+        }
+    }
+
+    fn render(template: &str, title: &str, extension: &str) -> String {
+        let started_at = chrono::Local
+            .with_ymd_and_hms(2025, 10, 1, 20, 30, 0)
+            .unwrap();
+        render_filename(template, &context(title), started_at, 2, extension)
+    }
+
+    #[test]
+    fn renders_filenames() {
+        assert_eq!(
+            render(DEFAULT_FILENAME_TEMPLATE, "", "flv"),
+            "douyu_主播_小明_20251001-203000.flv"
+        );
+        assert_eq!(
+            render("{title}_{segment}", "今晚: 通宵?", "mp4"),
+            "今晚_ 通宵__2.mp4"
+        );
+        // The container decides the extension, whatever the template or title ends with
+        assert_eq!(render("{room}.FLV", "", "mp4"), "9999.mp4");
+        assert_eq!(render("{title}", "回放.Mp4", "flv"), "回放.flv");
+        assert_eq!(render("{title}", "İİ", "flv"), "İİ.flv");
+        assert_eq!(
+            render("../{room}/{anchor}", "", "flv"),
+            ".._9999_主播_小明.flv"
+        );
+    }
+
+    #[test]
+    fn strips_media_extensions_on_char_boundaries() {
+        assert_eq!(strip_media_extension("直播.FLV"), "直播");
+        assert_eq!(strip_media_extension("直播.mP4"), "直播");
+        assert_eq!(strip_media_extension("直播.mkv"), "直播.mkv");
+        // Four bytes from the end is inside the first character here
+        assert_eq!(strip_media_extension("直lv"), "直lv");
+        assert_eq!(strip_media_extension("flv"), "flv");
+    }
+
+    async fn record(name: &str, options: StreamRecordingOptions) -> Vec<Vec<u8>> {
+        let dir =
+            std::env::temp_dir().join(format!("dtv-recorder-{}-{}", name, std::process::id()));
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let options = StreamRecordingOptions {
+            filename_template: Some("{room}_{segment}".to_string()),
+            ..options
+        };
+        let mut writer = SegmentWriter::new(dir.clone(), context(""), &options);
+        // In uneven chunks, the way the network delivers it
+        let mut reader = FlvReader::new();
+        for chunk in SAMPLE.chunks(1000) {
+            for item in reader.push(chunk).unwrap() {
+                writer.write_item(item).await.unwrap();
+            }
+        }
+        writer.close().await.unwrap();
+
+        let extension = options.container.extension();
+        let files: Vec<Vec<u8>> = (1..=writer.segment)
+            .map(|segment| {
+                std::fs::read(dir.join(format!("9999_{}.{}", segment, extension))).unwrap()
+            })
+            .collect();
+        let total: usize = files.iter().map(|file| file.len()).sum();
+        assert_eq!(writer.total_bytes, total as u64);
+        std::fs::remove_dir_all(&dir).unwrap();
+        files
+    }
+
+    // Each file plays on its own: header, metadata and sequence headers, then a keyframe
+    // at 0 ms
+    fn check_flv_segment(file: &[u8]) -> Vec<u32> {
+        let items = FlvReader::new().push(file).unwrap();
+        assert!(matches!(items[0], FlvItem::Header(_)));
+        let tags: Vec<&FlvTag> = items[1..]
+            .iter()
+            .map(|item| match item {
+                FlvItem::Tag(tag) => tag,
+                FlvItem::Header(_) => panic!("a second FLV header"),
+            })
+            .collect();
+        assert_eq!(tags[0].tag_type, TAG_SCRIPT);
+        assert!(tags[1].tag_type == TAG_VIDEO && tags[1].is_sequence_header());
+        assert!(tags[2].tag_type == TAG_AUDIO && tags[2].is_sequence_header());
+        assert!(tags[3].is_video_keyframe());
+        assert_eq!(tags[3].timestamp, 0);
+        tags.iter()
+            .filter(|tag| tag.is_video_frame())
+            .map(|tag| tag.timestamp)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn splits_at_the_first_keyframe_past_the_duration_limit() {
+        let files = record(
+            "duration",
+            StreamRecordingOptions {
+                max_segment_secs: Some(1),
+                ..Default::default()
+            },
+        )
+        .await;
+        // 990 ms is still short of a second; 1980 ms isn't
+        assert_eq!(files.len(), 2);
+        let frames: Vec<Vec<u32>> = files.iter().map(|file| check_flv_segment(file)).collect();
+        assert_eq!(frames[0].len(), 60);
+        assert_eq!(frames[0].last(), Some(&1947));
+        assert_eq!(frames[1], [0]);
+    }
+
+    #[tokio::test]
+    async fn splits_at_every_keyframe_past_the_size_limit() {
+        let files = record(
+            "size",
+            StreamRecordingOptions {
+                max_segment_bytes: Some(1),
+                ..Default::default()
+            },
+        )
+        .await;
+        assert_eq!(files.len(), 3);
+        let frames: Vec<usize> = files
+            .iter()
+            .map(|file| check_flv_segment(file).len())
+            .collect();
+        assert_eq!(frames, [30, 30, 1]);
+    }
+
+    #[tokio::test]
+    async fn writes_each_mp4_segment_as_a_whole_file() {
+        let files = record(
+            "mp4",
+            StreamRecordingOptions {
+                max_segment_secs: Some(1),
+                container: RecordingContainer::Mp4,
+                ..Default::default()
+            },
+        )
+        .await;
+        assert_eq!(files.len(), 2);
+        for file in files {
+            assert_eq!(&file[4..8], b"ftyp");
+            let mfra_len = u32::from_be_bytes(file[file.len() - 4..].try_into().unwrap()) as usize;
+            assert_eq!(&file[file.len() - mfra_len + 4..][..4], b"mfra");
+        }
+    }
+}
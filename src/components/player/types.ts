@@ -6,6 +6,7 @@ import { Platform } from '../../platforms/common/types';
 export interface RustGetStreamUrlPayload {
   args: {
     room_id_str: string;
+    quality?: string | null; // Douyin: preferred quality key; degrades from there
   };
   platform: Platform; // Platform enum from common/types
 }
@@ -1,8 +1,9 @@
 import { invoke } from '@tauri-apps/api/core'; // Added import for invoke
 import * as douyuApi from '../douyu/api';
 import * as douyuParsers from '../douyu/parsers'; // Import Douyu parsers
+import type { DouyuStreamQualityOptions } from '../douyu/types';
 import { Platform } from './types'; // Import Platform enum
-import type { SupportedPlatform, StreamerDetails, StreamPlaybackDetails, CommonCategoryGroup, CommonPlatformCategory } from './types'; // Import SupportedPlatform, StreamerDetails, and StreamPlaybackDetails, and CommonCategoryGroup, and CommonPlatformCategory
+import type { FlvStreamStats, ProxySettings, ProxyStreamHeaders, ProxyStreamOrigin, SupportedPlatform, StreamerDetails, StreamPlaybackDetails, CommonCategoryGroup, CommonPlatformCategory } from './types'; // Import SupportedPlatform, StreamerDetails, and StreamPlaybackDetails, and CommonCategoryGroup, and CommonPlatformCategory
 
 let currentPlatform: SupportedPlatform = 'douyu'; 
 
@@ -64,11 +65,12 @@ export async function fetchSubCategories(parentId: string): Promise<CommonPlatfo
 }
 
 // --- Stream Playback Details --- //
-export async function fetchStreamPlaybackDetails(roomId: string, platform?: Platform): Promise<StreamPlaybackDetails> {
+// `douyuQuality` picks the Douyu bitrate and preferred CDN; other platforms ignore it
+export async function fetchStreamPlaybackDetails(roomId: string, platform?: Platform, douyuQuality?: DouyuStreamQualityOptions): Promise<StreamPlaybackDetails> {
   const targetPlatform = platform || currentPlatform; // Use provided platform or fallback to global
   switch (targetPlatform) {
     case Platform.DOUYU: // Use Platform enum
-      const rawUrl = await douyuApi.fetchDouyuStreamUrlRaw(roomId);
+      const rawUrl = await douyuApi.fetchDouyuStreamUrlRaw(roomId, douyuQuality);
       return douyuParsers.parseDouyuStreamDataToPlaybackDetails(roomId, rawUrl);
     case Platform.DOUYIN:
       return Promise.reject('Douyin stream details should be provided directly, not fetched via this common service.');
@@ -76,4 +78,44 @@ export async function fetchStreamPlaybackDetails(roomId: string, platform?: Plat
       console.error(`Platform ${targetPlatform} not supported for fetchStreamPlaybackDetails`);
       return Promise.reject(`Platform ${targetPlatform} not supported`);
   }
-}
\ No newline at end of file
+}
+
+// --- Stream Stats --- //
+// Stats for a proxied stream (the /live.flv one when streamId is omitted); null when nothing is playing
+export async function getStreamStats(streamId?: string): Promise<FlvStreamStats | null> {
+  return invoke<FlvStreamStats | null>('get_stream_stats', { streamId: streamId ?? null });
+}
+
+// --- Proxied Streams --- //
+// Serves `url` at /stream/{id}.flv (.m3u8 for HLS) on the local proxy, starting it if needed, and
+// returns that URL. Re-adding an id replaces its upstream; other ids keep playing. With an origin,
+// a dropped FLV upstream is re-resolved through that room and spliced in without the player noticing.
+export async function addProxyStream(id: string, url: string, headers?: ProxyStreamHeaders, origin?: ProxyStreamOrigin): Promise<string> {
+  return invoke<string>('add_proxy_stream', { id, url, headers: headers ?? null, origin: origin ?? null });
+}
+
+export async function removeProxyStream(id: string): Promise<void> {
+  await invoke('remove_proxy_stream', { id });
+}
+
+// A new preferred port applies the next time the proxy server starts
+export async function getProxySettings(): Promise<ProxySettings> {
+  return invoke<ProxySettings>('get_proxy_settings');
+}
+
+export async function setProxySettings(settings: ProxySettings): Promise<void> {
+  await invoke('set_proxy_settings', { settings });
+}
+
+// The proxy serves the same stream remuxed to fragmented MP4 next to its FLV route
+export function toMp4ProxyUrl(flvProxyUrl: string): string {
+  return flvProxyUrl.replace(/\.flv$/, '.mp4');
+}
+
+// Starts a /stream/{id}.flv (or .mp4) URL `secondsBack` behind live; needs dvrSeconds in the
+// proxy settings. Starting a new connection this way is also how a paused player catches up.
+export function toTimeShiftUrl(proxyUrl: string, secondsBack: number): string {
+  const url = new URL(proxyUrl);
+  url.searchParams.set('offset', String(-Math.abs(Math.round(secondsBack))));
+  return url.toString();
+}
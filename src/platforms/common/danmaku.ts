@@ -0,0 +1,183 @@
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type Event as TauriEvent, type UnlistenFn } from '@tauri-apps/api/event';
+import Artplayer from 'artplayer';
+import { Ref } from 'vue';
+import type { DanmakuMessage } from '../../components/player/types';
+import type { ActiveDanmakuListener, AssExportOptions, DanmakuEnvelope, SupportedPlatform } from './types';
+
+const MAX_DANMAKU_MESSAGES = 200;
+
+// Must match danmaku_event_name in src-tauri/src/platforms/common/danmaku.rs
+export function danmakuEventName(platform: SupportedPlatform, roomId: string): string {
+  return `danmaku-${platform}-${roomId}`;
+}
+
+export async function listenDanmaku(
+  platform: SupportedPlatform,
+  roomId: string,
+  handler: (envelope: DanmakuEnvelope) => void
+): Promise<UnlistenFn> {
+  return listen<DanmakuEnvelope>(danmakuEventName(platform, roomId), (event: TauriEvent<DanmakuEnvelope>) => {
+    if (event.payload) {
+      handler(event.payload);
+    }
+  });
+}
+
+// Rooms with a running backend listener, across all platforms
+export async function listActiveDanmakuListeners(): Promise<ActiveDanmakuListener[]> {
+  return invoke<ActiveDanmakuListener[]>('list_active_danmaku_listeners');
+}
+
+// Turns recording of a room's danmaku to a .jsonl file on or off; resolves to the file path while on
+export async function setDanmakuRecording(
+  platform: SupportedPlatform,
+  roomId: string,
+  enabled: boolean
+): Promise<string | null> {
+  return invoke<string | null>('set_danmaku_recording', { platform, roomId, enabled });
+}
+
+// Converts a .jsonl recording to an .ass or Bilibili .xml file next to it; resolves to the new file's path
+export async function exportDanmakuRecording(
+  path: string,
+  format: 'ass' | 'xml',
+  assOptions?: AssExportOptions
+): Promise<string> {
+  return invoke<string>('export_danmaku_recording', { path, format, assOptions: assOptions ?? null });
+}
+
+function connectionStateMessage(
+  envelope: Extract<DanmakuEnvelope, { type: 'connectionState' }>
+): DanmakuMessage | null {
+  let content: string;
+  switch (envelope.state) {
+    case 'reconnecting': {
+      const seconds = Math.ceil((envelope.retryInMs ?? 0) / 1000);
+      content = `弹幕连接已断开，${seconds} 秒后重连（第 ${envelope.attempt} 次）`;
+      break;
+    }
+    case 'connected':
+      // The first connection is already announced by the player
+      if (envelope.attempt === 0) {
+        return null;
+      }
+      content = '弹幕已重新连接';
+      break;
+    case 'failed':
+      content = `弹幕连接失败${envelope.error ? `: ${envelope.error}` : ''}，请尝试刷新播放器。`;
+      break;
+    default:
+      return null;
+  }
+  return {
+    id: `system-conn-${envelope.timestamp}`,
+    type: envelope.state === 'failed' ? 'error' : 'system',
+    isSystem: true,
+    nickname: '系统消息',
+    content,
+    room_id: envelope.roomId,
+  };
+}
+
+/**
+ * Converts a backend danmaku event into a DanmuList entry.
+ * Only chat, finished gifts, nobles, live status, connection problems and system events are
+ * shown in the list; everything else returns null.
+ */
+export function envelopeToDanmakuMessage(envelope: DanmakuEnvelope): DanmakuMessage | null {
+  switch (envelope.type) {
+    case 'chat':
+      return {
+        type: 'chat',
+        uid: envelope.user.uid ?? undefined,
+        nickname: envelope.user.nickname || '未知用户',
+        level: envelope.user.level != null ? String(envelope.user.level) : undefined,
+        content: envelope.content,
+        badgeName: envelope.user.badgeName ?? undefined,
+        badgeLevel: envelope.user.badgeLevel != null ? String(envelope.user.badgeLevel) : undefined,
+        color: envelope.color ?? undefined,
+        room_id: envelope.roomId,
+      };
+    case 'gift':
+      // Combos are shown once, when they end
+      if (!envelope.repeatEnd) {
+        return null;
+      }
+      return {
+        type: 'gift',
+        uid: envelope.user.uid ?? undefined,
+        nickname: envelope.user.nickname || '未知用户',
+        content: `送出 ${envelope.giftName} x${envelope.comboCount}`,
+        badgeName: envelope.user.badgeName ?? undefined,
+        badgeLevel: envelope.user.badgeLevel != null ? String(envelope.user.badgeLevel) : undefined,
+        room_id: envelope.roomId,
+      };
+    case 'noble':
+      return {
+        type: 'noble',
+        uid: envelope.user.uid ?? undefined,
+        nickname: envelope.user.nickname || '未知用户',
+        content: `开通了 ${envelope.name || `${envelope.level}级贵族`}`,
+        room_id: envelope.roomId,
+      };
+    case 'system':
+      return {
+        id: `system-${envelope.timestamp}`,
+        type: 'system',
+        isSystem: true,
+        nickname: '系统消息',
+        content: envelope.content,
+        room_id: envelope.roomId,
+      };
+    case 'liveStatusChanged':
+      return {
+        id: `system-live-${envelope.timestamp}`,
+        type: 'system',
+        isSystem: true,
+        nickname: '系统消息',
+        content: envelope.isLive ? '主播开播了' : '直播已结束',
+        room_id: envelope.roomId,
+      };
+    case 'connectionState':
+      return connectionStateMessage(envelope);
+    default:
+      return null;
+  }
+}
+
+/**
+ * Feeds a room's danmaku events into the player overlay and the DanmuList.
+ * Shared by the per-platform player helpers once their backend listener is running.
+ * `onLiveStatusChange` is called when the platform reports the stream starting or ending.
+ */
+export async function attachDanmakuToPlayer(
+  platform: SupportedPlatform,
+  roomId: string,
+  artInstance: Artplayer,
+  danmakuMessagesRef: Ref<DanmakuMessage[]>,
+  onLiveStatusChange?: (isLive: boolean) => void
+): Promise<UnlistenFn> {
+  return listenDanmaku(platform, roomId, (envelope) => {
+    if (envelope.type === 'liveStatusChanged') {
+      onLiveStatusChange?.(envelope.isLive);
+    }
+
+    const message = envelopeToDanmakuMessage(envelope);
+    if (!message) {
+      return;
+    }
+
+    if (envelope.type === 'chat' && artInstance?.plugins?.artplayerPluginDanmuku) {
+      artInstance.plugins.artplayerPluginDanmuku.emit({
+        text: message.content,
+        color: message.color || '#FFFFFF',
+      });
+    }
+
+    danmakuMessagesRef.value.push(message);
+    if (danmakuMessagesRef.value.length > MAX_DANMAKU_MESSAGES) {
+      danmakuMessagesRef.value.splice(0, danmakuMessagesRef.value.length - MAX_DANMAKU_MESSAGES);
+    }
+  });
+}
@@ -0,0 +1,75 @@
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type { SupportedPlatform } from './types';
+
+// Must match RECORDING_STATUS_EVENT in src-tauri/src/recording/stream_recorder.rs
+const RECORDING_STATUS_EVENT = 'recording-status';
+
+// 'mp4' is fragmented MP4 remuxed from the FLV stream, indexed when the file is closed
+export type RecordingContainer = 'flv' | 'mp4';
+
+// Mirrors StreamRecordingOptions; omitted fields use the backend defaults
+export interface StreamRecordingOptions {
+  filenameTemplate?: string; // {platform} {room} {anchor} {title} {date} {segment}; default "{platform}_{anchor}_{date}"; the extension follows `container`
+  maxSegmentBytes?: number;
+  maxSegmentSecs?: number;
+  quality?: string;
+  outputDir?: string; // Defaults to <Videos>/DTV
+  container?: RecordingContainer; // Default 'flv'
+}
+
+export interface RecordingStatus {
+  platform: SupportedPlatform;
+  roomId: string;
+  state: 'recording' | 'stopped' | 'failed';
+  path: string | null;
+  segment: number;
+  bytesWritten: number;
+  durationMs: number;
+  bitrateKbps: number;
+  error: string | null;
+}
+
+export async function startStreamRecording(
+  platform: SupportedPlatform,
+  roomId: string,
+  options?: StreamRecordingOptions
+): Promise<void> {
+  await invoke('start_stream_recording', { platform, roomId, options: options ?? null });
+}
+
+export async function stopStreamRecording(platform: SupportedPlatform, roomId: string): Promise<void> {
+  await invoke('stop_stream_recording', { platform, roomId });
+}
+
+// Status for every running recording arrives on one channel; filter by platform / roomId
+export async function listenRecordingStatus(handler: (status: RecordingStatus) => void): Promise<UnlistenFn> {
+  return listen<RecordingStatus>(RECORDING_STATUS_EVENT, (event) => {
+    if (event.payload) {
+      handler(event.payload);
+    }
+  });
+}
+
+// Mirrors AutoRecordRule in src-tauri/src/recording/auto_record.rs
+export interface AutoRecordRule {
+  platform: SupportedPlatform;
+  roomId: string;
+  enabled?: boolean; // Default true
+  quality?: string | null;
+  recordDanmaku?: boolean;
+  maxDurationSecs?: number | null; // Per broadcast
+  keepLast?: number | null; // Recordings kept per room; null keeps all
+  filenameTemplate?: string | null;
+  outputDir?: string | null; // Defaults to <Videos>/DTV
+  container?: RecordingContainer; // Default 'flv'
+}
+
+export async function getAutoRecordRules(): Promise<AutoRecordRule[]> {
+  return invoke<AutoRecordRule[]>('get_auto_record_rules');
+}
+
+// Replaces (and saves) every rule; the backend re-checks all rooms right away
+export async function setAutoRecordRules(rules: AutoRecordRule[]): Promise<void> {
+  await invoke('set_auto_record_rules', { rules });
+}
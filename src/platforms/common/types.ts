@@ -1,4 +1,4 @@
-export type SupportedPlatform = 'douyu' | 'bilibili' | 'douyin'; // Add other platforms as needed
+export type SupportedPlatform = 'douyu' | 'bilibili' | 'douyin' | 'huya'; // Add other platforms as needed
  
 
 export enum Platform {
@@ -77,6 +77,73 @@ export interface CommonDanmakuMessage {
   rawData?: any; 
 }
 
+// Unified danmaku events emitted by the backend on `danmaku-{platform}-{roomId}`.
+// Mirrors DanmakuEnvelope / DanmakuEvent in src-tauri/src/platforms/common/danmaku.rs.
+export interface DanmakuUser {
+  uid?: string | null;
+  nickname: string;
+  level?: number | null;
+  badgeName?: string | null;
+  badgeLevel?: number | null;
+}
+
+export type DanmakuConnectionState = 'connecting' | 'connected' | 'reconnecting' | 'failed';
+
+export type DanmakuEvent =
+  | { type: 'chat'; user: DanmakuUser; content: string; color?: string | null }
+  | {
+      type: 'gift';
+      user: DanmakuUser;
+      giftId?: string | null;
+      giftName: string;
+      count: number; // gifts added by this event
+      iconUrl?: string | null;
+      comboCount: number; // running total of the combo
+      repeatEnd: boolean; // last update of the combo
+      value?: number | null; // worth of `count` gifts in platform currency
+    }
+  | { type: 'enter'; user: DanmakuUser }
+  | { type: 'like'; user?: DanmakuUser | null; count: number }
+  | { type: 'follow'; user: DanmakuUser }
+  | { type: 'noble'; user: DanmakuUser; level: number; name?: string | null }
+  | { type: 'userLevelUp'; user: DanmakuUser; level: number }
+  | { type: 'badgeLevelUp'; user: DanmakuUser }
+  | { type: 'fansRank'; fansCount?: number | null; top: DanmakuUser[] }
+  | { type: 'broadcast'; content: string; targetRoomId?: string | null }
+  | { type: 'roomStats'; viewerCount?: number | null; viewerCountText?: string | null }
+  | { type: 'system'; content: string }
+  | { type: 'liveStatusChanged'; isLive: boolean }
+  | {
+      type: 'connectionState';
+      state: DanmakuConnectionState;
+      attempt: number; // reconnect attempt, 0 for the first connection
+      retryInMs?: number | null;
+      error?: string | null;
+    };
+
+export type DanmakuEnvelope = DanmakuEvent & {
+  platform: SupportedPlatform;
+  roomId: string;
+  timestamp: number; // ms since epoch, when the backend received it
+};
+
+// Returned by the list_active_danmaku_listeners command
+export interface ActiveDanmakuListener {
+  platform: SupportedPlatform;
+  roomId: string;
+}
+
+// Mirrors AssExportOptions in src-tauri/src/recording/ass.rs; omitted fields use the backend defaults
+export interface AssExportOptions {
+  fontName?: string;
+  fontSize?: number;
+  scrollDurationSecs?: number; // Time for a comment to cross the screen; lower is faster
+  width?: number;
+  height?: number;
+  area?: number; // Fraction of the screen height used for danmaku
+  opacity?: number; // 0-1
+}
+
 export interface StreamQuality {
   quality: string; // e.g., 'source', '1080p', '720p'
   description: string; // e.g., '原画', '高清', '标清'
@@ -114,5 +181,61 @@ export interface LiveStreamInfo {
   stream_url?: string | null;
   status?: number | null; // Add status field, consistent with Rust struct
   error_message?: string | null;
+  qualities?: DouyinStreamQuality[]; // Douyin: what the room offers, best first
+  quality?: string | null;           // Douyin: key of the quality that's playing
   // Potentially other platform-specific fields if not covered by StreamRoomDetails
-} 
\ No newline at end of file
+}
+
+// Mirrors DouyinStreamQuality in src-tauri/src/platforms/douyin/stream_quality.rs
+export interface DouyinStreamQuality {
+  key: string; // e.g. "origin", "hd"; passed back as the preferred quality
+  name: string;
+  codec: 'h264' | 'h265';
+  resolution?: string | null; // e.g. "1920x1080"
+  bitrate_kbps?: number | null;
+  fps?: number | null;
+}
+
+// Mirrors FlvStreamStats in src-tauri/src/media/flv/stats.rs
+export interface KeyframePosition {
+  timestampMs: number;
+  offset: number; // Byte offset in the proxied stream
+}
+
+export interface FlvStreamStats {
+  videoCodec: string | null; // e.g. "avc1.640028", "hvc1.1.6.L120.B0"
+  width: number | null;
+  height: number | null;
+  fps: number | null;
+  audioCodec: string | null; // e.g. "mp4a.40.2"
+  audioSampleRate: number | null;
+  audioChannels: number | null;
+  bitrateKbps: number | null;
+  durationMs: number;
+  bytes: number;
+  videoFrames: number;
+  keyframes: KeyframePosition[]; // Most recent 100
+  metadata: Record<string, unknown> | null; // Raw onMetaData
+  encoder: string | null;
+  error: string | null;
+}
+
+// Mirrors ProxyStreamHeaders in src-tauri/src/proxy/streams.rs; sent with every upstream request
+export interface ProxyStreamHeaders {
+  referer?: string | null;
+  cookie?: string | null;
+  userAgent?: string | null; // Defaults to a desktop Chrome UA
+}
+
+// Mirrors ProxySettings in src-tauri/src/proxy/settings.rs
+export interface ProxySettings {
+  preferredPort?: number | null; // Falls back to a free port when unset or taken
+  dvrSeconds?: number | null; // Time-shift buffer per FLV stream; unset or 0 is off
+}
+
+// Mirrors ProxyStreamOrigin in src-tauri/src/proxy/streams.rs
+export interface ProxyStreamOrigin {
+  platform: SupportedPlatform;
+  roomId: string;
+  quality?: string | null;
+}
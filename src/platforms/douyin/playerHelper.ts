@@ -1,21 +1,14 @@
 import { invoke } from '@tauri-apps/api/core';
-import { listen, type Event as TauriEvent } from '@tauri-apps/api/event';
 import Artplayer from 'artplayer';
 import { Ref } from 'vue';
 import { Platform } from '../common/types';
 import type { DanmakuMessage, RustGetStreamUrlPayload } from '../../components/player/types';
-import type { LiveStreamInfo } from '../common/types';
+import type { DouyinStreamQuality, LiveStreamInfo } from '../common/types';
+import { attachDanmakuToPlayer } from '../common/danmaku';
 
-
-export interface DouyinRustDanmakuPayload {
-  room_id?: string; 
-  user: string;      // Nickname from Rust's DanmakuFrontendPayload
-  content: string;
-  user_level: number; // from Rust's i64
-  fans_club_level: number; // from Rust's i32
-}
-
-export async function fetchAndPrepareDouyinStreamConfig(roomId: string): Promise<{ 
+// `preferredQuality` is a key from a previous result's `qualities`; the backend falls back to the
+// nearest lower one (then higher) when the room doesn't offer it
+export async function fetchAndPrepareDouyinStreamConfig(roomId: string, preferredQuality?: string): Promise<{ 
   streamUrl: string | null;
   streamType: string | undefined; 
   title?: string | null; 
@@ -23,13 +16,15 @@ export async function fetchAndPrepareDouyinStreamConfig(roomId: string): Promise
   avatar?: string | null; 
   isLive: boolean; 
   initialError: string | null; // Made non-optional, will always be string or null
+  qualities: DouyinStreamQuality[];
+  quality: string | null;
 }> {
   if (!roomId) {
-    return { streamUrl: null, streamType: undefined, title: null, anchorName: null, avatar: null, isLive: false, initialError: '房间ID未提供' };
+    return { streamUrl: null, streamType: undefined, title: null, anchorName: null, avatar: null, isLive: false, initialError: '房间ID未提供', qualities: [], quality: null };
   }
 
   try {
-    const payloadData = { args: { room_id_str: roomId } };
+    const payloadData = { args: { room_id_str: roomId, quality: preferredQuality ?? null } };
     const result = await invoke<LiveStreamInfo>('get_douyin_live_stream_url', { payload: payloadData });
 
     if (result.error_message) {
@@ -42,6 +37,8 @@ export async function fetchAndPrepareDouyinStreamConfig(roomId: string): Promise
         avatar: result.avatar,
         isLive: result.status === 2,
         initialError: result.error_message, // string | null from Rust
+        qualities: result.qualities ?? [],
+        quality: result.quality ?? null,
       };
     }
 
@@ -50,7 +47,10 @@ export async function fetchAndPrepareDouyinStreamConfig(roomId: string): Promise
     let uiMessage: string | null = null; 
 
     if (streamAvailable && result.stream_url) {
-      if (result.stream_url.startsWith('http://127.0.0.1') && result.stream_url.endsWith('/live.flv')) {
+      if (result.stream_url.startsWith('http://127.0.0.1') && result.stream_url.endsWith('.m3u8')) {
+        // HLS fallback; the proxy rewrites the playlist so segments load through it too
+        streamType = 'm3u8';
+      } else if (result.stream_url.startsWith('http://127.0.0.1') && result.stream_url.endsWith('.flv')) {
         streamType = 'flv';
       } else if (result.stream_url.includes('pull-hls') || result.stream_url.endsWith('.m3u8')) {
         console.warn(`[DouyinPlayerHelper] Received HLS-like stream URL (${result.stream_url}), but expected flv. Overriding to flv.`);
@@ -78,6 +78,8 @@ export async function fetchAndPrepareDouyinStreamConfig(roomId: string): Promise
       avatar: result.avatar,
       isLive: streamAvailable,
       initialError: uiMessage, // uiMessage is definitely string or null here.
+      qualities: result.qualities ?? [],
+      quality: result.quality ?? null,
     };
 
   } catch (e: any) {
@@ -89,7 +91,9 @@ export async function fetchAndPrepareDouyinStreamConfig(roomId: string): Promise
         anchorName: null, 
         avatar: null, 
         isLive: false, 
-        initialError: e.message || '获取直播信息失败: 未知错误' // Ensure string here
+        initialError: e.message || '获取直播信息失败: 未知错误', // Ensure string here
+        qualities: [],
+        quality: null,
     };
   }
 }
@@ -97,7 +101,8 @@ export async function fetchAndPrepareDouyinStreamConfig(roomId: string): Promise
 export async function startDouyinDanmakuListener(
   roomId: string,
   artInstance: Artplayer, // For emitting danmaku to player
-  danmakuMessagesRef: Ref<DanmakuMessage[]> // For updating DanmuList
+  danmakuMessagesRef: Ref<DanmakuMessage[]>, // For updating DanmuList
+  onLiveStatusChange?: (isLive: boolean) => void
 ): Promise<() => void> {
   
   const rustPayload: RustGetStreamUrlPayload = { 
@@ -105,46 +110,17 @@ export async function startDouyinDanmakuListener(
     platform: Platform.DOUYIN, 
   };
   await invoke('start_douyin_danmu_listener', { payload: rustPayload });
-  
-  const eventName = 'danmaku-message';
-
-  const unlisten = await listen<DouyinRustDanmakuPayload>(eventName, (event: TauriEvent<DouyinRustDanmakuPayload>) => {
-    
 
-    if (artInstance && artInstance.plugins && artInstance.plugins.artplayerPluginDanmuku && event.payload) {
-      const rustP = event.payload;
-      const frontendDanmaku: DanmakuMessage = {
-        nickname: rustP.user || '未知用户',
-        content: rustP.content || '',
-        level: String(rustP.user_level || 0),
-        badgeLevel: rustP.fans_club_level > 0 ? String(rustP.fans_club_level) : undefined,
-        room_id: rustP.room_id || roomId, // Ensure room_id is present
-      };
-
-      artInstance.plugins.artplayerPluginDanmuku.emit({
-        text: frontendDanmaku.content,
-        color: frontendDanmaku.color || '#FFFFFF', 
-      });
-      danmakuMessagesRef.value.push(frontendDanmaku);
-      if (danmakuMessagesRef.value.length > 200) { // Manage danmaku array size
-        danmakuMessagesRef.value.splice(0, danmakuMessagesRef.value.length - 200);
-      }
-    }
-  });
-  return unlisten;
+  return attachDanmakuToPlayer('douyin', roomId, artInstance, danmakuMessagesRef, onLiveStatusChange);
 }
 
-export async function stopDouyinDanmaku(currentUnlistenFn: (() => void) | null): Promise<void> {
+export async function stopDouyinDanmaku(roomId: string, currentUnlistenFn: (() => void) | null): Promise<void> {
   if (currentUnlistenFn) {
     currentUnlistenFn();
   }
   try {
-    const rustPayload: RustGetStreamUrlPayload = { 
-      args: { room_id_str: "stop_listening" }, 
-      platform: Platform.DOUYIN, 
-    };
-    await invoke('start_douyin_danmu_listener', { payload: rustPayload });
+    await invoke('stop_douyin_danmu_listener', { roomId });
   } catch (error) {
     console.error('[DouyinPlayerHelper] Error stopping Douyin danmaku listener:', error);
   }
-} 
\ No newline at end of file
+}
@@ -1,5 +1,5 @@
 import { invoke } from '@tauri-apps/api/core';
-import type { DouyuRoomInfo, DouyuRawCategoriesResponseData } from './types';
+import type { DouyuRoomInfo, DouyuRawCategoriesResponseData, DouyuStreamQualities, DouyuStreamQualityOptions } from './types';
 
 export async function fetchDouyuRoomInfo(roomId: string): Promise<DouyuRoomInfo> {
   if (!roomId) {
@@ -76,14 +76,18 @@ export async function startDouyuDanmakuListener(roomId: string): Promise<void> {
 }
 
 
-export async function fetchDouyuStreamUrlRaw(roomId: string): Promise<string> {
+export async function fetchDouyuStreamUrlRaw(roomId: string, options?: DouyuStreamQualityOptions): Promise<string> {
   if (!roomId) {
     console.warn('[Douyu API] fetchDouyuStreamUrlRaw: roomId is not provided.');
     return Promise.reject('Room ID is required to fetch stream URL.');
   }
   try {
     // Assuming get_stream_url_cmd returns a simple string URL
-    const url = await invoke<string>('get_stream_url_cmd', { roomId }); 
+    const url = await invoke<string>('get_stream_url_cmd', {
+      roomId,
+      rate: options?.rate ?? null,
+      cdn: options?.cdn ?? null,
+    });
     if (!url) {
       console.error(`[Douyu API] fetchDouyuStreamUrlRaw: Received empty URL for room ${roomId}`);
       return Promise.reject('Empty stream URL received');
@@ -95,6 +99,21 @@ export async function fetchDouyuStreamUrlRaw(roomId: string): Promise<string> {
   }
 }
 
+/**
+ * Lists the bitrates and CDN lines a live Douyu room offers.
+ */
+export async function fetchDouyuStreamQualities(roomId: string): Promise<DouyuStreamQualities> {
+  if (!roomId) {
+    return Promise.reject('Room ID is required to fetch stream qualities.');
+  }
+  try {
+    return await invoke<DouyuStreamQualities>('get_stream_qualities', { roomId });
+  } catch (error) {
+    console.error(`[Douyu API] Error fetching Douyu stream qualities for ${roomId}:`, error);
+    throw error;
+  }
+}
+
 /**
  * Fetches raw category data from the Douyu platform.
  */
@@ -1,6 +1,4 @@
 import type { StreamerDetails, StreamPlaybackDetails, CommonCategoryGroup, CommonPlatformCategory } from '../common/types';
-import { CommonDanmakuMessage } from '../common/types';
-import { v4 as uuidv4 } from 'uuid';
 import { DouyuRawCategoriesResponseData, DouyuRawCategoryGroup, DouyuRawGameCategory, DouyuRoomInfo } from './types';
 
 /**
@@ -43,63 +41,6 @@ export function parseDouyuRoomDataToStreamerDetails(roomId: string, data: DouyuR
   };
 }
 
-export function parseDouyuDanmakuMessage(rawPayload: any): CommonDanmakuMessage | null {
-  if (!rawPayload || !rawPayload.type) {
-    // console.warn('[Douyu Parser] Received danmaku payload without type:', rawPayload);
-    return null; // Or handle as a generic system message
-  }
-
-  let commonType: CommonDanmakuMessage['type'] = 'other';
-  let content = rawPayload.content || '';
-
-  switch (rawPayload.type) {
-    case 'chatmsg':
-      commonType = 'chat';
-      break;
-    case 'uenter':
-      // commonType = 'enter';
-      // content = `${rawPayload.nickname || '用户'} 进入直播间`; // Create content for enter messages
-      return null; // Filter out 'uenter' messages
-    case 'gifttransform': // Example: Douyu gift type
-    case 'dgb':
-      commonType = 'gift';
-      content = `${rawPayload.nickname || '用户'}赠送了${rawPayload.gfid || '礼物'}`;
-      // More detailed gift parsing could be done here
-      break;
-    // Add other Douyu specific type mappings here
-    default:
-      // console.log('[Douyu Parser] Unhandled Douyu danmaku type:', rawPayload.type, rawPayload);
-      // Keep as 'other' or return null if you don't want to display unhandled types
-      break;
-  }
-
-  // Convert Douyu color (numeric string) to hex if present
-  let hexColor: string | undefined = undefined;
-  if (rawPayload.color) {
-    const numColor = parseInt(rawPayload.color, 10);
-    if (!isNaN(numColor) && numColor >= 0 && numColor <= 16777215) {
-      hexColor = '#' + numColor.toString(16).padStart(6, '0');
-    }
-  }
-
-  return {
-    id: uuidv4(), // Generate a unique ID for the list key
-    platform: 'douyu',
-    type: commonType,
-    sender: {
-      uid: rawPayload.uid,
-      nickname: rawPayload.nickname || '未知用户',
-      level: rawPayload.level ? parseInt(rawPayload.level, 10) : undefined,
-      badgeName: rawPayload.badgeName,
-      badgeLevel: rawPayload.badgeLevel ? parseInt(rawPayload.badgeLevel, 10) : undefined,
-    },
-    content: content,
-    timestamp: Date.now(), // Use current time as received time
-    color: hexColor,
-    rawData: rawPayload, // Include raw data for potential future use or debugging
-  };
-}
-
 /**
  * Parses a raw Douyu stream URL into the common StreamPlaybackDetails format.
  * Currently, this is a simple wrapper as Douyu typically provides a direct M3U8 or FLV URL.
@@ -1,33 +1,20 @@
 import { invoke } from '@tauri-apps/api/core';
-import { listen, type Event as TauriEvent } from '@tauri-apps/api/event';
 import Artplayer from 'artplayer';
 import { Ref } from 'vue';
 import { Platform } from '../common/types';
 import type { DanmakuMessage } from '../../components/player/types'; // Corrected path
-import { fetchStreamPlaybackDetails } from '../common/apiService';
-import { parseDouyuDanmakuMessage } from './parsers'; // <-- Import the parser
+import { addProxyStream, fetchStreamPlaybackDetails, removeProxyStream } from '../common/apiService';
+import { attachDanmakuToPlayer } from '../common/danmaku';
+import type { DouyuStreamQualityOptions } from './types';
 
-// Specific type for Douyu's raw danmaku payload from Rust event
-export interface DouyuRustDanmakuPayload {
-  type: "chatmsg" | "uenter";
-  room_id: string;
-  nickname: string;
-  content: string; // only for chatmsg
-  level: string;
-  badgeName?: string;
-  badgeLevel?: string;
-  color?: string;
-  uid?: string; // only for uenter
-}
-
-export async function getDouyuStreamConfig(roomId: string): Promise<{ streamUrl: string, streamType: string | undefined }> {
+export async function getDouyuStreamConfig(roomId: string, quality?: DouyuStreamQualityOptions): Promise<{ streamUrl: string, streamType: string | undefined }> {
   let finalStreamUrl: string | null = null;
   let streamType: string | undefined = undefined;
   const MAX_STREAM_FETCH_ATTEMPTS = 1; // Changed to 1 attempt
 
   for (let attempt = 1; attempt <= MAX_STREAM_FETCH_ATTEMPTS; attempt++) {
     try {
-      const playbackDetails = await fetchStreamPlaybackDetails(roomId, Platform.DOUYU);
+      const playbackDetails = await fetchStreamPlaybackDetails(roomId, Platform.DOUYU, quality);
       if (playbackDetails && playbackDetails.primaryUrl) {
         finalStreamUrl = playbackDetails.primaryUrl;
         streamType = playbackDetails.format; // 直接使用后端返回的 format
@@ -75,9 +62,10 @@ export async function getDouyuStreamConfig(roomId: string): Promise<{ streamUrl:
   }
 
   try {
-    // Assuming stopProxy is handled separately or before calling this if needed
-    await invoke('set_stream_url_cmd', { url: finalStreamUrl });
-    const proxyUrl = await invoke<string>('start_proxy')
+    // Each room has its own proxy route, so another room playing elsewhere isn't replaced
+    // Reconnects re-resolve at the same rate; the CDN is re-picked then, as the old one may be the problem
+    const origin = { platform: 'douyu' as const, roomId, quality: quality?.rate !== undefined ? String(quality.rate) : undefined };
+    const proxyUrl = await addProxyStream(douyuProxyStreamId(roomId), finalStreamUrl, undefined, origin);
     return { streamUrl: proxyUrl, streamType };
   } catch (e: any) {
     throw new Error(`设置斗鱼代理失败: ${e.message}`);
@@ -87,48 +75,13 @@ export async function getDouyuStreamConfig(roomId: string): Promise<{ streamUrl:
 export async function startDouyuDanmakuListener(
   roomId: string,
   artInstance: Artplayer, // For emitting danmaku to player
-  danmakuMessagesRef: Ref<DanmakuMessage[]> // For updating DanmuList
+  danmakuMessagesRef: Ref<DanmakuMessage[]>, // For updating DanmuList
+  onLiveStatusChange?: (isLive: boolean) => void
 ): Promise<() => void> {
 
   await invoke('start_danmaku_listener', { roomId });
-  
-  const eventName = `danmaku-${roomId}`;
-
-  const unlisten = await listen<DouyuRustDanmakuPayload>(eventName, (event: TauriEvent<DouyuRustDanmakuPayload>) => {
-
-    if (artInstance && artInstance.plugins && artInstance.plugins.artplayerPluginDanmuku && event.payload) {
-
-      const commonDanmaku = parseDouyuDanmakuMessage(event.payload);
-      
-      if (commonDanmaku) {
-
-        artInstance.plugins.artplayerPluginDanmuku.emit({
-          text: commonDanmaku.content, // Use content from parsed message
-          color: commonDanmaku.color || '#FFFFFF', // Use color from parsed message
-        });
 
-       
-        const displayDanmaku: DanmakuMessage = { // Adapting to DanmuList's expected DanmakuMessage type
-            nickname: commonDanmaku.sender.nickname,
-            content: commonDanmaku.content,
-            level: commonDanmaku.sender.level ? String(commonDanmaku.sender.level) : '0',
-            badgeName: commonDanmaku.sender.badgeName,
-            badgeLevel: commonDanmaku.sender.badgeLevel ? String(commonDanmaku.sender.badgeLevel) : undefined,
-            color: commonDanmaku.color,
-            uid: commonDanmaku.sender.uid,
-            room_id: roomId, // roomId is available in this scope
-            // id and timestamp are part of CommonDanmakuMessage but might not be directly used by DanmuList's item display
-        };
-        danmakuMessagesRef.value.push(displayDanmaku);
-        
-        if (danmakuMessagesRef.value.length > 200) { // Manage danmaku array size
-            danmakuMessagesRef.value.splice(0, danmakuMessagesRef.value.length - 200);
-        }
-      }
-    }
-  });
-  
-  return unlisten;
+  return attachDanmakuToPlayer('douyu', roomId, artInstance, danmakuMessagesRef, onLiveStatusChange);
 }
 
 export async function stopDouyuDanmaku(roomId: string, currentUnlistenFn: (() => void) | null): Promise<void> {
@@ -144,9 +97,14 @@ export async function stopDouyuDanmaku(roomId: string, currentUnlistenFn: (() =>
   }
 }
 
-export async function stopDouyuProxy(): Promise<void> {
+function douyuProxyStreamId(roomId: string): string {
+  return `douyu-${roomId}`;
+}
+
+// Removes only this room's proxy route; the proxy server itself keeps running
+export async function stopDouyuProxy(roomId: string): Promise<void> {
   try {
-    await invoke('stop_proxy');
+    await removeProxyStream(douyuProxyStreamId(roomId));
   } catch (e) {
     console.error('[DouyuPlayerHelper] Error stopping proxy server:', e);
   }
@@ -112,4 +112,37 @@ export interface DouyuRoomInfoData {
   ownerDetail?: DouyuRoomInfoOwner; // Nested object with more detailed owner information (in some APIs)
 }
 
-export type DouyuRoomInfo = DouyuRoomInfoData; 
\ No newline at end of file
+export type DouyuRoomInfo = DouyuRoomInfoData; 
+/**
+ * A bitrate offered by getH5Play's `multirates`. Rate 0 is the original quality.
+ */
+export interface DouyuStreamRate {
+  name: string; // e.g. "原画", "蓝光4M"
+  rate: number;
+  bit: number;  // kbps
+}
+
+/**
+ * A CDN line from getH5Play's `cdnsWithName`.
+ */
+export interface DouyuStreamCdn {
+  name: string;
+  cdn: string; // e.g. "hw-h5", "ws-h5"
+  isH265: boolean;
+}
+
+export interface DouyuStreamQualities {
+  rates: DouyuStreamRate[];
+  cdns: DouyuStreamCdn[];
+  currentRate: number;
+  currentCdn: string;
+}
+
+/**
+ * What to ask get_stream_url_cmd for. The CDN is tried first; the room's other lines are
+ * the fallback when it doesn't serve the stream.
+ */
+export interface DouyuStreamQualityOptions {
+  rate?: number;
+  cdn?: string;
+}